@@ -1,3 +1,6 @@
+// Raw pointers are owned and validated by the C caller; see each function.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 
@@ -4,7 +4,7 @@ use knowledge::{Handoff, HandoffStatus, TokenCounter};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::{self, Read};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use workflow::{Gate, GateStatus, Phase};
 
 #[derive(Parser)]
@@ -88,7 +88,7 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn validate_handoff(file: &PathBuf) -> Result<ValidationResult> {
+fn validate_handoff(file: &Path) -> Result<ValidationResult> {
     let mut errors = Vec::new();
     let mut warnings = Vec::new();
 
@@ -154,7 +154,7 @@ fn validate_handoff(file: &PathBuf) -> Result<ValidationResult> {
     })
 }
 
-fn check_gate(phase_str: &str, mission_dir: &PathBuf) -> Result<GateCheckResult> {
+fn check_gate(phase_str: &str, mission_dir: &Path) -> Result<GateCheckResult> {
     // Parse phase
     let phase: Phase = serde_json::from_str(&format!("\"{}\"", phase_str))
         .with_context(|| format!("Invalid phase: {}. Valid: idea, design, implement, verify, document, release", phase_str))?;
@@ -273,7 +273,7 @@ mod tests {
         let mut file = NamedTempFile::new().unwrap();
         file.write_all(handoff.as_bytes()).unwrap();
 
-        let result = validate_handoff(&file.path().to_path_buf()).unwrap();
+        let result = validate_handoff(file.path()).unwrap();
         assert!(result.valid);
         assert!(result.errors.is_empty());
     }
@@ -293,7 +293,7 @@ mod tests {
         let mut file = NamedTempFile::new().unwrap();
         file.write_all(handoff.as_bytes()).unwrap();
 
-        let result = validate_handoff(&file.path().to_path_buf()).unwrap();
+        let result = validate_handoff(file.path()).unwrap();
         assert!(!result.valid);
         assert!(result.errors.iter().any(|e| e.contains("task_id")));
     }
@@ -0,0 +1,369 @@
+//! Shared event schema for MissionControl.
+//!
+//! `stream-parser` produces [`UnifiedEvent`]s as NDJSON and `mc-protocol`
+//! consumes them. Both depend on this crate so the field set is defined once,
+//! and both check [`SCHEMA_VERSION`] at startup against the version the other
+//! side advertises in `MC_EVENTS_SCHEMA`.
+
+pub mod redact;
+pub mod ring;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// Version of the [`UnifiedEvent`] wire shape. Bump on any field change.
+pub const SCHEMA_VERSION: u32 = 18;
+
+/// Environment variable a supervisor sets to the schema version it expects.
+pub const SCHEMA_ENV: &str = "MC_EVENTS_SCHEMA";
+
+/// Unified event format that the orchestrator and UI expect
+///
+/// Unknown fields are rejected on deserialization so a newer producer is
+/// reported loudly instead of having its fields silently dropped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnifiedEvent {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    /// Pairs a `tool_result` with the `tool_call` it answers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_use_id: Option<String>,
+    /// On a subagent's events, the `tool_use_id` of the call that launched it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub turn: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Agent session to resume (Claude Code `--resume`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Model the agent runs on, from its `agent_start` event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Turns the agent reported for the whole session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_turns: Option<u32>,
+    /// API round-trips so far; one turn spans several when tools are used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_messages: Option<u64>,
+    /// Cost the agent reported for the whole session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_cost_usd: Option<f64>,
+    /// Wall-clock length of the session the agent reported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// Input tokens: of one API message on a `usage` event, of the whole
+    /// session on `session_end`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_tokens: Option<u64>,
+    /// Output tokens, counted like `input_tokens`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_tokens: Option<u64>,
+    /// Input tokens read from the prompt cache, counted like `input_tokens`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_read_tokens: Option<u64>,
+    /// Input tokens written to the prompt cache, counted like `input_tokens`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_write_tokens: Option<u64>,
+    /// Argument keys and types seen per tool, when profiling is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_profile: Option<Value>,
+    /// When the event was produced, as RFC 3339
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// Position in the agent's stream, counting from 1; always written so
+    /// gaps and reordering show. 0 when the producer did not number events.
+    #[serde(default)]
+    pub seq: u64,
+    /// What the parser saw before giving up, on a terminal `error` event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<Value>,
+    /// Set when `content` or `result` was cut short by [`UnifiedEvent::truncate`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<bool>,
+    /// Length in bytes of the longest truncated field before truncation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_bytes: Option<u64>,
+    /// On a `heartbeat`, whole seconds since the agent's last event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_secs: Option<u64>,
+    /// On an `agent_exit`, the code the agent process exited with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// On an `agent_exit`, the signal that killed the agent process
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+    /// On an `agent_exit` from a throttled stream, events dropped because
+    /// the output queue was full
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dropped_events: Option<u64>,
+    /// On a `file_change`, the file a tool call edited or wrote; on a
+    /// `media`, the file its bytes were saved to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// On a `file_change`, lines the edit added
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines_added: Option<u64>,
+    /// On a `file_change`, lines the edit removed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines_removed: Option<u64>,
+    /// On a `media`, the MIME type of the attachment, like `image/png`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    /// On a `media`, the attachment's size in bytes once decoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_bytes: Option<u64>,
+}
+
+impl UnifiedEvent {
+    pub fn new(event_type: &str) -> Self {
+        UnifiedEvent {
+            event_type: event_type.to_string(),
+            agent_id: None,
+            content: None,
+            tool: None,
+            args: None,
+            result: None,
+            tool_use_id: None,
+            parent_id: None,
+            turn: None,
+            tokens: None,
+            status: None,
+            error: None,
+            session_id: None,
+            model: None,
+            num_turns: None,
+            api_messages: None,
+            total_cost_usd: None,
+            duration_ms: None,
+            input_tokens: None,
+            output_tokens: None,
+            cache_read_tokens: None,
+            cache_write_tokens: None,
+            tool_profile: None,
+            timestamp: None,
+            seq: 0,
+            diagnostics: None,
+            truncated: None,
+            content_bytes: None,
+            idle_secs: None,
+            exit_code: None,
+            signal: None,
+            dropped_events: None,
+            path: None,
+            lines_added: None,
+            lines_removed: None,
+            media_type: None,
+            media_bytes: None,
+        }
+    }
+
+    pub fn with_agent_id(mut self, id: &str) -> Self {
+        self.agent_id = Some(id.to_string());
+        self
+    }
+
+    pub fn with_content(mut self, content: &str) -> Self {
+        self.content = Some(content.to_string());
+        self
+    }
+
+    pub fn with_tool(mut self, tool: &str, args: Value) -> Self {
+        self.tool = Some(tool.to_string());
+        self.args = Some(args);
+        self
+    }
+
+    pub fn with_result(mut self, result: &str) -> Self {
+        self.result = Some(result.to_string());
+        self
+    }
+
+    pub fn with_tool_use_id(mut self, id: Option<&str>) -> Self {
+        self.tool_use_id = id.map(str::to_string);
+        self
+    }
+
+    pub fn with_turn(mut self, turn: u32) -> Self {
+        self.turn = Some(turn);
+        self
+    }
+
+    pub fn with_tokens(mut self, tokens: u32) -> Self {
+        self.tokens = Some(tokens);
+        self
+    }
+
+    pub fn with_status(mut self, status: &str) -> Self {
+        self.status = Some(status.to_string());
+        self
+    }
+
+    pub fn with_session_id(mut self, session_id: Option<&str>) -> Self {
+        self.session_id = session_id.map(str::to_string);
+        self
+    }
+
+    /// Cut `content` and `result` to at most `max_bytes`, marking the event
+    /// `truncated` and recording the original length in `content_bytes`.
+    pub fn truncate(&mut self, max_bytes: usize) {
+        for field in [&mut self.content, &mut self.result].into_iter().flatten() {
+            if field.len() <= max_bytes {
+                continue;
+            }
+            let original = field.len() as u64;
+            field.truncate(truncation_point(field, max_bytes));
+            self.truncated = Some(true);
+            self.content_bytes = Some(self.content_bytes.unwrap_or(0).max(original));
+        }
+    }
+
+    /// Mask secrets in `content`, `result` and `args` before the event is
+    /// serialized; see [`redact::redact`] for what counts as one.
+    pub fn redact(&mut self, secrets: &[String]) {
+        for field in [&mut self.content, &mut self.result].into_iter().flatten() {
+            *field = redact::redact(field, secrets);
+        }
+        if let Some(args) = &mut self.args {
+            redact::redact_value(args, secrets);
+        }
+    }
+}
+
+/// The longest prefix of `text` within `max_bytes` that ends on a character
+/// boundary and outside an ANSI escape sequence.
+fn truncation_point(text: &str, max_bytes: usize) -> usize {
+    let mut end = max_bytes.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    // An escape with no final byte (`@`..`~`) after its `[` was cut mid-way
+    if let Some(escape) = text[..end].rfind('\u{1b}') {
+        let sequence = &text[escape + 1..end];
+        let finished = sequence
+            .strip_prefix('[')
+            .is_some_and(|params| params.bytes().any(|b| (0x40..=0x7e).contains(&b)));
+        if !finished {
+            end = escape;
+        }
+    }
+    end
+}
+
+/// The producer and consumer disagree on the event schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaMismatch {
+    pub expected: String,
+    pub actual: u32,
+}
+
+impl fmt::Display for SchemaMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "event schema mismatch: {}={} but this binary speaks schema v{}",
+            SCHEMA_ENV, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for SchemaMismatch {}
+
+/// Check an advertised schema version against [`SCHEMA_VERSION`].
+///
+/// `None` (nothing advertised) is accepted so standalone use keeps working.
+pub fn check_schema_version(advertised: Option<&str>) -> Result<(), SchemaMismatch> {
+    match advertised {
+        None => Ok(()),
+        Some(value) if value.trim().parse::<u32>() == Ok(SCHEMA_VERSION) => Ok(()),
+        Some(value) => Err(SchemaMismatch {
+            expected: value.to_string(),
+            actual: SCHEMA_VERSION,
+        }),
+    }
+}
+
+/// Check the version advertised in [`SCHEMA_ENV`], if any.
+pub fn check_schema_env() -> Result<(), SchemaMismatch> {
+    check_schema_version(std::env::var(SCHEMA_ENV).ok().as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trip_preserves_shape() {
+        let event = UnifiedEvent::new("tool_call")
+            .with_agent_id("agent-1")
+            .with_tool("bash", json!({"command": "ls"}))
+            .with_turn(2);
+
+        let line = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            line,
+            r#"{"type":"tool_call","agent_id":"agent-1","tool":"bash","args":{"command":"ls"},"turn":2,"seq":0}"#
+        );
+
+        let decoded: UnifiedEvent = serde_json::from_str(&line).unwrap();
+        assert_eq!(decoded, event);
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), line);
+    }
+
+    #[test]
+    fn test_truncate_respects_characters_and_escapes() {
+        let mut event = UnifiedEvent::new("tool_result").with_result(&"é".repeat(10));
+        event.truncate(5);
+        assert_eq!(event.result.as_deref(), Some("éé"));
+        assert_eq!(event.truncated, Some(true));
+        assert_eq!(event.content_bytes, Some(20));
+
+        let mut event = UnifiedEvent::new("output").with_content("ok \u{1b}[31mred");
+        event.truncate(6);
+        assert_eq!(event.content.as_deref(), Some("ok "));
+
+        let mut event = UnifiedEvent::new("output").with_content("short");
+        event.truncate(5);
+        assert_eq!(event.truncated, None);
+        assert_eq!(event.content_bytes, None);
+    }
+
+    #[test]
+    fn test_missing_seq_reads_as_unnumbered() {
+        let event: UnifiedEvent = serde_json::from_str(r#"{"type":"turn","turn":1}"#).unwrap();
+        assert_eq!(event.seq, 0);
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected() {
+        let err = serde_json::from_str::<UnifiedEvent>(r#"{"type":"turn","turn":1,"shiny":true}"#)
+            .unwrap_err();
+        assert!(err.to_string().contains("shiny"));
+    }
+
+    #[test]
+    fn test_check_schema_version() {
+        assert!(check_schema_version(None).is_ok());
+        assert!(check_schema_version(Some(&SCHEMA_VERSION.to_string())).is_ok());
+        let err = check_schema_version(Some("999")).unwrap_err();
+        assert!(err.to_string().contains("999"));
+    }
+}
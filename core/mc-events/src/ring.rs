@@ -0,0 +1,282 @@
+//! Bounded on-disk history of forwarded events.
+//!
+//! For agent `X` in an events directory:
+//! - `X.ndjson` — every event, when forwarding without limits
+//! - `X.ndjson.active` — newest events when limits are set
+//! - `X.ndjson.1` .. `X.ndjson.K` — older generations, `.1` the most recent
+//! - `X.ndjson.lock` — held exclusively while rotating and shared while a
+//!   reader opens the files, so a reader never sees a half-rotated set
+//!
+//! The writer lives in stream-parser and the reader in mc-protocol; both use
+//! this module so they agree on the layout.
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// When to rotate the active file, and how many old generations to keep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RingLimits {
+    pub max_events: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub generations: u32,
+}
+
+impl Default for RingLimits {
+    fn default() -> Self {
+        RingLimits {
+            max_events: None,
+            max_bytes: None,
+            generations: 3,
+        }
+    }
+}
+
+impl RingLimits {
+    pub fn is_bounded(&self) -> bool {
+        self.max_events.is_some() || self.max_bytes.is_some()
+    }
+}
+
+/// The unbounded log, `{agent}.ndjson`.
+pub fn log_path(dir: &Path, agent: &str) -> PathBuf {
+    dir.join(format!("{}.ndjson", agent))
+}
+
+/// The file new events are appended to under limits.
+pub fn active_path(dir: &Path, agent: &str) -> PathBuf {
+    dir.join(format!("{}.ndjson.active", agent))
+}
+
+/// Rotated generation `n` (1 is the most recent).
+pub fn generation_path(dir: &Path, agent: &str, n: u32) -> PathBuf {
+    dir.join(format!("{}.ndjson.{}", agent, n))
+}
+
+fn lock_path(dir: &Path, agent: &str) -> PathBuf {
+    dir.join(format!("{}.ndjson.lock", agent))
+}
+
+fn open_lock(dir: &Path, agent: &str) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path(dir, agent))
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Appends events for one agent, rotating when the limits are reached.
+pub struct RingWriter {
+    dir: PathBuf,
+    agent: String,
+    limits: RingLimits,
+    file: File,
+    events: u64,
+    bytes: u64,
+}
+
+impl RingWriter {
+    /// Open (or resume) the agent's log in `dir`, creating the directory.
+    pub fn open(dir: &Path, agent: &str, limits: RingLimits) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = if limits.is_bounded() {
+            active_path(dir, agent)
+        } else {
+            log_path(dir, agent)
+        };
+
+        // Resume counts so a restarted forwarder still honors the limits
+        let (events, bytes) = match File::open(&path) {
+            Ok(file) => {
+                let bytes = file.metadata()?.len();
+                let events = BufReader::new(file).lines().count() as u64;
+                (events, bytes)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (0, 0),
+            Err(e) => return Err(e),
+        };
+
+        Ok(RingWriter {
+            dir: dir.to_path_buf(),
+            agent: agent.to_string(),
+            limits,
+            file: open_append(&path)?,
+            events,
+            bytes,
+        })
+    }
+
+    /// Append one NDJSON line (without its trailing newline).
+    pub fn append(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        let full_events = self.limits.max_events.is_some_and(|max| self.events >= max);
+        let full_bytes = self
+            .limits
+            .max_bytes
+            .is_some_and(|max| self.bytes + len > max);
+        if self.events > 0 && (full_events || full_bytes) {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{}", line)?;
+        self.file.flush()?;
+        self.events += 1;
+        self.bytes += len;
+        Ok(())
+    }
+
+    /// Shift every generation up by one and start a fresh active file.
+    fn rotate(&mut self) -> io::Result<()> {
+        let lock = open_lock(&self.dir, &self.agent)?;
+        lock.lock()?;
+
+        let result = (|| {
+            let keep = self.limits.generations;
+            let active = active_path(&self.dir, &self.agent);
+            if keep == 0 {
+                return fs::remove_file(&active);
+            }
+
+            let oldest = generation_path(&self.dir, &self.agent, keep);
+            if oldest.exists() {
+                fs::remove_file(&oldest)?;
+            }
+            for n in (1..keep).rev() {
+                let from = generation_path(&self.dir, &self.agent, n);
+                if from.exists() {
+                    fs::rename(&from, generation_path(&self.dir, &self.agent, n + 1))?;
+                }
+            }
+            fs::rename(&active, generation_path(&self.dir, &self.agent, 1))
+        })();
+
+        if result.is_ok() {
+            self.file = open_append(&active_path(&self.dir, &self.agent))?;
+            self.events = 0;
+            self.bytes = 0;
+        }
+        lock.unlock()?;
+        result
+    }
+}
+
+/// Every stored line for `agent`, oldest first, keeping only the last `last`
+/// when given.
+pub fn read_lines(dir: &Path, agent: &str, last: Option<usize>) -> io::Result<Vec<String>> {
+    // Open every file under the lock; reading the handles afterwards is safe
+    // even if the writer rotates, since renames don't affect open files.
+    let files = {
+        let lock = open_lock(dir, agent)?;
+        lock.lock_shared()?;
+
+        let mut paths = vec![log_path(dir, agent)];
+        let mut n = 1;
+        let mut generations = Vec::new();
+        while generation_path(dir, agent, n).exists() {
+            generations.push(generation_path(dir, agent, n));
+            n += 1;
+        }
+        paths.extend(generations.into_iter().rev());
+        paths.push(active_path(dir, agent));
+
+        let mut files = Vec::new();
+        for path in paths {
+            match File::open(&path) {
+                Ok(file) => files.push(file),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        lock.unlock()?;
+        files
+    };
+
+    let mut lines = VecDeque::new();
+    for file in files {
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            lines.push_back(line);
+            if last.is_some_and(|last| lines.len() > last) {
+                lines.pop_front();
+            }
+        }
+    }
+    Ok(lines.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn event(i: usize) -> String {
+        format!(r#"{{"type":"text","content":"event {}"}}"#, i)
+    }
+
+    #[test]
+    fn test_rotates_and_stitches_most_recent_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let limits = RingLimits {
+            max_events: Some(10),
+            generations: 2,
+            ..RingLimits::default()
+        };
+
+        let mut writer = RingWriter::open(dir, "agent-1", limits).unwrap();
+        for i in 1..=45 {
+            writer.append(&event(i)).unwrap();
+        }
+
+        // 45 events: .2 holds 21-30, .1 holds 31-40, active holds 41-45
+        assert!(generation_path(dir, "agent-1", 2).exists());
+        assert!(!generation_path(dir, "agent-1", 3).exists());
+        assert!(!log_path(dir, "agent-1").exists());
+
+        let lines = read_lines(dir, "agent-1", Some(20)).unwrap();
+        let expected: Vec<String> = (26..=45).map(event).collect();
+        assert_eq!(lines, expected);
+
+        // Everything still on disk, in order
+        let all = read_lines(dir, "agent-1", None).unwrap();
+        assert_eq!(all, (21..=45).map(event).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_byte_limit_and_resume() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let line_len = event(1).len() as u64 + 1;
+        let limits = RingLimits {
+            max_bytes: Some(line_len * 3),
+            ..RingLimits::default()
+        };
+
+        let mut writer = RingWriter::open(dir, "a", limits).unwrap();
+        for i in 1..=4 {
+            writer.append(&event(i)).unwrap();
+        }
+        drop(writer);
+
+        // A restarted writer picks up the active file's size
+        let mut writer = RingWriter::open(dir, "a", limits).unwrap();
+        for i in 5..=7 {
+            writer.append(&event(i)).unwrap();
+        }
+
+        let active = fs::read_to_string(active_path(dir, "a")).unwrap();
+        assert_eq!(active.lines().count(), 1);
+        assert_eq!(
+            read_lines(dir, "a", None).unwrap(),
+            (1..=7).map(event).collect::<Vec<_>>()
+        );
+    }
+}
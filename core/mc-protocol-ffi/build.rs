@@ -0,0 +1,32 @@
+/// Ownership rules repeated at the top of the generated header.
+#[cfg(feature = "header")]
+const HEADER: &str = "/*
+ * mc-protocol C ABI. Generated by cbindgen; do not edit.
+ *
+ * Ownership rules:
+ * - Inputs are UTF-8 buffers passed as pointer + length. They are only read
+ *   during the call and stay owned by the caller. A null pointer is accepted
+ *   only with a length of 0.
+ * - Every function returns a NUL-terminated JSON string owned by the caller,
+ *   which must release it with mc_protocol_free_string() exactly once and
+ *   never with free().
+ * - Failures, including Rust panics, come back as {\"error\": \"...\"}; the
+ *   return value is never NULL.
+ */";
+
+fn main() {
+    #[cfg(feature = "header")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        cbindgen::Builder::new()
+            .with_crate(&crate_dir)
+            .with_language(cbindgen::Language::C)
+            .with_header(HEADER)
+            .with_include_guard("MC_PROTOCOL_H")
+            .with_cpp_compat(true)
+            .with_documentation(true)
+            .generate()
+            .expect("Unable to generate bindings")
+            .write_to_file(format!("{}/include/mc_protocol.h", crate_dir));
+    }
+}
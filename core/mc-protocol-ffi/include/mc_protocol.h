@@ -0,0 +1,58 @@
+/*
+ * mc-protocol C ABI. Generated by cbindgen; do not edit.
+ *
+ * Ownership rules:
+ * - Inputs are UTF-8 buffers passed as pointer + length. They are only read
+ *   during the call and stay owned by the caller. A null pointer is accepted
+ *   only with a length of 0.
+ * - Every function returns a NUL-terminated JSON string owned by the caller,
+ *   which must release it with mc_protocol_free_string() exactly once and
+ *   never with free().
+ * - Failures, including Rust panics, come back as {"error": "..."}; the
+ *   return value is never NULL.
+ */
+
+#ifndef MC_PROTOCOL_H
+#define MC_PROTOCOL_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Free a string returned by any `mc_protocol_*` function. Null is ignored.
+ */
+void mc_protocol_free_string(char *ptr);
+
+/**
+ * Validate a task file. Returns `{"valid": bool, "errors": [...]}`.
+ */
+char *mc_protocol_validate_task(const uint8_t *path, uintptr_t path_len);
+
+/**
+ * Parse a response file into its summary, details, files, and notes.
+ */
+char *mc_protocol_parse_response(const uint8_t *path, uintptr_t path_len);
+
+/**
+ * Count tokens in a string. Returns `{"tokens": n}`.
+ */
+char *mc_protocol_count_string_tokens(const uint8_t *text, uintptr_t text_len);
+
+/**
+ * Check whether a conversation file ends with the completion marker.
+ *
+ * Returns `{"complete": true, "response": "..."}` or `{"complete": false}`.
+ */
+char *mc_protocol_check_conversation_complete(const uint8_t *path, uintptr_t path_len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MC_PROTOCOL_H */
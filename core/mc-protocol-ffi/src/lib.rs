@@ -0,0 +1,125 @@
+//! C ABI for the core mc-protocol checks, so in-process callers don't have
+//! to spawn the CLI for every file.
+//!
+//! Ownership rules, for every function below:
+//! - Inputs are UTF-8 byte buffers passed as pointer + length. They are only
+//!   read during the call and remain owned by the caller. A null pointer is
+//!   accepted only together with a length of 0.
+//! - The return value is a NUL-terminated JSON string allocated by this
+//!   library. The caller owns it and must release it with
+//!   `mc_protocol_free_string` exactly once; never with `free()`.
+//! - Failures, including panics, are reported as `{"error": "..."}` rather
+//!   than unwinding across the boundary. The return value is never null.
+
+// Raw pointers are owned and validated by the C caller; see the rules above.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use mc_protocol::{conversation, protocol, tokens};
+use serde_json::{json, Value};
+
+/// Free a string returned by any `mc_protocol_*` function. Null is ignored.
+#[no_mangle]
+pub extern "C" fn mc_protocol_free_string(ptr: *mut c_char) {
+    if !ptr.is_null() {
+        unsafe {
+            drop(CString::from_raw(ptr));
+        }
+    }
+}
+
+/// Validate a task file. Returns `{"valid": bool, "errors": [...]}`.
+#[no_mangle]
+pub extern "C" fn mc_protocol_validate_task(path: *const u8, path_len: usize) -> *mut c_char {
+    call(|| {
+        let path = from_utf8(path, path_len)?;
+        let result = protocol::validate_task(&path).map_err(|e| e.to_string())?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    })
+}
+
+/// Parse a response file into its summary, details, files, and notes.
+#[no_mangle]
+pub extern "C" fn mc_protocol_parse_response(path: *const u8, path_len: usize) -> *mut c_char {
+    call(|| {
+        let path = from_utf8(path, path_len)?;
+        let result = protocol::parse_response(&path).map_err(|e| e.to_string())?;
+        serde_json::to_value(result).map_err(|e| e.to_string())
+    })
+}
+
+/// Count tokens in a string. Returns `{"tokens": n}`.
+#[no_mangle]
+pub extern "C" fn mc_protocol_count_string_tokens(text: *const u8, text_len: usize) -> *mut c_char {
+    call(|| {
+        let text = from_utf8(text, text_len)?;
+        Ok(json!({ "tokens": tokens::count_string_tokens(&text) }))
+    })
+}
+
+/// Check whether a conversation file ends with the completion marker.
+///
+/// Returns `{"complete": true, "response": "..."}` or `{"complete": false}`.
+#[no_mangle]
+pub extern "C" fn mc_protocol_check_conversation_complete(
+    path: *const u8,
+    path_len: usize,
+) -> *mut c_char {
+    call(|| {
+        let path = from_utf8(path, path_len)?;
+        let completion = conversation::Completion::default();
+        match conversation::check_complete(Path::new(&path), &completion)
+            .map_err(|e| e.to_string())?
+        {
+            Some(done) => Ok(json!({ "complete": true, "response": done.exchange.response })),
+            None => Ok(json!({ "complete": false })),
+        }
+    })
+}
+
+/// Run `f`, turning errors and panics into error JSON.
+fn call<F>(f: F) -> *mut c_char
+where
+    F: FnOnce() -> Result<Value, String>,
+{
+    let value = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(error)) => json!({ "error": error }),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            json!({ "error": format!("panic: {}", message) })
+        }
+    };
+    to_c_string(&value.to_string())
+}
+
+/// Borrow a caller-owned UTF-8 buffer as a String.
+fn from_utf8(ptr: *const u8, len: usize) -> Result<String, String> {
+    if ptr.is_null() {
+        return if len == 0 {
+            Ok(String::new())
+        } else {
+            Err("null pointer with non-zero length".to_string())
+        };
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(ptr, len) };
+    std::str::from_utf8(bytes)
+        .map(str::to_string)
+        .map_err(|e| format!("invalid UTF-8 input: {}", e))
+}
+
+/// Helper to convert a Rust string to an owned C string.
+fn to_c_string(s: &str) -> *mut c_char {
+    // JSON escapes control characters, so interior NULs can't occur
+    CString::new(s)
+        .map(CString::into_raw)
+        .unwrap_or(std::ptr::null_mut())
+}
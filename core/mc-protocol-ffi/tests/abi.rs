@@ -0,0 +1,105 @@
+//! Load the built cdylib the way a foreign caller would and exercise each export.
+
+use std::ffi::CStr;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use libloading::{Library, Symbol};
+use serde_json::Value;
+
+type InputFn = unsafe extern "C" fn(*const u8, usize) -> *mut c_char;
+type FreeFn = unsafe extern "C" fn(*mut c_char);
+
+/// The cdylib sits next to the `deps/` directory holding this test binary.
+fn library_path() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let profile_dir = exe.parent().and_then(|deps| deps.parent()).unwrap();
+    profile_dir.join(libloading::library_filename("mc_protocol_ffi"))
+}
+
+struct Abi {
+    lib: Library,
+}
+
+impl Abi {
+    /// Load once and never unload: closing a Rust cdylib that has registered
+    /// thread-local destructors crashes the process on thread exit.
+    fn load() -> &'static Self {
+        static ABI: OnceLock<Abi> = OnceLock::new();
+        ABI.get_or_init(|| {
+            let lib = unsafe { Library::new(library_path()) }.expect("cdylib should be built");
+            Abi { lib }
+        })
+    }
+
+    /// Call an export with `input` and decode (then free) the returned JSON.
+    fn call(&self, name: &str, input: &[u8]) -> Value {
+        unsafe {
+            let func: Symbol<InputFn> = self.lib.get(name.as_bytes()).unwrap();
+            let free: Symbol<FreeFn> = self.lib.get(b"mc_protocol_free_string").unwrap();
+
+            let ptr = func(input.as_ptr(), input.len());
+            assert!(!ptr.is_null());
+            let json = CStr::from_ptr(ptr).to_str().unwrap().to_string();
+            free(ptr);
+            serde_json::from_str(&json).unwrap()
+        }
+    }
+}
+
+#[test]
+fn test_exports_round_trip_json() {
+    let abi = Abi::load();
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    let task = temp_dir.path().join("task.md");
+    fs::write(&task, "# Task: 001\n").unwrap();
+    let result = abi.call(
+        "mc_protocol_validate_task",
+        task.to_str().unwrap().as_bytes(),
+    );
+    assert_eq!(result["valid"], false);
+    assert!(!result["errors"].as_array().unwrap().is_empty());
+
+    let response = temp_dir.path().join("response.md");
+    fs::write(&response, "# Response: 001\n\n## Summary\nDone.\n").unwrap();
+    let result = abi.call(
+        "mc_protocol_parse_response",
+        response.to_str().unwrap().as_bytes(),
+    );
+    assert_eq!(result["summary"], "Done.");
+
+    let result = abi.call("mc_protocol_count_string_tokens", b"Hello world");
+    assert!(result["tokens"].as_u64().unwrap() > 0);
+
+    let conversation = temp_dir.path().join("conversation.md");
+    fs::write(&conversation, "## Assistant\n\nHi there.\n\n---END---\n").unwrap();
+    let result = abi.call(
+        "mc_protocol_check_conversation_complete",
+        conversation.to_str().unwrap().as_bytes(),
+    );
+    assert_eq!(result["complete"], true);
+    assert_eq!(result["response"], "Hi there.");
+}
+
+#[test]
+fn test_errors_come_back_as_json() {
+    let abi = Abi::load();
+
+    let result = abi.call("mc_protocol_parse_response", b"/nonexistent/response.md");
+    assert!(result["error"].as_str().unwrap().contains("not found"));
+
+    let result = abi.call("mc_protocol_count_string_tokens", &[0xff, 0xfe]);
+    assert!(result["error"].as_str().unwrap().contains("UTF-8"));
+
+    unsafe {
+        let func: Symbol<InputFn> = abi.lib.get(b"mc_protocol_validate_task").unwrap();
+        let free: Symbol<FreeFn> = abi.lib.get(b"mc_protocol_free_string").unwrap();
+        let ptr = func(std::ptr::null(), 4);
+        let json = CStr::from_ptr(ptr).to_str().unwrap().to_string();
+        free(ptr);
+        assert!(json.contains("null pointer"));
+    }
+}
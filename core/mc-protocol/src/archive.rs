@@ -0,0 +1,380 @@
+//! Moving completed tasks out of the mission's working directories.
+//!
+//! A task is archived with its status and response files, into
+//! `{mission_dir}/archive/{YYYY-MM-DD}/` under the same `tasks/`, `status/`
+//! and `responses/` names, so the watchers stop seeing it and
+//! [`unarchive`] can put it back as it was.
+
+use crate::clock::{self, Clock};
+use crate::error::McError;
+use crate::protocol::{self, TaskFilter};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Which tasks [`archive`] moves.
+#[derive(Debug, Clone)]
+pub struct ArchiveOptions {
+    /// Only tasks whose files were all last modified longer ago than this
+    pub older_than: Duration,
+    /// Exactly these tasks, whatever their age, instead
+    pub task_ids: Vec<String>,
+    /// Report what would be moved without moving it
+    pub dry_run: bool,
+    /// Source of "now" for file ages and the archive date
+    pub clock: Arc<dyn Clock>,
+}
+
+impl Default for ArchiveOptions {
+    fn default() -> Self {
+        ArchiveOptions {
+            older_than: Duration::from_secs(7 * 24 * 60 * 60),
+            task_ids: Vec::new(),
+            dry_run: false,
+            clock: clock::system(),
+        }
+    }
+}
+
+/// A task that was (or in a dry run, would be) moved.
+#[derive(Debug, Serialize)]
+pub struct MovedTask {
+    pub task_id: String,
+    /// Where its files ended up
+    pub files: Vec<String>,
+}
+
+/// A requested task left in place, and why.
+#[derive(Debug, Serialize)]
+pub struct SkippedTask {
+    pub task_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArchiveReport {
+    pub dry_run: bool,
+    pub archive_dir: String,
+    pub archived: Vec<MovedTask>,
+    /// Tasks asked for by id that couldn't be archived
+    pub skipped: Vec<SkippedTask>,
+}
+
+/// Move complete tasks (task, status and response files all present) into
+/// today's archive directory.
+///
+/// A task without a status file is never archived. A task's claim file, if
+/// any, moves with it. Should a move fail partway, the files already moved
+/// are put back before the error is returned.
+pub fn archive(mission_dir: &Path, options: &ArchiveOptions) -> Result<ArchiveReport, McError> {
+    if !mission_dir.is_dir() {
+        return Err(McError::not_found(format!(
+            "Mission directory not found: {}",
+            mission_dir.display()
+        )));
+    }
+
+    let now = options.clock.system_now();
+    let date = chrono::DateTime::<chrono::Utc>::from(now).format("%Y-%m-%d");
+    let archive_dir = mission_dir.join("archive").join(date.to_string());
+    let mut report = ArchiveReport {
+        dry_run: options.dry_run,
+        archive_dir: archive_dir.to_string_lossy().to_string(),
+        archived: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    let task_ids: Vec<String> = if options.task_ids.is_empty() {
+        protocol::task_files(mission_dir, &TaskFilter::default())?
+            .iter()
+            .map(|path| protocol::task_id(path))
+            .collect()
+    } else {
+        options.task_ids.clone()
+    };
+
+    for task_id in task_ids {
+        let files = task_files(&task_id);
+        if let Some(reason) = incomplete(mission_dir, &files) {
+            if !options.task_ids.is_empty() {
+                report.skipped.push(SkippedTask { task_id, reason });
+            }
+            continue;
+        }
+        if options.task_ids.is_empty() {
+            let newest = files
+                .iter()
+                .filter_map(|file| fs::metadata(mission_dir.join(file)).ok())
+                .filter_map(|metadata| metadata.modified().ok())
+                .max();
+            let age = newest.and_then(|newest| now.duration_since(newest).ok());
+            if age.is_none_or(|age| age <= options.older_than) {
+                continue;
+            }
+        }
+
+        let files: Vec<&PathBuf> = files
+            .iter()
+            .filter(|file| mission_dir.join(file).exists())
+            .collect();
+        let moves: Vec<(PathBuf, PathBuf)> = files
+            .iter()
+            .map(|file| (mission_dir.join(file), archive_dir.join(file)))
+            .collect();
+        if !options.dry_run {
+            move_all(&moves)?;
+        }
+        report.archived.push(MovedTask {
+            task_id,
+            files: moves
+                .iter()
+                .map(|(_, to)| to.to_string_lossy().to_string())
+                .collect(),
+        });
+    }
+    Ok(report)
+}
+
+/// Move an archived task's files back into the mission, from the most
+/// recent archive that holds it.
+///
+/// Nothing is moved if any of the files already exists in the mission again.
+pub fn unarchive(mission_dir: &Path, task_id: &str) -> Result<MovedTask, McError> {
+    let archive_root = mission_dir.join("archive");
+    let mut dates: Vec<PathBuf> = match fs::read_dir(&archive_root) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(McError::io(archive_root.display(), e)),
+    };
+    dates.sort();
+
+    let files = task_files(task_id);
+    let archive_dir = dates
+        .iter()
+        .rev()
+        .find(|dir| dir.join(&files[0]).exists())
+        .ok_or_else(|| McError::not_found(format!("Task {} is not archived", task_id)))?;
+
+    let moves: Vec<(PathBuf, PathBuf)> = files
+        .iter()
+        .filter(|file| archive_dir.join(file).exists())
+        .map(|file| (archive_dir.join(file), mission_dir.join(file)))
+        .collect();
+    if let Some((_, to)) = moves.iter().find(|(_, to)| to.exists()) {
+        return Err(McError::Io(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Would overwrite {}", to.display()),
+        )));
+    }
+    move_all(&moves)?;
+
+    // Leave no empty directories behind in the archive
+    for dir in ["tasks", "status", "responses"] {
+        let _ = fs::remove_dir(archive_dir.join(dir));
+    }
+    let _ = fs::remove_dir(archive_dir);
+
+    Ok(MovedTask {
+        task_id: task_id.to_string(),
+        files: moves
+            .iter()
+            .map(|(_, to)| to.to_string_lossy().to_string())
+            .collect(),
+    })
+}
+
+/// A task's files relative to the mission directory: the task, status and
+/// response files, then the optional claim file.
+fn task_files(task_id: &str) -> [PathBuf; 4] {
+    [
+        Path::new("tasks").join(format!("task-{}.md", task_id)),
+        Path::new("status").join(format!("task-{}.status", task_id)),
+        Path::new("responses").join(format!("task-{}.md", task_id)),
+        Path::new("status").join(format!("task-{}.claimed", task_id)),
+    ]
+}
+
+/// Which of the task, status and response files is missing, if one is.
+fn incomplete(mission_dir: &Path, files: &[PathBuf; 4]) -> Option<String> {
+    let missing = ["task", "status", "response"]
+        .into_iter()
+        .zip(files)
+        .find(|(_, file)| !mission_dir.join(file).is_file())?;
+    Some(format!("no {} file", missing.0))
+}
+
+/// Move each `(from, to)` pair, putting back the ones already moved if one
+/// fails.
+fn move_all(moves: &[(PathBuf, PathBuf)]) -> Result<(), McError> {
+    for (done, (from, to)) in moves.iter().enumerate() {
+        if let Err(e) = move_file(from, to) {
+            for (from, to) in moves[..done].iter().rev() {
+                let _ = move_file(to, from);
+            }
+            return Err(McError::io(from.display(), e));
+        }
+    }
+    Ok(())
+}
+
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    move_with(from, to, |from, to| fs::rename(from, to))
+}
+
+/// Move `from` to `to` with `rename`, copying and removing instead when
+/// they are on different devices. The copy keeps the modification time.
+fn move_with<R>(from: &Path, to: &Path, rename: R) -> io::Result<()>
+where
+    R: Fn(&Path, &Path) -> io::Result<()>,
+{
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            let modified = fs::metadata(from)?.modified()?;
+            fs::copy(from, to)?;
+            File::options()
+                .write(true)
+                .open(to)?
+                .set_modified(modified)?;
+            fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prune::tests::write_aged;
+    use tempfile::TempDir;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// Task `id`'s files, as many of task, status and response as `parts`.
+    fn write_task(mission: &Path, id: &str, parts: usize, age: Duration) {
+        for file in &task_files(id)[..parts] {
+            write_aged(&mission.join(file), id, age);
+        }
+    }
+
+    #[test]
+    fn test_archives_only_old_complete_tasks() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission = temp_dir.path();
+        write_task(mission, "old", 3, 10 * DAY);
+        write_task(mission, "new", 3, DAY);
+        write_task(mission, "waiting", 1, 10 * DAY);
+        // A response but no status: never archived, however old
+        write_task(mission, "unfinished", 1, 10 * DAY);
+        write_aged(&mission.join("responses/task-unfinished.md"), "", 10 * DAY);
+        write_aged(&mission.join("status/task-old.claimed"), "", 10 * DAY);
+
+        let dry = archive(
+            mission,
+            &ArchiveOptions {
+                dry_run: true,
+                ..ArchiveOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(dry.archived.len(), 1);
+        assert!(mission.join("tasks/task-old.md").exists());
+
+        let report = archive(mission, &ArchiveOptions::default()).unwrap();
+        let archive_dir = PathBuf::from(&report.archive_dir);
+        assert_eq!(report.archived.len(), 1);
+        assert_eq!(report.archived[0].task_id, "old");
+        assert_eq!(report.archived[0].files.len(), 4);
+        assert!(report.skipped.is_empty());
+        for file in task_files("old") {
+            assert!(!mission.join(&file).exists());
+            assert!(archive_dir.join(&file).exists());
+        }
+        assert!(mission.join("tasks/task-new.md").exists());
+        assert!(mission.join("tasks/task-unfinished.md").exists());
+    }
+
+    #[test]
+    fn test_archive_by_id_reports_skips() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission = temp_dir.path();
+        write_task(mission, "001", 3, Duration::ZERO);
+        write_task(mission, "002", 1, Duration::ZERO);
+
+        let options = ArchiveOptions {
+            task_ids: vec!["001".into(), "002".into(), "003".into()],
+            ..ArchiveOptions::default()
+        };
+        let report = archive(mission, &options).unwrap();
+        assert_eq!(report.archived.len(), 1);
+        let skipped: Vec<(&str, &str)> = report
+            .skipped
+            .iter()
+            .map(|s| (s.task_id.as_str(), s.reason.as_str()))
+            .collect();
+        assert_eq!(
+            skipped,
+            [("002", "no status file"), ("003", "no task file")]
+        );
+    }
+
+    #[test]
+    fn test_unarchive_restores_task() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission = temp_dir.path();
+        write_task(mission, "001", 3, 10 * DAY);
+        let report = archive(mission, &ArchiveOptions::default()).unwrap();
+
+        let restored = unarchive(mission, "001").unwrap();
+        assert_eq!(restored.files.len(), 3);
+        for file in &task_files("001")[..3] {
+            assert_eq!(fs::read_to_string(mission.join(file)).unwrap(), "001");
+        }
+        // The emptied archive directory goes too
+        assert!(!Path::new(&report.archive_dir).exists());
+
+        assert!(unarchive(mission, "001").is_err());
+    }
+
+    #[test]
+    fn test_unarchive_refuses_to_overwrite() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission = temp_dir.path();
+        write_task(mission, "001", 3, 10 * DAY);
+        archive(mission, &ArchiveOptions::default()).unwrap();
+        write_task(mission, "001", 1, Duration::ZERO);
+
+        let error = unarchive(mission, "001").unwrap_err();
+        assert!(error.to_string().contains("Would overwrite"), "{}", error);
+        assert!(!mission.join("status/task-001.status").exists());
+    }
+
+    #[test]
+    fn test_move_across_devices_copies() {
+        let temp_dir = TempDir::new().unwrap();
+        let from = temp_dir.path().join("tasks/task-001.md");
+        let to = temp_dir.path().join("archive/2026-01-22/tasks/task-001.md");
+        write_aged(&from, "# Task: 001\n", 10 * DAY);
+        let modified = fs::metadata(&from).unwrap().modified().unwrap();
+
+        let cross_device = |_: &Path, _: &Path| Err(io::Error::from(io::ErrorKind::CrossesDevices));
+        move_with(&from, &to, cross_device).unwrap();
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(&to).unwrap(), "# Task: 001\n");
+        assert_eq!(fs::metadata(&to).unwrap().modified().unwrap(), modified);
+
+        // Other failures are not retried as a copy
+        let denied = |_: &Path, _: &Path| Err(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(move_with(&to, &from, denied).is_err());
+        assert!(to.exists());
+    }
+}
@@ -0,0 +1,221 @@
+use crate::error::McError;
+use knowledge::TokenCounter;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Prefix of a Context line that references an attachment.
+pub const ATTACH_PREFIX: &str = "@attach ";
+
+/// Directory under the mission dir holding attachments.
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+/// A file referenced from a task's Context with `@attach <path>`.
+///
+/// Attachments are content-addressed: the file name stem is the SHA-256 of
+/// the file's bytes, so a reference pins exactly one version of the content.
+#[derive(Debug, Serialize)]
+pub struct Attachment {
+    /// Reference as written in the task
+    pub path: String,
+    pub exists: bool,
+    pub bytes: u64,
+    pub tokens: usize,
+    /// File content, only when inlining was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+/// Attachment references in a block of text, in order of appearance.
+pub fn references(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| line.trim().strip_prefix(ATTACH_PREFIX))
+        .map(|reference| reference.trim().to_string())
+        .filter(|reference| !reference.is_empty())
+        .collect()
+}
+
+/// Directory references are relative to: the one containing the mission dir.
+pub fn project_root(mission_dir: &Path) -> PathBuf {
+    match mission_dir.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Hex SHA-256 of raw bytes (no normalization, unlike [`crate::hash`]).
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Find a reference on disk, refusing anything outside the mission's
+/// attachments dir (absolute paths, `..`, symlinks pointing elsewhere).
+fn locate(reference: &str, mission_dir: &Path) -> Result<PathBuf, String> {
+    let path = project_root(mission_dir)
+        .join(reference)
+        .canonicalize()
+        .map_err(|_| format!("Attachment not found: {}", reference))?;
+    let allowed = mission_dir
+        .join(ATTACHMENTS_DIR)
+        .canonicalize()
+        .map_err(|_| format!("Attachment not found: {}", reference))?;
+    if !path.starts_with(&allowed) {
+        return Err(format!(
+            "Attachment outside {}/: {}",
+            ATTACHMENTS_DIR, reference
+        ));
+    }
+    Ok(path)
+}
+
+/// Look up each reference, counting bytes and tokens.
+///
+/// References outside the mission's attachments dir are never read and
+/// show up as missing.
+pub fn resolve(references: &[String], mission_dir: &Path, inline: bool) -> Vec<Attachment> {
+    if references.is_empty() {
+        return Vec::new();
+    }
+
+    let counter = TokenCounter::new();
+    references
+        .iter()
+        .map(
+            |reference| match locate(reference, mission_dir).ok().map(fs::read) {
+                Some(Ok(bytes)) => {
+                    let text = String::from_utf8_lossy(&bytes);
+                    Attachment {
+                        path: reference.clone(),
+                        exists: true,
+                        bytes: bytes.len() as u64,
+                        tokens: counter.count(&text),
+                        content: inline.then(|| text.to_string()),
+                    }
+                }
+                _ => Attachment {
+                    path: reference.clone(),
+                    exists: false,
+                    bytes: 0,
+                    tokens: 0,
+                    content: None,
+                },
+            },
+        )
+        .collect()
+}
+
+/// Check that a reference exists and that its content matches its name.
+///
+/// Returns a validation error message, or `None` if the attachment is sound.
+pub fn verify(reference: &str, mission_dir: &Path) -> Option<String> {
+    let path = match locate(reference, mission_dir) {
+        Ok(path) => path,
+        Err(error) => return Some(error),
+    };
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(_) => return Some(format!("Attachment not found: {}", reference)),
+    };
+
+    let expected = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let actual = sha256_hex(&bytes);
+    if expected != actual {
+        return Some(format!(
+            "Attachment hash mismatch: {} has content hash {}",
+            reference, actual
+        ));
+    }
+    None
+}
+
+/// Copy a file into the mission's attachments dir under its content hash.
+///
+/// Returns the reference to put after [`ATTACH_PREFIX`]. Storing the same
+/// content twice is a no-op.
+pub fn store(mission_dir: &Path, file: &Path) -> Result<String, McError> {
+    let bytes =
+        fs::read(file).map_err(|e| McError::io(format!("Failed to read {}", file.display()), e))?;
+    let extension = file
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_else(|| "md".to_string());
+    let name = format!("{}.{}", sha256_hex(&bytes), extension);
+
+    let dir = mission_dir.join(ATTACHMENTS_DIR);
+    fs::create_dir_all(&dir)?;
+    let dest = dir.join(&name);
+    if !dest.exists() {
+        fs::write(&dest, &bytes)?;
+    }
+
+    let mission_name = mission_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(format!("{}/{}/{}", mission_name, ATTACHMENTS_DIR, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_references() {
+        let context = "Background below.\n@attach .mission/attachments/abc.md\n  @attach  b.md \n";
+        assert_eq!(
+            references(context),
+            vec![".mission/attachments/abc.md", "b.md"]
+        );
+    }
+
+    #[test]
+    fn test_store_and_verify() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().join(".mission");
+        let doc = temp_dir.path().join("design.md");
+        fs::write(&doc, "# Design\n\nLots of context.\n").unwrap();
+
+        let reference = store(&mission_dir, &doc).unwrap();
+        assert!(reference.starts_with(".mission/attachments/"));
+        assert_eq!(store(&mission_dir, &doc).unwrap(), reference);
+
+        assert_eq!(verify(&reference, &mission_dir), None);
+
+        fs::write(temp_dir.path().join(&reference), "tampered").unwrap();
+        let error = verify(&reference, &mission_dir).unwrap();
+        assert!(error.contains("hash mismatch"));
+
+        assert!(verify(".mission/attachments/missing.md", &mission_dir)
+            .unwrap()
+            .contains("not found"));
+    }
+
+    #[test]
+    fn test_references_outside_attachments_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().join("project/.mission");
+        fs::create_dir_all(mission_dir.join(ATTACHMENTS_DIR)).unwrap();
+        let secret = temp_dir.path().join("secret.md");
+        fs::write(&secret, "hunter2").unwrap();
+
+        let absolute = secret.to_string_lossy().to_string();
+        let dotdot = ".mission/attachments/../../../secret.md".to_string();
+        for reference in [&absolute, &dotdot] {
+            let error = verify(reference, &mission_dir).unwrap();
+            assert!(error.contains("outside"), "{}", error);
+        }
+
+        let resolved = resolve(&[absolute, dotdot], &mission_dir, true);
+        assert!(resolved
+            .iter()
+            .all(|attachment| !attachment.exists && attachment.content.is_none()));
+    }
+}
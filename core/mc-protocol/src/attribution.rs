@@ -0,0 +1,409 @@
+//! Which turn and which tool used the tokens: conversation.md token counts
+//! joined with token use reported in an agent's event stream.
+//!
+//! Turn N is the Nth assistant response (see [`tokens::turn_breakdown`]).
+//! An event belongs to the turn in its own `turn` field, else the turn set by
+//! the most recent `turn` event. Events before any `turn` event are placed by
+//! timestamp, in the first response written at or after them.
+
+use crate::error::McError;
+use crate::events::{self, LineError};
+use crate::tokens::{self, TurnTokens};
+use chrono::{DateTime, FixedOffset};
+use mc_events::UnifiedEvent;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// Tool name for results whose call couldn't be identified.
+const UNKNOWN_TOOL: &str = "unknown";
+
+/// Token use attributed to one conversation turn.
+#[derive(Debug, Serialize)]
+pub struct TurnAttribution {
+    pub turn: usize,
+    pub timestamp: Option<String>,
+    pub conversation_tokens: usize,
+    /// Tokens events reported outside tool results (thinking, usage)
+    pub usage_tokens: usize,
+    /// Tool result tokens by tool name
+    pub tool_tokens: BTreeMap<String, usize>,
+    pub cost_usd: f64,
+}
+
+/// A turn present on only one side of the join.
+#[derive(Debug, Serialize)]
+pub struct Unmatched {
+    /// `conversation` or `events`
+    pub source: &'static str,
+    /// `None` for events carrying neither a turn nor a usable timestamp
+    pub turn: Option<usize>,
+    pub tokens: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AttributionTotals {
+    pub conversation_tokens: usize,
+    pub usage_tokens: usize,
+    pub tool_tokens: usize,
+    /// Event tokens that couldn't be placed in any turn
+    pub unattributed_tokens: usize,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AttributionReport {
+    pub turns: Vec<TurnAttribution>,
+    pub totals: AttributionTotals,
+    pub unmatched: Vec<Unmatched>,
+    /// Event lines that didn't match the event schema
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub event_errors: Vec<LineError>,
+}
+
+/// Join `{mission_dir}/conversation.md` with the events in `events_path`.
+pub fn attribution(mission_dir: &Path, events_path: &Path) -> Result<AttributionReport, McError> {
+    let conv_path = mission_dir.join("conversation.md");
+    if !conv_path.exists() {
+        return Err(McError::not_found(format!(
+            "File not found: {}",
+            conv_path.display()
+        )));
+    }
+    if !events_path.exists() {
+        return Err(McError::not_found(format!(
+            "File not found: {}",
+            events_path.display()
+        )));
+    }
+
+    let turns = tokens::turn_breakdown(&fs::read_to_string(&conv_path)?);
+    let (events, event_errors) = events::parse_events(&fs::read_to_string(events_path)?);
+    let mut report = join(&turns, &events);
+    report.event_errors = event_errors;
+    Ok(report)
+}
+
+/// Attribute each event's tokens to a conversation turn.
+pub fn join(turns: &[TurnTokens], events: &[UnifiedEvent]) -> AttributionReport {
+    let mut rows: Vec<TurnAttribution> = turns
+        .iter()
+        .map(|t| TurnAttribution {
+            turn: t.turn,
+            timestamp: t.timestamp.clone(),
+            conversation_tokens: t.tokens,
+            usage_tokens: 0,
+            tool_tokens: BTreeMap::new(),
+            cost_usd: 0.0,
+        })
+        .collect();
+    let response_times: Vec<Option<DateTime<FixedOffset>>> = turns
+        .iter()
+        .map(|t| t.timestamp.as_deref().and_then(parse_time))
+        .collect();
+
+    let mut current_turn = None;
+    let mut last_tool: Option<&str> = None;
+    let mut tools_by_id: BTreeMap<&str, &str> = BTreeMap::new();
+    let mut with_events = BTreeSet::new();
+    let mut missing: BTreeMap<Option<usize>, usize> = BTreeMap::new();
+
+    for event in events {
+        match event.event_type.as_str() {
+            "turn" => {
+                current_turn = event.turn.map(|t| t as usize);
+                continue;
+            }
+            "tool_call" => {
+                last_tool = event.tool.as_deref();
+                if let (Some(id), Some(tool)) = (event.tool_use_id.as_deref(), last_tool) {
+                    tools_by_id.insert(id, tool);
+                }
+            }
+            _ => {}
+        }
+
+        let tokens = event_tokens(event);
+        if tokens == 0 {
+            continue;
+        }
+        let turn = event
+            .turn
+            .map(|t| t as usize)
+            .or(current_turn)
+            .or_else(|| turn_at(event.timestamp.as_deref()?, &response_times));
+
+        let row = match turn.and_then(|t| rows.iter_mut().find(|r| r.turn == t)) {
+            Some(row) => row,
+            None => {
+                *missing.entry(turn).or_insert(0) += tokens;
+                continue;
+            }
+        };
+        with_events.insert(row.turn);
+        if event.event_type == "tool_result" {
+            let by_id = event
+                .tool_use_id
+                .as_deref()
+                .and_then(|id| tools_by_id.get(id).copied());
+            let tool = event
+                .tool
+                .as_deref()
+                .or(by_id)
+                .or(last_tool)
+                .unwrap_or(UNKNOWN_TOOL);
+            *row.tool_tokens.entry(tool.to_string()).or_insert(0) += tokens;
+        } else {
+            row.usage_tokens += tokens;
+        }
+    }
+
+    let mut unmatched: Vec<Unmatched> = rows
+        .iter()
+        .filter(|row| !with_events.contains(&row.turn))
+        .map(|row| Unmatched {
+            source: "conversation",
+            turn: Some(row.turn),
+            tokens: row.conversation_tokens,
+            reason: "no events for this turn".to_string(),
+        })
+        .collect();
+    unmatched.extend(missing.into_iter().map(|(turn, tokens)| Unmatched {
+        source: "events",
+        turn,
+        tokens,
+        reason: match turn {
+            Some(turn) => format!("conversation has no turn {}", turn),
+            None => "no turn number or timestamp to place these events".to_string(),
+        },
+    }));
+
+    let mut totals = AttributionTotals::default();
+    for row in rows.iter_mut() {
+        let tool_tokens: usize = row.tool_tokens.values().sum();
+        row.cost_usd =
+            tokens::estimate_cost_usd(row.conversation_tokens + row.usage_tokens + tool_tokens);
+        totals.conversation_tokens += row.conversation_tokens;
+        totals.usage_tokens += row.usage_tokens;
+        totals.tool_tokens += tool_tokens;
+    }
+    totals.unattributed_tokens = unmatched
+        .iter()
+        .filter(|u| u.source == "events")
+        .map(|u| u.tokens)
+        .sum();
+    totals.cost_usd = tokens::estimate_cost_usd(
+        totals.conversation_tokens
+            + totals.usage_tokens
+            + totals.tool_tokens
+            + totals.unattributed_tokens,
+    );
+
+    AttributionReport {
+        turns: rows,
+        totals,
+        unmatched,
+        event_errors: Vec::new(),
+    }
+}
+
+/// Tokens an event reports, or for a tool result without a count, the
+/// tokens in its result text.
+fn event_tokens(event: &UnifiedEvent) -> usize {
+    match (event.tokens, event.event_type.as_str(), &event.result) {
+        (Some(tokens), _, _) => tokens as usize,
+        (None, "tool_result", Some(result)) => tokens::count_string_tokens(result),
+        _ => 0,
+    }
+}
+
+/// The first turn whose response was written at or after `timestamp`.
+fn turn_at(timestamp: &str, response_times: &[Option<DateTime<FixedOffset>>]) -> Option<usize> {
+    let at = parse_time(timestamp)?;
+    response_times
+        .iter()
+        .position(|time| time.is_some_and(|time| time >= at))
+        .map(|i| i + 1)
+}
+
+fn parse_time(value: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(value).ok()
+}
+
+/// The per-turn rows as CSV, one column per tool, with a closing `total` row.
+pub fn to_csv(report: &AttributionReport) -> String {
+    let tools: BTreeSet<&str> = report
+        .turns
+        .iter()
+        .flat_map(|row| row.tool_tokens.keys().map(String::as_str))
+        .collect();
+
+    let mut header = vec![
+        "turn".to_string(),
+        "timestamp".to_string(),
+        "conversation_tokens".to_string(),
+        "usage_tokens".to_string(),
+    ];
+    header.extend(
+        tools
+            .iter()
+            .map(|tool| csv_field(&format!("tool:{}", tool))),
+    );
+    header.push("cost_usd".to_string());
+
+    let mut lines = vec![header.join(",")];
+    for row in &report.turns {
+        let mut fields = vec![
+            row.turn.to_string(),
+            csv_field(row.timestamp.as_deref().unwrap_or_default()),
+            row.conversation_tokens.to_string(),
+            row.usage_tokens.to_string(),
+        ];
+        fields.extend(
+            tools
+                .iter()
+                .map(|tool| row.tool_tokens.get(*tool).copied().unwrap_or(0).to_string()),
+        );
+        fields.push(format!("{:.6}", row.cost_usd));
+        lines.push(fields.join(","));
+    }
+
+    let totals = &report.totals;
+    let mut fields = vec![
+        "total".to_string(),
+        String::new(),
+        totals.conversation_tokens.to_string(),
+        totals.usage_tokens.to_string(),
+    ];
+    fields.extend(tools.iter().map(|tool| {
+        report
+            .turns
+            .iter()
+            .filter_map(|row| row.tool_tokens.get(*tool))
+            .sum::<usize>()
+            .to_string()
+    }));
+    fields.push(format!("{:.6}", totals.cost_usd));
+    lines.push(fields.join(","));
+
+    lines.join("\n") + "\n"
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::count_string_tokens;
+    use tempfile::TempDir;
+
+    const CONVERSATION: &str = "\
+## Human
+List the files
+
+---
+
+## Assistant [2026-01-22T10:01:00Z]
+There are two files.
+
+---END---
+
+## Human
+Read the readme
+
+---
+
+## Assistant [2026-01-22T10:05:00Z]
+The readme describes the project.
+
+---END---
+";
+
+    const EVENTS: &str = r#"{"type":"tool_result","agent_id":"agent-1","tool":"grep","result":"a","tokens":5,"timestamp":"2026-01-22T10:00:30Z"}
+{"type":"turn","agent_id":"agent-1","turn":1}
+{"type":"tool_call","agent_id":"agent-1","tool":"bash","args":{"command":"ls"}}
+{"type":"tool_result","agent_id":"agent-1","result":"src","tokens":40}
+{"type":"thinking","agent_id":"agent-1","content":"two files","tokens":15}
+{"type":"turn","agent_id":"agent-1","turn":2}
+{"type":"tool_call","agent_id":"agent-1","tool":"read_file","args":{"path":"README.md"},"tool_use_id":"call-1"}
+{"type":"tool_call","agent_id":"agent-1","tool":"bash","args":{"command":"wc -l README.md"},"tool_use_id":"call-2"}
+{"type":"tool_result","agent_id":"agent-1","result":"readme","tokens":120,"tool_use_id":"call-1"}
+{"type":"tool_result","agent_id":"agent-1","result":"ok","tokens":10,"tool_use_id":"call-2"}
+{"type":"turn","agent_id":"agent-1","turn":3}
+{"type":"tool_result","agent_id":"agent-1","tool":"bash","result":"x","tokens":7}
+"#;
+
+    fn fixture() -> (TempDir, std::path::PathBuf) {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("conversation.md"), CONVERSATION).unwrap();
+        let events = temp_dir.path().join("agent-1.ndjson");
+        fs::write(&events, EVENTS).unwrap();
+        (temp_dir, events)
+    }
+
+    #[test]
+    fn test_joins_turns_and_reports_unmatched() {
+        let (temp_dir, events) = fixture();
+        let report = attribution(temp_dir.path(), &events).unwrap();
+
+        let turn1 =
+            count_string_tokens("List the files") + count_string_tokens("There are two files.");
+        let turn2 = count_string_tokens("Read the readme")
+            + count_string_tokens("The readme describes the project.");
+
+        assert_eq!(report.turns.len(), 2);
+        let first = &report.turns[0];
+        assert_eq!(first.conversation_tokens, turn1);
+        assert_eq!(first.usage_tokens, 15);
+        // grep placed by timestamp, bash by the preceding tool_call
+        assert_eq!(
+            first.tool_tokens,
+            BTreeMap::from([("bash".to_string(), 40), ("grep".to_string(), 5)])
+        );
+        assert_eq!(first.cost_usd, tokens::estimate_cost_usd(turn1 + 60));
+
+        // Parallel calls resolved by tool_use_id rather than call order
+        let second = &report.turns[1];
+        assert_eq!(second.conversation_tokens, turn2);
+        assert_eq!(
+            second.tool_tokens,
+            BTreeMap::from([("bash".to_string(), 10), ("read_file".to_string(), 120)])
+        );
+
+        assert_eq!(report.unmatched.len(), 1);
+        assert_eq!(report.unmatched[0].source, "events");
+        assert_eq!(report.unmatched[0].turn, Some(3));
+        assert_eq!(report.unmatched[0].tokens, 7);
+
+        assert_eq!(report.totals.conversation_tokens, turn1 + turn2);
+        assert_eq!(report.totals.usage_tokens, 15);
+        assert_eq!(report.totals.tool_tokens, 175);
+        assert_eq!(report.totals.unattributed_tokens, 7);
+    }
+
+    #[test]
+    fn test_csv_has_column_per_tool_and_totals() {
+        let (temp_dir, events) = fixture();
+        let report = attribution(temp_dir.path(), &events).unwrap();
+        let csv = to_csv(&report);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "turn,timestamp,conversation_tokens,usage_tokens,tool:bash,tool:grep,tool:read_file,cost_usd"
+        );
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2].starts_with("2,2026-01-22T10:05:00Z,"));
+        assert!(lines[2].contains(",10,0,120,"));
+        assert!(lines[3].starts_with("total,,"));
+        assert!(lines[3].contains(",50,5,120,"));
+    }
+}
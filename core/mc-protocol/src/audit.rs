@@ -0,0 +1,240 @@
+use crate::error::McError;
+use crate::protocol;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Whether a response's Files Modified matches what actually changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditVerdict {
+    /// Every declared file changed and every change was declared
+    Clean,
+    Mismatch,
+}
+
+/// Declared files (from the response) compared with changed files (from git).
+#[derive(Debug, Serialize)]
+pub struct AuditReport {
+    pub verdict: AuditVerdict,
+    pub declared_and_changed: Vec<String>,
+    pub declared_but_unchanged: Vec<String>,
+    pub changed_but_undeclared: Vec<String>,
+}
+
+/// Compare the response's Files Modified against `changed` files.
+///
+/// Both sides are normalized to repo-relative paths first.
+pub fn audit_response(
+    response_file: &str,
+    repo_root: &Path,
+    changed: &[String],
+) -> Result<AuditReport, McError> {
+    let parsed = protocol::parse_response_in(response_file, repo_root)?;
+    // Canonical, so absolute declared paths under a relative root still match
+    let repo_root = repo_root
+        .canonicalize()
+        .unwrap_or_else(|_| repo_root.to_path_buf());
+    Ok(compare(&parsed.files_modified, changed, &repo_root))
+}
+
+/// Sort declared and changed paths into the three audit sets.
+pub fn compare(declared: &[String], changed: &[String], repo_root: &Path) -> AuditReport {
+    let declared: BTreeSet<String> = declared
+        .iter()
+        .filter_map(|entry| declared_path(entry))
+        .map(|path| normalize(&path, repo_root))
+        .collect();
+    let changed: BTreeSet<String> = changed.iter().map(|p| normalize(p, repo_root)).collect();
+
+    let declared_and_changed: Vec<String> = declared.intersection(&changed).cloned().collect();
+    let declared_but_unchanged: Vec<String> = declared.difference(&changed).cloned().collect();
+    let changed_but_undeclared: Vec<String> = changed.difference(&declared).cloned().collect();
+
+    let verdict = if declared_but_unchanged.is_empty() && changed_but_undeclared.is_empty() {
+        AuditVerdict::Clean
+    } else {
+        AuditVerdict::Mismatch
+    };
+
+    AuditReport {
+        verdict,
+        declared_and_changed,
+        declared_but_unchanged,
+        changed_but_undeclared,
+    }
+}
+
+/// Changed files in `repo_root` according to `git status --porcelain`.
+pub fn git_changed_files(repo_root: &Path) -> Result<Vec<String>, McError> {
+    let output = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=all"])
+        .current_dir(repo_root)
+        .output()
+        .map_err(|e| McError::io("Failed to run git", e))?;
+    if !output.status.success() {
+        return Err(McError::Io(std::io::Error::other(format!(
+            "git status failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))));
+    }
+    Ok(parse_porcelain(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Changed files from a file with one path per line (for hosts without git).
+pub fn read_changed_files(path: &str) -> Result<Vec<String>, McError> {
+    let content =
+        fs::read_to_string(path).map_err(|e| McError::io(format!("Failed to read {}", path), e))?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Paths from `git status --porcelain` (v1) output.
+///
+/// Renames and copies (`R  old -> new`) report the new path.
+pub fn parse_porcelain(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter(|line| line.len() > 3)
+        .map(|line| {
+            let path = &line[3..];
+            let path = match path.split_once(" -> ") {
+                Some((_, new)) => new,
+                None => path,
+            };
+            unquote(path)
+        })
+        .collect()
+}
+
+/// Git quotes paths containing spaces or special characters.
+fn unquote(path: &str) -> String {
+    path.strip_prefix('"')
+        .and_then(|p| p.strip_suffix('"'))
+        .map(|p| p.replace("\\\"", "\"").replace("\\\\", "\\"))
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// The path in a Files Modified entry such as `` `src/lib.rs` (new) ``.
+fn declared_path(entry: &str) -> Option<String> {
+    let path = entry
+        .split_whitespace()
+        .next()?
+        .trim_matches('`')
+        .trim_end_matches(':');
+    if path.is_empty() || path.eq_ignore_ascii_case("none") {
+        None
+    } else {
+        Some(path.to_string())
+    }
+}
+
+/// Repo-relative, forward-slashed form of `path`.
+fn normalize(path: &str, repo_root: &Path) -> String {
+    let path = path.replace('\\', "/");
+    let root = repo_root.to_string_lossy().replace('\\', "/");
+    let root = root.trim_end_matches('/');
+
+    let relative = if !root.is_empty() && root != "." {
+        path.strip_prefix(root)
+            .and_then(|p| p.strip_prefix('/'))
+            .unwrap_or(&path)
+    } else {
+        &path
+    };
+    relative.trim_start_matches("./").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_porcelain_renames_and_quotes() {
+        let output = " M src/lib.rs\nR  old/name.rs -> new/name.rs\n?? \"docs/with space.md\"\nA  added.rs\n";
+        assert_eq!(
+            parse_porcelain(output),
+            vec![
+                "src/lib.rs",
+                "new/name.rs",
+                "docs/with space.md",
+                "added.rs"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_audit_against_stubbed_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let response = temp_dir.path().join("task-001.md");
+        fs::write(
+            &response,
+            "# Response: 001\n\n## Summary\nDone.\n\n## Files Modified\n- `src/lib.rs` (updated)\n- /work/repo/src/main.rs\n- README.md\n",
+        )
+        .unwrap();
+
+        let changed = vec![
+            "src/lib.rs".to_string(),
+            "./src/main.rs".to_string(),
+            "Cargo.lock".to_string(),
+        ];
+        let report = audit_response(
+            response.to_str().unwrap(),
+            Path::new("/work/repo"),
+            &changed,
+        )
+        .unwrap();
+
+        assert_eq!(report.verdict, AuditVerdict::Mismatch);
+        assert_eq!(
+            report.declared_and_changed,
+            vec!["src/lib.rs", "src/main.rs"]
+        );
+        assert_eq!(report.declared_but_unchanged, vec!["README.md"]);
+        assert_eq!(report.changed_but_undeclared, vec!["Cargo.lock"]);
+    }
+
+    #[test]
+    fn test_clean_when_sets_match() {
+        let report = compare(
+            &["src/lib.rs".to_string()],
+            &["src/lib.rs".to_string()],
+            Path::new("."),
+        );
+        assert_eq!(report.verdict, AuditVerdict::Clean);
+    }
+
+    #[test]
+    fn test_git_changed_files_in_temp_repo() {
+        if Command::new("git").arg("--version").output().is_err() {
+            eprintln!("git not found on PATH, skipping");
+            return;
+        }
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(root)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["init", "-q"]);
+        fs::write(root.join("a.rs"), "fn a() {}\n").unwrap();
+        git(&["add", "a.rs"]);
+        git(&["mv", "a.rs", "b.rs"]);
+        fs::write(root.join("c.rs"), "fn c() {}\n").unwrap();
+
+        let mut changed = git_changed_files(root).unwrap();
+        changed.sort();
+        assert_eq!(changed, vec!["b.rs", "c.rs"]);
+    }
+}
@@ -0,0 +1,67 @@
+//! Stopping a blocking watch before it resolves.
+//!
+//! A watch whose [`Cancel`] fires returns a `cancelled` result instead of
+//! waiting on: when its cancel file exists, or when its flag is set, as the
+//! CLI does on SIGINT and SIGTERM. It is checked before the watch starts, on
+//! every event and at least every [`CHECK_INTERVAL`] in between.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Longest a watch goes without checking whether it was cancelled.
+pub const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What cancelled a watch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelReason {
+    /// The cancel file appeared
+    CancelFile,
+    /// The flag was set, by a signal or by the embedding program
+    Signal,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Cancel {
+    /// A file whose appearance cancels the watch
+    pub file: Option<PathBuf>,
+    /// Set to cancel the watch from a signal handler or another thread
+    pub flag: Arc<AtomicBool>,
+}
+
+impl Cancel {
+    /// Why the watch should stop now, if it should.
+    pub fn requested(&self) -> Option<CancelReason> {
+        if self.flag.load(Ordering::Relaxed) {
+            return Some(CancelReason::Signal);
+        }
+        match &self.file {
+            Some(file) if file.exists() => Some(CancelReason::CancelFile),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requested_by_file_or_flag() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let cancel = Cancel {
+            file: Some(temp_dir.path().join("cancel")),
+            ..Cancel::default()
+        };
+        assert_eq!(cancel.requested(), None);
+
+        std::fs::write(temp_dir.path().join("cancel"), "").unwrap();
+        assert_eq!(cancel.requested(), Some(CancelReason::CancelFile));
+
+        cancel.flag.store(true, Ordering::Relaxed);
+        assert_eq!(cancel.requested(), Some(CancelReason::Signal));
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Source of time for watch deadlines and backoff.
+///
+/// Production code uses [`SystemClock`]. Tests (here and in embedding
+/// orchestrators) use [`MockClock`] so timeouts resolve without real waits.
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// Monotonic time, for deadlines.
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time, for comparing against file timestamps.
+    fn system_now(&self) -> SystemTime;
+
+    /// Block for `duration` (backoff between retries).
+    fn sleep(&self, duration: Duration);
+
+    /// Real time to block waiting for an event, given `remaining` until the deadline.
+    fn wait_slice(&self, remaining: Duration) -> Duration {
+        remaining
+    }
+
+    /// Called after waiting `slice` without receiving an event.
+    fn waited(&self, _slice: Duration) {}
+}
+
+/// The real clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Shared handle to the production clock.
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// Real time a watcher blocks per wait under [`MockClock`], so it notices
+/// manual steps quickly.
+const MOCK_WAIT_SLICE: Duration = Duration::from_millis(1);
+
+/// A clock that only moves when told to.
+///
+/// Time advances through [`MockClock::advance`], through `sleep`, and, when
+/// built with [`MockClock::with_auto_advance`], by a fixed step each time a
+/// watcher waits without seeing an event.
+#[derive(Debug)]
+pub struct MockClock {
+    base: Instant,
+    system_base: SystemTime,
+    offset: Mutex<Duration>,
+    auto_advance: Option<Duration>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        MockClock {
+            base: Instant::now(),
+            system_base: SystemTime::now(),
+            offset: Mutex::new(Duration::ZERO),
+            auto_advance: None,
+        }
+    }
+
+    /// Advance by `step` whenever a watcher waits without an event.
+    pub fn with_auto_advance(step: Duration) -> Self {
+        MockClock {
+            auto_advance: Some(step),
+            ..MockClock::new()
+        }
+    }
+
+    /// Step the clock forward.
+    pub fn advance(&self, duration: Duration) {
+        *self.offset.lock().unwrap() += duration;
+    }
+
+    /// Total time the clock has been advanced.
+    pub fn elapsed(&self) -> Duration {
+        *self.offset.lock().unwrap()
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        MockClock::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.base + self.elapsed()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        self.system_base + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+
+    fn wait_slice(&self, remaining: Duration) -> Duration {
+        remaining.min(MOCK_WAIT_SLICE)
+    }
+
+    fn waited(&self, _slice: Duration) {
+        if let Some(step) = self.auto_advance {
+            self.advance(step);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_steps_manually() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        clock.advance(Duration::from_secs(300));
+        clock.sleep(Duration::from_millis(5));
+        assert_eq!(clock.now() - start, Duration::from_millis(300_005));
+        assert_eq!(clock.elapsed(), Duration::from_millis(300_005));
+    }
+
+    #[test]
+    fn test_auto_advance_only_on_idle_waits() {
+        let clock = MockClock::with_auto_advance(Duration::from_secs(10));
+        assert_eq!(clock.elapsed(), Duration::ZERO);
+        clock.waited(clock.wait_slice(Duration::from_secs(60)));
+        assert_eq!(clock.elapsed(), Duration::from_secs(10));
+    }
+}
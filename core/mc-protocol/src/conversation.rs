@@ -1,116 +1,771 @@
-use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
-use serde::Serialize;
+use crate::cancel::CancelReason;
+use crate::error::McError;
+use crate::fswatch::{self, FsWatch, WatchOptions};
+use crate::hash;
+use crate::protocol;
+use chrono::{DateTime, FixedOffset};
+use notify::RecursiveMode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs;
-use std::path::Path;
-use std::sync::mpsc::channel;
-use std::time::Duration;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "status")]
 pub enum ConversationResult {
     #[serde(rename = "complete")]
-    Complete { response: String },
+    Complete {
+        response: String,
+        /// The Human turn the response answered
+        prompt: Option<String>,
+        prompt_timestamp: Option<String>,
+        response_timestamp: Option<String>,
+        /// Re-reads needed before the file stopped changing
+        settle_retries: u32,
+        completed_by: CompletedBy,
+        /// Byte offset of the marker line, to pass back as `--since-offset`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        marker_offset: Option<usize>,
+    },
     #[serde(rename = "timeout")]
     Timeout,
+    /// Stopped by [`WatchOptions::cancel`] before the marker appeared
+    #[serde(rename = "cancelled")]
+    Cancelled { reason: CancelReason },
 }
 
-const END_MARKER: &str = "---END---";
+/// The marker that ends a response unless [`Completion::markers`] says otherwise.
+pub const END_MARKER: &str = "---END---";
 
-/// Watch conversation.md for the ---END--- completion marker.
+/// What counts as the conversation being complete.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    /// Lines that end a response
+    pub markers: Vec<String>,
+    /// Which marker counts
+    pub after: After,
+    /// A file whose appearance completes the conversation, marker or not
+    pub done_file: Option<PathBuf>,
+}
+
+impl Default for Completion {
+    fn default() -> Self {
+        Completion {
+            markers: vec![END_MARKER.to_string()],
+            after: After::EndOfFile,
+            done_file: None,
+        }
+    }
+}
+
+/// Where a marker must be for the conversation to be complete.
+///
+/// Only [`After::EndOfFile`] needs the marker to be the file's last line;
+/// the others still see a response that a later Human turn already follows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum After {
+    /// Ending the file
+    #[default]
+    EndOfFile,
+    /// Ending an assistant response later than this 1-based one
+    Turn(usize),
+    /// Ending an assistant response, on a line starting past this byte offset
+    Offset(usize),
+}
+
+/// Which part of a [`Completion`] fired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompletedBy {
+    Marker,
+    DoneFile,
+}
+
+/// An assistant response and the Human turn it answered.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub response: String,
+    /// The last Human turn since the previous assistant response
+    pub prompt: Option<String>,
+    pub prompt_timestamp: Option<String>,
+    pub response_timestamp: Option<String>,
+}
+
+impl Exchange {
+    /// The assistant turn at `index` of `turns`, with its prompt.
+    fn at(turns: &[ConversationTurn], index: usize) -> Self {
+        let response = &turns[index];
+        let prompt = turns[..index]
+            .iter()
+            .rev()
+            .take_while(|t| t.role != Role::Assistant)
+            .find(|t| t.role == Role::Human);
+        Exchange {
+            response: response.content.clone(),
+            prompt: prompt.map(|t| t.content.clone()),
+            prompt_timestamp: prompt.and_then(|t| t.timestamp.clone()),
+            response_timestamp: response.timestamp.clone(),
+        }
+    }
+
+    fn into_result(
+        self,
+        settle_retries: u32,
+        completed_by: CompletedBy,
+        marker_offset: Option<usize>,
+    ) -> ConversationResult {
+        ConversationResult::Complete {
+            response: self.response,
+            prompt: self.prompt,
+            prompt_timestamp: self.prompt_timestamp,
+            response_timestamp: self.response_timestamp,
+            settle_retries,
+            completed_by,
+            marker_offset,
+        }
+    }
+}
+
+/// A complete conversation's last response, and what said it was complete.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completed {
+    pub exchange: Exchange,
+    pub completed_by: CompletedBy,
+    /// Byte offset of the marker line, when a marker fired
+    pub marker_offset: Option<usize>,
+}
+
+/// Watch conversation.md until `completion` is met.
 ///
-/// Returns when the file ends with ---END--- after the last ## Assistant section.
+/// Returns once a marker ends an assistant response where
+/// [`Completion::after`] asks for it, or when the done file appears.
 pub fn watch(
     mission_dir: &str,
     timeout: Duration,
-) -> Result<ConversationResult, Box<dyn std::error::Error>> {
+    completion: &Completion,
+    options: &WatchOptions,
+) -> Result<ConversationResult, McError> {
+    watch_streaming(mission_dir, timeout, completion, options, |_| {})
+}
+
+/// [`watch`], passing `on_chunk` the response as it is written.
+///
+/// The response streamed is the last ## Assistant section while no turn
+/// follows it. Each chunk is only the text appended since the last one; when
+/// the section is rewritten rather than extended, its whole text is sent
+/// again. The rest of the response is sent before the watch returns.
+pub fn watch_streaming<F: FnMut(&str)>(
+    mission_dir: &str,
+    timeout: Duration,
+    completion: &Completion,
+    options: &WatchOptions,
+    mut on_chunk: F,
+) -> Result<ConversationResult, McError> {
     let conv_path = Path::new(mission_dir).join("conversation.md");
 
+    if let Some(reason) = options.cancel.requested() {
+        return Ok(ConversationResult::Cancelled { reason });
+    }
+
+    let mut streamed = Streamed::default();
+    let mut stream = |streamed: &mut Streamed| -> Result<(), McError> {
+        let content = read_conversation(&conv_path)?;
+        streamed.update(&content, &completion.markers, &mut on_chunk);
+        Ok(())
+    };
+
     // Check if already complete
-    if conv_path.exists() {
-        if let Some(response) = check_complete(&conv_path)? {
-            return Ok(ConversationResult::Complete { response });
+    let deadline = options.clock.now() + timeout;
+    stream(&mut streamed)?;
+    if let Some(result) = settle_complete(&conv_path, completion, options, deadline)? {
+        stream(&mut streamed)?;
+        return Ok(result);
+    }
+
+    // Ensure the watched directories exist
+    let done_dir = completion.done_file.as_deref().and_then(Path::parent);
+    for dir in conv_path.parent().into_iter().chain(done_dir) {
+        if !dir.as_os_str().is_empty() && !dir.exists() {
+            fs::create_dir_all(dir)?;
         }
     }
 
-    // Ensure parent directory exists
-    if let Some(parent) = conv_path.parent() {
-        if !parent.exists() {
-            fs::create_dir_all(parent)?;
+    // Watch the mission directory (conversation.md's parent), and the done
+    // file's when it lives elsewhere
+    let mut roots = vec![(
+        options.watch_path(conv_path.parent().unwrap_or(Path::new("."))),
+        RecursiveMode::NonRecursive,
+    )];
+    if let Some(dir) = done_dir {
+        let dir = options.watch_path(if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        });
+        if roots.iter().all(|(root, _)| *root != dir) {
+            roots.push((dir, RecursiveMode::NonRecursive));
         }
     }
+    let fs_watch = FsWatch::with_roots(&roots, options, deadline)?;
+    let done_name = completion.done_file.as_deref().and_then(Path::file_name);
 
-    // Set up watcher on the mission directory
-    let (tx, rx) = channel();
-    let mut watcher = RecommendedWatcher::new(tx, Config::default())?;
+    let result = fswatch::wait_for(&fs_watch, deadline, |event| {
+        // Check if conversation.md or the done file changed
+        let relevant = event.paths.iter().any(|p| {
+            p.ends_with("conversation.md") || done_name.is_some_and(|name| p.ends_with(name))
+        });
+        if relevant {
+            stream(&mut streamed)?;
+            let result = settle_complete(&conv_path, completion, options, deadline)?;
+            if result.is_some() {
+                stream(&mut streamed)?;
+            }
+            return Ok(result);
+        }
+        Ok(None)
+    })?;
+
+    Ok(match (result, fs_watch.cancelled()) {
+        (Some(result), _) => result,
+        (None, Some(reason)) => ConversationResult::Cancelled { reason },
+        (None, None) => ConversationResult::Timeout,
+    })
+}
 
-    // Watch the mission directory (conversation.md's parent)
-    let watch_path = conv_path.parent().unwrap_or(Path::new("."));
-    watcher.watch(watch_path, RecursiveMode::NonRecursive)?;
+/// Check if the conversation file ends with one of the markers, or the done
+/// file exists.
+pub fn check_complete(path: &Path, completion: &Completion) -> Result<Option<Completed>, McError> {
+    let content = read_conversation(path)?;
 
-    let deadline = std::time::Instant::now() + timeout;
-    loop {
-        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
-        if remaining.is_zero() {
-            return Ok(ConversationResult::Timeout);
+    if let Some((exchange, offset)) = complete_response(&content, completion) {
+        return Ok(Some(Completed {
+            exchange,
+            completed_by: CompletedBy::Marker,
+            marker_offset: Some(offset),
+        }));
+    }
+    if completion.done_file.as_deref().is_some_and(Path::exists) {
+        return Ok(Some(Completed {
+            exchange: extract_last_response(&content, &completion.markers),
+            completed_by: CompletedBy::DoneFile,
+            marker_offset: None,
+        }));
+    }
+    Ok(None)
+}
+
+/// The conversation file's content, empty while it doesn't exist.
+fn read_conversation(path: &Path) -> Result<String, McError> {
+    if !path.exists() {
+        return Ok(String::new());
+    }
+    Ok(fs::read_to_string(path)?)
+}
+
+/// The part of the response being written that was already streamed.
+#[derive(Debug, Default)]
+struct Streamed {
+    /// Where the response's section starts
+    start: usize,
+    text: String,
+}
+
+impl Streamed {
+    /// Send `on_chunk` whatever `content` adds to the response being written.
+    fn update<S: AsRef<str>>(
+        &mut self,
+        content: &str,
+        markers: &[S],
+        on_chunk: &mut impl FnMut(&str),
+    ) {
+        let Some(turn) = parse_turns(content, markers).pop() else {
+            return;
+        };
+        if turn.role != Role::Assistant {
+            return;
+        }
+        // From the first line of text, past the blank line under the header
+        let text = content[turn.start_offset..turn.end_offset.unwrap_or(content.len())]
+            .trim_start_matches(['\r', '\n']);
+        let new = match text.strip_prefix(self.text.as_str()) {
+            Some(new) if turn.start_offset == self.start => new,
+            // A new section, or this one rewritten
+            _ => text,
+        };
+        if !new.is_empty() {
+            on_chunk(new);
         }
+        self.start = turn.start_offset;
+        self.text = text.to_string();
+    }
+}
+
+/// Like [`check_complete`], but once a marker is seen, wait for the file to
+/// stop changing and judge the settled content instead.
+pub(crate) fn settle_complete(
+    path: &Path,
+    completion: &Completion,
+    options: &WatchOptions,
+    deadline: Instant,
+) -> Result<Option<ConversationResult>, McError> {
+    match check_complete(path, completion)? {
+        None => return Ok(None),
+        Some(completed) if completed.completed_by == CompletedBy::DoneFile => {
+            return Ok(Some(completed.exchange.into_result(
+                0,
+                CompletedBy::DoneFile,
+                None,
+            )))
+        }
+        Some(_) => {}
+    }
 
-        match rx.recv_timeout(remaining) {
-            Ok(Ok(event)) => {
-                // Check if conversation.md was modified
-                if event.paths.iter().any(|p| p.ends_with("conversation.md")) {
-                    if let Some(response) = check_complete(&conv_path)? {
-                        return Ok(ConversationResult::Complete { response });
-                    }
+    let settled = fswatch::read_settled(path, options, deadline)?;
+    Ok(
+        complete_response(&settled.content, completion).map(|(exchange, offset)| {
+            exchange.into_result(settled.retries, CompletedBy::Marker, Some(offset))
+        }),
+    )
+}
+
+/// The response a marker in `content` completed, and the marker's offset.
+fn complete_response(content: &str, completion: &Completion) -> Option<(Exchange, usize)> {
+    let turns = parse_turns(content, &completion.markers);
+    let mut responses = turns
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| t.role == Role::Assistant);
+    let (index, offset) = match completion.after {
+        After::EndOfFile => {
+            let trimmed = content.trim_end();
+            let marker = completion
+                .markers
+                .iter()
+                .find(|m| trimmed.ends_with(m.as_str()))?;
+            let index = responses.next_back().map(|(i, _)| i);
+            (index, trimmed.len() - marker.len())
+        }
+        After::Turn(n) => responses
+            .skip(n)
+            .find_map(|(i, t)| Some((Some(i), t.end_offset?)))?,
+        After::Offset(offset) => responses.find_map(|(i, t)| match t.end_offset {
+            Some(end) if end > offset => Some((Some(i), end)),
+            _ => None,
+        })?,
+    };
+    let exchange = index.map(|i| Exchange::at(&turns, i)).unwrap_or_default();
+    Some((exchange, offset))
+}
+
+/// Extract the last assistant response, and its prompt, from the conversation file.
+fn extract_last_response<S: AsRef<str>>(content: &str, markers: &[S]) -> Exchange {
+    let turns = parse_turns(content, markers);
+    turns
+        .iter()
+        .rposition(|turn| turn.role == Role::Assistant)
+        .map(|i| Exchange::at(&turns, i))
+        .unwrap_or_default()
+}
+
+/// Separator written after a Human section.
+const TURN_SEPARATOR: &str = "---";
+
+/// Who wrote a conversation turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Human,
+    Assistant,
+    Other,
+}
+
+/// One `## Role [timestamp]` section of conversation.md.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversationTurn {
+    pub role: Role,
+    pub timestamp: Option<String>,
+    pub content: String,
+    /// Followed by `---END---` (assistant) or `---` (human)
+    pub complete: bool,
+    /// Byte offset of the line after the header
+    #[serde(default)]
+    pub start_offset: usize,
+    /// Byte offset of the line that completed the turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_offset: Option<usize>,
+    /// Hash of the normalized turn content, for cache invalidation
+    pub content_hash: String,
+}
+
+/// Split conversation.md content into its turns, in file order.
+///
+/// Inside a fenced code block, `## ` lines and `---` are turn content; only
+/// the end marker still ends the turn, so a fence left open can't keep a
+/// response from completing.
+pub fn parse_conversation(content: &str) -> Vec<ConversationTurn> {
+    parse_turns(content, &[END_MARKER])
+}
+
+/// [`parse_conversation`], with `markers` ending responses instead of `---END---`.
+fn parse_turns<S: AsRef<str>>(content: &str, markers: &[S]) -> Vec<ConversationTurn> {
+    let is_marker = |line: &str| markers.iter().any(|m| line == m.as_ref());
+    let mut turns = Vec::new();
+    let mut current: Option<OpenTurn> = None;
+    // Backticks of the fence the current turn has open
+    let mut open_fence: Option<usize> = None;
+    let mut offset = 0;
+
+    for raw in content.split_inclusive('\n') {
+        let line_offset = offset;
+        offset += raw.len();
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if open_fence.is_none() {
+            if let Some(header) = line.strip_prefix("## ") {
+                if let Some(turn) = current.take() {
+                    turns.push(finish_turn(turn));
                 }
+                let (role, timestamp) = parse_header(header);
+                current = Some((role, timestamp, Vec::new(), offset, None));
+                continue;
             }
-            Ok(Err(e)) => return Err(Box::new(e)),
-            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
-                return Ok(ConversationResult::Timeout);
+        }
+
+        if let Some((role, _, body, _, end)) = current.as_mut() {
+            if end.is_some() {
+                continue;
             }
-            Err(e) => return Err(Box::new(e)),
+            let trimmed = line.trim();
+            let terminator = match role {
+                _ if open_fence.is_some() => is_marker(trimmed),
+                Role::Assistant => is_marker(trimmed),
+                _ => trimmed == TURN_SEPARATOR || is_marker(trimmed),
+            };
+            if terminator {
+                *end = Some(line_offset);
+                open_fence = None;
+                continue;
+            }
+            body.push(line);
+            open_fence = match (protocol::fence(line), open_fence) {
+                (Some((ticks, _)), None) => Some(ticks),
+                (Some((ticks, rest)), Some(open)) if ticks >= open && rest.is_empty() => None,
+                (_, open) => open,
+            };
         }
     }
+
+    if let Some(turn) = current.take() {
+        turns.push(finish_turn(turn));
+    }
+    turns
 }
 
-/// Check if the conversation file is complete (ends with ---END--- marker).
-fn check_complete(path: &Path) -> Result<Option<String>, Box<dyn std::error::Error>> {
+/// Read and parse the conversation file at `path`.
+pub fn parse(path: &Path) -> Result<Vec<ConversationTurn>, McError> {
     if !path.exists() {
-        return Ok(None);
+        return Err(McError::not_found(format!(
+            "File not found: {}",
+            path.display()
+        )));
     }
+    Ok(parse_conversation(&fs::read_to_string(path)?))
+}
 
-    let content = fs::read_to_string(path)?;
-    if content.trim().ends_with(END_MARKER) {
-        Ok(Some(extract_last_response(&content)))
-    } else {
-        Ok(None)
+/// A turn being read: role, timestamp, body lines, and the offsets of the
+/// body and of the line that ended it.
+type OpenTurn<'a> = (Role, Option<String>, Vec<&'a str>, usize, Option<usize>);
+
+fn finish_turn((role, timestamp, body, start, end): OpenTurn) -> ConversationTurn {
+    let content = body.join("\n").trim().to_string();
+    ConversationTurn {
+        role,
+        timestamp,
+        content_hash: hash::content_hash(&content),
+        content,
+        complete: end.is_some(),
+        start_offset: start,
+        end_offset: end,
     }
 }
 
-/// Extract the last assistant response from the conversation file.
-fn extract_last_response(content: &str) -> String {
-    // Find the last "## Assistant" section
-    if let Some(assistant_pos) = content.rfind("## Assistant") {
-        let after_header = &content[assistant_pos..];
+/// Parse `Assistant [2026-01-22T10:30:45Z]` into its role and timestamp.
+fn parse_header(header: &str) -> (Role, Option<String>) {
+    let header = header.trim();
+    let (name, rest) = match header.find('[') {
+        Some(pos) => (header[..pos].trim(), Some(&header[pos + 1..])),
+        None => (header, None),
+    };
 
-        // Skip the header line itself
-        if let Some(newline_pos) = after_header.find('\n') {
-            let response_start = &after_header[newline_pos + 1..];
+    let role = match name {
+        "Human" => Role::Human,
+        "Assistant" => Role::Assistant,
+        _ => Role::Other,
+    };
+    let timestamp = rest
+        .and_then(|r| r.find(']').map(|end| r[..end].trim().to_string()))
+        .filter(|t| !t.is_empty());
 
-            // Extract content until ---END---
-            if let Some(end_pos) = response_start.find(END_MARKER) {
-                return response_start[..end_pos].trim().to_string();
-            }
+    (role, timestamp)
+}
+
+/// Which assistant response to select.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TurnSelector {
+    /// The most recent assistant response
+    Last,
+    /// The nth assistant response, 1-based
+    Nth(usize),
+    /// Assistant responses timestamped within `[since, until)`; either bound may be open
+    ByTimestampRange {
+        since: Option<String>,
+        until: Option<String>,
+    },
+}
+
+impl fmt::Display for TurnSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TurnSelector::Last => write!(f, "the last turn"),
+            TurnSelector::Nth(n) => write!(f, "turn {}", n),
+            TurnSelector::ByTimestampRange { since, until } => write!(
+                f,
+                "timestamps from {} to {}",
+                since.as_deref().unwrap_or("the start"),
+                until.as_deref().unwrap_or("now")
+            ),
         }
     }
+}
+
+/// An assistant response picked out of the conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectedResponse {
+    /// 1-based position among assistant responses
+    pub turn: usize,
+    pub timestamp: Option<String>,
+    pub complete: bool,
+    pub response: String,
+}
+
+/// Select the assistant response(s) matching `selector`, in file order.
+///
+/// Range bounds must be RFC3339; responses without a parseable timestamp
+/// never match a range.
+pub fn select_responses(
+    content: &str,
+    selector: &TurnSelector,
+) -> Result<Vec<SelectedResponse>, McError> {
+    let responses: Vec<SelectedResponse> = parse_conversation(content)
+        .into_iter()
+        .filter(|t| t.role == Role::Assistant)
+        .enumerate()
+        .map(|(i, t)| SelectedResponse {
+            turn: i + 1,
+            timestamp: t.timestamp,
+            complete: t.complete,
+            response: t.content,
+        })
+        .collect();
+
+    match selector {
+        TurnSelector::Last => Ok(responses.into_iter().last().into_iter().collect()),
+        TurnSelector::Nth(n) => Ok(responses.into_iter().filter(|r| r.turn == *n).collect()),
+        TurnSelector::ByTimestampRange { since, until } => {
+            let since = since.as_deref().map(parse_timestamp).transpose()?;
+            let until = until.as_deref().map(parse_timestamp).transpose()?;
+            Ok(responses
+                .into_iter()
+                .filter(|r| {
+                    let at = match r.timestamp.as_deref().map(parse_timestamp) {
+                        Some(Ok(at)) => at,
+                        _ => return false,
+                    };
+                    since.is_none_or(|s| at >= s) && until.is_none_or(|u| at < u)
+                })
+                .collect())
+        }
+    }
+}
+
+/// Read conversation.md and select responses, failing when nothing matches.
+pub fn read_responses(
+    mission_dir: &str,
+    selector: &TurnSelector,
+) -> Result<Vec<SelectedResponse>, McError> {
+    let conv_path = Path::new(mission_dir).join("conversation.md");
+    if !conv_path.exists() {
+        return Err(McError::not_found(format!(
+            "File not found: {}",
+            conv_path.display()
+        )));
+    }
+
+    let content = fs::read_to_string(&conv_path)?;
+    let selected = select_responses(&content, selector)?;
+    if selected.is_empty() {
+        let total = parse_conversation(&content)
+            .iter()
+            .filter(|t| t.role == Role::Assistant)
+            .count();
+        return Err(McError::Validation(format!(
+            "No assistant response matches {} (conversation has {} responses)",
+            selector, total
+        )));
+    }
+    Ok(selected)
+}
+
+/// Get a single assistant response; for ranges, the most recent match.
+pub fn get_response(content: &str, selector: &TurnSelector) -> Option<String> {
+    select_responses(content, selector)
+        .ok()?
+        .pop()
+        .map(|r| r.response)
+}
+
+/// A turn written by [`append`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppendedTurn {
+    pub path: String,
+    pub role: Role,
+    pub timestamp: String,
+    /// Closed with `---END---`
+    pub end: bool,
+}
+
+/// Append a `## Human [timestamp]` or `## Assistant [timestamp]` turn to the
+/// mission's conversation.md, creating the file and directory if needed.
+///
+/// Human turns close with the `---` separator; `end` closes either role with
+/// `---END---` instead. The file is held under an exclusive lock for the one
+/// write, so turns appended by several processes never interleave. Existing
+/// content is never rewritten.
+pub fn append(
+    mission_dir: &str,
+    role: Role,
+    content: &str,
+    end: bool,
+) -> Result<AppendedTurn, McError> {
+    let name = match role {
+        Role::Human => "Human",
+        Role::Assistant => "Assistant",
+        Role::Other => {
+            return Err(McError::Validation(
+                "Turns can only be appended as human or assistant".to_string(),
+            ))
+        }
+    };
+    let timestamp = chrono::Utc::now()
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        .to_string();
+    let mut turn = format!("## {} [{}]\n\n{}\n", name, timestamp, content.trim());
+    let terminator = match (end, role) {
+        (true, _) => Some(END_MARKER),
+        (false, Role::Human) => Some(TURN_SEPARATOR),
+        (false, _) => None,
+    };
+    if let Some(terminator) = terminator {
+        turn.push_str(&format!("\n{}\n", terminator));
+    }
+
+    let conv_path = Path::new(mission_dir).join("conversation.md");
+    fs::create_dir_all(mission_dir).map_err(|e| McError::io(mission_dir, e))?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&conv_path)
+        .map_err(|e| McError::io(conv_path.display(), e))?;
+    file.lock()
+        .map_err(|e| McError::io(format!("Locking {}", conv_path.display()), e))?;
+    // Checked under the lock, so the blank line lands only between turns
+    if file.metadata()?.len() > 0 {
+        turn.insert(0, '\n');
+    }
+    file.write_all(turn.as_bytes())
+        .map_err(|e| McError::io(conv_path.display(), e))?;
+    file.unlock()?;
+
+    Ok(AppendedTurn {
+        path: conv_path.to_string_lossy().to_string(),
+        role,
+        timestamp,
+        end,
+    })
+}
 
-    String::new()
+fn parse_timestamp(value: &str) -> Result<DateTime<FixedOffset>, McError> {
+    DateTime::parse_from_rfc3339(value)
+        .map_err(|e| McError::Parse(format!("Invalid RFC3339 timestamp '{}': {}", value, e)))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
+    use std::io::Write;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_append_creates_and_extends() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().join(".mission");
+        let mission = mission_dir.to_str().unwrap();
+
+        append(mission, Role::Human, "Hello?", false).unwrap();
+        let appended = append(mission, Role::Assistant, "Hi.\n", true).unwrap();
+        assert_eq!(appended.role, Role::Assistant);
+
+        let content = fs::read_to_string(mission_dir.join("conversation.md")).unwrap();
+        let turns = parse_conversation(&content);
+        assert_eq!(turns.len(), 2);
+        assert!(turns.iter().all(|t| t.complete && t.timestamp.is_some()));
+        assert_eq!(turns[1].content, "Hi.");
+        assert_eq!(
+            complete_response(&content, &Completion::default())
+                .map(|(exchange, _)| exchange.response),
+            Some("Hi.".to_string())
+        );
+
+        assert!(matches!(
+            append(mission, Role::Other, "?", false),
+            Err(McError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_concurrent_appends_stay_whole() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission = temp_dir.path().to_str().unwrap().to_string();
+        // Large enough that an unlocked append could be split
+        let bodies: Vec<String> = ["a", "b"].iter().map(|c| c.repeat(256 * 1024)).collect();
+
+        let handles: Vec<_> = bodies
+            .iter()
+            .cloned()
+            .zip([Role::Human, Role::Assistant])
+            .map(|(body, role)| {
+                let mission = mission.clone();
+                std::thread::spawn(move || append(&mission, role, &body, true).unwrap())
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let content = fs::read_to_string(temp_dir.path().join("conversation.md")).unwrap();
+        let mut contents: Vec<String> = parse_conversation(&content)
+            .into_iter()
+            .map(|t| t.content)
+            .collect();
+        contents.sort();
+        assert_eq!(contents, bodies);
+    }
+
     #[test]
     fn test_extract_last_response() {
         let content = r#"## Human [2026-01-22T10:30:00Z]
@@ -125,7 +780,7 @@ I'm doing well, thank you for asking!
 
 ---END---"#;
 
-        let response = extract_last_response(content);
+        let response = extract_last_response(content, &[END_MARKER]).response;
         assert_eq!(response, "I'm doing well, thank you for asking!");
     }
 
@@ -157,10 +812,34 @@ This has multiple lines.
 
 ---END---"#;
 
-        let response = extract_last_response(content);
+        let exchange = extract_last_response(content, &[END_MARKER]);
+        let response = &exchange.response;
         assert!(response.contains("Second response"));
         assert!(response.contains("multiple lines"));
         assert!(!response.contains("First response"));
+        assert_eq!(exchange.prompt.as_deref(), Some("Second message."));
+        assert_eq!(
+            exchange.prompt_timestamp.as_deref(),
+            Some("2026-01-22T10:32:00Z")
+        );
+        assert_eq!(
+            exchange.response_timestamp.as_deref(),
+            Some("2026-01-22T10:32:30Z")
+        );
+    }
+
+    #[test]
+    fn test_exchange_without_prompt_or_timestamps() {
+        let content = "## Assistant\n\nUnprompted.\n\n---END---\n";
+        let (exchange, _) = complete_response(content, &Completion::default()).unwrap();
+        assert_eq!(exchange.response, "Unprompted.");
+        assert_eq!(exchange.prompt, None);
+        assert_eq!(exchange.response_timestamp, None);
+
+        let json =
+            serde_json::to_value(exchange.into_result(0, CompletedBy::Marker, None)).unwrap();
+        assert!(json["prompt"].is_null());
+        assert!(json["prompt_timestamp"].is_null());
     }
 
     #[test]
@@ -170,7 +849,7 @@ This has multiple lines.
 
         fs::write(&conv_path, "## Assistant\n\nStill typing...").unwrap();
 
-        let result = check_complete(&conv_path).unwrap();
+        let result = check_complete(&conv_path, &Completion::default()).unwrap();
         assert!(result.is_none());
     }
 
@@ -179,15 +858,129 @@ This has multiple lines.
         let temp_dir = TempDir::new().unwrap();
         let conv_path = temp_dir.path().join("conversation.md");
 
-        fs::write(
-            &conv_path,
-            "## Assistant [time]\n\nDone!\n\n---END---",
+        fs::write(&conv_path, "## Assistant [time]\n\nDone!\n\n---END---").unwrap();
+
+        let result = check_complete(&conv_path, &Completion::default()).unwrap();
+        assert!(result.is_some());
+        assert_eq!(result.unwrap().exchange.response, "Done!");
+    }
+
+    #[test]
+    fn test_check_complete_custom_markers() {
+        let temp_dir = TempDir::new().unwrap();
+        let conv_path = temp_dir.path().join("conversation.md");
+        let completion = Completion {
+            markers: vec!["<END_OF_RESPONSE>".to_string(), END_MARKER.to_string()],
+            ..Completion::default()
+        };
+
+        fs::write(&conv_path, "## Assistant\n\nDone!\n\n<END_OF_RESPONSE>\n").unwrap();
+        let result = check_complete(&conv_path, &completion).unwrap().unwrap();
+        assert_eq!(result.exchange.response, "Done!");
+        assert_eq!(result.completed_by, CompletedBy::Marker);
+        assert!(check_complete(&conv_path, &Completion::default())
+            .unwrap()
+            .is_none());
+
+        fs::write(&conv_path, "## Assistant\n\nAlso done.\n\n---END---\n").unwrap();
+        let result = check_complete(&conv_path, &completion).unwrap().unwrap();
+        assert_eq!(result.exchange.response, "Also done.");
+    }
+
+    /// The response [`complete_response`] finds, and its marker's offset.
+    fn response_at(content: &str, completion: &Completion) -> Option<(String, usize)> {
+        complete_response(content, completion).map(|(exchange, offset)| (exchange.response, offset))
+    }
+
+    #[test]
+    fn test_complete_before_later_human_turn() {
+        let content = "## Human\n\nHi\n\n---\n\n## Assistant\n\nHello.\n\n---END---\n\n\
+                       ## Human\n\nNext question\n\n---\n";
+        let marker = content.find("---END---").unwrap();
+
+        // The file no longer ends with the marker
+        assert_eq!(response_at(content, &Completion::default()), None);
+
+        let after_turn = Completion {
+            after: After::Turn(0),
+            ..Completion::default()
+        };
+        assert_eq!(
+            response_at(content, &after_turn),
+            Some(("Hello.".to_string(), marker))
+        );
+        let since = |offset| Completion {
+            after: After::Offset(offset),
+            ..Completion::default()
+        };
+        assert_eq!(
+            response_at(content, &since(0)),
+            Some(("Hello.".to_string(), marker))
+        );
+        // Passing the offset back waits for the next response
+        assert_eq!(response_at(content, &since(marker)), None);
+        let later = format!("{}\n## Assistant\n\nAnswer.\n\n---END---\n", content);
+        let second = later.rfind("---END---").unwrap();
+        assert_eq!(
+            response_at(&later, &since(marker)),
+            Some(("Answer.".to_string(), second))
+        );
+        assert_eq!(
+            response_at(&later, &Completion::default()),
+            Some(("Answer.".to_string(), second))
+        );
+    }
+
+    #[test]
+    fn test_check_complete_done_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let conv_path = temp_dir.path().join("conversation.md");
+        let completion = Completion {
+            done_file: Some(temp_dir.path().join("done")),
+            ..Completion::default()
+        };
+
+        fs::write(&conv_path, "## Assistant\n\nNo marker here").unwrap();
+        assert!(check_complete(&conv_path, &completion).unwrap().is_none());
+
+        fs::write(temp_dir.path().join("done"), "").unwrap();
+        let result = check_complete(&conv_path, &completion).unwrap().unwrap();
+        assert_eq!(result.exchange.response, "No marker here");
+        assert_eq!(result.completed_by, CompletedBy::DoneFile);
+    }
+
+    #[test]
+    fn test_watch_done_file_elsewhere() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().join(".mission");
+        let done_file = temp_dir.path().join("signals").join("done");
+        let completion = Completion {
+            done_file: Some(done_file.clone()),
+            ..Completion::default()
+        };
+
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            fs::write(done_file, "").unwrap();
+        });
+        let result = watch(
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(5),
+            &completion,
+            &WatchOptions::default(),
         )
         .unwrap();
+        writer.join().unwrap();
 
-        let result = check_complete(&conv_path).unwrap();
-        assert!(result.is_some());
-        assert_eq!(result.unwrap(), "Done!");
+        match result {
+            ConversationResult::Complete { completed_by, .. } => {
+                assert_eq!(completed_by, CompletedBy::DoneFile)
+            }
+            other => panic!("Expected complete, got {:?}", other),
+        }
+        let result = Exchange::default().into_result(0, CompletedBy::DoneFile, None);
+        let json = serde_json::to_value(result).unwrap();
+        assert_eq!(json["completed_by"], "done_file");
     }
 
     #[test]
@@ -202,11 +995,347 @@ This has multiple lines.
         )
         .unwrap();
 
-        let result = watch(mission_dir.to_str().unwrap(), Duration::from_millis(100)).unwrap();
+        let options = WatchOptions {
+            clock: Arc::new(MockClock::with_auto_advance(Duration::from_secs(60))),
+            ..WatchOptions::default()
+        };
+        let result = watch(
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(300),
+            &Completion::default(),
+            &options,
+        )
+        .unwrap();
 
         match result {
             ConversationResult::Timeout => {}
-            ConversationResult::Complete { .. } => panic!("Expected timeout"),
+            other => panic!("Expected timeout, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_watch_cancelled_by_flag() {
+        let temp_dir = TempDir::new().unwrap();
+        let options = WatchOptions::default();
+        let flag = options.cancel.flag.clone();
+
+        let setter = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            flag.store(true, Ordering::Relaxed);
+        });
+        let result = watch(
+            temp_dir.path().to_str().unwrap(),
+            Duration::from_secs(300),
+            &Completion::default(),
+            &options,
+        )
+        .unwrap();
+        setter.join().unwrap();
+
+        match result {
+            ConversationResult::Cancelled { reason } => assert_eq!(reason, CancelReason::Signal),
+            other => panic!("Expected cancelled, got {:?}", other),
         }
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_watch_symlinked_mission_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("current");
+        fs::create_dir_all(&target).unwrap();
+        let link = temp_dir.path().join(".mission");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let conv_path = link.join("conversation.md");
+        fs::write(&conv_path, "## Human\n\nHello\n\n---\n\n").unwrap();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            let mut content = fs::read_to_string(&conv_path).unwrap();
+            content.push_str("## Assistant\n\nHi.\n\n---END---\n");
+            fs::write(&conv_path, content).unwrap();
+        });
+
+        let result = watch(
+            link.to_str().unwrap(),
+            Duration::from_secs(5),
+            &Completion::default(),
+            &WatchOptions::default(),
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        match result {
+            ConversationResult::Complete { response, .. } => assert_eq!(response, "Hi."),
+            other => panic!("Expected complete, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_watch_waits_for_writes_to_settle() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().to_path_buf();
+        let conv_path = mission_dir.join("conversation.md");
+        fs::write(&conv_path, "## Human\n\nHello\n\n---\n\n").unwrap();
+
+        // The first write already ends with the marker; the rest lands 30 ms later
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            let mut file = fs::OpenOptions::new()
+                .append(true)
+                .open(&conv_path)
+                .unwrap();
+            file.write_all(b"## Assistant\n\nPart one.\n\n---END---")
+                .unwrap();
+            file.sync_all().unwrap();
+            std::thread::sleep(Duration::from_millis(30));
+            file.write_all(b"\n\n## Assistant\n\nPart two.\n\n---END---\n")
+                .unwrap();
+        });
+
+        let result = watch(
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(5),
+            &Completion::default(),
+            &WatchOptions::default(),
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        match result {
+            ConversationResult::Complete { response, .. } => assert_eq!(response, "Part two."),
+            other => panic!("Expected complete, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_streamed_sends_only_new_text() {
+        let mut chunks = Vec::new();
+        let mut streamed = Streamed::default();
+        let mut update = |content: &str| {
+            let mut sent = Vec::new();
+            streamed.update(content, &[END_MARKER], &mut |c: &str| {
+                sent.push(c.to_string())
+            });
+            chunks.extend(sent);
+        };
+
+        update("## Human\n\nHi\n\n---\n");
+        update("## Human\n\nHi\n\n---\n\n## Assistant\n\nHel");
+        update("## Human\n\nHi\n\n---\n\n## Assistant\n\nHel");
+        update("## Human\n\nHi\n\n---\n\n## Assistant\n\nHello.\n\n---END---\n");
+        // Rewritten shorter: the whole text again
+        update("## Assistant\n\nBye");
+        assert_eq!(chunks, vec!["Hel", "lo.\n\n", "Bye"]);
+    }
+
+    #[test]
+    fn test_watch_streaming() {
+        let temp_dir = TempDir::new().unwrap();
+        let conv_path = temp_dir.path().join("conversation.md");
+        fs::write(&conv_path, "## Human\n\nHello\n\n---\n\n").unwrap();
+
+        let writer = std::thread::spawn(move || {
+            let mut file = fs::OpenOptions::new()
+                .append(true)
+                .open(&conv_path)
+                .unwrap();
+            for part in ["## Assistant\n\nOne, ", "two, ", "three.\n\n---END---\n"] {
+                std::thread::sleep(Duration::from_millis(100));
+                file.write_all(part.as_bytes()).unwrap();
+                file.sync_all().unwrap();
+            }
+        });
+
+        let mut text = String::new();
+        let result = watch_streaming(
+            temp_dir.path().to_str().unwrap(),
+            Duration::from_secs(5),
+            &Completion::default(),
+            &WatchOptions::default(),
+            |chunk| text.push_str(chunk),
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        assert_eq!(text, "One, two, three.\n\n");
+        match result {
+            ConversationResult::Complete { response, .. } => {
+                assert_eq!(response, "One, two, three.")
+            }
+            other => panic!("Expected complete, got {:?}", other),
+        }
+    }
+
+    const THREE_TURNS: &str = r#"## Human [2026-01-22T10:30:00Z]
+
+First message.
+
+---
+
+## Assistant [2026-01-22T10:30:45Z]
+
+First response.
+
+---END---
+
+## Human [2026-01-22T10:32:00Z]
+
+Second message.
+
+---
+
+## Assistant [2026-01-22T10:32:30Z]
+
+Second response.
+
+---END---
+
+## Human [2026-01-22T10:40:00Z]
+
+Third message.
+
+---
+
+## Assistant [2026-01-22T10:41:00Z]
+
+Third response, still typing"#;
+
+    #[test]
+    fn test_parse_conversation_turns() {
+        let turns = parse_conversation(THREE_TURNS);
+        assert_eq!(turns.len(), 6);
+        assert_eq!(turns[0].role, Role::Human);
+        assert_eq!(turns[0].timestamp.as_deref(), Some("2026-01-22T10:30:00Z"));
+        assert_eq!(turns[1].content, "First response.");
+        assert!(turns[1].complete);
+        assert!(!turns[5].complete);
+    }
+
+    #[test]
+    fn test_parse_conversation_edge_cases() {
+        let content = r#"Preamble that belongs to no turn.
+
+## Human
+
+Show me the template.
+
+---
+
+## Assistant [2026-01-22T10:30:45Z]
+
+Here it is:
+
+```markdown
+## Assistant
+
+---
+```
+
+---END---
+
+## Assistant [not a time]
+
+Still wri"#;
+        let turns = parse_conversation(content);
+        assert_eq!(turns.len(), 3);
+        assert_eq!(turns[0].timestamp, None);
+        assert!(turns[0].complete);
+        assert!(turns[1].content.contains("## Assistant\n\n---\n```"));
+        assert!(turns[1].complete);
+        assert_eq!(turns[2].timestamp.as_deref(), Some("not a time"));
+        assert_eq!(turns[2].content, "Still wri");
+        assert!(!turns[2].complete);
+    }
+
+    #[test]
+    fn test_extract_last_response_with_fake_headers_in_code() {
+        let content = r#"## Human [2026-01-22T10:30:00Z]
+
+How do I format a conversation file?
+
+---
+
+## Assistant [2026-01-22T10:30:45Z]
+
+Like this:
+
+```markdown
+## Human
+
+Question?
+
+---
+
+## Assistant
+
+Answer.
+```
+
+Each turn starts with its own header.
+
+---END---
+"#;
+        let exchange = extract_last_response(content, &[END_MARKER]);
+        assert_eq!(
+            exchange.prompt.as_deref(),
+            Some("How do I format a conversation file?")
+        );
+        let response = exchange.response;
+        assert!(response.starts_with("Like this:"));
+        assert!(response.contains("## Assistant\n\nAnswer.\n```"));
+        assert!(response.ends_with("Each turn starts with its own header."));
+    }
+
+    #[test]
+    fn test_open_fence_still_ends_at_marker() {
+        let content = "## Assistant\n\n```sh\nmake\n\n---END---\n";
+        let turns = parse_conversation(content);
+        assert!(turns[0].complete);
+        assert_eq!(
+            complete_response(content, &Completion::default())
+                .map(|(exchange, _)| exchange.response),
+            Some("```sh\nmake".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_missing_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let err = parse(&temp_dir.path().join("conversation.md")).unwrap_err();
+        assert!(matches!(err, McError::Io(_)));
+    }
+
+    #[test]
+    fn test_get_response_nth() {
+        let response = get_response(THREE_TURNS, &TurnSelector::Nth(2));
+        assert_eq!(response.as_deref(), Some("Second response."));
+        assert_eq!(
+            get_response(THREE_TURNS, &TurnSelector::Last).as_deref(),
+            Some("Third response, still typing")
+        );
+    }
+
+    #[test]
+    fn test_select_responses_timestamp_range() {
+        let selector = TurnSelector::ByTimestampRange {
+            since: Some("2026-01-22T10:30:30Z".to_string()),
+            until: Some("2026-01-22T10:35:00Z".to_string()),
+        };
+        let selected = select_responses(THREE_TURNS, &selector).unwrap();
+        let turns: Vec<usize> = selected.iter().map(|r| r.turn).collect();
+        assert_eq!(turns, vec![1, 2]);
+        assert_eq!(
+            get_response(THREE_TURNS, &selector).as_deref(),
+            Some("Second response.")
+        );
+    }
+
+    #[test]
+    fn test_get_response_out_of_range() {
+        assert!(get_response(THREE_TURNS, &TurnSelector::Nth(7)).is_none());
+        assert!(select_responses(THREE_TURNS, &TurnSelector::Nth(0))
+            .unwrap()
+            .is_empty());
+    }
 }
@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+/// Name of the mission directory looked for when discovering.
+pub const MISSION_DIR_NAME: &str = ".mission";
+
+/// A resolved mission directory and how it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissionDir {
+    /// Absolute path of the mission directory.
+    pub path: PathBuf,
+    /// True when found by walking up from the working directory.
+    pub discovered: bool,
+    /// Farther `.mission` directories that the chosen one shadows.
+    pub shadowed: Vec<PathBuf>,
+}
+
+/// Every `.mission` directory from `start` up to the filesystem root, nearest first.
+pub fn find_mission_dirs(start: &Path) -> Vec<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(MISSION_DIR_NAME))
+        .filter(|candidate| candidate.is_dir())
+        .collect()
+}
+
+/// Resolve the mission directory for a command, like git does for `.git`.
+///
+/// An explicit directory always wins (made absolute against `cwd`). Otherwise,
+/// when `discover` is set, the nearest `.mission` walking up from `cwd` is
+/// used; if none exists, or discovery is off, `cwd/.mission` is used as before.
+pub fn resolve(explicit: Option<&str>, discover: bool, cwd: &Path) -> MissionDir {
+    if let Some(dir) = explicit {
+        return MissionDir {
+            path: cwd.join(dir),
+            discovered: false,
+            shadowed: Vec::new(),
+        };
+    }
+
+    if discover {
+        let mut found = find_mission_dirs(cwd).into_iter();
+        if let Some(nearest) = found.next() {
+            return MissionDir {
+                path: nearest,
+                discovered: true,
+                shadowed: found.collect(),
+            };
+        }
+    }
+
+    MissionDir {
+        path: cwd.join(MISSION_DIR_NAME),
+        discovered: false,
+        shadowed: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_discovers_from_nested_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join(".mission")).unwrap();
+        let nested = root.join("src").join("deep").join("module");
+        fs::create_dir_all(&nested).unwrap();
+
+        let resolved = resolve(None, true, &nested);
+        assert_eq!(resolved.path, root.join(".mission"));
+        assert!(resolved.discovered);
+        assert!(resolved.shadowed.is_empty());
+    }
+
+    #[test]
+    fn test_nearest_mission_dir_wins() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let child = root.join("child");
+        fs::create_dir_all(root.join(".mission")).unwrap();
+        fs::create_dir_all(child.join(".mission")).unwrap();
+
+        let resolved = resolve(None, true, &child);
+        assert_eq!(resolved.path, child.join(".mission"));
+        assert_eq!(resolved.shadowed, vec![root.join(".mission")]);
+    }
+
+    #[test]
+    fn test_no_discover_uses_cwd() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join(".mission")).unwrap();
+        let nested = root.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+
+        let resolved = resolve(None, false, &nested);
+        assert_eq!(resolved.path, nested.join(".mission"));
+        assert!(!resolved.discovered);
+    }
+
+    #[test]
+    fn test_explicit_dir_is_made_absolute() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join(".mission")).unwrap();
+
+        let resolved = resolve(Some("custom"), true, temp_dir.path());
+        assert_eq!(resolved.path, temp_dir.path().join("custom"));
+        assert!(!resolved.discovered);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::prune::{self, PruneOptions};
+use serde::Serialize;
+use std::path::Path;
+
+/// One health check on a mission directory.
+#[derive(Debug, Serialize)]
+pub struct DoctorCheck {
+    pub name: &'static str,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DoctorReport {
+    pub healthy: bool,
+    pub checks: Vec<DoctorCheck>,
+    /// Commands that would fix what the checks found
+    pub recommendations: Vec<String>,
+}
+
+/// Inspect a mission directory for problems that break the protocol.
+pub fn doctor(mission_dir: &Path) -> DoctorReport {
+    let mut checks = Vec::new();
+    let mut recommendations = Vec::new();
+
+    let exists = mission_dir.is_dir();
+    checks.push(DoctorCheck {
+        name: "mission_dir",
+        ok: exists,
+        detail: (!exists).then(|| format!("{} is not a directory", mission_dir.display())),
+    });
+
+    if exists {
+        let dry_run = PruneOptions {
+            dry_run: true,
+            ..PruneOptions::default()
+        };
+        match prune::prune(mission_dir, &dry_run) {
+            Ok(report) if report.removed.is_empty() => checks.push(DoctorCheck {
+                name: "stale_housekeeping",
+                ok: true,
+                detail: None,
+            }),
+            Ok(report) => {
+                checks.push(DoctorCheck {
+                    name: "stale_housekeeping",
+                    ok: false,
+                    detail: Some(format!(
+                        "{} stale heartbeat, cancel, or lock file(s)",
+                        report.removed.len()
+                    )),
+                });
+                recommendations.push(format!(
+                    "mc-protocol prune --mission-dir {}",
+                    mission_dir.display()
+                ));
+            }
+            Err(e) => checks.push(DoctorCheck {
+                name: "stale_housekeeping",
+                ok: false,
+                detail: Some(e.to_string()),
+            }),
+        }
+    }
+
+    DoctorReport {
+        healthy: checks.iter().all(|check| check.ok),
+        checks,
+        recommendations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_recommends_prune_for_stale_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission = temp_dir.path();
+        assert!(doctor(mission).healthy);
+
+        let heartbeat = mission.join("task-001.alive");
+        fs::write(&heartbeat, "").unwrap();
+        File::options()
+            .write(true)
+            .open(&heartbeat)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(24 * 60 * 60))
+            .unwrap();
+
+        let report = doctor(mission);
+        assert!(!report.healthy);
+        assert_eq!(report.recommendations.len(), 1);
+        assert!(report.recommendations[0].starts_with("mc-protocol prune"));
+        // Diagnosing never removes anything
+        assert!(heartbeat.exists());
+    }
+}
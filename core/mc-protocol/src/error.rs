@@ -0,0 +1,129 @@
+//! The error type of the library's fallible functions.
+//!
+//! It serializes as `{"kind": "...", "error": "..."}`, so the CLI's error
+//! output keeps its `error` message and gains the kind alongside it. A
+//! watcher that could not be set up carries its retry count too, as
+//! `{"kind": "watch_init", "error": {"attempts": 3, "source": "..."}}`.
+//!
+//! Watches report running out of time as `Ok(WatchResult::Timeout)` (or
+//! their own `Timeout` result); [`McError::Timeout`] is for callers that
+//! have to treat a wait that ran out as a failure.
+
+use crate::fswatch::WatchInitError;
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error, Serialize)]
+#[serde(tag = "kind", content = "error", rename_all = "snake_case")]
+pub enum McError {
+    /// Reading or writing mission files, including files that don't exist
+    #[error("{0}")]
+    Io(
+        #[from]
+        #[serde(serialize_with = "display")]
+        io::Error,
+    ),
+    /// Running a file watcher
+    #[error("{0}")]
+    Notify(
+        #[from]
+        #[serde(serialize_with = "display")]
+        notify::Error,
+    ),
+    /// A wait ran out of time before it could finish
+    #[error("{0}")]
+    Timeout(String),
+    /// Setting up a file watcher, which failed on every attempt
+    #[error("failed to initialize file watcher after {attempts} attempt(s): {source}")]
+    WatchInit {
+        attempts: u32,
+        #[source]
+        #[serde(serialize_with = "display")]
+        source: notify::Error,
+    },
+    /// Input that is well-formed but not acceptable
+    #[error("{0}")]
+    Validation(String),
+    /// Input that can't be read as what it should be
+    #[error("{0}")]
+    Parse(String),
+}
+
+impl McError {
+    /// An [`McError::Io`] for a path that doesn't exist, described as `what`.
+    pub fn not_found(what: impl Display) -> Self {
+        McError::Io(io::Error::new(io::ErrorKind::NotFound, what.to_string()))
+    }
+
+    /// An [`McError::Io`] for `error`, its message prefixed with `context`.
+    pub fn io(context: impl Display, error: io::Error) -> Self {
+        McError::Io(io::Error::new(
+            error.kind(),
+            format!("{}: {}", context, error),
+        ))
+    }
+}
+
+impl From<WatchInitError> for McError {
+    fn from(e: WatchInitError) -> Self {
+        McError::WatchInit {
+            attempts: e.attempts,
+            source: e.source,
+        }
+    }
+}
+
+impl From<serde_json::Error> for McError {
+    fn from(e: serde_json::Error) -> Self {
+        McError::Parse(e.to_string())
+    }
+}
+
+fn display<T: Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_kind_and_message() {
+        let json = serde_json::to_value(McError::not_found("File not found: task.md")).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"kind": "io", "error": "File not found: task.md"})
+        );
+        let json = serde_json::to_value(McError::Validation("too long".to_string())).unwrap();
+        assert_eq!(json["kind"], "validation");
+        let json = serde_json::to_value(McError::Timeout("no response".to_string())).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"kind": "timeout", "error": "no response"})
+        );
+    }
+
+    #[test]
+    fn test_watch_init_keeps_attempts_and_source() {
+        let error = McError::from(WatchInitError {
+            attempts: 3,
+            source: notify::Error::generic("inotify watch limit reached"),
+        });
+        assert!(matches!(
+            &error,
+            McError::WatchInit { attempts: 3, source } if source.to_string() == "inotify watch limit reached"
+        ));
+        assert!(std::error::Error::source(&error).is_some());
+
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "kind": "watch_init",
+                "error": {"attempts": 3, "source": "inotify watch limit reached"},
+            })
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use crate::error::McError;
+use mc_events::{UnifiedEvent, SCHEMA_VERSION};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// A line of an event stream that didn't match the shared schema.
+#[derive(Debug, Serialize)]
+pub struct LineError {
+    pub line: usize,
+    pub error: String,
+}
+
+/// Summary of an event stream checked against the shared schema.
+#[derive(Debug, Serialize)]
+pub struct EventReport {
+    pub schema_version: u32,
+    pub events: usize,
+    pub by_type: BTreeMap<String, usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<LineError>,
+}
+
+/// Parse stream-parser NDJSON into events.
+///
+/// Lines that don't decode as a [`UnifiedEvent`] (including ones carrying
+/// fields this build doesn't know) are returned as errors with their
+/// 1-based line number instead of being dropped.
+pub fn parse_events(content: &str) -> (Vec<UnifiedEvent>, Vec<LineError>) {
+    let mut events = Vec::new();
+    let mut errors = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<UnifiedEvent>(line) {
+            Ok(event) => events.push(event),
+            Err(e) => errors.push(LineError {
+                line: i + 1,
+                error: format!("{} (schema v{})", e, SCHEMA_VERSION),
+            }),
+        }
+    }
+
+    (events, errors)
+}
+
+/// Read an event file and report how it matches the shared schema.
+pub fn check_events(path: &Path) -> Result<EventReport, McError> {
+    if !path.exists() {
+        return Err(McError::not_found(format!(
+            "File not found: {}",
+            path.display()
+        )));
+    }
+
+    let content = fs::read_to_string(path)?;
+    let (events, errors) = parse_events(&content);
+
+    let mut by_type = BTreeMap::new();
+    for event in &events {
+        *by_type.entry(event.event_type.clone()).or_insert(0) += 1;
+    }
+
+    Ok(EventReport {
+        schema_version: SCHEMA_VERSION,
+        events: events.len(),
+        by_type,
+        errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("agent-1.ndjson");
+        fs::write(
+            &path,
+            concat!(
+                r#"{"type":"turn","agent_id":"agent-1","turn":1}"#,
+                "\n",
+                r#"{"type":"tool_call","agent_id":"agent-1","tool":"bash","args":{"command":"ls"}}"#,
+                "\n",
+                r#"{"type":"turn","agent_id":"agent-1","turn":2,"from_the_future":1}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let report = check_events(&path).unwrap();
+        assert_eq!(report.events, 2);
+        assert_eq!(report.by_type.get("turn"), Some(&1));
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].line, 3);
+        assert!(report.errors[0].error.contains("from_the_future"));
+    }
+}
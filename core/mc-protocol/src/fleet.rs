@@ -0,0 +1,461 @@
+//! Watching many missions from one process.
+//!
+//! Every mission shares a single watcher (one inotify instance on Linux),
+//! but each gets its own non-recursive watch: a task's status dir, or a
+//! conversation's mission dir. Missions under a common parent are not
+//! folded into one recursive watch on that parent. A recursive inotify
+//! watch still costs one watch per directory below it, so it would spend
+//! more of `max_user_watches` than per-mission watches, not less, and it
+//! would report every change in whatever else lives there (often `/tmp` or
+//! `$HOME`). One watch per mission keeps the cost predictable: a fleet of
+//! `n` missions needs at most `n` watches.
+
+use crate::cancel::CancelReason;
+use crate::conversation::{self, ConversationResult};
+use crate::error::McError;
+use crate::fswatch::{self, FsWatch, WatchOptions};
+use crate::protocol::Layout;
+use crate::watcher::{self, WatchResult};
+use notify::{Event, RecursiveMode};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// One mission to watch: a task when `task_id` is set, otherwise the conversation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FleetEntry {
+    pub mission_dir: String,
+    pub task_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum FleetOutcome {
+    Complete {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        response_path: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        response: Option<String>,
+    },
+    Timeout,
+    Cancelled {
+        reason: CancelReason,
+    },
+    Error {
+        error: String,
+    },
+}
+
+/// A resolved mission, tagged with the mission dir it came from.
+#[derive(Debug, Serialize)]
+pub struct FleetRecord {
+    pub mission_dir: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    #[serde(flatten)]
+    pub outcome: FleetOutcome,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct FleetSummary {
+    pub complete: usize,
+    pub timeout: usize,
+    pub cancelled: usize,
+    pub error: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FleetReport {
+    pub missions: Vec<FleetRecord>,
+    pub summary: FleetSummary,
+}
+
+/// Parse a missions file: one `dir` or `dir task_id` per line.
+///
+/// Blank lines and lines starting with `#` are ignored.
+pub fn parse_missions_file(path: &str) -> Result<Vec<FleetEntry>, McError> {
+    let content = fs::read_to_string(path)?;
+    Ok(parse_missions(&content))
+}
+
+fn parse_missions(content: &str) -> Vec<FleetEntry> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut parts = line.split_whitespace();
+            FleetEntry {
+                mission_dir: parts.next().unwrap_or_default().to_string(),
+                task_id: parts.next().map(str::to_string),
+            }
+        })
+        .collect()
+}
+
+/// Watch many missions with a single watcher until all resolve or the deadline hits.
+///
+/// `on_record` is called as each mission resolves, in resolution order.
+/// Per-mission problems (such as a missing directory) are reported as
+/// `error` records rather than aborting the fleet.
+pub fn watch_fleet<F>(
+    entries: &[FleetEntry],
+    timeout: Duration,
+    options: &WatchOptions,
+    mut on_record: F,
+) -> Result<FleetReport, McError>
+where
+    F: FnMut(&FleetRecord),
+{
+    let deadline = options.clock.now() + timeout;
+    let mut records = Vec::new();
+    let mut emit = |entry: &FleetEntry, outcome: FleetOutcome| {
+        let record = FleetRecord {
+            mission_dir: entry.mission_dir.clone(),
+            task_id: entry.task_id.clone(),
+            outcome,
+        };
+        on_record(&record);
+        records.push(record);
+    };
+
+    let mut pending: Vec<(&FleetEntry, PathBuf)> = Vec::new();
+    for entry in entries {
+        match prepare(entry, options) {
+            Ok(dir) => pending.push((entry, dir)),
+            Err(e) => emit(
+                entry,
+                FleetOutcome::Error {
+                    error: e.to_string(),
+                },
+            ),
+        }
+    }
+
+    let mut roots = fleet_roots(&pending, options);
+    let mut fs_watch = FsWatch::with_roots(&roots, options, deadline)?;
+
+    // Sweep everything once the watcher is live, so nothing slips in
+    // between, then only the missions each event touches
+    let mut event: Option<Event> = None;
+    loop {
+        pending.retain(|(entry, dir)| {
+            if let Some(event) = &event {
+                if !event.paths.iter().any(|p| p.starts_with(dir)) {
+                    return true;
+                }
+            }
+            match check(entry, options, deadline) {
+                Some(outcome) => {
+                    emit(entry, outcome);
+                    false
+                }
+                None => true,
+            }
+        });
+
+        // A nested task dir that has just appeared replaces the ancestor
+        // standing in for it, so re-root and sweep again
+        let wanted = fleet_roots(&pending, options);
+        if wanted.iter().any(|root| !roots.contains(root)) {
+            roots = wanted;
+            fs_watch = FsWatch::with_roots(&roots, options, deadline)?;
+            event = None;
+            continue;
+        }
+
+        if pending.is_empty() {
+            break;
+        }
+        event = match fs_watch.next_debounced(deadline)? {
+            Some(next) => Some(next),
+            None => break,
+        };
+    }
+
+    let cancelled = fs_watch.cancelled();
+    for (entry, _) in pending {
+        match cancelled {
+            Some(reason) => emit(entry, FleetOutcome::Cancelled { reason }),
+            None => emit(entry, FleetOutcome::Timeout),
+        }
+    }
+
+    let mut summary = FleetSummary::default();
+    for record in &records {
+        match record.outcome {
+            FleetOutcome::Complete { .. } => summary.complete += 1,
+            FleetOutcome::Timeout => summary.timeout += 1,
+            FleetOutcome::Cancelled { .. } => summary.cancelled += 1,
+            FleetOutcome::Error { .. } => summary.error += 1,
+        }
+    }
+
+    Ok(FleetReport {
+        missions: records,
+        summary,
+    })
+}
+
+/// Validate a mission dir and return the path events for it arrive under.
+fn prepare(entry: &FleetEntry, options: &WatchOptions) -> Result<PathBuf, McError> {
+    let dir = Path::new(&entry.mission_dir);
+    if !dir.is_dir() {
+        return Err(McError::not_found(format!(
+            "Mission directory not found: {}",
+            entry.mission_dir
+        )));
+    }
+    if let Some(task_id) = &entry.task_id {
+        let paths = watcher::task_paths(task_id, &entry.mission_dir, options);
+        if paths.layout == Layout::Flat {
+            fs::create_dir_all(dir.join("status"))?;
+        }
+    }
+    Ok(options.watch_path(dir))
+}
+
+/// The dirs to watch for the pending missions, each non-recursively.
+///
+/// A task is watched through its status dir, or the nearest dir above it
+/// that exists yet; a conversation through its mission dir. Only identical
+/// roots are merged, so missions sharing a parent never widen the watch to
+/// that parent.
+fn fleet_roots(
+    pending: &[(&FleetEntry, PathBuf)],
+    options: &WatchOptions,
+) -> Vec<(PathBuf, RecursiveMode)> {
+    let dirs: Vec<PathBuf> = pending
+        .iter()
+        .map(|(entry, _)| {
+            let dir = match &entry.task_id {
+                Some(task_id) => {
+                    let status = watcher::task_paths(task_id, &entry.mission_dir, options).status;
+                    status.parent().map(Path::to_path_buf).unwrap_or_default()
+                }
+                None => PathBuf::from(&entry.mission_dir),
+            };
+            watcher::watch_root(&dir, RecursiveMode::NonRecursive, options).0
+        })
+        .collect();
+    fswatch::plan_roots(&dirs)
+        .into_iter()
+        .map(|root| (root, RecursiveMode::NonRecursive))
+        .collect()
+}
+
+fn check(entry: &FleetEntry, options: &WatchOptions, deadline: Instant) -> Option<FleetOutcome> {
+    match &entry.task_id {
+        Some(task_id) => match watcher::check_task(task_id, &entry.mission_dir, options, deadline)?
+        {
+            WatchResult::Complete { response_path, .. } => Some(FleetOutcome::Complete {
+                response_path: Some(response_path),
+                response: None,
+            }),
+            WatchResult::Timeout
+            | WatchResult::Cancelled { .. }
+            | WatchResult::StatusOnly { .. } => None,
+        },
+        None => {
+            let conv_path = Path::new(&entry.mission_dir).join("conversation.md");
+            match conversation::settle_complete(
+                &conv_path,
+                &conversation::Completion::default(),
+                options,
+                deadline,
+            ) {
+                Ok(Some(ConversationResult::Complete { response, .. })) => {
+                    Some(FleetOutcome::Complete {
+                        response_path: None,
+                        response: Some(response),
+                    })
+                }
+                Ok(_) => None,
+                Err(e) => Some(FleetOutcome::Error {
+                    error: e.to_string(),
+                }),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::sync::Arc;
+    use std::thread;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_missions() {
+        let entries = parse_missions("# fleet\n/m/a 001\n\n/m/b\n");
+        assert_eq!(
+            entries,
+            vec![
+                FleetEntry {
+                    mission_dir: "/m/a".to_string(),
+                    task_id: Some("001".to_string()),
+                },
+                FleetEntry {
+                    mission_dir: "/m/b".to_string(),
+                    task_id: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_watch_fleet_completion_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for name in ["a", "b", "c"] {
+            fs::create_dir_all(root.join(name)).unwrap();
+        }
+
+        let entries = vec![
+            FleetEntry {
+                mission_dir: root.join("a").to_string_lossy().to_string(),
+                task_id: Some("001".to_string()),
+            },
+            FleetEntry {
+                mission_dir: root.join("b").to_string_lossy().to_string(),
+                task_id: Some("002".to_string()),
+            },
+            FleetEntry {
+                mission_dir: root.join("c").to_string_lossy().to_string(),
+                task_id: None,
+            },
+            FleetEntry {
+                mission_dir: root.join("missing").to_string_lossy().to_string(),
+                task_id: Some("003".to_string()),
+            },
+        ];
+
+        // Complete c, then a, then b
+        let writer_root = root.to_path_buf();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            fs::write(
+                writer_root.join("c").join("conversation.md"),
+                "## Assistant\n\nDone.\n\n---END---",
+            )
+            .unwrap();
+            thread::sleep(Duration::from_millis(10));
+            fs::write(writer_root.join("a/status/task-001.status"), "DONE").unwrap();
+            thread::sleep(Duration::from_millis(10));
+            fs::write(writer_root.join("b/status/task-002.status"), "DONE").unwrap();
+        });
+
+        let mut order = Vec::new();
+        let report = watch_fleet(
+            &entries,
+            Duration::from_secs(5),
+            &WatchOptions::default(),
+            |record| {
+                order.push(record.mission_dir.clone());
+            },
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        let names: Vec<&str> = order
+            .iter()
+            .map(|dir| dir.rsplit('/').next().unwrap())
+            .collect();
+        assert_eq!(names, vec!["missing", "c", "a", "b"]);
+        assert_eq!(report.summary.complete, 3);
+        assert_eq!(report.summary.error, 1);
+        assert_eq!(report.summary.timeout, 0);
+    }
+
+    #[test]
+    fn test_watch_fleet_nested_dir_created_later() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission = temp_dir.path().join("a");
+        fs::create_dir_all(&mission).unwrap();
+        let entries = vec![FleetEntry {
+            mission_dir: mission.to_string_lossy().to_string(),
+            task_id: Some("001".to_string()),
+        }];
+
+        let writer_mission = mission.clone();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            fs::create_dir_all(writer_mission.join("tasks/001")).unwrap();
+            thread::sleep(Duration::from_millis(20));
+            fs::write(writer_mission.join("tasks/001/status"), "DONE").unwrap();
+        });
+
+        let options = WatchOptions {
+            layout: Some(Layout::Nested),
+            ..WatchOptions::default()
+        };
+        let report = watch_fleet(&entries, Duration::from_secs(5), &options, |_| {}).unwrap();
+        writer.join().unwrap();
+        assert_eq!(report.summary.complete, 1);
+        assert!(!mission.join("status").exists());
+    }
+
+    #[test]
+    fn test_watch_fleet_large_fleet() {
+        const MISSIONS: usize = 200;
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let entries: Vec<FleetEntry> = (0..MISSIONS)
+            .map(|i| {
+                let dir = root.join(format!("m{:03}", i));
+                fs::create_dir_all(&dir).unwrap();
+                FleetEntry {
+                    mission_dir: dir.to_string_lossy().to_string(),
+                    task_id: Some("001".to_string()),
+                }
+            })
+            .collect();
+
+        // One non-recursive watch per mission, never the shared parent
+        let options = WatchOptions {
+            settle: Duration::ZERO,
+            ..WatchOptions::default()
+        };
+        let pending: Vec<(&FleetEntry, PathBuf)> = entries
+            .iter()
+            .map(|entry| (entry, prepare(entry, &options).unwrap()))
+            .collect();
+        let roots = fleet_roots(&pending, &options);
+        assert_eq!(roots.len(), MISSIONS);
+        assert!(roots
+            .iter()
+            .all(|(dir, mode)| *mode == RecursiveMode::NonRecursive && dir.ends_with("status")));
+
+        let writer_root = root.to_path_buf();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            for i in (0..MISSIONS).rev() {
+                let status = writer_root.join(format!("m{:03}/status/task-001.status", i));
+                fs::write(status, "DONE").unwrap();
+            }
+        });
+        let report = watch_fleet(&entries, Duration::from_secs(10), &options, |_| {}).unwrap();
+        writer.join().unwrap();
+        assert_eq!(report.summary.complete, MISSIONS);
+        assert_eq!(report.summary.timeout, 0);
+    }
+
+    #[test]
+    fn test_watch_fleet_timeout() {
+        let temp_dir = TempDir::new().unwrap();
+        let entries = vec![FleetEntry {
+            mission_dir: temp_dir.path().to_string_lossy().to_string(),
+            task_id: Some("001".to_string()),
+        }];
+
+        let options = WatchOptions {
+            clock: Arc::new(MockClock::with_auto_advance(Duration::from_secs(60))),
+            ..WatchOptions::default()
+        };
+        let report = watch_fleet(&entries, Duration::from_secs(300), &options, |_| {}).unwrap();
+        assert_eq!(report.summary.timeout, 1);
+    }
+}
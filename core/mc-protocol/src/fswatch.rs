@@ -0,0 +1,1023 @@
+use crate::cancel::{self, Cancel, CancelReason};
+use crate::clock::{self, Clock};
+use crate::error::McError;
+use crate::protocol::Layout;
+use notify::event::{CreateKind, DataChange, ModifyKind, RemoveKind};
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Retry policy for creating the watcher and registering its roots.
+///
+/// Busy hosts intermittently fail watcher setup with EMFILE or inotify
+/// ENOSPC; retrying shortly after usually succeeds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InitRetry {
+    /// Attempts after the first one
+    pub retries: u32,
+    /// Delay before the first retry; doubles on each further retry
+    pub backoff: Duration,
+}
+
+impl Default for InitRetry {
+    fn default() -> Self {
+        InitRetry {
+            retries: 3,
+            backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+/// A failed watcher setup attempt that will be retried.
+#[derive(Debug, Serialize)]
+pub struct RetryAttempt {
+    pub attempt: u32,
+    pub error: String,
+    pub backoff_ms: u64,
+}
+
+/// Default for [`WatchOptions::debounce`].
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Poll interval used when notify can't be set up at all.
+pub const FALLBACK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How a watch learns that files changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchStrategy {
+    /// OS file events (inotify, FSEvents, ...), falling back to polling
+    /// every [`FALLBACK_POLL_INTERVAL`] if the watcher can't be set up
+    Notify,
+    /// Rescan the watched paths on this interval instead. For filesystems
+    /// where notify events never arrive, such as NFS or some container mounts
+    Poll(Duration),
+}
+
+/// Tuning shared by the blocking watch functions.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    pub init_retry: InitRetry,
+    /// Called before each watcher setup retry
+    pub on_retry: Option<fn(&RetryAttempt)>,
+    /// Time source for deadlines and backoff
+    pub clock: Arc<dyn Clock>,
+    /// Watch the target of a symlinked mission dir rather than the link
+    pub follow_symlinks: bool,
+    /// Delay between the reads that confirm a completed file has stopped changing
+    pub settle: Duration,
+    pub strategy: WatchStrategy,
+    /// Called when notify setup failed for good and the watch polls instead
+    pub on_fallback: Option<fn(&WatchInitError)>,
+    pub cancel: Cancel,
+    /// Quiet time a path needs before its latest event is handled; see
+    /// [`Debouncer`]
+    pub debounce: Duration,
+    /// Where the watched tasks' files live; detected per task when unset
+    pub layout: Option<Layout>,
+    /// A watcher to subscribe to instead of setting one up, for watches
+    /// inside its root
+    pub shared: Option<Arc<SharedWatcher>>,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions {
+            init_retry: InitRetry::default(),
+            on_retry: None,
+            clock: clock::system(),
+            follow_symlinks: true,
+            settle: Duration::from_millis(50),
+            strategy: WatchStrategy::Notify,
+            on_fallback: None,
+            cancel: Cancel::default(),
+            debounce: DEFAULT_DEBOUNCE,
+            layout: None,
+            shared: None,
+        }
+    }
+}
+
+impl WatchOptions {
+    /// Path to hand the watcher for `dir`.
+    ///
+    /// Some platforms watch a symlink itself rather than its target, so the
+    /// dir is canonicalized unless `follow_symlinks` is off. Output paths
+    /// should still be built from the dir the caller gave.
+    pub fn watch_path(&self, dir: &Path) -> PathBuf {
+        if self.follow_symlinks {
+            if let Ok(canonical) = dir.canonicalize() {
+                return canonical;
+            }
+        }
+        std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf())
+    }
+}
+
+/// A file read once its content stopped changing.
+#[derive(Debug)]
+pub struct Settled {
+    pub content: String,
+    /// Re-reads that found the content still changing
+    pub retries: u32,
+}
+
+/// Re-read `path` every `options.settle` until two consecutive reads match.
+///
+/// Completion checks use this so a reader that wins the race against the
+/// writer's final flush doesn't return truncated content. Never waits past
+/// `deadline`; the latest read is returned then.
+pub fn read_settled(
+    path: &Path,
+    options: &WatchOptions,
+    deadline: Instant,
+) -> std::io::Result<Settled> {
+    let mut content = fs::read_to_string(path)?;
+    let mut retries = 0;
+    while !options.settle.is_zero() && options.clock.now() + options.settle <= deadline {
+        options.clock.sleep(options.settle);
+        let next = fs::read_to_string(path)?;
+        if next == content {
+            break;
+        }
+        retries += 1;
+        content = next;
+    }
+    Ok(Settled { content, retries })
+}
+
+/// Watcher setup failed on every attempt.
+#[derive(Debug, Error)]
+#[error("failed to initialize file watcher after {attempts} attempt(s): {source}")]
+pub struct WatchInitError {
+    pub attempts: u32,
+    #[source]
+    pub source: notify::Error,
+}
+
+type EventSender = Sender<notify::Result<Event>>;
+
+/// Creates the underlying notify watcher. Injectable so tests can simulate
+/// transient setup failures.
+pub trait WatcherFactory {
+    fn create(&mut self, tx: EventSender) -> notify::Result<Box<dyn Watcher + Send>>;
+}
+
+/// The platform's recommended watcher.
+pub struct NotifyFactory;
+
+impl WatcherFactory for NotifyFactory {
+    fn create(&mut self, tx: EventSender) -> notify::Result<Box<dyn Watcher + Send>> {
+        Ok(Box::new(RecommendedWatcher::new(tx, Config::default())?))
+    }
+}
+
+/// What a polled file looked like at the last scan.
+type Stamp = (Option<std::time::SystemTime>, u64);
+
+/// Rescans the watched roots and turns differences into notify events.
+struct Poller {
+    roots: Vec<(PathBuf, RecursiveMode)>,
+    interval: Duration,
+    next_scan: Instant,
+    seen: BTreeMap<PathBuf, Stamp>,
+    pending: VecDeque<Event>,
+}
+
+impl Poller {
+    fn new(roots: &[(PathBuf, RecursiveMode)], interval: Duration, now: Instant) -> Self {
+        let roots = roots.to_vec();
+        Poller {
+            seen: snapshot(&roots),
+            roots,
+            interval,
+            next_scan: now + interval,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Queue an event for every path created, changed or removed since the
+    /// last scan.
+    fn scan(&mut self, now: Instant) {
+        let current = snapshot(&self.roots);
+        for (path, stamp) in &current {
+            let kind = match self.seen.get(path) {
+                None => EventKind::Create(CreateKind::Any),
+                Some(old) if old != stamp => EventKind::Modify(ModifyKind::Data(DataChange::Any)),
+                Some(_) => continue,
+            };
+            self.pending
+                .push_back(Event::new(kind).add_path(path.clone()));
+        }
+        for path in self.seen.keys().filter(|p| !current.contains_key(*p)) {
+            let event = Event::new(EventKind::Remove(RemoveKind::Any)).add_path(path.clone());
+            self.pending.push_back(event);
+        }
+        self.seen = current;
+        self.next_scan = now + self.interval;
+    }
+}
+
+/// Modification time and length of every entry under `roots`, the roots
+/// included. Unreadable entries are left out.
+fn snapshot(roots: &[(PathBuf, RecursiveMode)]) -> BTreeMap<PathBuf, Stamp> {
+    fn visit(path: &Path, recursive: bool, depth: usize, out: &mut BTreeMap<PathBuf, Stamp>) {
+        let Ok(meta) = fs::metadata(path) else {
+            return;
+        };
+        out.insert(path.to_path_buf(), (meta.modified().ok(), meta.len()));
+        if !meta.is_dir() || (depth > 0 && !recursive) {
+            return;
+        }
+        let Ok(entries) = fs::read_dir(path) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            visit(&entry.path(), recursive, depth + 1, out);
+        }
+    }
+
+    let mut out = BTreeMap::new();
+    for (root, mode) in roots {
+        visit(root, *mode == RecursiveMode::Recursive, 0, &mut out);
+    }
+    out
+}
+
+/// Where a watch's events come from.
+enum Source {
+    Notify {
+        _watcher: Box<dyn Watcher + Send>,
+        rx: Receiver<notify::Result<Event>>,
+    },
+    /// A subscription to a [`SharedWatcher`]
+    Shared(Receiver<notify::Result<Event>>),
+    Poll(RefCell<Poller>),
+}
+
+/// One recursive watcher whose events any number of watches share.
+///
+/// A watch whose roots all lie inside the shared root subscribes to it (see
+/// [`WatchOptions::shared`]) rather than setting up a watcher of its own, so
+/// a long-lived process holds a single watcher however many watches it runs.
+/// Each subscriber only gets the events under its own roots.
+pub struct SharedWatcher {
+    root: PathBuf,
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+    _watcher: Mutex<Box<dyn Watcher + Send>>,
+}
+
+struct Subscriber {
+    roots: Vec<(PathBuf, RecursiveMode)>,
+    tx: EventSender,
+}
+
+impl Subscriber {
+    fn wants(&self, event: &Event) -> bool {
+        event.paths.iter().any(|path| {
+            self.roots.iter().any(|(root, mode)| match mode {
+                RecursiveMode::Recursive => path.starts_with(root),
+                RecursiveMode::NonRecursive => path == root || path.parent() == Some(root),
+            })
+        })
+    }
+}
+
+impl SharedWatcher {
+    /// Watch `root` recursively, retrying setup as [`FsWatch::init`] does.
+    /// `root` should already be a [`WatchOptions::watch_path`].
+    pub fn new(root: &Path, options: &WatchOptions) -> Result<Arc<Self>, WatchInitError> {
+        let deadline = options.clock.now() + Duration::from_secs(60);
+        let roots = [(root.to_path_buf(), RecursiveMode::Recursive)];
+        let source = FsWatch::init(&roots, &mut NotifyFactory, options, deadline)?.source;
+        let Source::Notify { _watcher, rx } = source else {
+            unreachable!("FsWatch::init only sets up notify watchers");
+        };
+
+        let subscribers: Arc<Mutex<Vec<Subscriber>>> = Arc::default();
+        let fan_out = subscribers.clone();
+        // Ends once the watcher, and with it the sending side, is dropped
+        std::thread::spawn(move || {
+            for result in rx {
+                let mut subscribers = fan_out.lock().unwrap_or_else(|e| e.into_inner());
+                // Subscribers whose watch is gone drop out on a failed send
+                subscribers.retain(|subscriber| match &result {
+                    Ok(event) if subscriber.wants(event) => {
+                        subscriber.tx.send(Ok(event.clone())).is_ok()
+                    }
+                    Ok(_) => true,
+                    Err(e) => subscriber
+                        .tx
+                        .send(Err(notify::Error::generic(&e.to_string())))
+                        .is_ok(),
+                });
+            }
+        });
+
+        Ok(Arc::new(SharedWatcher {
+            root: root.to_path_buf(),
+            subscribers,
+            _watcher: Mutex::new(_watcher),
+        }))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The events under `roots`, if they all lie inside the shared root.
+    fn subscribe(
+        &self,
+        roots: &[(PathBuf, RecursiveMode)],
+    ) -> Option<Receiver<notify::Result<Event>>> {
+        if !roots.iter().all(|(root, _)| root.starts_with(&self.root)) {
+            return None;
+        }
+        let (tx, rx) = channel();
+        let subscriber = Subscriber {
+            roots: roots.to_vec(),
+            tx,
+        };
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(subscriber);
+        Some(rx)
+    }
+}
+
+impl std::fmt::Debug for SharedWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedWatcher")
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A notify watcher plus the channel its events arrive on, or a poller
+/// standing in for one.
+///
+/// Shared by every blocking watch so the deadline handling lives in one place.
+pub struct FsWatch {
+    source: Source,
+    clock: Arc<dyn Clock>,
+    cancel: Cancel,
+    debouncer: RefCell<Debouncer>,
+}
+
+impl FsWatch {
+    /// Watch a single path.
+    pub fn new(
+        path: &Path,
+        mode: RecursiveMode,
+        options: &WatchOptions,
+        deadline: Instant,
+    ) -> Result<Self, WatchInitError> {
+        Self::with_roots(&[(path.to_path_buf(), mode)], options, deadline)
+    }
+
+    /// Watch several roots with one underlying watcher, following
+    /// `options.strategy`.
+    pub fn with_roots(
+        roots: &[(PathBuf, RecursiveMode)],
+        options: &WatchOptions,
+        deadline: Instant,
+    ) -> Result<Self, WatchInitError> {
+        let subscription = options
+            .shared
+            .as_ref()
+            .and_then(|shared| shared.subscribe(roots));
+        if let Some(rx) = subscription {
+            return Ok(FsWatch {
+                source: Source::Shared(rx),
+                clock: options.clock.clone(),
+                cancel: options.cancel.clone(),
+                debouncer: RefCell::new(Debouncer::new(options.debounce)),
+            });
+        }
+        match options.strategy {
+            WatchStrategy::Notify => {
+                Self::init_or_poll(roots, &mut NotifyFactory, options, deadline)
+            }
+            WatchStrategy::Poll(interval) => Ok(Self::poll(roots, interval, options)),
+        }
+    }
+
+    /// Watch by rescanning `roots` every `interval`.
+    pub fn poll(
+        roots: &[(PathBuf, RecursiveMode)],
+        interval: Duration,
+        options: &WatchOptions,
+    ) -> Self {
+        let poller = Poller::new(roots, interval, options.clock.now());
+        FsWatch {
+            source: Source::Poll(RefCell::new(poller)),
+            clock: options.clock.clone(),
+            cancel: options.cancel.clone(),
+            debouncer: RefCell::new(Debouncer::new(options.debounce)),
+        }
+    }
+
+    /// Like [`FsWatch::init`], but once every attempt has failed, poll every
+    /// [`FALLBACK_POLL_INTERVAL`] instead of giving up. `options.on_fallback`
+    /// hears about the switch.
+    pub fn init_or_poll<F: WatcherFactory>(
+        roots: &[(PathBuf, RecursiveMode)],
+        factory: &mut F,
+        options: &WatchOptions,
+        deadline: Instant,
+    ) -> Result<Self, WatchInitError> {
+        Self::init(roots, factory, options, deadline).or_else(|e| {
+            if let Some(on_fallback) = options.on_fallback {
+                on_fallback(&e);
+            }
+            Ok(Self::poll(roots, FALLBACK_POLL_INTERVAL, options))
+        })
+    }
+
+    /// Set up the watcher, retrying transient failures with exponential backoff.
+    ///
+    /// Time spent backing off counts against `deadline`: no retry is attempted
+    /// once its backoff would run past it.
+    pub fn init<F: WatcherFactory>(
+        roots: &[(PathBuf, RecursiveMode)],
+        factory: &mut F,
+        options: &WatchOptions,
+        deadline: Instant,
+    ) -> Result<Self, WatchInitError> {
+        let mut backoff = options.init_retry.backoff;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let (tx, rx) = channel();
+            let result = factory.create(tx).and_then(|mut watcher| {
+                for (path, mode) in roots {
+                    watcher.watch(path, *mode)?;
+                }
+                Ok(watcher)
+            });
+
+            let error = match result {
+                Ok(watcher) => {
+                    return Ok(FsWatch {
+                        source: Source::Notify {
+                            _watcher: watcher,
+                            rx,
+                        },
+                        clock: options.clock.clone(),
+                        cancel: options.cancel.clone(),
+                        debouncer: RefCell::new(Debouncer::new(options.debounce)),
+                    });
+                }
+                Err(e) => e,
+            };
+
+            let out_of_time = options.clock.now() + backoff > deadline;
+            if attempt > options.init_retry.retries || out_of_time {
+                return Err(WatchInitError {
+                    attempts: attempt,
+                    source: error,
+                });
+            }
+
+            if let Some(on_retry) = options.on_retry {
+                on_retry(&RetryAttempt {
+                    attempt,
+                    error: error.to_string(),
+                    backoff_ms: backoff.as_millis() as u64,
+                });
+            }
+            options.clock.sleep(backoff);
+            backoff *= 2;
+        }
+    }
+
+    /// Block for the next event, returning `None` once `deadline` passes
+    /// on the watch's clock or the watch is cancelled; [`FsWatch::cancelled`]
+    /// tells the two apart.
+    pub fn next_event(&self, deadline: Instant) -> Result<Option<Event>, McError> {
+        let rx = match &self.source {
+            Source::Notify { rx, .. } | Source::Shared(rx) => rx,
+            Source::Poll(poller) => return Ok(self.next_polled(poller, deadline)),
+        };
+        loop {
+            if self.cancelled().is_some() {
+                return Ok(None);
+            }
+            let remaining = deadline.saturating_duration_since(self.clock.now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+
+            let slice = self.clock.wait_slice(remaining.min(cancel::CHECK_INTERVAL));
+            match rx.recv_timeout(slice) {
+                Ok(Ok(event)) => return Ok(self.cancelled().is_none().then_some(event)),
+                Ok(Err(e)) => return Err(McError::Notify(e)),
+                Err(RecvTimeoutError::Timeout) => self.clock.waited(slice),
+                Err(e) => return Err(McError::Notify(notify::Error::generic(&e.to_string()))),
+            }
+        }
+    }
+
+    /// Like [`FsWatch::next_event`], but with bursts of events for the same
+    /// paths coalesced into their last event by the watch's [`Debouncer`].
+    ///
+    /// Events still waiting out their window when `deadline` passes are
+    /// returned then rather than dropped, so a burst's final event is always
+    /// seen. The watchers read events through this.
+    pub fn next_debounced(&self, deadline: Instant) -> Result<Option<Event>, McError> {
+        let mut debouncer = self.debouncer.borrow_mut();
+        loop {
+            if self.cancelled().is_some() {
+                return Ok(None);
+            }
+            let now = self.clock.now();
+            if let Some(event) = debouncer.pop_ready(now) {
+                return Ok(Some(event));
+            }
+            if now >= deadline {
+                return Ok(debouncer.flush());
+            }
+            let until = debouncer
+                .next_due()
+                .map_or(deadline, |due| due.min(deadline));
+            if let Some(event) = self.next_event(until)? {
+                debouncer.push(event, self.clock.now());
+            }
+        }
+    }
+
+    fn next_polled(&self, poller: &RefCell<Poller>, deadline: Instant) -> Option<Event> {
+        let mut poller = poller.borrow_mut();
+        loop {
+            if self.cancelled().is_some() {
+                return None;
+            }
+            if let Some(event) = poller.pending.pop_front() {
+                return Some(event);
+            }
+            let now = self.clock.now();
+            if now >= deadline {
+                return None;
+            }
+            if now >= poller.next_scan {
+                poller.scan(now);
+                continue;
+            }
+            let wait = (poller.next_scan - now).min(deadline - now);
+            self.clock.sleep(wait.min(cancel::CHECK_INTERVAL));
+        }
+    }
+
+    /// Why the watch was cancelled, if it was.
+    pub fn cancelled(&self) -> Option<CancelReason> {
+        self.cancel.requested()
+    }
+}
+
+/// Wait on `fs_watch` until `check` finds what it is looking for or
+/// `deadline` passes.
+///
+/// `check` sees every debounced event and returns `Some` to stop the wait;
+/// `None` is returned on timeout. This is the loop behind the single-file
+/// watches.
+pub fn wait_for<T, F>(
+    fs_watch: &FsWatch,
+    deadline: Instant,
+    mut check: F,
+) -> Result<Option<T>, McError>
+where
+    F: FnMut(&Event) -> Result<Option<T>, McError>,
+{
+    while let Some(event) = fs_watch.next_debounced(deadline)? {
+        if let Some(found) = check(&event)? {
+            return Ok(Some(found));
+        }
+    }
+    Ok(None)
+}
+
+/// Coalesces bursts of events for the same paths into the last one.
+///
+/// Writers that save through a temp file and a rename produce several events
+/// per change. Each set of paths keeps only its latest event, released once
+/// no newer one has arrived for the window, so the event delivered is always
+/// the burst's final one. A removal or rename away is kept apart from the
+/// paths' other events, so a file replaced within the window still shows as
+/// gone, then back. A zero window passes events straight through.
+#[derive(Debug)]
+pub struct Debouncer {
+    window: Duration,
+    /// Latest event per set of paths and when it arrived
+    pending: Vec<(Event, Instant)>,
+}
+
+impl Debouncer {
+    pub fn new(window: Duration) -> Self {
+        Debouncer {
+            window,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Take in an event seen at `now`, replacing any pending one for its paths.
+    pub fn push(&mut self, event: Event, now: Instant) {
+        let same = |e: &Event| e.paths == event.paths && gone(e) == gone(&event);
+        match self.pending.iter_mut().find(|(e, _)| same(e)) {
+            Some(slot) => *slot = (event, now),
+            None => self.pending.push((event, now)),
+        }
+    }
+
+    /// When the earliest pending event's window ends.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.pending.iter().map(|(_, at)| *at + self.window).min()
+    }
+
+    /// The oldest pending event, if its paths have been quiet for the window.
+    pub fn pop_ready(&mut self, now: Instant) -> Option<Event> {
+        let index = self.oldest()?;
+        (now >= self.pending[index].1 + self.window).then(|| self.pending.remove(index).0)
+    }
+
+    /// The oldest pending event, whether its window has ended or not.
+    pub fn flush(&mut self) -> Option<Event> {
+        let index = self.oldest()?;
+        Some(self.pending.remove(index).0)
+    }
+
+    fn oldest(&self) -> Option<usize> {
+        (0..self.pending.len()).min_by_key(|&i| self.pending[i].1)
+    }
+}
+
+/// Whether `event` takes its paths away: a removal or a rename.
+fn gone(event: &Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+    )
+}
+
+/// Plan the watch roots needed to cover `dirs`, each watched non-recursively.
+///
+/// Only identical roots are merged. Widening sibling dirs to their shared
+/// parent would watch whatever else lives there (often `/tmp` or `$HOME`),
+/// and a dir inside another root is not covered by a non-recursive watch.
+pub fn plan_roots(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut roots = dirs.to_vec();
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use tempfile::TempDir;
+
+    /// Fails the first `failures` attempts the way a busy host does.
+    struct FlakyFactory {
+        failures: u32,
+        calls: u32,
+    }
+
+    impl WatcherFactory for FlakyFactory {
+        fn create(&mut self, tx: EventSender) -> notify::Result<Box<dyn Watcher + Send>> {
+            self.calls += 1;
+            if self.calls <= self.failures {
+                return Err(notify::Error::io(std::io::Error::other(
+                    "Too many open files (os error 24)",
+                )));
+            }
+            NotifyFactory.create(tx)
+        }
+    }
+
+    fn retry_options(retries: u32, backoff_ms: u64, clock: &Arc<MockClock>) -> WatchOptions {
+        WatchOptions {
+            init_retry: InitRetry {
+                retries,
+                backoff: Duration::from_millis(backoff_ms),
+            },
+            clock: clock.clone(),
+            ..WatchOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_init_retries_transient_failures() {
+        let temp_dir = TempDir::new().unwrap();
+        let roots = [(temp_dir.path().to_path_buf(), RecursiveMode::NonRecursive)];
+        let mut factory = FlakyFactory {
+            failures: 2,
+            calls: 0,
+        };
+
+        let clock = Arc::new(MockClock::new());
+        let deadline = clock.now() + Duration::from_secs(5);
+        FsWatch::init(
+            &roots,
+            &mut factory,
+            &retry_options(3, 10, &clock),
+            deadline,
+        )
+        .unwrap();
+
+        assert_eq!(factory.calls, 3);
+        // Backoff doubles: 10ms then 20ms
+        assert_eq!(clock.elapsed(), Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_init_gives_up_with_last_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let roots = [(temp_dir.path().to_path_buf(), RecursiveMode::NonRecursive)];
+        let mut factory = FlakyFactory {
+            failures: u32::MAX,
+            calls: 0,
+        };
+
+        let clock = Arc::new(MockClock::new());
+        let deadline = clock.now() + Duration::from_secs(5);
+        let err = FsWatch::init(&roots, &mut factory, &retry_options(2, 1, &clock), deadline)
+            .err()
+            .unwrap();
+        assert_eq!(err.attempts, 3);
+        assert!(err.to_string().contains("Too many open files"));
+    }
+
+    #[test]
+    fn test_init_retries_stop_at_deadline() {
+        let temp_dir = TempDir::new().unwrap();
+        let roots = [(temp_dir.path().to_path_buf(), RecursiveMode::NonRecursive)];
+        let mut factory = FlakyFactory {
+            failures: u32::MAX,
+            calls: 0,
+        };
+
+        // 20ms fits in the budget, the following 40ms doesn't
+        let clock = Arc::new(MockClock::new());
+        let deadline = clock.now() + Duration::from_millis(50);
+        let err = FsWatch::init(
+            &roots,
+            &mut factory,
+            &retry_options(10, 20, &clock),
+            deadline,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.attempts, 2);
+        assert_eq!(clock.elapsed(), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_init_or_poll_falls_back_to_polling() {
+        let temp_dir = TempDir::new().unwrap();
+        let roots = [(temp_dir.path().to_path_buf(), RecursiveMode::NonRecursive)];
+        let mut factory = FlakyFactory {
+            failures: u32::MAX,
+            calls: 0,
+        };
+
+        let clock = Arc::new(MockClock::new());
+        let deadline = clock.now() + Duration::from_secs(5);
+        let fs_watch =
+            FsWatch::init_or_poll(&roots, &mut factory, &retry_options(1, 1, &clock), deadline)
+                .unwrap();
+        assert!(matches!(fs_watch.source, Source::Poll(_)));
+    }
+
+    #[test]
+    fn test_poll_reports_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let kept = temp_dir.path().join("kept");
+        let removed = temp_dir.path().join("removed");
+        std::fs::write(&kept, "a").unwrap();
+        std::fs::write(&removed, "a").unwrap();
+
+        let clock = Arc::new(MockClock::new());
+        let options = WatchOptions {
+            clock: clock.clone(),
+            ..WatchOptions::default()
+        };
+        let roots = [(temp_dir.path().to_path_buf(), RecursiveMode::NonRecursive)];
+        let fs_watch = FsWatch::poll(&roots, Duration::from_millis(10), &options);
+
+        std::fs::write(&kept, "ab").unwrap();
+        std::fs::remove_file(&removed).unwrap();
+        std::fs::write(temp_dir.path().join("created"), "a").unwrap();
+
+        let deadline = clock.now() + Duration::from_secs(1);
+        let mut events = Vec::new();
+        while let Some(event) = fs_watch.next_event(deadline).unwrap() {
+            // The dir itself changes too as entries come and go
+            if event.paths[0] != temp_dir.path() {
+                let name = event.paths[0].file_name().unwrap().to_string_lossy();
+                events.push((name.to_string(), event.kind));
+            }
+        }
+        events.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(events.len(), 3, "{:?}", events);
+        assert!(events[0].1.is_create());
+        assert!(events[1].1.is_modify());
+        assert!(events[2].1.is_remove());
+        // Only the first scan found anything; later ones waited out the deadline
+        assert_eq!(clock.elapsed(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_shared_watcher_sends_each_watch_its_own_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let options = WatchOptions::default();
+        let root = options.watch_path(temp_dir.path());
+        for dir in ["a", "b"] {
+            std::fs::create_dir(root.join(dir)).unwrap();
+        }
+        let options = WatchOptions {
+            shared: Some(SharedWatcher::new(&root, &options).unwrap()),
+            ..options
+        };
+        let watch = |dir: &str| {
+            let roots = [(root.join(dir), RecursiveMode::NonRecursive)];
+            let deadline = Instant::now() + Duration::from_secs(1);
+            let fs_watch = FsWatch::with_roots(&roots, &options, deadline).unwrap();
+            assert!(matches!(fs_watch.source, Source::Shared(_)));
+            fs_watch
+        };
+        let (a, b) = (watch("a"), watch("b"));
+
+        std::fs::write(root.join("b/file"), "b").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let event = b.next_event(deadline).unwrap().expect("b's event");
+        assert_eq!(event.paths, vec![root.join("b/file")]);
+        let deadline = Instant::now() + Duration::from_millis(200);
+        assert!(a.next_event(deadline).unwrap().is_none());
+
+        // A watch outside the shared root sets up its own watcher
+        let outside = TempDir::new().unwrap();
+        let roots = [(outside.path().to_path_buf(), RecursiveMode::NonRecursive)];
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let fs_watch = FsWatch::with_roots(&roots, &options, deadline).unwrap();
+        assert!(matches!(fs_watch.source, Source::Notify { .. }));
+    }
+
+    #[test]
+    fn test_plan_roots_keeps_siblings_apart() {
+        let dirs = vec![
+            PathBuf::from("/missions/a/status"),
+            PathBuf::from("/missions/b/status"),
+            PathBuf::from("/missions/a/status"),
+        ];
+        let roots = plan_roots(&dirs);
+        assert_eq!(
+            roots,
+            vec![
+                PathBuf::from("/missions/a/status"),
+                PathBuf::from("/missions/b/status")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_roots_keeps_nested() {
+        let dirs = vec![PathBuf::from("/srv/a"), PathBuf::from("/srv/a/status")];
+        let roots = plan_roots(&dirs);
+        assert_eq!(roots, dirs);
+    }
+
+    #[test]
+    fn test_read_settled_stops_at_deadline() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("status");
+        std::fs::write(&path, "DONE").unwrap();
+
+        let clock = Arc::new(MockClock::new());
+        let options = WatchOptions {
+            clock: clock.clone(),
+            ..WatchOptions::default()
+        };
+
+        let settled = read_settled(&path, &options, clock.now() + Duration::from_secs(1)).unwrap();
+        assert_eq!(settled.content, "DONE");
+        assert_eq!(settled.retries, 0);
+        assert_eq!(clock.elapsed(), options.settle);
+
+        // Too close to the deadline to wait at all
+        let start = clock.elapsed();
+        read_settled(&path, &options, clock.now() + Duration::from_millis(10)).unwrap();
+        assert_eq!(clock.elapsed(), start);
+    }
+
+    fn touched(path: &str) -> Event {
+        Event::new(EventKind::Modify(ModifyKind::Any)).add_path(PathBuf::from(path))
+    }
+
+    #[test]
+    fn test_debouncer_keeps_last_event_of_burst() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut debouncer = Debouncer::new(Duration::from_millis(100));
+
+        debouncer.push(touched("/m/a"), ms(0));
+        debouncer.push(
+            Event::new(EventKind::Create(CreateKind::File)).add_path("/m/a".into()),
+            ms(30),
+        );
+        debouncer.push(touched("/m/a"), ms(60));
+        // Each event restarts the window
+        assert_eq!(debouncer.next_due(), Some(ms(160)));
+        assert!(debouncer.pop_ready(ms(150)).is_none());
+
+        let event = debouncer.pop_ready(ms(160)).unwrap();
+        assert_eq!(event.kind, EventKind::Modify(ModifyKind::Any));
+        assert!(debouncer.pop_ready(ms(1000)).is_none());
+        assert_eq!(debouncer.next_due(), None);
+    }
+
+    #[test]
+    fn test_debouncer_separates_paths() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut debouncer = Debouncer::new(Duration::from_millis(100));
+
+        debouncer.push(touched("/m/a"), ms(0));
+        debouncer.push(touched("/m/b"), ms(50));
+        debouncer.push(touched("/m/a"), ms(80));
+
+        // b has been quiet longest now that a was touched again
+        assert_eq!(
+            debouncer.pop_ready(ms(150)).unwrap().paths,
+            [PathBuf::from("/m/b")]
+        );
+        assert!(debouncer.pop_ready(ms(150)).is_none());
+        assert_eq!(
+            debouncer.pop_ready(ms(180)).unwrap().paths,
+            [PathBuf::from("/m/a")]
+        );
+    }
+
+    #[test]
+    fn test_debouncer_keeps_removal_apart() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut debouncer = Debouncer::new(Duration::from_millis(100));
+
+        // A file replaced within the window
+        debouncer.push(touched("/m/a"), ms(0));
+        let removed = Event::new(EventKind::Remove(RemoveKind::File)).add_path("/m/a".into());
+        debouncer.push(removed, ms(10));
+        let created = Event::new(EventKind::Create(CreateKind::File)).add_path("/m/a".into());
+        debouncer.push(created, ms(20));
+
+        let kinds: Vec<EventKind> = std::iter::from_fn(|| debouncer.pop_ready(ms(200)))
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                EventKind::Remove(RemoveKind::File),
+                EventKind::Create(CreateKind::File)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_debouncer_flush_and_zero_window() {
+        let now = Instant::now();
+        let mut debouncer = Debouncer::new(Duration::from_millis(100));
+        debouncer.push(touched("/m/a"), now);
+        // Still inside its window, but a deadline must not drop it
+        assert!(debouncer.flush().is_some());
+        assert!(debouncer.flush().is_none());
+
+        let mut debouncer = Debouncer::new(Duration::ZERO);
+        debouncer.push(touched("/m/a"), now);
+        assert!(debouncer.pop_ready(now).is_some());
+    }
+
+    #[test]
+    fn test_next_debounced_delivers_trailing_event() {
+        let temp_dir = TempDir::new().unwrap();
+        let roots = [(temp_dir.path().to_path_buf(), RecursiveMode::NonRecursive)];
+        let fs_watch = FsWatch::poll(&roots, Duration::from_millis(10), &WatchOptions::default());
+        let path = temp_dir.path().join("status.md");
+        std::fs::write(&path, "WORKING").unwrap();
+        std::fs::write(&path, "DONE").unwrap();
+
+        let deadline = Instant::now() + Duration::from_millis(500);
+        let mut seen = Vec::new();
+        while let Some(event) = fs_watch.next_debounced(deadline).unwrap() {
+            seen.extend(event.paths);
+        }
+        assert_eq!(seen.iter().filter(|p| **p == path).count(), 1);
+    }
+}
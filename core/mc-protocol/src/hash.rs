@@ -0,0 +1,49 @@
+/// Hash content for change detection.
+///
+/// The content is normalized first (CRLF → LF, trailing whitespace trimmed
+/// from each line and from the end) so cosmetic rewrites keep the same hash.
+/// The algorithm is SHA-256 by default, or BLAKE3 with the `blake3` feature;
+/// the result is prefixed with the algorithm name, e.g. `sha256:ab12…`.
+pub fn content_hash(content: &str) -> String {
+    digest(normalize(content).as_bytes())
+}
+
+/// Normalize line endings and trailing whitespace.
+pub fn normalize(content: &str) -> String {
+    let unix = content.replace("\r\n", "\n");
+    let lines: Vec<&str> = unix.lines().map(str::trim_end).collect();
+    lines.join("\n").trim_end().to_string()
+}
+
+#[cfg(not(feature = "blake3"))]
+fn digest(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    let hash = Sha256::digest(bytes);
+    let hex: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256:{}", hex)
+}
+
+#[cfg(feature = "blake3")]
+fn digest(bytes: &[u8]) -> String {
+    format!("blake3:{}", blake3::hash(bytes).to_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_stable_across_line_endings() {
+        let lf = "## Summary\n\nDone.\n";
+        let crlf = "## Summary\r\n\r\nDone.\r\n";
+        let trailing = "## Summary  \n\nDone.\t\n\n\n";
+        assert_eq!(content_hash(lf), content_hash(crlf));
+        assert_eq!(content_hash(lf), content_hash(trailing));
+    }
+
+    #[test]
+    fn test_hash_changes_with_content() {
+        assert_ne!(content_hash("Done."), content_hash("Done!"));
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How often a running hook is polled for exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Outcome of running a completion hook.
+///
+/// Hook failures never change the primary watch result; they are only
+/// reported alongside it.
+#[derive(Debug, Serialize)]
+pub struct HookReport {
+    pub command: String,
+    pub exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Build the MC_* environment for a watch result.
+///
+/// `status` and `response_path` are lifted from the result JSON when present.
+pub fn result_env(result: &Value) -> Vec<(String, String)> {
+    let mut env = Vec::new();
+    if let Some(status) = result.get("status").and_then(|v| v.as_str()) {
+        env.push(("MC_STATUS".to_string(), status.to_string()));
+    }
+    if let Some(path) = result.get("response_path").and_then(|v| v.as_str()) {
+        env.push(("MC_RESPONSE_PATH".to_string(), path.to_string()));
+    }
+    env
+}
+
+/// Run `command` through the shell with the result JSON on stdin.
+///
+/// Waits up to `timeout` for the hook to exit, killing it if it overruns.
+pub fn run(
+    command: &str,
+    payload: &Value,
+    env: &[(String, String)],
+    timeout: Duration,
+) -> HookReport {
+    let mut report = HookReport {
+        command: command.to_string(),
+        exit_code: None,
+        timed_out: false,
+        error: None,
+    };
+
+    let mut child = match shell(command)
+        .envs(env.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            report.error = Some(format!("Failed to spawn hook: {}", e));
+            return report;
+        }
+    };
+
+    // Feed stdin from a separate thread so a hook that never reads it can't block us
+    if let Some(mut stdin) = child.stdin.take() {
+        let body = payload.to_string();
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(body.as_bytes());
+        });
+    }
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                report.exit_code = status.code();
+                if !status.success() {
+                    report.error = Some(format!("Hook exited with {}", status));
+                }
+                return report;
+            }
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                report.timed_out = true;
+                report.error = Some(format!("Hook timed out after {:?}", timeout));
+                return report;
+            }
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                report.error = Some(format!("Failed to wait for hook: {}", e));
+                return report;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_hook_receives_payload_and_env() {
+        let temp_dir = TempDir::new().unwrap();
+        let stdin_file = temp_dir.path().join("stdin.json");
+        let env_file = temp_dir.path().join("env.txt");
+
+        let command = format!(
+            "cat > {} && printf '%s|%s|%s' \"$MC_TASK_ID\" \"$MC_STATUS\" \"$MC_RESPONSE_PATH\" > {}",
+            stdin_file.display(),
+            env_file.display()
+        );
+        let payload = json!({"status": "complete", "response_path": "responses/task-001.md"});
+        let mut env = result_env(&payload);
+        env.push(("MC_TASK_ID".to_string(), "001".to_string()));
+
+        let report = run(&command, &payload, &env, Duration::from_secs(5));
+        assert_eq!(report.exit_code, Some(0));
+        assert!(report.error.is_none());
+
+        let received: Value =
+            serde_json::from_str(&fs::read_to_string(&stdin_file).unwrap()).unwrap();
+        assert_eq!(received, payload);
+        assert_eq!(
+            fs::read_to_string(&env_file).unwrap(),
+            "001|complete|responses/task-001.md"
+        );
+    }
+
+    #[test]
+    fn test_hook_failure_is_reported() {
+        let report = run(
+            "exit 7",
+            &json!({"status": "timeout"}),
+            &[],
+            Duration::from_secs(5),
+        );
+        assert_eq!(report.exit_code, Some(7));
+        assert!(report.error.is_some());
+        assert!(!report.timed_out);
+    }
+
+    #[test]
+    fn test_hook_timeout_kills_command() {
+        let report = run("sleep 5", &json!({}), &[], Duration::from_millis(50));
+        assert!(report.timed_out);
+        assert_eq!(report.exit_code, None);
+    }
+}
@@ -1,4 +1,46 @@
+//! The mission protocol: watching tasks and conversations, reading task and
+//! response files, and counting tokens. The `mc-protocol` binary is a thin
+//! CLI over these functions; the most used ones are re-exported here.
+
+pub mod archive;
+pub mod attachments;
+pub mod attribution;
+pub mod audit;
+pub mod cancel;
+pub mod clock;
 pub mod conversation;
+pub mod discover;
+pub mod doctor;
+pub mod error;
+pub mod events;
+pub mod fleet;
+pub mod fswatch;
+pub mod hash;
+pub mod hooks;
+#[cfg(feature = "notifications")]
+pub mod notifications;
+pub mod prompt;
 pub mod protocol;
+pub mod prune;
+pub mod selftest;
+#[cfg(unix)]
+pub mod serve;
+pub mod spec;
 pub mod tokens;
 pub mod watcher;
+
+pub use conversation::{
+    check_complete, watch as watch_conversation, watch_streaming as watch_conversation_streaming,
+    After, CompletedBy, Completion, ConversationResult, Exchange,
+};
+pub use error::McError;
+pub use fswatch::WatchOptions;
+pub use protocol::{
+    parse_response, parse_response_in, parse_response_with, parse_task, validate_response,
+    validate_task, Headers, ParsedResponse, ResponseOptions, ValidationResult,
+};
+pub use tokens::{count_tokens, TokenUsage};
+pub use watcher::{
+    watch_inbox, watch_task, watch_task_with_progress, InboxOptions, InboxResult, ProgressEvent,
+    WatchResult,
+};
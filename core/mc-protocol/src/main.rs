@@ -1,7 +1,20 @@
-use clap::{Parser, Subcommand};
-use mc_protocol::{conversation, protocol, tokens, watcher};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use mc_protocol::cancel::Cancel;
+use mc_protocol::fswatch::{
+    InitRetry, RetryAttempt, WatchInitError, WatchOptions, WatchStrategy, FALLBACK_POLL_INTERVAL,
+};
+#[cfg(unix)]
+use mc_protocol::serve;
+use mc_protocol::McError;
+use mc_protocol::{
+    archive, attribution, audit, conversation, discover, doctor, events, fleet, hooks, prompt,
+    protocol, prune, selftest, tokens, watcher,
+};
 use serde::Serialize;
-use std::path::Path;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::time::Duration;
 
 #[derive(Parser)]
@@ -10,6 +23,12 @@ use std::time::Duration;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Don't walk up from the cwd looking for .mission when --mission-dir is omitted
+    #[arg(long, global = true)]
+    no_discover: bool,
+    /// Watch a symlinked mission dir as the link itself instead of its target
+    #[arg(long, global = true)]
+    no_follow_symlinks: bool,
 }
 
 #[derive(Subcommand)]
@@ -18,40 +37,786 @@ enum Commands {
     WatchTask {
         #[arg(long)]
         task_id: String,
-        #[arg(long, default_value = ".mission")]
-        mission_dir: String,
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        #[arg(long, default_value = "300")]
+        timeout: u64,
+        /// Print a progress line every SECS while waiting (default 30) and
+        /// whenever the task's files change; the result stays the last line
+        #[arg(
+            long,
+            value_name = "SECS",
+            num_args = 0..=1,
+            default_missing_value = "30",
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        progress: Option<u64>,
+        /// Also wait for the response file to be non-empty and fully written
+        /// (valid, or unchanged for 200ms); reports status_only if it never is
+        #[arg(long)]
+        require_response: bool,
+        /// Where the task's files live (flat, nested or auto)
+        #[arg(long, value_enum, default_value = "auto")]
+        layout: LayoutArg,
+        #[command(flatten)]
+        hooks: HookArgs,
+        #[command(flatten)]
+        watch_init: WatchInitArgs,
+    },
+    /// Watch several tasks with one watcher, printing a line as each completes
+    WatchTasks {
+        /// Comma-separated task IDs
+        #[arg(long, value_delimiter = ',', required = true)]
+        task_ids: Vec<String>,
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
         #[arg(long, default_value = "300")]
         timeout: u64,
+        /// Where the tasks' files live (flat, nested or auto, per task)
+        #[arg(long, value_enum, default_value = "auto")]
+        layout: LayoutArg,
+        #[command(flatten)]
+        watch_init: WatchInitArgs,
+    },
+    /// Wait for a task nobody has picked up yet (no status or claim file),
+    /// oldest first
+    WatchInbox {
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        #[arg(long, default_value = "300")]
+        timeout: u64,
+        /// Only take tasks of this priority (normal, high or critical)
+        #[arg(long, value_parser = parse_priority)]
+        priority: Option<protocol::Priority>,
+        /// Create status/task-{id}.claimed so no other watcher takes the task
+        #[arg(long)]
+        claim: bool,
+        #[command(flatten)]
+        watch_init: WatchInitArgs,
     },
     /// Watch for conversation response (blocks until ---END--- marker or timeout)
     WatchConversation {
-        #[arg(long, default_value = ".mission")]
-        mission_dir: String,
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        #[arg(long, default_value = "300")]
+        timeout: u64,
+        /// Line that ends a response (repeatable; default: ---END---)
+        #[arg(long)]
+        end_marker: Vec<String>,
+        /// File whose appearance completes the conversation, marker or not
+        #[arg(long)]
+        done_file: Option<PathBuf>,
+        /// Complete on a marker ending an assistant response after this 1-based one,
+        /// even if more turns follow it
+        #[arg(long, conflicts_with = "since_offset")]
+        after_turn: Option<usize>,
+        /// Complete on a marker past this byte offset (a previous marker_offset),
+        /// even if more turns follow it
+        #[arg(long)]
+        since_offset: Option<usize>,
+        /// Print each piece of the response as it is written, one
+        /// `{"status": "partial", "text": ...}` line per piece; the result
+        /// stays the last line
+        #[arg(long)]
+        stream: bool,
+        #[command(flatten)]
+        hooks: HookArgs,
+        #[command(flatten)]
+        watch_init: WatchInitArgs,
+    },
+    /// Print assistant responses from conversation.md (default: the last one)
+    GetResponse {
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        /// 1-based assistant response to select
+        #[arg(long, conflicts_with_all = ["since", "until"])]
+        turn: Option<usize>,
+        /// Select responses timestamped at or after this RFC3339 time
+        #[arg(long)]
+        since: Option<String>,
+        /// Select responses timestamped before this RFC3339 time
+        #[arg(long)]
+        until: Option<String>,
+    },
+    /// Print every turn of conversation.md as a JSON array
+    ParseConversation {
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+    },
+    /// Append a turn to conversation.md, locking it against other writers
+    Append {
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        #[arg(long, value_enum)]
+        role: AppendRole,
+        #[arg(long, required_unless_present = "content_file")]
+        content: Option<String>,
+        /// Read the content from a file instead
+        #[arg(long, conflicts_with = "content")]
+        content_file: Option<PathBuf>,
+        /// Close the turn with ---END---
+        #[arg(long)]
+        end: bool,
+    },
+    /// Watch many missions at once (one `dir [task_id]` per line of the missions file)
+    WatchFleet {
+        #[arg(long)]
+        missions_file: String,
+        /// Emit one NDJSON record per mission as it resolves
+        #[arg(long)]
+        stream: bool,
         #[arg(long, default_value = "300")]
         timeout: u64,
+        #[command(flatten)]
+        watch_init: WatchInitArgs,
     },
     /// Validate task file format
     ValidateTask {
+        #[command(flatten)]
+        file: TaskFileArgs,
+    },
+    /// Validate response file format
+    ValidateResponse {
         #[arg(long)]
         file: String,
+        /// Task the response must be for
+        #[arg(long)]
+        task_id: Option<String>,
+    },
+    /// Parse a task file into metadata, sections, and attachments
+    ParseTask {
+        #[arg(long)]
+        file: String,
+        /// Include attachment content in the output
+        #[arg(long)]
+        inline_attachments: bool,
+    },
+    /// Report a task's token use per section and where it could be split
+    AnalyzeTask {
+        #[arg(long)]
+        file: String,
+        #[arg(long, default_value = "8000")]
+        budget: usize,
+    },
+    /// Assemble a task and recent conversation into one prompt file within a token budget
+    RenderPrompt {
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        #[arg(long)]
+        task_id: String,
+        #[arg(long, default_value = "100000")]
+        budget: usize,
+        /// Output file (default: {mission_dir}/prompts/task-{id}.md)
+        #[arg(long)]
+        out: Option<String>,
+    },
+    /// Write a new task file into the mission
+    CreateTask {
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        #[arg(long)]
+        task_id: String,
+        /// normal, high or critical
+        #[arg(long, default_value = "normal")]
+        priority: String,
+        #[arg(long, required_unless_present = "instructions_file")]
+        instructions: Option<String>,
+        /// Read the instructions from a file instead
+        #[arg(long, conflicts_with = "instructions")]
+        instructions_file: Option<PathBuf>,
+        #[arg(long)]
+        context: Option<String>,
+        /// Read the context from a file instead
+        #[arg(long, conflicts_with = "context")]
+        context_file: Option<PathBuf>,
+        /// File to store as a content-addressed attachment (repeatable)
+        #[arg(long)]
+        attach: Vec<String>,
+        /// Overwrite an existing task file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Convert a task file between markdown and JSON
+    ConvertTask {
+        #[arg(long)]
+        file: PathBuf,
+        /// Format to write (default: whichever the file isn't in)
+        #[arg(long, value_enum)]
+        to: Option<FormatArg>,
+        /// File to write (default: the input with the new format's extension)
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Overwrite an existing output file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Validate every task file in the mission
+    ValidateAll {
+        #[command(flatten)]
+        tasks: TaskListArgs,
+    },
+    /// List the mission's tasks and where each stands; the json document is oldest first
+    ListTasks {
+        #[command(flatten)]
+        tasks: TaskListArgs,
+    },
+    /// Report whether a task's dependencies all have status files
+    CheckReady {
+        #[arg(long)]
+        task_id: String,
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
     },
     /// Parse response file
     ParseResponse {
+        #[command(flatten)]
+        file: TaskFileArgs,
+        /// Skip parsing and exit with code 4 if the content hash matches
+        #[arg(long)]
+        if_changed: Option<String>,
+        /// Root that Files Modified entries are checked against
+        #[arg(long, default_value = ".")]
+        repo_root: String,
+        /// Match section headers exactly instead of ignoring case, spacing and aliases
+        #[arg(long)]
+        strict_headers: bool,
+    },
+    /// Compare a response's Files Modified against the files that actually changed
+    AuditResponse {
+        #[arg(long)]
+        file: String,
+        #[arg(long, default_value = ".")]
+        repo_root: String,
+        /// Read changed files from `git status --porcelain` in the repo root
+        #[arg(
+            long,
+            conflicts_with = "changed_files",
+            required_unless_present = "changed_files"
+        )]
+        git: bool,
+        /// Read changed files from this file, one path per line
+        #[arg(long)]
+        changed_files: Option<String>,
+    },
+    /// Print an agent's forwarded events, stitching rotated generations in order
+    ReadEvents {
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        #[arg(long)]
+        agent: String,
+        /// Only the most recent N events
+        #[arg(long)]
+        last: Option<usize>,
+    },
+    /// Check a stream-parser NDJSON file against the shared event schema
+    CheckEvents {
         #[arg(long)]
         file: String,
     },
     /// Watch conversation.md and report token usage
     WatchTokens {
-        #[arg(long, default_value = ".mission")]
-        mission_dir: String,
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
         #[arg(long, default_value = "300")]
         timeout: u64,
+        /// Keep watching until the timeout, printing a line on every change;
+        /// the last line is the latest count
+        #[arg(long)]
+        follow: bool,
+        /// Stop with exit code 3 once the conversation is over this many tokens
+        #[arg(long)]
+        max_tokens: Option<usize>,
+        /// Stop with exit code 3 once the estimated cost is over this many USD
+        #[arg(long)]
+        max_cost_usd: Option<f64>,
+        #[command(flatten)]
+        pricing: PricingArgs,
+        #[command(flatten)]
+        watch_init: WatchInitArgs,
     },
     /// Count tokens in conversation.md (one-shot, no watching)
     CountTokens {
-        #[arg(long, default_value = ".mission")]
-        mission_dir: String,
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        /// Count these files instead, reporting each and the total
+        #[arg(long, conflicts_with_all = ["stdin", "glob"])]
+        file: Vec<PathBuf>,
+        /// Count text read from stdin instead
+        #[arg(long, conflicts_with = "glob")]
+        stdin: bool,
+        /// Count the files matching these patterns, relative to the mission
+        /// directory (e.g. 'tasks/*.md'); missing paths get a warning entry
+        #[arg(long)]
+        glob: Vec<String>,
+        #[command(flatten)]
+        pricing: PricingArgs,
+    },
+    /// Attribute token use per turn and tool, joining conversation.md with an event stream
+    Attribution {
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        /// stream-parser NDJSON for the agent
+        #[arg(long)]
+        events: String,
+        /// Also write the per-turn rows as CSV to this file
+        #[arg(long)]
+        csv: Option<String>,
+    },
+    /// Remove stale heartbeat, cancel, and lock files from the mission
+    Prune {
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        /// Only files older than this (e.g. 90s, 30m, 1h, 2d)
+        #[arg(long, default_value = "1h", value_parser = prune::parse_age)]
+        older_than: Duration,
+        /// Comma-separated kinds to prune
+        #[arg(long, value_delimiter = ',', default_value = "heartbeat,cancel,lock")]
+        kinds: Vec<prune::HousekeepingKind>,
+        /// Report what would be removed without removing it
+        #[arg(long)]
+        dry_run: bool,
     },
+    /// Move complete tasks (task, status and response) into archive/{date}/
+    Archive {
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        /// Only tasks whose files are all older than this (e.g. 12h, 7d)
+        #[arg(long, default_value = "7d", value_parser = prune::parse_age)]
+        older_than: Duration,
+        /// Archive these tasks, whatever their age, instead (repeatable)
+        #[arg(long)]
+        task_id: Vec<String>,
+        /// Report what would be moved without moving it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Move an archived task's files back into the mission
+    Unarchive {
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        #[arg(long)]
+        task_id: String,
+    },
+    /// Check the mission directory for problems and suggest fixes
+    Doctor {
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+    },
+    /// Answer newline-delimited JSON requests on a Unix socket, sharing one
+    /// watcher on the mission dir between them, until SIGINT or SIGTERM
+    #[cfg(unix)]
+    Serve {
+        /// Socket to listen on; removed again on exit
+        #[arg(long)]
+        socket: PathBuf,
+        /// Mission directory (default: nearest .mission above the cwd)
+        #[arg(long)]
+        mission_dir: Option<String>,
+        #[arg(long, value_enum, default_value = "auto")]
+        layout: LayoutArg,
+        #[command(flatten)]
+        watch_init: WatchInitArgs,
+    },
+    /// Smoke-test watching and the protocol against a scratch mission
+    Selftest {
+        /// Directory to create the scratch mission in (default: the system temp dir)
+        #[arg(long)]
+        dir: Option<String>,
+        /// Seconds each watch stage may wait
+        #[arg(long, default_value = "10")]
+        timeout: u64,
+        #[command(flatten)]
+        watch_init: WatchInitArgs,
+    },
+}
+
+/// Commands to run when a watch resolves. The result JSON is passed on stdin.
+#[derive(Args)]
+struct HookArgs {
+    /// Shell command to run when the watch completes
+    #[arg(long)]
+    on_complete: Option<String>,
+    /// Shell command to run when the watch times out
+    #[arg(long)]
+    on_timeout: Option<String>,
+    /// Seconds to wait for a hook before killing it
+    #[arg(long, default_value = "30")]
+    hook_timeout: u64,
+    #[cfg(feature = "notifications")]
+    #[command(flatten)]
+    notify: NotifyArgs,
+}
+
+/// Notifications sent when a watch resolves. Failures are logged, never fatal.
+#[cfg(feature = "notifications")]
+#[derive(Args)]
+struct NotifyArgs {
+    /// POST the result as JSON to this URL
+    #[arg(long, env = "MC_NOTIFY_WEBHOOK")]
+    notify_webhook: Option<String>,
+    /// Show a desktop notification
+    #[arg(long, env = "MC_NOTIFY_DESKTOP")]
+    notify_desktop: bool,
+}
+
+#[cfg(feature = "notifications")]
+impl NotifyArgs {
+    /// Send `{subject}_{status}` for the result, logging any failures to stderr.
+    fn send(&self, subject: &str, result: &Value, env: &[(String, String)]) {
+        let notifier = mc_protocol::notifications::Notifier {
+            webhook: self.notify_webhook.clone(),
+            desktop: self.notify_desktop,
+            ..Default::default()
+        };
+        if !notifier.is_enabled() {
+            return;
+        }
+
+        let status = result
+            .get("status")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown");
+        let event = format!("{}_{}", subject, status);
+        let mission: serde_json::Map<String, Value> = env
+            .iter()
+            .map(|(key, value)| {
+                let key = key.trim_start_matches("MC_").to_lowercase();
+                (key, Value::from(value.as_str()))
+            })
+            .collect();
+
+        for failure in notifier.send(&event, result, &Value::Object(mission)) {
+            let log = serde_json::json!({
+                "log": "notification failed",
+                "sink": failure.sink,
+                "error": failure.error,
+            });
+            eprintln!("{}", log);
+        }
+    }
+}
+
+/// Options shared by commands that walk every task in a mission.
+#[derive(Args)]
+struct TaskListArgs {
+    /// Mission directory (default: nearest .mission above the cwd)
+    #[arg(long)]
+    mission_dir: Option<String>,
+    /// `json` prints one document at the end; `ndjson` prints a record per file as it goes
+    #[arg(long, value_enum, default_value = "json")]
+    format: OutputFormat,
+    /// Visit at most this many task files
+    #[arg(long)]
+    limit: Option<usize>,
+    /// Skip task files last modified before this RFC3339 time
+    #[arg(long)]
+    since: Option<String>,
+}
+
+impl TaskListArgs {
+    fn filter(&self) -> Result<protocol::TaskFilter, McError> {
+        let since = match &self.since {
+            Some(since) => Some(
+                chrono::DateTime::parse_from_rfc3339(since)
+                    .map_err(|e| McError::Parse(format!("Invalid --since '{}': {}", since, e)))?
+                    .into(),
+            ),
+            None => None,
+        };
+        Ok(protocol::TaskFilter {
+            limit: self.limit,
+            since,
+        })
+    }
+}
+
+fn parse_priority(value: &str) -> Result<protocol::Priority, String> {
+    protocol::Priority::parse(value)
+        .ok_or_else(|| format!("expected one of: {}", protocol::PRIORITIES.join(", ")))
+}
+
+/// A task's file, given directly or found in the mission by task id.
+#[derive(Args)]
+struct TaskFileArgs {
+    #[arg(long, required_unless_present = "task_id")]
+    file: Option<String>,
+    /// Look the file up by task id instead
+    #[arg(long, conflicts_with = "file")]
+    task_id: Option<String>,
+    /// Mission directory to look in (default: nearest .mission above the cwd)
+    #[arg(long, requires = "task_id")]
+    mission_dir: Option<String>,
+    #[arg(long, value_enum, default_value = "auto", requires = "task_id")]
+    layout: LayoutArg,
+}
+
+impl TaskFileArgs {
+    /// The file as given, or the task's file that `pick` chooses.
+    fn resolve(self, no_discover: bool, pick: fn(&protocol::TaskPaths) -> &PathBuf) -> String {
+        if let Some(file) = self.file {
+            return file;
+        }
+        let task_id = self.task_id.unwrap_or_default();
+        let mission_dir = resolve_mission_dir(self.mission_dir, no_discover);
+        let mission_dir = Path::new(&mission_dir);
+        let layout = self
+            .layout
+            .layout()
+            .unwrap_or_else(|| protocol::Layout::detect(mission_dir, &task_id));
+        pick(&layout.paths(mission_dir, &task_id))
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+/// Where task files live: `flat` (tasks/, status/, responses/), `nested`
+/// (tasks/{id}/) or `auto`, nested once the task's directory exists.
+#[derive(Clone, Copy, ValueEnum)]
+enum LayoutArg {
+    Auto,
+    Flat,
+    Nested,
+}
+
+impl LayoutArg {
+    fn layout(self) -> Option<protocol::Layout> {
+        match self {
+            LayoutArg::Auto => None,
+            LayoutArg::Flat => Some(protocol::Layout::Flat),
+            LayoutArg::Nested => Some(protocol::Layout::Nested),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FormatArg {
+    Markdown,
+    Json,
+}
+
+impl FormatArg {
+    fn format(self) -> protocol::TaskFormat {
+        match self {
+            FormatArg::Markdown => protocol::TaskFormat::Markdown,
+            FormatArg::Json => protocol::TaskFormat::Json,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AppendRole {
+    Human,
+    Assistant,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Json,
+    Ndjson,
+}
+
+impl OutputFormat {
+    /// Print a record now (NDJSON) or keep it for the final document (JSON).
+    fn emit<T: Serialize>(self, record: &T, buffered: &mut Vec<Value>) {
+        match self {
+            OutputFormat::Json => buffered.push(to_json(record)),
+            OutputFormat::Ndjson => println!("{}", to_json(record)),
+        }
+    }
+
+    /// The closing output: the whole document, or just the summary record.
+    fn finish<S: Serialize>(self, key: &str, buffered: Vec<Value>, summary: &S) -> Value {
+        match self {
+            OutputFormat::Json => serde_json::json!({ key: buffered, "summary": summary }),
+            OutputFormat::Ndjson => serde_json::json!({ "summary": summary }),
+        }
+    }
+}
+
+/// Retries for transient watcher setup failures (EMFILE, inotify limits).
+#[derive(Args)]
+struct WatchInitArgs {
+    /// Times to retry watcher setup before giving up
+    #[arg(long, default_value = "3")]
+    watch_init_retries: u32,
+    /// Delay before the first setup retry, doubled on each further retry
+    #[arg(long, default_value = "100")]
+    watch_init_backoff_ms: u64,
+    /// Delay between re-reads confirming a completed file stopped changing (0 disables)
+    #[arg(long, default_value = "50")]
+    settle_ms: u64,
+    /// Quiet time a path needs before its latest change is checked, so a
+    /// burst of events for one write is handled once (0 disables)
+    #[arg(long, default_value = "100")]
+    debounce_ms: u64,
+    /// Poll for changes on this interval instead of using OS file events,
+    /// for filesystems that don't deliver them (NFS, some container mounts)
+    #[arg(long)]
+    poll_interval_ms: Option<u64>,
+    /// Stop waiting, with status cancelled, as soon as this file exists
+    #[arg(long)]
+    cancel_file: Option<PathBuf>,
+}
+
+/// Prices to estimate token cost with.
+#[derive(Args)]
+struct PricingArgs {
+    /// Model to price tokens for (e.g. claude-sonnet-4, claude-3-5-haiku)
+    #[arg(long)]
+    model: Option<String>,
+    /// Input price in USD per million tokens, overriding the model's
+    #[arg(long)]
+    price_input: Option<f64>,
+    /// Output price in USD per million tokens, overriding the model's
+    #[arg(long)]
+    price_output: Option<f64>,
+    /// Fraction of the input price paid for cached input (e.g. 0.1); all of
+    /// a conversation before its most recent Human section is taken as cached
+    #[arg(long)]
+    cache_discount: Option<f64>,
+}
+
+impl PricingArgs {
+    fn pricing(&self) -> tokens::Pricing {
+        tokens::PricingTable::default()
+            .pricing(self.model.as_deref(), self.price_input, self.price_output)
+            .with_cache_discount(self.cache_discount)
+    }
+}
+
+impl WatchInitArgs {
+    fn options(&self, follow_symlinks: bool) -> WatchOptions {
+        WatchOptions {
+            init_retry: InitRetry {
+                retries: self.watch_init_retries,
+                backoff: Duration::from_millis(self.watch_init_backoff_ms),
+            },
+            on_retry: Some(log_retry),
+            follow_symlinks,
+            settle: Duration::from_millis(self.settle_ms),
+            debounce: Duration::from_millis(self.debounce_ms),
+            strategy: match self.poll_interval_ms {
+                Some(ms) => WatchStrategy::Poll(Duration::from_millis(ms)),
+                None => WatchStrategy::Notify,
+            },
+            on_fallback: Some(log_fallback),
+            cancel: Cancel {
+                file: self.cancel_file.clone(),
+                flag: interrupt_flag(),
+            },
+            ..WatchOptions::default()
+        }
+    }
+}
+
+/// A flag set by SIGINT or SIGTERM, so a watch returns a cancelled result
+/// instead of the process dying mid-write.
+fn interrupt_flag() -> Arc<AtomicBool> {
+    let flag = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        if let Err(e) = signal_hook::flag::register(signal, Arc::clone(&flag)) {
+            eprintln!("Error handling signal {}: {}", signal, e);
+        }
+    }
+    flag
+}
+
+/// Log a watcher setup retry to stderr, keeping stdout for the result.
+fn log_retry(attempt: &RetryAttempt) {
+    let mut record = serde_json::json!({ "log": "retrying watcher setup" });
+    if let (Some(obj), Value::Object(fields)) = (record.as_object_mut(), to_json(attempt)) {
+        obj.extend(fields);
+    }
+    eprintln!("{}", record);
+}
+
+/// Log that watcher setup gave up and the watch polls instead.
+fn log_fallback(error: &WatchInitError) {
+    let record = serde_json::json!({
+        "log": "falling back to polling",
+        "error": error.to_string(),
+        "poll_interval_ms": FALLBACK_POLL_INTERVAL.as_millis() as u64,
+    });
+    eprintln!("{}", record);
+}
+
+impl HookArgs {
+    /// Send notifications, then run the hook matching the result status and
+    /// attach its report as `hook`. `subject` names what was watched.
+    fn apply(&self, subject: &str, mut result: Value, mut env: Vec<(String, String)>) -> Value {
+        #[cfg(feature = "notifications")]
+        self.notify.send(subject, &result, &env);
+        #[cfg(not(feature = "notifications"))]
+        let _ = subject;
+
+        let command = match result.get("status").and_then(|v| v.as_str()) {
+            Some("complete") => self.on_complete.as_deref(),
+            Some("timeout") => self.on_timeout.as_deref(),
+            _ => None,
+        };
+
+        if let Some(command) = command {
+            env.extend(hooks::result_env(&result));
+            let report = hooks::run(
+                command,
+                &result,
+                &env,
+                Duration::from_secs(self.hook_timeout),
+            );
+            if let Some(obj) = result.as_object_mut() {
+                obj.insert("hook".to_string(), serde_json::to_value(report).unwrap());
+            }
+        }
+
+        result
+    }
+}
+
+/// Resolve the mission directory, discovering it when not given explicitly.
+fn resolve_mission_dir(explicit: Option<String>, no_discover: bool) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let resolved = discover::resolve(explicit.as_deref(), !no_discover, &cwd);
+
+    if !resolved.shadowed.is_empty() {
+        let log = serde_json::json!({
+            "log": "using nearest mission directory",
+            "mission_dir": resolved.path,
+            "ignored": resolved.shadowed,
+        });
+        eprintln!("{}", log);
+    }
+
+    resolved.path.to_string_lossy().to_string()
+}
+
+/// Record the mission directory a command ran against in its output.
+fn with_mission_dir(mut output: Value, mission_dir: &str) -> Value {
+    if let Some(obj) = output.as_object_mut() {
+        obj.insert("mission_dir".to_string(), Value::from(mission_dir));
+    }
+    output
+}
+
+fn to_json<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap()
 }
 
 #[derive(Serialize)]
@@ -59,56 +824,630 @@ struct ErrorOutput {
     error: String,
 }
 
+/// Exit code for `watch-tokens` when the conversation is over its budget.
+const EXIT_BUDGET_EXCEEDED: i32 = 3;
+
+/// Exit code for `parse-response --if-changed` when the content hash matches.
+const EXIT_UNCHANGED: i32 = 4;
+
 fn main() {
     let cli = Cli::parse();
+    let mut exit_code = 0;
 
-    let result: Result<String, Box<dyn std::error::Error>> = match cli.command {
+    // Refuse to run against an event producer speaking a different schema
+    if let Err(e) = mc_events::check_schema_env() {
+        let error_output = ErrorOutput {
+            error: e.to_string(),
+        };
+        eprintln!("{}", serde_json::to_string(&error_output).unwrap());
+        std::process::exit(1);
+    }
+
+    let no_discover = cli.no_discover;
+    let follow_symlinks = !cli.no_follow_symlinks;
+    let result: Result<Value, Box<dyn std::error::Error>> = match cli.command {
         Commands::WatchTask {
             task_id,
             mission_dir,
             timeout,
-        } => watcher::watch_task(&task_id, &mission_dir, Duration::from_secs(timeout))
-            .map(|r| serde_json::to_string(&r).unwrap()),
+            progress,
+            require_response,
+            layout,
+            hooks,
+            watch_init,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let timeout = Duration::from_secs(timeout);
+            let heartbeat = progress.map(Duration::from_secs);
+            let options = WatchOptions {
+                layout: layout.layout(),
+                ..watch_init.options(follow_symlinks)
+            };
+            watcher::watch_task_with_progress(
+                &task_id,
+                &mission_dir,
+                timeout,
+                &options,
+                require_response,
+                heartbeat.unwrap_or(timeout),
+                |event| {
+                    if heartbeat.is_some() {
+                        println!("{}", to_json(&event));
+                    }
+                },
+            )
+            .map(|r| {
+                let env = vec![
+                    ("MC_TASK_ID".to_string(), task_id.clone()),
+                    ("MC_MISSION_DIR".to_string(), mission_dir.clone()),
+                ];
+                hooks.apply("task", with_mission_dir(to_json(&r), &mission_dir), env)
+            })
+            .map_err(|e| e.into())
+        }
+
+        Commands::WatchTasks {
+            task_ids,
+            mission_dir,
+            timeout,
+            layout,
+            watch_init,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let options = WatchOptions {
+                layout: layout.layout(),
+                ..watch_init.options(follow_symlinks)
+            };
+            watcher::watch_tasks(
+                &task_ids,
+                &mission_dir,
+                Duration::from_secs(timeout),
+                &options,
+                |record| println!("{}", to_json(record)),
+            )
+            .map(|r| with_mission_dir(to_json(&r), &mission_dir))
+            .map_err(|e| e.into())
+        }
+
+        Commands::WatchInbox {
+            mission_dir,
+            timeout,
+            priority,
+            claim,
+            watch_init,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            watcher::watch_inbox(
+                &mission_dir,
+                Duration::from_secs(timeout),
+                &watcher::InboxOptions { priority, claim },
+                &watch_init.options(follow_symlinks),
+            )
+            .map(|r| with_mission_dir(to_json(&r), &mission_dir))
+            .map_err(|e| e.into())
+        }
 
         Commands::WatchConversation {
             mission_dir,
             timeout,
-        } => conversation::watch(&mission_dir, Duration::from_secs(timeout))
-            .map(|r| serde_json::to_string(&r).unwrap()),
+            end_marker,
+            done_file,
+            after_turn,
+            since_offset,
+            stream,
+            hooks,
+            watch_init,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let options = watch_init.options(follow_symlinks);
+            let after = match (after_turn, since_offset) {
+                (Some(n), _) => conversation::After::Turn(n),
+                (None, Some(offset)) => conversation::After::Offset(offset),
+                (None, None) => conversation::After::EndOfFile,
+            };
+            let mut completion = conversation::Completion {
+                after,
+                done_file,
+                ..conversation::Completion::default()
+            };
+            if !end_marker.is_empty() {
+                completion.markers = end_marker;
+            }
+            conversation::watch_streaming(
+                &mission_dir,
+                Duration::from_secs(timeout),
+                &completion,
+                &options,
+                |text| {
+                    if stream {
+                        println!(
+                            "{}",
+                            serde_json::json!({ "status": "partial", "text": text })
+                        );
+                    }
+                },
+            )
+            .map(|r| {
+                let env = vec![("MC_MISSION_DIR".to_string(), mission_dir.clone())];
+                hooks.apply(
+                    "conversation",
+                    with_mission_dir(to_json(&r), &mission_dir),
+                    env,
+                )
+            })
+            .map_err(|e| e.into())
+        }
+
+        Commands::GetResponse {
+            mission_dir,
+            turn,
+            since,
+            until,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let selector = match (turn, since, until) {
+                (Some(n), _, _) => conversation::TurnSelector::Nth(n),
+                (None, None, None) => conversation::TurnSelector::Last,
+                (None, since, until) => {
+                    conversation::TurnSelector::ByTimestampRange { since, until }
+                }
+            };
+            conversation::read_responses(&mission_dir, &selector)
+                .map(|r| with_mission_dir(serde_json::json!({ "responses": r }), &mission_dir))
+                .map_err(|e| e.into())
+        }
+
+        Commands::ParseConversation { mission_dir } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            conversation::parse(&Path::new(&mission_dir).join("conversation.md"))
+                .map(|turns| with_mission_dir(serde_json::json!({ "turns": turns }), &mission_dir))
+                .map_err(|e| e.into())
+        }
+
+        Commands::Append {
+            mission_dir,
+            role,
+            content,
+            content_file,
+            end,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let role = match role {
+                AppendRole::Human => conversation::Role::Human,
+                AppendRole::Assistant => conversation::Role::Assistant,
+            };
+            let content = match content_file {
+                Some(file) => {
+                    std::fs::read_to_string(&file).map_err(|e| McError::io(file.display(), e))
+                }
+                None => Ok(content.unwrap_or_default()),
+            };
+            content
+                .and_then(|content| conversation::append(&mission_dir, role, &content, end))
+                .map(|r| with_mission_dir(to_json(&r), &mission_dir))
+                .map_err(|e| e.into())
+        }
+
+        Commands::WatchFleet {
+            missions_file,
+            stream,
+            timeout,
+            watch_init,
+        } => fleet::parse_missions_file(&missions_file)
+            .and_then(|entries| {
+                let options = watch_init.options(follow_symlinks);
+                fleet::watch_fleet(&entries, Duration::from_secs(timeout), &options, |record| {
+                    if stream {
+                        println!("{}", to_json(record));
+                    }
+                })
+                .map(|report| {
+                    if stream {
+                        serde_json::json!({ "summary": report.summary })
+                    } else {
+                        to_json(&report)
+                    }
+                })
+            })
+            .map_err(|e| e.into()),
 
         Commands::ValidateTask { file } => {
-            protocol::validate_task(&file).map(|r| serde_json::to_string(&r).unwrap())
+            let file = file.resolve(no_discover, |paths| &paths.task);
+            protocol::validate_task(&file)
+                .map(|r| to_json(&r))
+                .map_err(|e| e.into())
         }
 
-        Commands::ParseResponse { file } => {
-            protocol::parse_response(&file).map(|r| serde_json::to_string(&r).unwrap())
+        Commands::ValidateResponse { file, task_id } => {
+            protocol::validate_response(&file, task_id.as_deref())
+                .map(|r| to_json(&r))
+                .map_err(|e| e.into())
         }
 
+        Commands::ParseTask {
+            file,
+            inline_attachments,
+        } => protocol::parse_task(&file, inline_attachments)
+            .map(|r| to_json(&r))
+            .map_err(|e| e.into()),
+
+        Commands::AnalyzeTask { file, budget } => protocol::parse_task(&file, false)
+            .map(|task| {
+                let counter = knowledge::TokenCounter::new();
+                to_json(&protocol::analyze_task_budget(&task, budget, &counter))
+            })
+            .map_err(|e| e.into()),
+
+        Commands::RenderPrompt {
+            mission_dir,
+            task_id,
+            budget,
+            out,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let out = out
+                .map(PathBuf::from)
+                .unwrap_or_else(|| prompt::default_out(Path::new(&mission_dir), &task_id));
+            prompt::render_prompt(Path::new(&mission_dir), &task_id, budget, &out)
+                .map(|r| with_mission_dir(to_json(&r), &mission_dir))
+                .map_err(|e| e.into())
+        }
+
+        Commands::CreateTask {
+            mission_dir,
+            task_id,
+            priority,
+            instructions,
+            instructions_file,
+            context,
+            context_file,
+            attach,
+            force,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let read = |text: Option<String>, file: Option<PathBuf>| match file {
+                Some(file) => std::fs::read_to_string(&file)
+                    .map(Some)
+                    .map_err(|e| McError::io(file.display(), e)),
+                None => Ok(text),
+            };
+            read(instructions, instructions_file)
+                .and_then(|instructions| {
+                    let task = protocol::NewTask {
+                        task_id,
+                        priority,
+                        instructions: instructions.unwrap_or_default(),
+                        context: read(context, context_file)?,
+                        attachments: attach.iter().map(PathBuf::from).collect(),
+                        force,
+                    };
+                    let path = protocol::create_task(Path::new(&mission_dir), &task)?;
+                    let validation = protocol::validate_task(&path.to_string_lossy())?;
+                    Ok(serde_json::json!({ "path": path, "validation": validation }))
+                })
+                .map(|r| with_mission_dir(r, &mission_dir))
+                .map_err(|e| e.into())
+        }
+
+        Commands::ConvertTask {
+            file,
+            to,
+            output,
+            force,
+        } => protocol::convert_task(&file, to.map(FormatArg::format), output.as_deref(), force)
+            .map(|r| to_json(&r))
+            .map_err(|e| e.into()),
+
+        Commands::ValidateAll { tasks } => {
+            let mission_dir = resolve_mission_dir(tasks.mission_dir.clone(), no_discover);
+            let mut records = Vec::new();
+            tasks
+                .filter()
+                .and_then(|filter| {
+                    protocol::validate_all(Path::new(&mission_dir), &filter, |record| {
+                        tasks.format.emit(record, &mut records)
+                    })
+                })
+                .map(|summary| {
+                    with_mission_dir(
+                        tasks.format.finish("files", records, &summary),
+                        &mission_dir,
+                    )
+                })
+                .map_err(|e| e.into())
+        }
+
+        Commands::ListTasks { tasks } => {
+            let mission_dir = resolve_mission_dir(tasks.mission_dir.clone(), no_discover);
+            let mut entries = Vec::new();
+            tasks
+                .filter()
+                .and_then(|filter| {
+                    protocol::list_tasks(Path::new(&mission_dir), &filter, |entry| {
+                        match tasks.format {
+                            OutputFormat::Json => entries.push(entry.clone()),
+                            OutputFormat::Ndjson => println!("{}", to_json(entry)),
+                        }
+                    })
+                })
+                .map(|summary| {
+                    // The document lists tasks oldest first; ndjson keeps walk order
+                    protocol::sort_by_created(&mut entries);
+                    let entries = entries.iter().map(to_json).collect();
+                    with_mission_dir(
+                        tasks.format.finish("tasks", entries, &summary),
+                        &mission_dir,
+                    )
+                })
+                .map_err(|e| e.into())
+        }
+
+        Commands::CheckReady {
+            task_id,
+            mission_dir,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            protocol::check_ready(Path::new(&mission_dir), &task_id)
+                .map(|r| with_mission_dir(to_json(&r), &mission_dir))
+                .map_err(|e| e.into())
+        }
+
+        Commands::ParseResponse {
+            file,
+            if_changed,
+            repo_root,
+            strict_headers,
+        } => {
+            let file = file.resolve(no_discover, |paths| &paths.response);
+            let options = protocol::ResponseOptions {
+                repo_root: PathBuf::from(repo_root),
+                strict_headers,
+            };
+            match protocol::response_unchanged(&file, if_changed.as_deref()) {
+                Ok(Some(hash)) => {
+                    exit_code = EXIT_UNCHANGED;
+                    Ok(serde_json::json!({ "unchanged": true, "content_hash": hash }))
+                }
+                Ok(None) => protocol::parse_response_with(&file, &options)
+                    .map(|r| to_json(&r))
+                    .map_err(|e| e.into()),
+                Err(e) => Err(e.into()),
+            }
+        }
+
+        Commands::AuditResponse {
+            file,
+            repo_root,
+            git,
+            changed_files,
+        } => {
+            let repo_root = Path::new(&repo_root);
+            let changed = match changed_files {
+                Some(list) if !git => audit::read_changed_files(&list),
+                _ => audit::git_changed_files(repo_root),
+            };
+            changed
+                .and_then(|changed| audit::audit_response(&file, repo_root, &changed))
+                .map(|r| to_json(&r))
+                .map_err(|e| e.into())
+        }
+
+        Commands::ReadEvents {
+            mission_dir,
+            agent,
+            last,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let dir = Path::new(&mission_dir).join("events");
+            mc_events::ring::read_lines(&dir, &agent, last)
+                .map(|lines| {
+                    // A line still being written by the forwarder won't parse yet
+                    let events: Vec<Value> = lines
+                        .iter()
+                        .filter_map(|line| serde_json::from_str(line).ok())
+                        .collect();
+                    let output = serde_json::json!({ "agent": agent, "events": events });
+                    with_mission_dir(output, &mission_dir)
+                })
+                .map_err(|e| e.into())
+        }
+
+        Commands::CheckEvents { file } => events::check_events(Path::new(&file))
+            .map(|r| to_json(&r))
+            .map_err(|e| e.into()),
+
         Commands::WatchTokens {
             mission_dir,
             timeout,
-        } => tokens::watch_conversation_tokens(Path::new(&mission_dir), timeout)
-            .map(|r| serde_json::to_string(&r).unwrap())
-            .map_err(|e| e.into()),
+            follow,
+            max_tokens,
+            max_cost_usd,
+            pricing,
+            watch_init,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let options = watch_init.options(follow_symlinks);
+            let pricing = pricing.pricing();
+            let budget = tokens::TokenBudget {
+                max_tokens,
+                max_cost_usd,
+            };
+            let usage = if follow {
+                tokens::watch_conversation_tokens_stream(
+                    Path::new(&mission_dir),
+                    timeout,
+                    &options,
+                    &pricing,
+                    &budget,
+                    |usage| println!("{}", with_mission_dir(to_json(usage), &mission_dir)),
+                )
+            } else {
+                tokens::watch_conversation_tokens(
+                    Path::new(&mission_dir),
+                    timeout,
+                    &options,
+                    &pricing,
+                    &budget,
+                )
+            };
+            if usage.as_ref().is_ok_and(|usage| usage.budget_exceeded) {
+                exit_code = EXIT_BUDGET_EXCEEDED;
+            }
+            usage
+                .map(|r| with_mission_dir(to_json(&r), &mission_dir))
+                .map_err(|e| e.into())
+        }
+
+        Commands::CountTokens {
+            mission_dir,
+            file,
+            stdin,
+            glob,
+            pricing,
+        } => {
+            let pricing = pricing.pricing();
+            if !file.is_empty() {
+                tokens::count_files(&file, &pricing)
+                    .map(|r| to_json(&r))
+                    .map_err(|e| e.into())
+            } else if stdin {
+                tokens::count_reader(std::io::stdin().lock(), &pricing)
+                    .map(|r| to_json(&r))
+                    .map_err(|e| e.into())
+            } else if !glob.is_empty() {
+                let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+                tokens::count_glob(Path::new(&mission_dir), &glob, &pricing)
+                    .map(|r| with_mission_dir(to_json(&r), &mission_dir))
+                    .map_err(|e| e.into())
+            } else {
+                let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+                let path = Path::new(&mission_dir).join("conversation.md");
+                tokens::count_tokens_priced(&path, &pricing)
+                    .map(|r| with_mission_dir(to_json(&r), &mission_dir))
+                    .map_err(|e| e.into())
+            }
+        }
 
-        Commands::CountTokens { mission_dir } => {
-            let path = Path::new(&mission_dir).join("conversation.md");
-            tokens::count_tokens(&path)
-                .map(|r| serde_json::to_string(&r).unwrap())
+        Commands::Attribution {
+            mission_dir,
+            events,
+            csv,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            attribution::attribution(Path::new(&mission_dir), Path::new(&events))
+                .and_then(|report| {
+                    if let Some(csv) = &csv {
+                        std::fs::write(csv, attribution::to_csv(&report))
+                            .map_err(|e| McError::io(format!("Failed to write {}", csv), e))?;
+                    }
+                    Ok(with_mission_dir(to_json(&report), &mission_dir))
+                })
                 .map_err(|e| e.into())
         }
+
+        Commands::Prune {
+            mission_dir,
+            older_than,
+            kinds,
+            dry_run,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let options = prune::PruneOptions {
+                older_than,
+                kinds,
+                dry_run,
+                ..prune::PruneOptions::default()
+            };
+            prune::prune(Path::new(&mission_dir), &options)
+                .map(|r| with_mission_dir(to_json(&r), &mission_dir))
+                .map_err(|e| e.into())
+        }
+
+        Commands::Archive {
+            mission_dir,
+            older_than,
+            task_id,
+            dry_run,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let options = archive::ArchiveOptions {
+                older_than,
+                task_ids: task_id,
+                dry_run,
+                ..archive::ArchiveOptions::default()
+            };
+            archive::archive(Path::new(&mission_dir), &options)
+                .map(|r| with_mission_dir(to_json(&r), &mission_dir))
+                .map_err(|e| e.into())
+        }
+
+        Commands::Unarchive {
+            mission_dir,
+            task_id,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            archive::unarchive(Path::new(&mission_dir), &task_id)
+                .map(|r| with_mission_dir(to_json(&r), &mission_dir))
+                .map_err(|e| e.into())
+        }
+
+        Commands::Doctor { mission_dir } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let report = doctor::doctor(Path::new(&mission_dir));
+            Ok(with_mission_dir(to_json(&report), &mission_dir))
+        }
+
+        #[cfg(unix)]
+        Commands::Serve {
+            socket,
+            mission_dir,
+            layout,
+            watch_init,
+        } => {
+            let mission_dir = resolve_mission_dir(mission_dir, no_discover);
+            let options = WatchOptions {
+                layout: layout.layout(),
+                ..watch_init.options(follow_symlinks)
+            };
+            serve::serve(&socket, &mission_dir, &options)
+                .map(|()| {
+                    with_mission_dir(serde_json::json!({ "status": "stopped" }), &mission_dir)
+                })
+                .map_err(|e| e.into())
+        }
+
+        Commands::Selftest {
+            dir,
+            timeout,
+            watch_init,
+        } => {
+            let parent = dir.map(PathBuf::from).unwrap_or_else(std::env::temp_dir);
+            let report = selftest::run(
+                &parent,
+                Duration::from_secs(timeout),
+                &watch_init.options(follow_symlinks),
+            );
+            if !report.passed {
+                exit_code = 1;
+            }
+            Ok(to_json(&report))
+        }
     };
 
     match result {
         Ok(output) => {
             println!("{}", output);
-            std::process::exit(0);
+            std::process::exit(exit_code);
         }
         Err(e) => {
-            let error_output = ErrorOutput {
-                error: e.to_string(),
+            // Library errors say what kind they are next to the message
+            let output = match e.downcast_ref::<McError>() {
+                Some(error) => serde_json::to_string(error),
+                None => serde_json::to_string(&ErrorOutput {
+                    error: e.to_string(),
+                }),
             };
-            eprintln!("{}", serde_json::to_string(&error_output).unwrap());
+            eprintln!("{}", output.unwrap());
             std::process::exit(1);
         }
     }
@@ -0,0 +1,250 @@
+//! Notify humans when a watch resolves: a webhook POST and/or a desktop
+//! notification.
+//!
+//! Like hooks, notification failures never change the primary watch result;
+//! they are returned so the caller can log them.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+
+/// Retry policy for webhook delivery.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WebhookRetry {
+    /// Total delivery attempts, including the first
+    pub attempts: u32,
+    /// Delay between attempts
+    pub delay: Duration,
+    /// Per-request timeout
+    pub timeout: Duration,
+}
+
+impl Default for WebhookRetry {
+    fn default() -> Self {
+        WebhookRetry {
+            attempts: 3,
+            delay: Duration::from_millis(500),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Where to send notifications.
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    /// URL to POST the payload to as JSON
+    pub webhook: Option<String>,
+    /// Show a local desktop notification
+    pub desktop: bool,
+    pub retry: WebhookRetry,
+}
+
+/// A sink that could not be notified.
+#[derive(Debug, Serialize)]
+pub struct NotifyFailure {
+    pub sink: &'static str,
+    pub error: String,
+}
+
+impl Notifier {
+    pub fn is_enabled(&self) -> bool {
+        self.webhook.is_some() || self.desktop
+    }
+
+    /// Notify every configured sink about `event` (e.g. `task_complete`).
+    ///
+    /// The payload is `result` with `event` and the `mission` metadata added.
+    pub fn send(&self, event: &str, result: &Value, mission: &Value) -> Vec<NotifyFailure> {
+        let payload = payload(event, result, mission);
+        let mut failures = Vec::new();
+
+        if let Some(url) = &self.webhook {
+            if let Err(error) = post_webhook(url, &payload, &self.retry) {
+                failures.push(NotifyFailure {
+                    sink: "webhook",
+                    error,
+                });
+            }
+        }
+        if self.desktop {
+            if let Err(error) = show_desktop(event, &payload) {
+                failures.push(NotifyFailure {
+                    sink: "desktop",
+                    error,
+                });
+            }
+        }
+
+        failures
+    }
+}
+
+/// The webhook body: the watch result plus what it was about.
+pub fn payload(event: &str, result: &Value, mission: &Value) -> Value {
+    let mut payload = match result {
+        Value::Object(fields) => fields.clone(),
+        other => {
+            let mut fields = serde_json::Map::new();
+            fields.insert("result".to_string(), other.clone());
+            fields
+        }
+    };
+    payload.insert("event".to_string(), Value::from(event));
+    payload.insert("mission".to_string(), mission.clone());
+    Value::Object(payload)
+}
+
+/// POST `payload` to `url`, retrying transport errors and 5xx responses.
+pub fn post_webhook(url: &str, payload: &Value, retry: &WebhookRetry) -> Result<(), String> {
+    let agent = ureq::AgentBuilder::new().timeout(retry.timeout).build();
+    let attempts = retry.attempts.max(1);
+
+    let mut last_error = String::new();
+    for attempt in 1..=attempts {
+        match agent.post(url).send_json(payload) {
+            Ok(_) => return Ok(()),
+            Err(ureq::Error::Status(code, _)) if code < 500 => {
+                return Err(format!("webhook returned HTTP {}", code));
+            }
+            Err(ureq::Error::Status(code, _)) => {
+                last_error = format!("webhook returned HTTP {}", code)
+            }
+            Err(e) => last_error = e.to_string(),
+        }
+        if attempt < attempts {
+            std::thread::sleep(retry.delay);
+        }
+    }
+
+    Err(format!("{} (after {} attempt(s))", last_error, attempts))
+}
+
+fn show_desktop(event: &str, payload: &Value) -> Result<(), String> {
+    let mission_dir = payload
+        .get("mission")
+        .and_then(|m| m.get("mission_dir"))
+        .or_else(|| payload.get("mission_dir"))
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+
+    notify_rust::Notification::new()
+        .summary(&format!("MissionControl: {}", event.replace('_', " ")))
+        .body(mission_dir)
+        .show()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Accept one request per status in `statuses`, answering each in turn.
+    /// Returns the bodies received.
+    fn serve(statuses: &'static [u16]) -> (String, thread::JoinHandle<Vec<Value>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(serde_json::from_slice(&body).unwrap());
+
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+            bodies
+        });
+
+        (url, server)
+    }
+
+    fn quick_retry() -> WebhookRetry {
+        WebhookRetry {
+            delay: Duration::from_millis(10),
+            ..WebhookRetry::default()
+        }
+    }
+
+    #[test]
+    fn test_webhook_delivers_payload() {
+        let (url, server) = serve(&[200]);
+        let notifier = Notifier {
+            webhook: Some(url),
+            retry: quick_retry(),
+            ..Notifier::default()
+        };
+
+        let result =
+            json!({ "status": "complete", "response_path": ".mission/responses/task-001.md" });
+        let mission = json!({ "mission_dir": ".mission", "task_id": "001" });
+        let failures = notifier.send("task_complete", &result, &mission);
+        assert!(failures.is_empty(), "{:?}", failures);
+
+        let bodies = server.join().unwrap();
+        assert_eq!(
+            bodies,
+            vec![json!({
+                "status": "complete",
+                "response_path": ".mission/responses/task-001.md",
+                "event": "task_complete",
+                "mission": { "mission_dir": ".mission", "task_id": "001" },
+            })]
+        );
+    }
+
+    #[test]
+    fn test_webhook_retries_server_errors() {
+        let (url, server) = serve(&[503, 200]);
+        let result = post_webhook(&url, &json!({ "status": "timeout" }), &quick_retry());
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_webhook_failure_is_reported_not_raised() {
+        // Bind then drop, so nothing is listening on the port
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let notifier = Notifier {
+            webhook: Some(format!("http://127.0.0.1:{}/hook", port)),
+            retry: WebhookRetry {
+                attempts: 2,
+                ..quick_retry()
+            },
+            ..Notifier::default()
+        };
+
+        let failures = notifier.send("conversation_complete", &json!({}), &json!({}));
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].sink, "webhook");
+        assert!(failures[0].error.contains("2 attempt(s)"));
+    }
+}
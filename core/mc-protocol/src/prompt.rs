@@ -0,0 +1,271 @@
+use crate::attachments::ATTACH_PREFIX;
+use crate::conversation::{self, ConversationTurn, Role};
+use crate::error::McError;
+use crate::protocol::{self, TaskSpec};
+use crate::spec;
+use knowledge::TokenCounter;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Tokens spent on each section of a rendered prompt.
+#[derive(Debug, Default, Serialize)]
+pub struct SectionTokens {
+    pub instructions: usize,
+    pub context: usize,
+    pub conversation: usize,
+}
+
+/// What went into a rendered prompt and what was left out.
+#[derive(Debug, Serialize)]
+pub struct PromptReport {
+    pub out: String,
+    pub spec_version: u32,
+    pub budget: usize,
+    pub total_tokens: usize,
+    pub sections: SectionTokens,
+    pub turns_included: usize,
+    pub turns_excluded: usize,
+    /// Tokens of the conversation turns that did not fit
+    pub excluded_tokens: usize,
+}
+
+/// Render the task and as much recent conversation as fits `budget` into `out`.
+///
+/// Instructions are included verbatim and Context with its attachments
+/// inlined; both are required, so the render fails if they alone exceed the
+/// budget. Conversation turns are then added newest first, whole turns only,
+/// and presented in file order.
+pub fn render_prompt(
+    mission_dir: &Path,
+    task_id: &str,
+    budget: usize,
+    out: &Path,
+) -> Result<PromptReport, McError> {
+    let task_path = mission_dir
+        .join("tasks")
+        .join(format!("task-{}.md", task_id));
+    let task = protocol::parse_task(&task_path.to_string_lossy(), true)?;
+
+    let conversation_path = mission_dir.join("conversation.md");
+    let turns = if conversation_path.exists() {
+        conversation::parse_conversation(&fs::read_to_string(&conversation_path)?)
+    } else {
+        Vec::new()
+    };
+
+    let counter = TokenCounter::new();
+    let (content, report) = render(&task, &turns, budget, &counter)?;
+
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(out, content)?;
+
+    Ok(PromptReport {
+        out: out.to_string_lossy().to_string(),
+        ..report
+    })
+}
+
+/// Default output path, `{mission_dir}/prompts/task-{id}.md`.
+pub fn default_out(mission_dir: &Path, task_id: &str) -> PathBuf {
+    mission_dir
+        .join("prompts")
+        .join(format!("task-{}.md", task_id))
+}
+
+fn render(
+    task: &TaskSpec,
+    turns: &[ConversationTurn],
+    budget: usize,
+    counter: &TokenCounter,
+) -> Result<(String, PromptReport), McError> {
+    let [instructions_section, context_section, conversation_section] = spec::PROMPT_SECTIONS;
+
+    let instructions = section(
+        instructions_section,
+        task.instructions.as_deref().unwrap_or_default(),
+    );
+    let context = section(context_section, &context_with_attachments(task));
+    let mut sections = SectionTokens {
+        instructions: counter.count(&instructions),
+        context: counter.count(&context),
+        conversation: 0,
+    };
+
+    let assemble = |included: &[String]| {
+        let mut prompt = format!("{}\n\n{}{}", spec::prompt_header(), instructions, context);
+        prompt.push_str(&section(conversation_section, &included.join("\n")));
+        prompt.push_str(&spec::end_marker());
+        prompt.push('\n');
+        prompt
+    };
+
+    let required = counter.count(&assemble(&[]));
+    if required > budget {
+        return Err(McError::Validation(format!(
+            "Instructions and context need {} tokens, over the budget of {}",
+            required, budget
+        )));
+    }
+
+    // Take turns newest first while their estimated cost fits
+    let blocks: Vec<String> = turns.iter().map(render_turn).collect();
+    let mut remaining = budget - required;
+    let mut first_included = blocks.len();
+    for (i, block) in blocks.iter().enumerate().rev() {
+        let tokens = counter.count(block);
+        if tokens > remaining {
+            break;
+        }
+        remaining -= tokens;
+        first_included = i;
+    }
+
+    // Token counts aren't strictly additive; drop the oldest until the whole fits
+    let mut prompt = assemble(&blocks[first_included..]);
+    let mut total_tokens = counter.count(&prompt);
+    while total_tokens > budget && first_included < blocks.len() {
+        first_included += 1;
+        prompt = assemble(&blocks[first_included..]);
+        total_tokens = counter.count(&prompt);
+    }
+
+    sections.conversation = total_tokens.saturating_sub(required);
+    let excluded_tokens = blocks[..first_included]
+        .iter()
+        .map(|block| counter.count(block))
+        .sum();
+
+    Ok((
+        prompt,
+        PromptReport {
+            out: String::new(),
+            spec_version: spec::PROMPT_SPEC_VERSION,
+            budget,
+            total_tokens,
+            sections,
+            turns_included: blocks.len() - first_included,
+            turns_excluded: first_included,
+            excluded_tokens,
+        },
+    ))
+}
+
+fn section(name: &str, body: &str) -> String {
+    format!("{}\n{}\n\n", spec::section_marker(name), body.trim())
+}
+
+/// Context with each `@attach` line replaced by the attachment's content.
+fn context_with_attachments(task: &TaskSpec) -> String {
+    let context = task.context.as_deref().unwrap_or_default();
+    context
+        .lines()
+        .map(|line| {
+            let reference = match line.trim().strip_prefix(ATTACH_PREFIX) {
+                Some(reference) => reference.trim(),
+                None => return line.to_string(),
+            };
+            match task
+                .attachments
+                .iter()
+                .find(|a| a.path == reference)
+                .and_then(|a| a.content.as_deref())
+            {
+                Some(content) => format!(
+                    "--- attachment: {} ---\n{}\n--- end attachment ---",
+                    reference,
+                    content.trim_end()
+                ),
+                None => line.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_turn(turn: &ConversationTurn) -> String {
+    let role = match turn.role {
+        Role::Human => "Human",
+        Role::Assistant => "Assistant",
+        Role::Other => "Other",
+    };
+    match &turn.timestamp {
+        Some(timestamp) => format!("### {} [{}]\n{}\n", role, timestamp, turn.content),
+        None => format!("### {}\n{}\n", role, turn.content),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn mission_with_conversation(turns: usize) -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().join(".mission");
+        let task = protocol::NewTask {
+            task_id: "003".to_string(),
+            priority: "normal".to_string(),
+            instructions: "Summarize the discussion.".to_string(),
+            context: Some("The team is choosing a storage engine.".to_string()),
+            ..protocol::NewTask::default()
+        };
+        protocol::create_task(&mission_dir, &task).unwrap();
+
+        let mut conversation = String::new();
+        for i in 1..=turns {
+            conversation.push_str(&format!(
+                "## Human\n\nQuestion {} about compaction, write amplification, and recovery.\n\n---\n\n## Assistant\n\nAnswer {} covering the tradeoffs in some detail.\n\n---END---\n\n",
+                i, i
+            ));
+        }
+        fs::write(mission_dir.join("conversation.md"), conversation).unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_budget_drops_oldest_turns_first() {
+        let temp_dir = mission_with_conversation(10);
+        let mission_dir = temp_dir.path().join(".mission");
+        let out = default_out(&mission_dir, "003");
+
+        let counter = TokenCounter::new();
+        let full = render_prompt(&mission_dir, "003", 100_000, &out).unwrap();
+        assert_eq!(full.turns_included, 20);
+        assert_eq!(full.turns_excluded, 0);
+
+        let budget = full.total_tokens / 2;
+        let report = render_prompt(&mission_dir, "003", budget, &out).unwrap();
+        let rendered = fs::read_to_string(&out).unwrap();
+
+        assert!(report.turns_excluded > 0);
+        assert!(report.total_tokens <= budget);
+        assert!(counter.count(&rendered) <= budget);
+        assert_eq!(report.total_tokens, counter.count(&rendered));
+
+        // The newest turn survives and the oldest is gone
+        assert!(rendered.contains("Answer 10 covering"));
+        assert!(!rendered.contains("Question 1 about"));
+
+        // Sections appear in spec order
+        let positions: Vec<usize> = spec::PROMPT_SECTIONS
+            .iter()
+            .map(|name| rendered.find(&spec::section_marker(name)).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        assert!(rendered.trim_end().ends_with(&spec::end_marker()));
+    }
+
+    #[test]
+    fn test_required_sections_over_budget_fail() {
+        let temp_dir = mission_with_conversation(1);
+        let mission_dir = temp_dir.path().join(".mission");
+        let out = default_out(&mission_dir, "003");
+
+        let err = render_prompt(&mission_dir, "003", 5, &out).unwrap_err();
+        assert!(err.to_string().contains("over the budget"));
+        assert!(!out.exists());
+    }
+}
@@ -1,20 +1,201 @@
+use crate::attachments::{self, Attachment};
+use crate::error::McError;
+use crate::hash;
+use knowledge::TokenCounter;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
-#[derive(Serialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ValidationResult {
     pub valid: bool,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub errors: Vec<String>,
+    /// Problems worth fixing that don't make the file invalid
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Id from the `# Task:` or `# Response:` header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    /// The task's priority, when it is a valid one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    /// The task's Created timestamp, when it is valid RFC 3339
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// Ids of the tasks this one waits for, from `Depends-On:`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// Front matter keys other than the ones validated above
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub metadata: Map<String, Value>,
+}
+
+/// How urgent a task is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Normal,
+    High,
+    Critical,
+}
+
+impl Priority {
+    /// Parse a priority, ignoring case.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "normal" => Some(Priority::Normal),
+            "high" => Some(Priority::High),
+            "critical" => Some(Priority::Critical),
+            _ => None,
+        }
+    }
+}
+
+/// Where a mission keeps each task's files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    /// `tasks/task-{id}.md`, `status/task-{id}.status` and
+    /// `responses/task-{id}.md`
+    Flat,
+    /// `tasks/{id}/task.md`, `tasks/{id}/status` and `tasks/{id}/response.md`
+    Nested,
+}
+
+impl Layout {
+    /// The layout task `task_id` is in: nested once `tasks/{id}/` exists.
+    pub fn detect(mission_dir: &Path, task_id: &str) -> Self {
+        if mission_dir.join("tasks").join(task_id).is_dir() {
+            Layout::Nested
+        } else {
+            Layout::Flat
+        }
+    }
+
+    /// Task `task_id`'s files in this layout, under `mission_dir` as given.
+    pub fn paths(self, mission_dir: &Path, task_id: &str) -> TaskPaths {
+        match self {
+            Layout::Flat => TaskPaths {
+                layout: self,
+                task: mission_dir
+                    .join("tasks")
+                    .join(format!("task-{}.md", task_id)),
+                status: mission_dir
+                    .join("status")
+                    .join(format!("task-{}.status", task_id)),
+                response: mission_dir
+                    .join("responses")
+                    .join(format!("task-{}.md", task_id)),
+            },
+            Layout::Nested => {
+                let dir = mission_dir.join("tasks").join(task_id);
+                TaskPaths {
+                    layout: self,
+                    task: dir.join("task.md"),
+                    status: dir.join("status"),
+                    response: dir.join("response.md"),
+                }
+            }
+        }
+    }
+}
+
+/// One task's files, from [`Layout::paths`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskPaths {
+    pub layout: Layout,
+    pub task: PathBuf,
+    pub status: PathBuf,
+    pub response: PathBuf,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Version of the [`ParsedResponse`] JSON shape. Bump on any field change.
+pub const RESPONSE_SCHEMA_VERSION: u32 = 2;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ParsedResponse {
+    /// [`RESPONSE_SCHEMA_VERSION`] of the parser; 0 in output from before it
+    #[serde(default)]
+    pub schema_version: u32,
     pub summary: Option<String>,
     pub details: Option<String>,
     pub files_modified: Vec<String>,
+    /// The `files_modified` entries with their paths and change kinds picked out
+    #[serde(default)]
+    pub file_changes: Vec<FileChange>,
+    /// Lines under Files Modified that don't look like paths
+    #[serde(default)]
+    pub files_modified_notes: Vec<String>,
     pub notes: Option<String>,
+    /// Every `## ` section by header, the ones above included
+    #[serde(default)]
+    pub sections: BTreeMap<String, String>,
+    /// Fenced code blocks from every section, in file order
+    #[serde(default)]
+    pub code_blocks: Vec<CodeBlock>,
+    /// Content of the code blocks whose language is `diff` or `patch`
+    #[serde(default)]
+    pub diffs: Vec<String>,
+    /// Hash of the normalized file content, for cache invalidation
+    #[serde(default)]
+    pub content_hash: String,
+    /// The YAML front matter, if the file starts with any
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
+}
+
+/// One entry of a response's Files Modified.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    /// Path before a rename written as `old -> new`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    /// The path exists under the repo root; never checked outside it
+    pub exists: bool,
+    /// The path leads out of the repo root, absolutely or through `..`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub outside_repo: bool,
+}
+
+/// What an entry says happened to its file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    /// The entry doesn't say
+    Unknown,
+}
+
+impl ChangeKind {
+    /// The kind an annotation such as `new` or `deleted` names.
+    fn from_word(word: &str) -> Option<Self> {
+        match word.trim().to_ascii_lowercase().as_str() {
+            "a" | "add" | "added" | "new" | "created" => Some(ChangeKind::Added),
+            "m" | "modify" | "modified" | "update" | "updated" | "changed" | "edited" => {
+                Some(ChangeKind::Modified)
+            }
+            "d" | "delete" | "deleted" | "remove" | "removed" => Some(ChangeKind::Deleted),
+            "r" | "rename" | "renamed" | "move" | "moved" => Some(ChangeKind::Renamed),
+            _ => None,
+        }
+    }
+}
+
+/// A fenced code block in a response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeBlock {
+    /// First word of the fence's info string
+    pub language: Option<String>,
+    pub content: String,
 }
 
 /// Validate a task file format.
@@ -34,227 +215,3378 @@ pub struct ParsedResponse {
 /// ## Response Instructions
 /// {instructions for response}
 /// ```
-pub fn validate_task(file_path: &str) -> Result<ValidationResult, Box<dyn std::error::Error>> {
+///
+/// A `Depends-On: 001, 002` line names tasks that must be done first (see
+/// [`check_ready`]).
+///
+/// The file may start with YAML front matter (see [`FrontMatter`]), whose
+/// `created` and `priority` take the place of the inline lines.
+///
+/// A `.json` file, or one starting with `{`, is validated as a [`JsonTask`]
+/// instead.
+pub fn validate_task(file_path: &str) -> Result<ValidationResult, McError> {
     let path = Path::new(file_path);
 
     if !path.exists() {
         return Ok(ValidationResult {
-            valid: false,
             errors: vec![format!("File not found: {}", file_path)],
+            ..Default::default()
         });
     }
 
     let content = fs::read_to_string(path)?;
-    let mut errors = Vec::new();
+    Ok(validate_task_content(&content, path))
+}
+
+/// [`validate_task`] on the already read `content` of the file at `path`.
+fn validate_task_content(content: &str, path: &Path) -> ValidationResult {
+    if TaskFormat::of(path, content) == TaskFormat::Json {
+        return validate_json_task(content, path);
+    }
+    let front = match FrontMatter::parse(content) {
+        Ok(front) => front,
+        Err(error) => {
+            return ValidationResult {
+                errors: vec![error],
+                ..Default::default()
+            }
+        }
+    };
+    let mut result = ValidationResult::default();
+    let errors = &mut result.errors;
 
     // Check for required sections
-    if !content.starts_with("# Task:") {
+    if !front.body.starts_with("# Task:") {
         errors.push("Missing '# Task:' header".to_string());
     }
+    result.task_id = metadata(front.body, "# Task:");
 
-    if !content.contains("## Instructions") {
+    if !has_section(content, "## Instructions", Headers::Exact) {
         errors.push("Missing '## Instructions' section".to_string());
     }
 
-    if !content.contains("## Response Instructions") {
+    if !has_section(content, "## Response Instructions", Headers::Exact) {
         errors.push("Missing '## Response Instructions' section".to_string());
     }
 
-    // Check for metadata
-    if !content.contains("Created:") {
-        errors.push("Missing 'Created:' timestamp".to_string());
+    // Check the metadata; other metadata lines are left alone
+    match front.field("created", "Created:") {
+        None => errors.push("Missing 'Created:' timestamp".to_string()),
+        Some((number, line, value)) => match chrono::DateTime::parse_from_rfc3339(&value) {
+            Ok(_) => result.created = Some(value),
+            Err(e) => errors.push(format!(
+                "Invalid RFC 3339 timestamp on line {} ({}): '{}'",
+                number, e, line
+            )),
+        },
     }
 
-    if !content.contains("Priority:") {
-        errors.push("Missing 'Priority:' field".to_string());
+    match front.field("priority", "Priority:") {
+        None => errors.push("Missing 'Priority:' field".to_string()),
+        Some((number, line, value)) => match Priority::parse(&value) {
+            Some(priority) => result.priority = Some(priority),
+            None => errors.push(format!(
+                "Invalid priority on line {} (expected one of: {}): '{}'",
+                number,
+                PRIORITIES.join(", "),
+                line
+            )),
+        },
     }
 
-    Ok(ValidationResult {
-        valid: errors.is_empty(),
-        errors,
+    result.depends_on = depends_on(&front);
+    if let Some(task_id) = &result.task_id {
+        if result.depends_on.contains(task_id) {
+            errors.push(format!("Task '{}' depends on itself", task_id));
+        }
+    }
+
+    let context = extract_section(content, "## Context", Headers::Exact).unwrap_or_default();
+    check_attachments(&context, path, errors);
+
+    result.metadata = front.extra(TASK_KEYS);
+    result.valid = result.errors.is_empty();
+    result
+}
+
+/// Whether the first line with content in a block is a YAML `key:` line,
+/// meaning the block was meant as front matter.
+fn opens_with_key(lines: &[&str]) -> bool {
+    let Some(line) = lines
+        .iter()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+    else {
+        return false;
+    };
+    line.split_once(':').is_some_and(|(key, _)| {
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
     })
 }
 
-/// Parse a response file to extract structured data.
+/// A YAML block at the top of a markdown file, between `---` lines:
 ///
-/// Expected format:
 /// ```markdown
-/// # Response: {id}
-/// Completed: {timestamp}
-///
-/// ## Summary
-/// {brief summary}
-///
-/// ## Details
-/// {full response}
+/// ---
+/// assignee: sam
+/// labels: [auth, frontend]
+/// ---
+/// # Task: 001
+/// ```
 ///
-/// ## Files Modified
-/// - path/to/file1.go
-/// - path/to/file2.ts
+/// Keys the format already has a line for (`created`, `priority`,
+/// `depends_on`, `completed`) win over that line; the rest are carried
+/// through as they are.
 ///
-/// ## Notes
-/// {any additional notes}
-/// ```
-pub fn parse_response(file_path: &str) -> Result<ParsedResponse, Box<dyn std::error::Error>> {
-    let path = Path::new(file_path);
+/// A file opening with a `---` horizontal rule is plain markdown: the block
+/// only counts as front matter when it is closed and holds a mapping.
+struct FrontMatter<'a> {
+    fields: Map<String, Value>,
+    /// The block's lines, without the `---` around them
+    lines: Vec<&'a str>,
+    /// The file after the block, or all of it when there is none
+    body: &'a str,
+    /// Lines before `body`, `---` lines included
+    offset: usize,
+}
 
-    if !path.exists() {
-        return Err(format!("File not found: {}", file_path).into());
+impl<'a> FrontMatter<'a> {
+    /// Split `content` into its front matter and body. A closed block that
+    /// opens with a `key:` line but isn't valid YAML is an error naming the
+    /// line of the file it's on; any other block that isn't a mapping is
+    /// left in the body.
+    fn parse(content: &'a str) -> Result<Self, String> {
+        let none = FrontMatter {
+            fields: Map::new(),
+            lines: Vec::new(),
+            body: content,
+            offset: 0,
+        };
+        let mut rest = match content.split_once('\n') {
+            Some((first, rest)) if first.trim_end() == "---" => rest,
+            _ => return Ok(none),
+        };
+
+        let mut lines = Vec::new();
+        let body = loop {
+            let (line, after) = match rest.split_once('\n') {
+                Some((line, after)) => (line, after),
+                None if !rest.is_empty() => (rest, ""),
+                None => return Ok(none),
+            };
+            if matches!(line.trim_end(), "---" | "...") {
+                break after;
+            }
+            lines.push(line);
+            rest = after;
+        };
+
+        let yaml = lines.join("\n");
+        let fields = match serde_yaml::from_str::<Value>(&yaml) {
+            Ok(Value::Object(fields)) => fields,
+            Ok(Value::Null) => Map::new(),
+            Ok(_) => return Ok(none),
+            Err(_) if !opens_with_key(&lines) => return Ok(none),
+            Err(e) => {
+                // The block starts on the file's second line; errors found
+                // at the end of it are put on its last line
+                let line = e
+                    .location()
+                    .map_or(1, |location| location.line().clamp(1, lines.len().max(1)));
+                let message = e.to_string();
+                let message = message.split(" at line ").next().unwrap_or_default();
+                return Err(format!(
+                    "Invalid front matter YAML on line {}: {}",
+                    line + 1,
+                    message
+                ));
+            }
+        };
+        Ok(FrontMatter {
+            fields,
+            offset: lines.len() + 2,
+            lines,
+            body,
+        })
     }
 
-    let content = fs::read_to_string(path)?;
+    /// `key` from the front matter, or else the inline line starting with
+    /// `prefix`, as [`metadata_line`] gives it, numbered from the file's top.
+    fn field(&self, key: &str, prefix: &str) -> Option<(usize, String, String)> {
+        if let Some(value) = self.fields.get(key) {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                Value::Null => String::new(),
+                value => value.to_string(),
+            };
+            let key_prefix = format!("{}:", key);
+            let (number, line) = self
+                .lines
+                .iter()
+                .enumerate()
+                .find(|(_, line)| line.starts_with(&key_prefix))
+                .map_or((2, ""), |(i, line)| (i + 2, line.trim_end()));
+            return Some((number, line.to_string(), value));
+        }
+        metadata_line(self.body, prefix).map(|(number, line, value)| {
+            (number + self.offset, line.to_string(), value.to_string())
+        })
+    }
 
-    Ok(ParsedResponse {
-        summary: extract_section(&content, "## Summary"),
-        details: extract_section(&content, "## Details"),
-        files_modified: extract_file_list(&content, "## Files Modified"),
-        notes: extract_section(&content, "## Notes"),
-    })
+    /// The fields other than `known`.
+    fn extra(&self, known: &[&str]) -> Map<String, Value> {
+        self.fields
+            .iter()
+            .filter(|(key, _)| !known.contains(&key.as_str()))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
 }
 
-/// Extract content between a section header and the next section.
-fn extract_section(content: &str, section: &str) -> Option<String> {
-    let section_start = content.find(section)?;
-    let after_header = &content[section_start + section.len()..];
+/// [`validate_task`] for a task in the JSON format.
+///
+/// `id`, `priority` and `instructions` are required; `created`, when given,
+/// must be RFC 3339. Fields [`JsonTask`] doesn't know are only warnings.
+fn validate_json_task(content: &str, path: &Path) -> ValidationResult {
+    let mut result = ValidationResult::default();
+    let fields = match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        Ok(_) => {
+            result
+                .errors
+                .push("Task JSON must be an object".to_string());
+            return result;
+        }
+        Err(e) => {
+            result.errors.push(format!("Invalid JSON: {}", e));
+            return result;
+        }
+    };
+
+    let mut errors = Vec::new();
+    let mut text = |key: &str, required: bool| match fields.get(key) {
+        None | Some(serde_json::Value::Null) => {
+            if required {
+                errors.push(format!("Missing '{}' field", key));
+            }
+            None
+        }
+        Some(serde_json::Value::String(value)) if required && value.trim().is_empty() => {
+            errors.push(format!("Empty '{}' field", key));
+            None
+        }
+        Some(serde_json::Value::String(value)) => Some(value.clone()),
+        Some(_) => {
+            errors.push(format!("'{}' must be a string", key));
+            None
+        }
+    };
 
-    // Skip to the content (after the header line)
-    let content_start = after_header.find('\n').map(|i| i + 1).unwrap_or(0);
-    let section_content = &after_header[content_start..];
+    result.task_id = text("id", true);
+    text("instructions", true);
+    text("response_instructions", false);
+    let context = text("context", false);
+    let created = text("created", false);
+    let priority = text("priority", true);
 
-    // Find the next section (## header)
-    let end = section_content
-        .find("\n## ")
-        .unwrap_or(section_content.len());
+    match fields.get("depends_on") {
+        None | Some(serde_json::Value::Null) => {}
+        Some(serde_json::Value::Array(items)) if items.iter().all(Value::is_string) => {
+            result.depends_on = items
+                .iter()
+                .filter_map(|item| item.as_str().map(str::to_string))
+                .collect();
+        }
+        Some(_) => errors.push("'depends_on' must be a list of task ids".to_string()),
+    }
 
-    let result = section_content[..end].trim();
-    if result.is_empty() {
-        None
-    } else {
-        Some(result.to_string())
+    if let Some(created) = created {
+        match chrono::DateTime::parse_from_rfc3339(&created) {
+            Ok(_) => result.created = Some(created),
+            Err(e) => errors.push(format!(
+                "Invalid RFC 3339 timestamp in 'created' ({}): '{}'",
+                e, created
+            )),
+        }
+    }
+    if let Some(priority) = priority {
+        match Priority::parse(&priority) {
+            Some(priority) => result.priority = Some(priority),
+            None => errors.push(format!(
+                "Invalid priority (expected one of: {}): '{}'",
+                PRIORITIES.join(", "),
+                priority
+            )),
+        }
     }
+    check_attachments(context.as_deref().unwrap_or_default(), path, &mut errors);
+
+    result.warnings = fields
+        .keys()
+        .filter(|key| !JSON_TASK_FIELDS.contains(&key.as_str()))
+        .map(|key| format!("Unknown field '{}'", key))
+        .collect();
+    result.errors = errors;
+    result.valid = result.errors.is_empty();
+    result
 }
 
-/// Extract a list of files from a section.
-fn extract_file_list(content: &str, section: &str) -> Vec<String> {
-    let section_content = match extract_section(content, section) {
-        Some(c) => c,
-        None => return Vec::new(),
+/// Front matter keys that stand in for a task's metadata lines.
+const TASK_KEYS: &[&str] = &["created", "priority", "depends_on"];
+
+/// The task's dependencies: front matter `depends_on`, a list or ids
+/// separated by commas, or else the `Depends-On:` line. Ids that YAML would
+/// read as numbers, like `001`, need quoting in front matter.
+fn depends_on(front: &FrontMatter) -> Vec<String> {
+    let ids: Vec<String> = match front.fields.get("depends_on") {
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(id) => id.clone(),
+                other => other.to_string(),
+            })
+            .collect(),
+        _ => front
+            .field("depends_on", "Depends-On:")
+            .map(|(_, _, value)| value.split(',').map(str::to_string).collect())
+            .unwrap_or_default(),
     };
+    let mut unique = Vec::new();
+    for id in ids.iter().map(|id| id.trim()).filter(|id| !id.is_empty()) {
+        if !unique.iter().any(|seen| seen == id) {
+            unique.push(id.to_string());
+        }
+    }
+    unique
+}
 
-    section_content
-        .lines()
-        .filter_map(|line| {
-            let trimmed = line.trim();
-            if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
-                Some(trimmed[2..].trim().to_string())
-            } else if !trimmed.is_empty() && !trimmed.starts_with('#') {
-                Some(trimmed.to_string())
-            } else {
-                None
-            }
-        })
-        .collect()
+/// Referenced attachments must exist and match their content hash.
+fn check_attachments(context: &str, task_path: &Path, errors: &mut Vec<String>) {
+    let mission_dir = mission_dir_of(task_path);
+    for reference in attachments::references(context) {
+        if let Some(error) = attachments::verify(&reference, &mission_dir) {
+            errors.push(error);
+        }
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
+/// How a task or response file is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskFormat {
+    Markdown,
+    Json,
+}
 
-    #[test]
-    fn test_validate_task_valid() {
-        let temp_dir = TempDir::new().unwrap();
-        let task_path = temp_dir.path().join("task.md");
+impl TaskFormat {
+    /// JSON for a `.json` file or content starting with `{`, else markdown.
+    pub fn of(path: &Path, content: &str) -> Self {
+        let json_extension = path.extension().is_some_and(|ext| ext == "json");
+        if json_extension || content.trim_start().starts_with('{') {
+            TaskFormat::Json
+        } else {
+            TaskFormat::Markdown
+        }
+    }
 
-        let content = r#"# Task: 001
-Created: 2026-01-22T10:00:00Z
-Priority: normal
+    pub fn extension(self) -> &'static str {
+        match self {
+            TaskFormat::Markdown => "md",
+            TaskFormat::Json => "json",
+        }
+    }
+}
 
-## Instructions
+/// Keys a JSON task may have.
+const JSON_TASK_FIELDS: &[&str] = &[
+    "id",
+    "created",
+    "priority",
+    "instructions",
+    "context",
+    "response_instructions",
+    "depends_on",
+];
 
-Implement the login form.
+/// A task in the JSON format, e.g.
+/// `{"id": "001", "priority": "high", "instructions": "...", "context": "..."}`.
+///
+/// Each field is one of the markdown format's metadata lines or sections, so
+/// [`task_to_markdown`] and [`markdown_to_task`] convert between the two
+/// without losing any of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JsonTask {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    pub priority: String,
+    pub instructions: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_instructions: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+}
 
-## Context
+/// Write `task` in the markdown format [`validate_task`] describes.
+pub fn task_to_markdown(task: &JsonTask) -> String {
+    let mut markdown = format!("# Task: {}\n", task.id);
+    if let Some(created) = &task.created {
+        markdown.push_str(&format!("Created: {}\n", created));
+    }
+    markdown.push_str(&format!("Priority: {}\n", task.priority));
+    if !task.depends_on.is_empty() {
+        markdown.push_str(&format!("Depends-On: {}\n", task.depends_on.join(", ")));
+    }
+    let sections = [
+        ("Instructions", Some(&task.instructions)),
+        ("Context", task.context.as_ref()),
+        ("Response Instructions", task.response_instructions.as_ref()),
+    ];
+    for (header, body) in sections {
+        if let Some(body) = body {
+            markdown.push_str(&format!("\n## {}\n{}\n", header, body.trim_end()));
+        }
+    }
+    markdown
+}
 
-This is the context.
+/// Read a markdown task into a [`JsonTask`].
+///
+/// The header, Priority and Instructions are required. Section bodies are
+/// kept as written, less the blank lines around them and trailing whitespace.
+/// Front matter `created` and `priority` are used over the inline lines; its
+/// other keys have no JSON field and are dropped.
+pub fn markdown_to_task(content: &str) -> Result<JsonTask, McError> {
+    let front = FrontMatter::parse(content).map_err(McError::Parse)?;
+    let content = front.body;
+    let missing = |what: &str| McError::Validation(format!("Missing {}", what));
+    let field = |key, prefix| {
+        front
+            .field(key, prefix)
+            .map(|(_, _, value)| value)
+            .filter(|value| !value.is_empty())
+    };
+    Ok(JsonTask {
+        id: metadata(content, "# Task:").ok_or_else(|| missing("'# Task:' header"))?,
+        created: field("created", "Created:"),
+        priority: field("priority", "Priority:").ok_or_else(|| missing("'Priority:' field"))?,
+        instructions: section_verbatim(content, "Instructions")
+            .ok_or_else(|| missing("'## Instructions' section"))?,
+        context: section_verbatim(content, "Context"),
+        response_instructions: section_verbatim(content, "Response Instructions"),
+        depends_on: depends_on(&front),
+    })
+}
 
-## Response Instructions
+/// A task file written in the other format by [`convert_task`].
+#[derive(Debug, Serialize)]
+pub struct ConvertedTask {
+    pub file: PathBuf,
+    pub output: PathBuf,
+    pub format: TaskFormat,
+}
 
-Write response to .mission/responses/task-001.md
-"#;
-        fs::write(&task_path, content).unwrap();
+/// Write the task in `file` to `output` in `format`.
+///
+/// `format` defaults to whichever the file isn't in, and `output` to the file
+/// with that format's extension. An existing output is only replaced when
+/// `force` is set.
+pub fn convert_task(
+    file: &Path,
+    format: Option<TaskFormat>,
+    output: Option<&Path>,
+    force: bool,
+) -> Result<ConvertedTask, McError> {
+    if !file.exists() {
+        return Err(McError::not_found(format!(
+            "File not found: {}",
+            file.display()
+        )));
+    }
+    let content = fs::read_to_string(file)?;
+    let (task, format) = match TaskFormat::of(file, &content) {
+        TaskFormat::Markdown => (
+            markdown_to_task(&content)?,
+            format.unwrap_or(TaskFormat::Json),
+        ),
+        TaskFormat::Json => (
+            serde_json::from_str(&content)?,
+            format.unwrap_or(TaskFormat::Markdown),
+        ),
+    };
+    let output = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| file.with_extension(format.extension()));
+    if output.exists() && !force {
+        return Err(McError::Io(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("Output file already exists: {}", output.display()),
+        )));
+    }
 
-        let result = validate_task(task_path.to_str().unwrap()).unwrap();
-        assert!(result.valid, "Errors: {:?}", result.errors);
+    let converted = match format {
+        TaskFormat::Markdown => task_to_markdown(&task),
+        TaskFormat::Json => serde_json::to_string_pretty(&task)? + "\n",
+    };
+    fs::write(&output, converted).map_err(|e| McError::io(output.display(), e))?;
+    Ok(ConvertedTask {
+        file: file.to_path_buf(),
+        output,
+        format,
+    })
+}
+
+/// A task file broken into its metadata and sections.
+#[derive(Debug, Serialize)]
+pub struct TaskSpec {
+    pub task_id: Option<String>,
+    pub created: Option<String>,
+    pub priority: Option<String>,
+    pub instructions: Option<String>,
+    pub context: Option<String>,
+    pub response_instructions: Option<String>,
+    /// Files referenced from Context with `@attach`
+    pub attachments: Vec<Attachment>,
+    pub depends_on: Vec<String>,
+    /// Front matter keys other than `created`, `priority` and `depends_on`
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub metadata: Map<String, Value>,
+}
+
+impl TaskSpec {
+    /// Tokens the attachments add when dispatched alongside the task.
+    pub fn attachment_tokens(&self) -> usize {
+        self.attachments.iter().map(|a| a.tokens).sum()
     }
+}
 
-    #[test]
-    fn test_validate_task_missing_sections() {
-        let temp_dir = TempDir::new().unwrap();
-        let task_path = temp_dir.path().join("task.md");
+/// Parse a task file (see [`validate_task`] for the format).
+///
+/// Attachments are resolved for size and token counts; their content is
+/// only included when `inline_attachments` is set.
+pub fn parse_task(file_path: &str, inline_attachments: bool) -> Result<TaskSpec, McError> {
+    let path = Path::new(file_path);
 
-        fs::write(&task_path, "Some random content").unwrap();
+    if !path.exists() {
+        return Err(McError::not_found(format!("File not found: {}", file_path)));
+    }
 
-        let result = validate_task(task_path.to_str().unwrap()).unwrap();
-        assert!(!result.valid);
-        assert!(result.errors.len() >= 3);
+    let content = fs::read_to_string(path)?;
+    let mission_dir = mission_dir_of(path);
+    if TaskFormat::of(path, &content) == TaskFormat::Json {
+        let task: JsonTask = serde_json::from_str(&content)?;
+        let references = attachments::references(task.context.as_deref().unwrap_or_default());
+        return Ok(TaskSpec {
+            task_id: Some(task.id),
+            created: task.created,
+            priority: Some(task.priority),
+            instructions: Some(task.instructions),
+            response_instructions: task.response_instructions,
+            attachments: attachments::resolve(&references, &mission_dir, inline_attachments),
+            context: task.context,
+            depends_on: task.depends_on,
+            metadata: Map::new(),
+        });
     }
+    let front = FrontMatter::parse(&content).map_err(McError::Parse)?;
+    let content = front.body;
+    let context = extract_section(content, "## Context", Headers::Exact);
+    let references = attachments::references(context.as_deref().unwrap_or_default());
+    let field = |key, prefix| {
+        front
+            .field(key, prefix)
+            .map(|(_, _, value)| value)
+            .filter(|value| !value.is_empty())
+    };
 
-    #[test]
-    fn test_parse_response() {
-        let temp_dir = TempDir::new().unwrap();
-        let response_path = temp_dir.path().join("response.md");
+    Ok(TaskSpec {
+        task_id: metadata(content, "# Task:"),
+        created: field("created", "Created:"),
+        priority: field("priority", "Priority:"),
+        instructions: extract_section(content, "## Instructions", Headers::Exact),
+        response_instructions: extract_section(content, "## Response Instructions", Headers::Exact),
+        attachments: attachments::resolve(&references, &mission_dir, inline_attachments),
+        context,
+        depends_on: depends_on(&front),
+        metadata: front.extra(TASK_KEYS),
+    })
+}
 
-        let content = r#"# Response: 001
-Completed: 2026-01-22T10:30:00Z
+/// Priorities a task may have.
+pub const PRIORITIES: &[&str] = &["normal", "high", "critical"];
 
-## Summary
+/// A task to write with [`create_task`].
+#[derive(Debug, Default)]
+pub struct NewTask {
+    pub task_id: String,
+    /// One of [`PRIORITIES`]
+    pub priority: String,
+    pub instructions: String,
+    pub context: Option<String>,
+    /// Files to store as attachments and reference from Context
+    pub attachments: Vec<PathBuf>,
+    /// Replace a task file that already exists
+    pub force: bool,
+}
 
-Implemented the login form with validation.
+/// Write `{mission_dir}/tasks/task-{id}.md` in the format [`validate_task`]
+/// expects, refusing to overwrite unless `force` is set.
+pub fn create_task(mission_dir: &Path, task: &NewTask) -> Result<PathBuf, McError> {
+    if Priority::parse(&task.priority).is_none() {
+        return Err(McError::Validation(format!(
+            "Invalid priority '{}' (expected one of: {})",
+            task.priority,
+            PRIORITIES.join(", ")
+        )));
+    }
+    let path = mission_dir
+        .join("tasks")
+        .join(format!("task-{}.md", task.task_id));
+    if path.exists() && !task.force {
+        return Err(McError::Io(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("Task file already exists: {}", path.display()),
+        )));
+    }
 
-## Details
+    let mut context = task.context.clone().unwrap_or_default();
+    for file in &task.attachments {
+        let reference = attachments::store(mission_dir, file)?;
+        if !context.is_empty() && !context.ends_with('\n') {
+            context.push('\n');
+        }
+        context.push_str(&format!("{}{}\n", attachments::ATTACH_PREFIX, reference));
+    }
 
-Created a new React component with email and password fields.
-Added form validation and error handling.
+    let mission_name = mission_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let content = format!(
+        "# Task: {id}\nCreated: {created}\nPriority: {priority}\n\n## Instructions\n{instructions}\n\n## Context\n{context}\n\n## Response Instructions\nWrite response to {mission}/responses/task-{id}.md\n",
+        id = task.task_id,
+        created = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        priority = task.priority,
+        instructions = task.instructions.trim_end(),
+        context = context.trim_end(),
+        mission = mission_name,
+    );
 
-## Files Modified
+    fs::create_dir_all(mission_dir.join("tasks"))?;
+    fs::write(&path, content)?;
+    Ok(path)
+}
 
-- src/components/LoginForm.tsx
-- src/components/LoginForm.test.tsx
-- src/styles/login.css
+/// The first line starting with `prefix`: its 1-based number, the line
+/// and the trimmed value after the prefix.
+fn metadata_line<'a>(content: &'a str, prefix: &str) -> Option<(usize, &'a str, &'a str)> {
+    content.lines().enumerate().find_map(|(i, line)| {
+        line.strip_prefix(prefix)
+            .map(|value| (i + 1, line.trim_end(), value.trim()))
+    })
+}
 
-## Notes
+/// Value of the first line starting with `prefix`.
+fn metadata(content: &str, prefix: &str) -> Option<String> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix(prefix))
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
 
-Consider adding rate limiting in the future.
-"#;
-        fs::write(&response_path, content).unwrap();
+/// Mission directory a task file belongs to, in either layout
+/// (`{mission_dir}/tasks/task-x.md` or `{mission_dir}/tasks/x/task.md`).
+fn mission_dir_of(task_path: &Path) -> PathBuf {
+    let parent = task_path.parent().unwrap_or(Path::new("."));
+    let is_tasks = |dir: &Path| dir.file_name().is_some_and(|n| n == "tasks");
+    match (parent.parent(), parent.parent().and_then(Path::parent)) {
+        (Some(mission_dir), _) if is_tasks(parent) => mission_dir.to_path_buf(),
+        (Some(tasks), Some(mission_dir)) if is_tasks(tasks) => mission_dir.to_path_buf(),
+        _ => parent.to_path_buf(),
+    }
+}
 
-        let result = parse_response(response_path.to_str().unwrap()).unwrap();
+/// Share of a task's token budget each section is expected to stay within.
+const RECOMMENDED_SHARES: &[(&str, f64)] = &[
+    ("instructions", 0.3),
+    ("context", 0.6),
+    ("response_instructions", 0.1),
+    ("attachments", 0.6),
+];
 
-        assert_eq!(
-            result.summary,
-            Some("Implemented the login form with validation.".to_string())
-        );
-        assert!(result.details.is_some());
-        assert_eq!(result.files_modified.len(), 3);
-        assert!(result.files_modified.contains(&"src/components/LoginForm.tsx".to_string()));
-        assert!(result.notes.is_some());
+/// Token usage of one task section against its recommended share.
+#[derive(Debug, Serialize)]
+pub struct SectionBudget {
+    pub section: String,
+    pub tokens: usize,
+    pub recommended_max: usize,
+    pub over_share: bool,
+}
+
+/// A place a section could be cut, moving the rest into a follow-up task.
+#[derive(Debug, Serialize)]
+pub struct SplitPoint {
+    pub section: String,
+    /// Byte offset into the section body where the cut would go
+    pub offset: usize,
+    /// Tokens removed from this task by cutting here
+    pub tokens_saved: usize,
+    /// Start of the text after the cut
+    pub preview: String,
+}
+
+/// Where a task's tokens go and how it could be trimmed to fit a budget.
+#[derive(Debug, Serialize)]
+pub struct BudgetAnalysis {
+    pub budget: usize,
+    pub total_tokens: usize,
+    pub over_budget: bool,
+    pub sections: Vec<SectionBudget>,
+    pub split_points: Vec<SplitPoint>,
+}
+
+/// Compare a task's sections against `budget` and suggest split points.
+///
+/// Split points fall on paragraph boundaries in Context and on list items in
+/// Instructions. Nothing is written; the analysis only describes the task.
+pub fn analyze_task_budget(
+    task: &TaskSpec,
+    budget: usize,
+    counter: &TokenCounter,
+) -> BudgetAnalysis {
+    let bodies = [
+        ("instructions", task.instructions.as_deref()),
+        ("context", task.context.as_deref()),
+        (
+            "response_instructions",
+            task.response_instructions.as_deref(),
+        ),
+    ];
+
+    let mut sections: Vec<SectionBudget> = bodies
+        .iter()
+        .map(|(name, body)| section_budget(name, counter.count(body.unwrap_or_default()), budget))
+        .collect();
+    if !task.attachments.is_empty() {
+        sections.push(section_budget(
+            "attachments",
+            task.attachment_tokens(),
+            budget,
+        ));
     }
+    let total_tokens = sections.iter().map(|s| s.tokens).sum();
 
-    #[test]
-    fn test_extract_section() {
-        let content = r#"## Summary
+    let mut split_points = Vec::new();
+    if let Some(instructions) = task.instructions.as_deref() {
+        for offset in list_item_offsets(instructions) {
+            split_points.push(split_point("instructions", instructions, offset, counter));
+        }
+    }
+    if let Some(context) = task.context.as_deref() {
+        for offset in paragraph_offsets(context) {
+            split_points.push(split_point("context", context, offset, counter));
+        }
+    }
 
-This is the summary.
+    BudgetAnalysis {
+        budget,
+        total_tokens,
+        over_budget: total_tokens > budget,
+        sections,
+        split_points,
+    }
+}
 
-## Details
+fn section_budget(section: &str, tokens: usize, budget: usize) -> SectionBudget {
+    let share = RECOMMENDED_SHARES
+        .iter()
+        .find(|(name, _)| *name == section)
+        .map(|(_, share)| *share)
+        .unwrap_or(1.0);
+    let recommended_max = (budget as f64 * share) as usize;
+    SectionBudget {
+        section: section.to_string(),
+        tokens,
+        recommended_max,
+        over_share: tokens > recommended_max,
+    }
+}
 
-These are the details.
-"#;
-        let summary = extract_section(content, "## Summary");
-        assert_eq!(summary, Some("This is the summary.".to_string()));
+fn split_point(section: &str, body: &str, offset: usize, counter: &TokenCounter) -> SplitPoint {
+    let rest = &body[offset..];
+    SplitPoint {
+        section: section.to_string(),
+        offset,
+        tokens_saved: counter.count(rest),
+        preview: rest
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .take(60)
+            .collect(),
+    }
+}
 
-        let details = extract_section(content, "## Details");
-        assert_eq!(details, Some("These are the details.".to_string()));
+/// Offsets where each paragraph after the first begins.
+fn paragraph_offsets(body: &str) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    let mut blank_run = false;
+    for line in body.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            blank_run = true;
+        } else {
+            if blank_run && offset > 0 {
+                offsets.push(offset);
+            }
+            blank_run = false;
+        }
+        offset += line.len();
+    }
+    offsets
+}
+
+/// Offsets where each list item after the first begins.
+fn list_item_offsets(body: &str) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    let mut seen_item = false;
+    for line in body.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let is_item = trimmed.starts_with("- ")
+            || trimmed.starts_with("* ")
+            || trimmed
+                .split_once(". ")
+                .is_some_and(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+        if is_item {
+            if seen_item {
+                offsets.push(offset);
+            }
+            seen_item = true;
+        }
+        offset += line.len();
+    }
+    offsets
+}
+
+/// Parse a response file to extract structured data.
+///
+/// Expected format:
+/// ```markdown
+/// # Response: {id}
+/// Completed: {timestamp}
+///
+/// ## Summary
+/// {brief summary}
+///
+/// ## Details
+/// {full response}
+///
+/// ## Files Modified
+/// - path/to/file1.go
+/// - path/to/file2.ts
+///
+/// ## Notes
+/// {any additional notes}
+/// ```
+///
+/// YAML front matter at the top (see [`FrontMatter`]) comes back as
+/// `metadata`; malformed front matter is a [`McError::Parse`]. A `.json`
+/// file, or one starting with `{`, is read as a [`JsonResponse`].
+/// Headers are matched tolerantly (see [`Headers::Tolerant`]), so
+/// `## files changed` fills `files_modified` and `##Summary` fills `summary`.
+/// Files Modified entries are checked for existence against the current
+/// directory; see [`parse_response_with`] for the repo root and exact headers.
+pub fn parse_response(file_path: &str) -> Result<ParsedResponse, McError> {
+    parse_response_with(file_path, &ResponseOptions::default())
+}
+
+/// [`parse_response`], checking Files Modified entries against `repo_root`.
+pub fn parse_response_in(file_path: &str, repo_root: &Path) -> Result<ParsedResponse, McError> {
+    let options = ResponseOptions {
+        repo_root: repo_root.to_path_buf(),
+        ..Default::default()
+    };
+    parse_response_with(file_path, &options)
+}
+
+/// Options for [`parse_response_with`].
+#[derive(Debug, Clone)]
+pub struct ResponseOptions {
+    /// Root that Files Modified entries are checked against.
+    pub repo_root: PathBuf,
+    /// Match section headers exactly ([`Headers::Exact`]) rather than
+    /// tolerating case, spacing and aliases.
+    pub strict_headers: bool,
+}
+
+impl Default for ResponseOptions {
+    fn default() -> Self {
+        ResponseOptions {
+            repo_root: PathBuf::from("."),
+            strict_headers: false,
+        }
+    }
+}
+
+/// [`parse_response`] with explicit [`ResponseOptions`].
+pub fn parse_response_with(
+    file_path: &str,
+    options: &ResponseOptions,
+) -> Result<ParsedResponse, McError> {
+    let path = Path::new(file_path);
+    let repo_root = options.repo_root.as_path();
+    let headers = if options.strict_headers {
+        Headers::Exact
+    } else {
+        Headers::Tolerant
+    };
+
+    if !path.exists() {
+        return Err(McError::not_found(format!("File not found: {}", file_path)));
+    }
+
+    let raw = fs::read_to_string(path)?;
+    // A JSON response is parsed as the markdown it stands for
+    let content = match TaskFormat::of(path, &raw) {
+        TaskFormat::Json => serde_json::from_str::<JsonResponse>(&raw)?.to_markdown(),
+        TaskFormat::Markdown => raw.clone(),
+    };
+    let front = FrontMatter::parse(&content).map_err(McError::Parse)?;
+    let content = front.body;
+    let (files_modified, files_modified_notes) =
+        extract_file_list(content, "## Files Modified", headers);
+    let file_changes = files_modified
+        .iter()
+        .filter(|entry| !entry.eq_ignore_ascii_case("none"))
+        .map(|entry| file_change(entry, repo_root))
+        .collect();
+    let code_blocks = extract_code_blocks(content);
+    let diffs = code_blocks
+        .iter()
+        .filter(|block| matches!(block.language.as_deref(), Some("diff" | "patch")))
+        .map(|block| block.content.clone())
+        .collect();
+
+    let mut sections = BTreeMap::new();
+    for (header, body) in Sections::new(content, headers) {
+        sections.entry(header.to_string()).or_insert(body);
+    }
+
+    Ok(ParsedResponse {
+        schema_version: RESPONSE_SCHEMA_VERSION,
+        summary: extract_section(content, "## Summary", headers),
+        details: extract_section(content, "## Details", headers),
+        files_modified,
+        file_changes,
+        files_modified_notes,
+        notes: extract_section(content, "## Notes", headers),
+        sections,
+        code_blocks,
+        diffs,
+        content_hash: hash::content_hash(&raw),
+        metadata: front.fields,
+    })
+}
+
+/// A response in the JSON format, e.g.
+/// `{"id": "001", "summary": "...", "files_modified": ["src/lib.rs"]}`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JsonResponse {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files_modified: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+impl JsonResponse {
+    /// The response in the markdown format [`parse_response`] describes.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# Response: {}\n", self.id);
+        if let Some(completed) = &self.completed {
+            markdown.push_str(&format!("Completed: {}\n", completed));
+        }
+        let files: Vec<String> = self
+            .files_modified
+            .iter()
+            .map(|file| format!("- {}", file))
+            .collect();
+        let files = (!files.is_empty()).then(|| files.join("\n"));
+        let sections = [
+            ("Summary", self.summary.as_ref()),
+            ("Details", self.details.as_ref()),
+            ("Files Modified", files.as_ref()),
+            ("Notes", self.notes.as_ref()),
+        ];
+        for (header, body) in sections {
+            if let Some(body) = body {
+                markdown.push_str(&format!("\n## {}\n{}\n", header, body.trim_end()));
+            }
+        }
+        markdown
+    }
+}
+
+/// Validate a response file (see [`parse_response`] for the format).
+///
+/// The header, Completed timestamp and a non-empty Summary are required; with
+/// `task_id` given, the header must name that task. An empty or missing Files
+/// Modified list and missing Notes are only warnings.
+pub fn validate_response(
+    file_path: &str,
+    task_id: Option<&str>,
+) -> Result<ValidationResult, McError> {
+    let path = Path::new(file_path);
+
+    if !path.exists() {
+        return Ok(ValidationResult {
+            errors: vec![format!("File not found: {}", file_path)],
+            ..Default::default()
+        });
+    }
+
+    let content = fs::read_to_string(path)?;
+    let content = match TaskFormat::of(path, &content) {
+        TaskFormat::Json => match serde_json::from_str::<JsonResponse>(&content) {
+            Ok(response) => response.to_markdown(),
+            Err(e) => {
+                return Ok(ValidationResult {
+                    errors: vec![format!("Invalid JSON: {}", e)],
+                    ..Default::default()
+                })
+            }
+        },
+        TaskFormat::Markdown => content,
+    };
+    let front = match FrontMatter::parse(&content) {
+        Ok(front) => front,
+        Err(error) => {
+            return Ok(ValidationResult {
+                errors: vec![error],
+                ..Default::default()
+            })
+        }
+    };
+    let content = front.body;
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    match (content.starts_with("# Response:"), task_id) {
+        (false, _) => errors.push("Missing '# Response:' header".to_string()),
+        (true, Some(expected)) => {
+            let found = metadata(content, "# Response:").unwrap_or_default();
+            if found != expected {
+                errors.push(format!(
+                    "Response is for task '{}', expected '{}'",
+                    found, expected
+                ));
+            }
+        }
+        (true, None) => {}
+    }
+
+    if front.field("completed", "Completed:").is_none() {
+        errors.push("Missing 'Completed:' timestamp".to_string());
+    }
+
+    // Tolerant like parse_response, so what validates is what parses
+    let headers = Headers::Tolerant;
+    if !has_section(content, "## Summary", headers) {
+        errors.push("Missing '## Summary' section".to_string());
+    } else if extract_section(content, "## Summary", headers).is_none() {
+        errors.push("Empty '## Summary' section".to_string());
+    }
+
+    if extract_file_list(content, "## Files Modified", headers)
+        .0
+        .is_empty()
+    {
+        warnings.push("No files listed under '## Files Modified'".to_string());
+    }
+
+    if !has_section(content, "## Notes", headers) {
+        warnings.push("Missing '## Notes' section".to_string());
+    }
+
+    Ok(ValidationResult {
+        valid: errors.is_empty(),
+        errors,
+        warnings,
+        task_id: metadata(content, "# Response:"),
+        metadata: front.extra(&["completed"]),
+        ..Default::default()
+    })
+}
+
+/// Check a response file against a previously seen content hash.
+///
+/// Returns `Some(hash)` when the file's current hash equals `previous`, so the
+/// caller can skip re-parsing; `None` when it changed or no hash was given.
+pub fn response_unchanged(
+    file_path: &str,
+    previous: Option<&str>,
+) -> Result<Option<String>, McError> {
+    let previous = match previous {
+        Some(previous) => previous,
+        None => return Ok(None),
+    };
+
+    let path = Path::new(file_path);
+    if !path.exists() {
+        return Err(McError::not_found(format!("File not found: {}", file_path)));
+    }
+
+    let current = hash::content_hash(&fs::read_to_string(path)?);
+    Ok(if current == previous {
+        Some(current)
+    } else {
+        None
+    })
+}
+
+/// Bounds on which task files a mission-wide command visits.
+#[derive(Debug, Clone, Default)]
+pub struct TaskFilter {
+    /// Stop after this many files
+    pub limit: Option<usize>,
+    /// Skip files last modified before this time
+    pub since: Option<SystemTime>,
+}
+
+/// Task files under `{mission_dir}/tasks/`, in path order, after filtering:
+/// flat `task-{id}.md` and `task-{id}.json` files, and each nested task's
+/// `{id}/task.md`.
+///
+/// Only paths are collected, so memory stays flat however large the task
+/// files are; callers read each file as they visit it.
+pub fn task_files(mission_dir: &Path, filter: &TaskFilter) -> Result<Vec<PathBuf>, McError> {
+    let tasks_dir = mission_dir.join("tasks");
+    if !tasks_dir.is_dir() {
+        return Err(McError::not_found(format!(
+            "Tasks directory not found: {}",
+            tasks_dir.display()
+        )));
+    }
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    for entry in fs::read_dir(&tasks_dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let task = path.join("task.md");
+            if task.is_file() {
+                files.push(task);
+            }
+        } else if path.is_file()
+            && path
+                .extension()
+                .is_some_and(|ext| ext == "md" || ext == "json")
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    if let Some(since) = filter.since {
+        files.retain(|path| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified >= since)
+        });
+    }
+    if let Some(limit) = filter.limit {
+        files.truncate(limit);
+    }
+    Ok(files)
+}
+
+/// Task id from a `task-{id}.md` file name, a nested `{id}/task.md`'s dir
+/// name, or the bare stem otherwise.
+pub(crate) fn task_id(path: &Path) -> String {
+    if path.file_name().is_some_and(|name| name == "task.md") {
+        if let Some(dir) = path.parent().and_then(Path::file_name) {
+            return dir.to_string_lossy().to_string();
+        }
+    }
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match stem.strip_prefix("task-") {
+        Some(id) => id.to_string(),
+        None => stem,
+    }
+}
+
+/// Validation outcome for one task file.
+#[derive(Debug, Serialize)]
+pub struct FileValidation {
+    pub file: String,
+    #[serde(flatten)]
+    pub result: ValidationResult,
+}
+
+/// Totals over a `validate_all` run.
+#[derive(Debug, Default, Serialize)]
+pub struct ValidationSummary {
+    pub files: usize,
+    pub valid: usize,
+    pub invalid: usize,
+    /// Dependency cycles among the files checked
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Validate every task file in the mission, reporting each as it is checked.
+pub fn validate_all<F>(
+    mission_dir: &Path,
+    filter: &TaskFilter,
+    mut on_record: F,
+) -> Result<ValidationSummary, McError>
+where
+    F: FnMut(&FileValidation),
+{
+    let mut summary = ValidationSummary::default();
+    let mut graph = BTreeMap::new();
+    for path in task_files(mission_dir, filter)? {
+        let file = path.to_string_lossy().to_string();
+        let result = validate_task(&file)?;
+        summary.files += 1;
+        if result.valid {
+            summary.valid += 1;
+        } else {
+            summary.invalid += 1;
+        }
+        let task_id = result.task_id.clone().unwrap_or_else(|| task_id(&path));
+        graph.insert(task_id, result.depends_on.clone());
+        on_record(&FileValidation { file, result });
+    }
+    summary.warnings = find_cycles(&graph)
+        .iter()
+        .map(|cycle| cycle_warning(cycle))
+        .collect();
+    Ok(summary)
+}
+
+/// Whether a task's dependencies are done, from [`check_ready`].
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub task_id: String,
+    /// Every dependency has a status file
+    pub ready: bool,
+    pub depends_on: Vec<String>,
+    /// Dependencies without a status file yet
+    pub missing: Vec<String>,
+    /// Dependency cycles the task is part of, which it can never get out of
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Check whether task `task_id`'s dependencies all have status files, in
+/// whichever layout each of them is in.
+pub fn check_ready(mission_dir: &Path, task_id: &str) -> Result<Readiness, McError> {
+    let mut path = Layout::detect(mission_dir, task_id)
+        .paths(mission_dir, task_id)
+        .task;
+    if !path.exists() && path.with_extension("json").exists() {
+        path.set_extension("json");
+    }
+    if !path.exists() {
+        return Err(McError::not_found(format!(
+            "Task file not found: {}",
+            path.display()
+        )));
+    }
+    let depends_on = read_dependencies(&path)?;
+    let missing = unmet_dependencies(mission_dir, &depends_on);
+    let warnings = find_cycles(&dependency_graph(mission_dir)?)
+        .iter()
+        .filter(|cycle| cycle.iter().any(|id| id == task_id))
+        .map(|cycle| cycle_warning(cycle))
+        .collect();
+
+    Ok(Readiness {
+        task_id: task_id.to_string(),
+        ready: missing.is_empty(),
+        depends_on,
+        missing,
+        warnings,
+    })
+}
+
+/// The ids in `depends_on` whose task has no status file yet.
+pub fn unmet_dependencies(mission_dir: &Path, depends_on: &[String]) -> Vec<String> {
+    depends_on
+        .iter()
+        .filter(|id| {
+            !Layout::detect(mission_dir, id)
+                .paths(mission_dir, id)
+                .status
+                .exists()
+        })
+        .cloned()
+        .collect()
+}
+
+/// A task file's dependencies, without validating the rest of it.
+fn read_dependencies(path: &Path) -> Result<Vec<String>, McError> {
+    let content = fs::read_to_string(path).map_err(|e| McError::io(path.display(), e))?;
+    if TaskFormat::of(path, &content) == TaskFormat::Json {
+        return Ok(serde_json::from_str::<JsonTask>(&content)?.depends_on);
+    }
+    let front = FrontMatter::parse(&content).map_err(McError::Parse)?;
+    Ok(depends_on(&front))
+}
+
+/// Each task in the mission's `tasks/` with the ids it depends on. Files
+/// that can't be read for their dependencies are left out.
+fn dependency_graph(mission_dir: &Path) -> Result<BTreeMap<String, Vec<String>>, McError> {
+    let mut graph = BTreeMap::new();
+    for path in task_files(mission_dir, &TaskFilter::default())? {
+        if let Ok(depends_on) = read_dependencies(&path) {
+            graph.insert(task_id(&path), depends_on);
+        }
+    }
+    Ok(graph)
+}
+
+/// Every cycle in `graph`, each once, starting from its smallest id.
+fn find_cycles(graph: &BTreeMap<String, Vec<String>>) -> Vec<Vec<String>> {
+    fn visit<'a>(
+        id: &'a str,
+        graph: &'a BTreeMap<String, Vec<String>>,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+        cycles: &mut BTreeSet<Vec<String>>,
+    ) {
+        if let Some(start) = path.iter().position(|on_path| *on_path == id) {
+            let mut cycle: Vec<String> = path[start..].iter().map(|id| id.to_string()).collect();
+            let smallest = (0..cycle.len()).min_by_key(|&i| &cycle[i]).unwrap_or(0);
+            cycle.rotate_left(smallest);
+            cycles.insert(cycle);
+            return;
+        }
+        if done.contains(id) {
+            return;
+        }
+        path.push(id);
+        for next in graph.get(id).into_iter().flatten() {
+            visit(next, graph, path, done, cycles);
+        }
+        path.pop();
+        done.insert(id);
+    }
+
+    let mut cycles = BTreeSet::new();
+    let mut done = HashSet::new();
+    for id in graph.keys() {
+        visit(id, graph, &mut Vec::new(), &mut done, &mut cycles);
+    }
+    cycles.into_iter().collect()
+}
+
+fn cycle_warning(cycle: &[String]) -> String {
+    let back = cycle.first().map(String::as_str).unwrap_or_default();
+    format!("Dependency cycle: {} -> {}", cycle.join(" -> "), back)
+}
+
+/// Where a task stands, judged from the mission directory.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    /// Only the task file
+    Pending,
+    /// Claimed, or with only one of its status and response files
+    InProgress,
+    /// Both the status and the response file have been written
+    Complete,
+    /// A status or response file whose task file doesn't exist
+    Orphaned,
+    /// The task file doesn't pass [`validate_task`]
+    Invalid,
+}
+
+/// One task in a mission listing.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskEntry {
+    pub task_id: String,
+    /// The task file; missing for an orphan
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    pub state: TaskState,
+    /// Why the task file is invalid
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    /// Hash of the normalized task file, for cache invalidation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+}
+
+/// Totals over a `list_tasks` run.
+#[derive(Debug, Default, Serialize)]
+pub struct TaskSummary {
+    pub tasks: usize,
+    pub pending: usize,
+    pub in_progress: usize,
+    pub complete: usize,
+    pub orphaned: usize,
+    pub invalid: usize,
+}
+
+/// List the mission's tasks, joining tasks/, status/ and responses/ by task
+/// id, and report each as soon as its file is read, in path order.
+///
+/// Orphans follow the task files, in id order. They are only looked for
+/// when `filter` lets every task file through, as otherwise the tasks it
+/// skipped would show up as orphans. [`sort_by_created`] puts a collected
+/// listing oldest first.
+pub fn list_tasks<F>(
+    mission_dir: &Path,
+    filter: &TaskFilter,
+    mut on_entry: F,
+) -> Result<TaskSummary, McError>
+where
+    F: FnMut(&TaskEntry),
+{
+    let status_dir = mission_dir.join("status");
+    let status_ids = ids_in(&status_dir, ".status")?;
+    let response_ids = ids_in(&mission_dir.join("responses"), ".md")?;
+
+    let mut summary = TaskSummary::default();
+    let mut report = |entry: TaskEntry| {
+        summary.tasks += 1;
+        *match entry.state {
+            TaskState::Pending => &mut summary.pending,
+            TaskState::InProgress => &mut summary.in_progress,
+            TaskState::Complete => &mut summary.complete,
+            TaskState::Orphaned => &mut summary.orphaned,
+            TaskState::Invalid => &mut summary.invalid,
+        } += 1;
+        on_entry(&entry);
+    };
+
+    let mut listed = HashSet::new();
+    for path in task_files(mission_dir, filter)? {
+        let task_id = task_id(&path);
+        let content = fs::read_to_string(&path)?;
+        let validation = validate_task_content(&content, &path);
+        let claimed = status_dir
+            .join(format!("task-{}.claimed", task_id))
+            .exists();
+        let state = match (
+            status_ids.contains(&task_id),
+            response_ids.contains(&task_id),
+        ) {
+            _ if !validation.valid => TaskState::Invalid,
+            (true, true) => TaskState::Complete,
+            (false, false) if !claimed => TaskState::Pending,
+            _ => TaskState::InProgress,
+        };
+        listed.insert(task_id.clone());
+        report(TaskEntry {
+            content_hash: Some(hash::content_hash(&content)),
+            file: Some(path.to_string_lossy().to_string()),
+            priority: validation.priority,
+            created: validation.created,
+            errors: validation.errors,
+            task_id,
+            state,
+        });
+    }
+
+    let unfiltered = filter.limit.is_none() && filter.since.is_none();
+    if unfiltered {
+        let orphans: BTreeSet<&String> = status_ids
+            .iter()
+            .chain(&response_ids)
+            .filter(|id| !listed.contains(*id))
+            .collect();
+        for task_id in orphans {
+            report(TaskEntry {
+                task_id: task_id.clone(),
+                file: None,
+                priority: None,
+                created: None,
+                state: TaskState::Orphaned,
+                errors: Vec::new(),
+                content_hash: None,
+            });
+        }
+    }
+    Ok(summary)
+}
+
+/// Order a [`list_tasks`] listing oldest first by Created.
+///
+/// Tasks without a valid Created come last, in id order, orphans among them.
+pub fn sort_by_created(entries: &mut [TaskEntry]) {
+    let created_at = |entry: &TaskEntry| {
+        let created = entry.created.as_deref()?;
+        chrono::DateTime::parse_from_rfc3339(created).ok()
+    };
+    // `None` sorts first, so order on whether Created is missing before it
+    entries.sort_by(|a, b| {
+        let (a_at, b_at) = (created_at(a), created_at(b));
+        (a_at.is_none(), a_at, &a.task_id).cmp(&(b_at.is_none(), b_at, &b.task_id))
+    });
+}
+
+/// Task ids of the `task-{id}{suffix}` files in `dir`, none if it is missing.
+fn ids_in(dir: &Path, suffix: &str) -> Result<BTreeSet<String>, McError> {
+    let mut ids = BTreeSet::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ids),
+        Err(e) => return Err(McError::io(dir.display(), e)),
+    };
+    for entry in entries {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        let id = name
+            .strip_prefix("task-")
+            .and_then(|rest| rest.strip_suffix(suffix));
+        if let Some(id) = id {
+            ids.insert(id.to_string());
+        }
+    }
+    Ok(ids)
+}
+
+/// How section headers are recognised and matched against the name asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Headers {
+    /// `## Name` exactly, as the protocol writes it.
+    Exact,
+    /// Any case, with or without the space after `##`, and the names in
+    /// `HEADER_ALIASES`: `##files changed` is a Files Modified section.
+    /// `### Summary` is still a subsection, not a Summary.
+    Tolerant,
+}
+
+/// Names agents use for a section, each group led by the canonical one.
+const HEADER_ALIASES: &[&[&str]] = &[
+    &["files modified", "files changed", "changed files"],
+    &["summary", "tl;dr", "tldr"],
+];
+
+impl Headers {
+    /// The header text of `line`, if it is a `##` header line.
+    fn of(self, line: &str) -> Option<&str> {
+        match self {
+            Headers::Exact => line.strip_prefix("## ").map(str::trim),
+            Headers::Tolerant => {
+                let rest = line.strip_prefix("##")?;
+                let header = rest.trim();
+                (!rest.starts_with('#') && !header.is_empty()).then_some(header)
+            }
+        }
+    }
+
+    /// Whether a header found in a file names the section `wanted`.
+    fn matches(self, found: &str, wanted: &str) -> bool {
+        match self {
+            Headers::Exact => found == wanted,
+            Headers::Tolerant => {
+                let normalize = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ");
+                let (found, wanted) = (
+                    normalize(found).to_lowercase(),
+                    normalize(wanted).to_lowercase(),
+                );
+                found == wanted
+                    || HEADER_ALIASES.iter().any(|names| {
+                        names.contains(&found.as_str()) && names.contains(&wanted.as_str())
+                    })
+            }
+        }
+    }
+}
+
+/// Extract the body of the first section headed `section`, such as `## Notes`.
+fn extract_section(content: &str, section: &str, headers: Headers) -> Option<String> {
+    let wanted = section.strip_prefix("## ").unwrap_or(section);
+    Sections::new(content, headers)
+        .find(|(found, _)| headers.matches(found, wanted))
+        .map(|(_, body)| body)
+        .filter(|body| !body.is_empty())
+}
+
+/// Whether `content` has the section, empty or not. Like [`extract_section`],
+/// only a whole header outside code blocks counts, so `## Summary of changes`
+/// in a pasted snippet is not a Summary.
+fn has_section(content: &str, section: &str, headers: Headers) -> bool {
+    let wanted = section.strip_prefix("## ").unwrap_or(section);
+    Sections::new(content, headers).any(|(found, _)| headers.matches(found, wanted))
+}
+
+/// A section's body as written, less the blank lines around it and trailing
+/// whitespace, for conversions that shouldn't touch it.
+fn section_verbatim(content: &str, header: &str) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut i = header_from(&lines, 0, Headers::Exact);
+    while i < lines.len() {
+        let start = i + 1;
+        let end = header_from(&lines, start, Headers::Exact);
+        if lines[i].strip_prefix("## ").map(str::trim) == Some(header) {
+            let body = &lines[start..end];
+            let first = body.iter().position(|line| !line.trim().is_empty())?;
+            return Some(body[first..].join("\n").trim_end().to_string());
+        }
+        i = end;
+    }
+    None
+}
+
+/// The `## ` sections of a file as (header, trimmed body) pairs, in order.
+///
+/// Text above the first header belongs to no section. A `## ` line inside a
+/// fenced code block is part of the block, not a header (see [`fence_end`]).
+/// Which lines are headers at all depends on `headers`.
+struct Sections<'a> {
+    lines: Vec<&'a str>,
+    next: usize,
+    headers: Headers,
+}
+
+impl<'a> Sections<'a> {
+    fn new(content: &'a str, headers: Headers) -> Self {
+        let lines: Vec<&str> = content.lines().collect();
+        let next = header_from(&lines, 0, headers);
+        Sections {
+            lines,
+            next,
+            headers,
+        }
+    }
+}
+
+impl<'a> Iterator for Sections<'a> {
+    type Item = (&'a str, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = self.headers.of(self.lines.get(self.next)?)?;
+        let start = self.next + 1;
+        self.next = header_from(&self.lines, start, self.headers);
+        let body = self.lines[start..self.next].join("\n").trim().to_string();
+        Some((header, body))
+    }
+}
+
+/// Index of the first header line at or after `start`, skipping code blocks,
+/// or `lines.len()`.
+fn header_from(lines: &[&str], start: usize, headers: Headers) -> usize {
+    let mut i = start;
+    while i < lines.len() {
+        if headers.of(lines[i]).is_some() {
+            return i;
+        }
+        i = match fence(lines[i]) {
+            Some(_) => i + 1 + fence_end(lines, i).1,
+            None => i + 1,
+        };
+    }
+    lines.len()
+}
+
+/// For the fence at `lines[open]`: the length of the block's content, and the
+/// lines from the content's start to the first line after the block.
+///
+/// A block is closed by a bare fence of at least as many backticks. It runs
+/// past a `## ` line only when that close looks like its own: no other fence
+/// as long sits in between and the fences after it pair up. Shorter fences
+/// are content, as in a ```` block quoting a ``` one. Otherwise the `## ` line
+/// is a real header the block didn't close before, and the block ends there,
+/// as it does at the end of the file.
+fn fence_end(lines: &[&str], open: usize) -> (usize, usize) {
+    let Some((ticks, _)) = fence(lines[open]) else {
+        return (0, 0);
+    };
+    let body = &lines[open + 1..];
+    let header = body.iter().position(|line| line.starts_with("## "));
+    let closed = body
+        .iter()
+        .position(|line| fence(line).is_some_and(|(n, rest)| n >= ticks && rest.is_empty()));
+    match (closed, header) {
+        (Some(end), Some(header)) if header < end => {
+            let fences_between = body[..end]
+                .iter()
+                .any(|line| fence(line).is_some_and(|(n, _)| n >= ticks));
+            let fences_after = body[end + 1..]
+                .iter()
+                .filter(|line| fence(line).is_some())
+                .count();
+            if fences_between || fences_after % 2 == 1 {
+                (header, header)
+            } else {
+                (end, end + 1)
+            }
+        }
+        (Some(end), _) => (end, end + 1),
+        (None, Some(header)) => (header, header),
+        (None, None) => (body.len(), body.len()),
+    }
+}
+
+/// Fenced code blocks in `content`.
+///
+/// Blocks end as [`fence_end`] describes; an unclosed block's trailing blank
+/// lines are dropped.
+fn extract_code_blocks(content: &str) -> Vec<CodeBlock> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let Some((_, info)) = fence(lines[i]) else {
+            i += 1;
+            continue;
+        };
+        let (len, next) = fence_end(&lines, i);
+        let body = &lines[i + 1..i + 1 + len];
+        let content = if next > len {
+            body.join("\n")
+        } else {
+            body.join("\n").trim_end().to_string()
+        };
+        blocks.push(CodeBlock {
+            language: info.split_whitespace().next().map(str::to_string),
+            content,
+        });
+        i += 1 + next;
+    }
+    blocks
+}
+
+/// Backtick count and info string of a fence line.
+pub(crate) fn fence(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim_start();
+    let ticks = trimmed.len() - trimmed.trim_start_matches('`').len();
+    (ticks >= 3).then(|| (ticks, trimmed[ticks..].trim()))
+}
+
+/// Extract a list of files from a section, and the lines that aren't files.
+///
+/// Bullet entries are always taken as files. Other lines must look like a
+/// path (see [`plausible_path`]) or they are returned as notes instead.
+fn extract_file_list(content: &str, section: &str, headers: Headers) -> (Vec<String>, Vec<String>) {
+    let section_content = match extract_section(content, section, headers) {
+        Some(c) => c,
+        None => return (Vec::new(), Vec::new()),
+    };
+
+    let mut files = Vec::new();
+    let mut notes = Vec::new();
+    for line in section_content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
+            let entry = trimmed[2..].trim().trim_end_matches(',').trim_end();
+            files.push(strip_quotes(entry, '`').unwrap_or(entry).to_string());
+        } else if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        } else if let Some(path) = plausible_path(trimmed) {
+            files.push(path.to_string());
+        } else {
+            notes.push(trimmed.to_string());
+        }
+    }
+    (files, notes)
+}
+
+/// The path on a non-bullet line, if the line looks like one.
+///
+/// It must have no spaces (unless quoted or backticked), contain a `/` or a
+/// file extension, and not end like a sentence.
+/// Parse a Files Modified entry such as `src/main.rs (new)`, `deleted: old.ts`,
+/// `M src/lib.rs` or `old.rs -> new.rs`.
+fn file_change(entry: &str, repo_root: &Path) -> FileChange {
+    let mut kind = None;
+    let mut rest = entry.trim();
+
+    // `deleted: old.ts`, `M src/lib.rs`
+    let prefix = rest
+        .split_once(':')
+        .filter(|(label, _)| !label.contains(['/', '\\']))
+        .or_else(|| {
+            rest.split_once(char::is_whitespace)
+                .filter(|(l, _)| l.len() == 1)
+        });
+    if let Some((label, path)) = prefix {
+        if let Some(found) = ChangeKind::from_word(label) {
+            kind = Some(found);
+            rest = path.trim();
+        }
+    }
+
+    // `src/main.rs (new)`
+    if let Some((path, note)) = rest.strip_suffix(')').and_then(|r| r.rsplit_once('(')) {
+        if let Some(found) = ChangeKind::from_word(note) {
+            kind = kind.or(Some(found));
+            rest = path.trim();
+        }
+    }
+
+    // Each side of a rename may be quoted on its own, leaving stray backticks
+    // once the entry's outer pair is gone
+    let clean = |path: &str| path.trim().trim_matches(['`', '"']).to_string();
+    let (path, old_path) = match ["->", "→", "=>"]
+        .iter()
+        .find_map(|arrow| rest.split_once(arrow))
+    {
+        Some((old, new)) => {
+            kind = kind.or(Some(ChangeKind::Renamed));
+            (clean(new), Some(clean(old)))
+        }
+        None => (clean(rest), None),
+    };
+
+    let (exists, outside_repo) = match inside_repo(Path::new(&path), repo_root) {
+        Some(full) => (fs::symlink_metadata(full).is_ok(), false),
+        None => (false, true),
+    };
+    FileChange {
+        path,
+        kind: kind.unwrap_or(ChangeKind::Unknown),
+        old_path,
+        exists,
+        outside_repo,
+    }
+}
+
+/// `path` resolved against `repo_root` without touching the filesystem, or
+/// `None` when it leads outside the root.
+fn inside_repo(path: &Path, repo_root: &Path) -> Option<PathBuf> {
+    use std::path::Component;
+
+    let root = std::path::absolute(repo_root).unwrap_or_else(|_| repo_root.to_path_buf());
+    let mut resolved = PathBuf::new();
+    for component in root.join(path).components() {
+        match component {
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir => {}
+            other => resolved.push(other),
+        }
+    }
+    let root: PathBuf = root
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect();
+    resolved.starts_with(&root).then_some(resolved)
+}
+
+fn plausible_path(line: &str) -> Option<&str> {
+    let (path, quoted) = match strip_quotes(line, '`').or_else(|| strip_quotes(line, '"')) {
+        Some(inner) => (inner.trim(), true),
+        None => (line, false),
+    };
+
+    if path.is_empty() || (!quoted && path.contains(char::is_whitespace)) {
+        return None;
+    }
+    if path.ends_with(['.', ',', ':', ';', '!', '?']) {
+        return None;
+    }
+    let has_extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.len() <= 10 && ext.chars().all(|c| c.is_ascii_alphanumeric()));
+    if path.contains('/') || has_extension {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// `text` without a surrounding pair of `quote` characters.
+fn strip_quotes(text: &str, quote: char) -> Option<&str> {
+    text.strip_prefix(quote)?.strip_suffix(quote)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_validate_task_valid() {
+        let temp_dir = TempDir::new().unwrap();
+        let task_path = temp_dir.path().join("task.md");
+
+        let content = r#"# Task: 001
+Created: 2026-01-22T10:00:00Z
+Priority: normal
+
+## Instructions
+
+Implement the login form.
+
+## Context
+
+This is the context.
+
+## Response Instructions
+
+Write response to .mission/responses/task-001.md
+"#;
+        fs::write(&task_path, content).unwrap();
+
+        let result = validate_task(task_path.to_str().unwrap()).unwrap();
+        assert!(result.valid, "Errors: {:?}", result.errors);
+        assert_eq!(result.task_id.as_deref(), Some("001"));
+        assert_eq!(result.priority, Some(Priority::Normal));
+        assert_eq!(result.created.as_deref(), Some("2026-01-22T10:00:00Z"));
+    }
+
+    #[test]
+    fn test_validate_task_checks_metadata_values() {
+        let temp_dir = TempDir::new().unwrap();
+        let task_path = temp_dir.path().join("task.md");
+        let file = task_path.to_str().unwrap();
+
+        fs::write(
+            &task_path,
+            "# Task: 002\nCreated: yesterday\nPriority: banana\n\n## Instructions\nDo it.\n\n## Response Instructions\nReply.\n",
+        )
+        .unwrap();
+        let result = validate_task(file).unwrap();
+        assert!(!result.valid);
+        assert_eq!(result.errors.len(), 2, "{:?}", result.errors);
+        assert!(result.errors[0].starts_with("Invalid RFC 3339 timestamp on line 2"));
+        assert!(result.errors[0].ends_with("'Created: yesterday'"));
+        assert_eq!(
+            result.errors[1],
+            "Invalid priority on line 3 (expected one of: normal, high, critical): 'Priority: banana'"
+        );
+        assert_eq!(result.task_id.as_deref(), Some("002"));
+        assert_eq!(result.priority, None);
+
+        // Any case, offsets and extra metadata lines are fine
+        fs::write(
+            &task_path,
+            "# Task: 003\nCreated: 2026-01-22T10:00:00+02:00\nPriority: HIGH\nOwner: infra\n\n## Instructions\nDo it.\n\n## Response Instructions\nReply.\n",
+        )
+        .unwrap();
+        let result = validate_task(file).unwrap();
+        assert!(result.valid, "{:?}", result.errors);
+        assert_eq!(result.priority, Some(Priority::High));
+    }
+
+    #[test]
+    fn test_validate_task_front_matter() {
+        let temp_dir = TempDir::new().unwrap();
+        let task_path = temp_dir.path().join("task.md");
+        let file = task_path.to_str().unwrap();
+
+        fs::write(
+            &task_path,
+            "---\nassignee: sam\nlabels: [auth, frontend]\npriority: critical\nparent: \"001\"\n---\n# Task: 004\nCreated: 2026-01-22T10:00:00Z\nPriority: normal\n\n## Instructions\nDo it.\n\n## Response Instructions\nReply.\n",
+        )
+        .unwrap();
+        let result = validate_task(file).unwrap();
+        assert!(result.valid, "{:?}", result.errors);
+        assert_eq!(result.task_id.as_deref(), Some("004"));
+        // Front matter wins over the inline line
+        assert_eq!(result.priority, Some(Priority::Critical));
+        assert_eq!(
+            Value::Object(result.metadata),
+            serde_json::json!({
+                "assignee": "sam",
+                "labels": ["auth", "frontend"],
+                "parent": "001",
+            })
+        );
+        let spec = parse_task(file, false).unwrap();
+        assert_eq!(spec.priority.as_deref(), Some("critical"));
+        assert_eq!(spec.metadata["assignee"], "sam");
+
+        // Errors are numbered by line of the file, front matter included
+        fs::write(
+            &task_path,
+            "---\ncreated: soon\n---\n# Task: 005\nPriority: banana\n\n## Instructions\nDo it.\n\n## Response Instructions\nReply.\n",
+        )
+        .unwrap();
+        let result = validate_task(file).unwrap();
+        assert_eq!(result.errors.len(), 2, "{:?}", result.errors);
+        assert!(result.errors[0].starts_with("Invalid RFC 3339 timestamp on line 2"));
+        assert!(result.errors[0].ends_with("'created: soon'"));
+        assert!(result.errors[1].starts_with("Invalid priority on line 5"));
+        assert!(result.metadata.is_empty());
+    }
+
+    #[test]
+    fn test_validate_task_malformed_front_matter() {
+        let temp_dir = TempDir::new().unwrap();
+        let task_path = temp_dir.path().join("task.md");
+        let file = task_path.to_str().unwrap();
+        let body = "# Task: 006\nCreated: 2026-01-22T10:00:00Z\nPriority: high\n\n## Instructions\nDo it.\n\n## Response Instructions\nReply.\n";
+
+        fs::write(
+            &task_path,
+            format!("---\nassignee: sam\nlabels: [auth\n---\n{}", body),
+        )
+        .unwrap();
+        let result = validate_task(file).unwrap();
+        assert!(!result.valid);
+        assert_eq!(result.errors.len(), 1);
+        assert!(
+            result.errors[0].starts_with("Invalid front matter YAML on line 3: "),
+            "{:?}",
+            result.errors
+        );
+    }
+
+    #[test]
+    fn test_leading_rule_is_not_front_matter() {
+        let temp_dir = TempDir::new().unwrap();
+        let task_path = temp_dir.path().join("task.md");
+        let file = task_path.to_str().unwrap();
+        let body = "# Task: 006\nCreated: 2026-01-22T10:00:00Z\nPriority: high\n\n## Instructions\nDo it.\n\n## Response Instructions\nReply.\n";
+
+        // An unclosed rule, a rule around prose, and a block that isn't a
+        // mapping all leave the file as plain markdown, header rule and all
+        for content in [
+            format!("---\n{}", body),
+            format!("---\nSome notes, and a [link.\n---\n{}", body),
+            format!("---\n- a list\n---\n{}", body),
+        ] {
+            fs::write(&task_path, content).unwrap();
+            let result = validate_task(file).unwrap();
+            assert_eq!(result.errors, vec!["Missing '# Task:' header"]);
+            assert!(result.metadata.is_empty());
+            let spec = parse_task(file, false).unwrap();
+            assert_eq!(spec.task_id.as_deref(), Some("006"));
+        }
+
+        let response_path = temp_dir.path().join("response.md");
+        fs::write(
+            &response_path,
+            "---\n# Response: 006\n\n## Summary\nDone.\n\n## Notes\nNone.\n",
+        )
+        .unwrap();
+        let response = parse_response(response_path.to_str().unwrap()).unwrap();
+        assert_eq!(response.summary.as_deref(), Some("Done."));
+        assert!(response.metadata.is_empty());
+    }
+
+    #[test]
+    fn test_parse_response_front_matter() {
+        let temp_dir = TempDir::new().unwrap();
+        let response_path = temp_dir.path().join("response.md");
+        let file = response_path.to_str().unwrap();
+
+        fs::write(
+            &response_path,
+            "---\nreviewer: kim\nconfidence: 0.8\ncompleted: 2026-01-22T10:30:00Z\n---\n# Response: 001\n\n## Summary\nDone.\n\n## Files Modified\n- src/lib.rs\n\n## Notes\nNone.\n",
+        )
+        .unwrap();
+        let result = parse_response(file).unwrap();
+        assert_eq!(result.summary.as_deref(), Some("Done."));
+        assert_eq!(result.files_modified, vec!["src/lib.rs"]);
+        assert_eq!(result.metadata["reviewer"], "kim");
+        assert_eq!(result.metadata["confidence"], 0.8);
+
+        // The front matter's completed stands in for the inline line
+        let validation = validate_response(file, Some("001")).unwrap();
+        assert!(validation.valid, "{:?}", validation.errors);
+        assert_eq!(validation.metadata.len(), 2);
+
+        fs::write(
+            &response_path,
+            "---\nreviewer: [kim\n---\n# Response: 001\n",
+        )
+        .unwrap();
+        let error = parse_response(file).unwrap_err();
+        assert!(
+            matches!(&error, McError::Parse(message) if message.contains("on line 2")),
+            "{:?}",
+            error
+        );
+        let validation = validate_response(file, None).unwrap();
+        assert!(validation.errors[0].starts_with("Invalid front matter YAML"));
+    }
+
+    #[test]
+    fn test_validate_task_missing_sections() {
+        let temp_dir = TempDir::new().unwrap();
+        let task_path = temp_dir.path().join("task.md");
+
+        fs::write(&task_path, "Some random content").unwrap();
+
+        let result = validate_task(task_path.to_str().unwrap()).unwrap();
+        assert!(!result.valid);
+        assert!(result.errors.len() >= 3);
+    }
+
+    const VALID_RESPONSE: &str = "# Response: 001
+Completed: 2026-01-22T10:30:00Z
+
+## Summary
+Added the login form.
+
+## Details
+Email and password fields with validation.
+
+## Files Modified
+- src/Login.tsx
+
+## Notes
+None.
+";
+
+    #[test]
+    fn test_validate_response_valid() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("task-001.md");
+        fs::write(&path, VALID_RESPONSE).unwrap();
+
+        let result = validate_response(path.to_str().unwrap(), Some("001")).unwrap();
+        assert!(result.valid);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+    }
+
+    #[test]
+    fn test_validate_response_wrong_task_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("task-002.md");
+        fs::write(&path, VALID_RESPONSE).unwrap();
+        let file = path.to_str().unwrap();
+
+        let result = validate_response(file, Some("002")).unwrap();
+        assert!(!result.valid);
+        assert_eq!(
+            result.errors,
+            vec!["Response is for task '001', expected '002'".to_string()]
+        );
+        // Without an expected id, any id will do
+        assert!(validate_response(file, None).unwrap().valid);
+    }
+
+    #[test]
+    fn test_validate_response_empty_sections() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("task-001.md");
+        fs::write(
+            &path,
+            "# Response: 001\n\n## Summary\n\n## Details\nSome.\n\n## Files Modified\n\n",
+        )
+        .unwrap();
+
+        let result = validate_response(path.to_str().unwrap(), None).unwrap();
+        assert!(!result.valid);
+        assert_eq!(
+            result.errors,
+            vec![
+                "Missing 'Completed:' timestamp".to_string(),
+                "Empty '## Summary' section".to_string(),
+            ]
+        );
+        assert_eq!(
+            result.warnings,
+            vec![
+                "No files listed under '## Files Modified'".to_string(),
+                "Missing '## Notes' section".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_response_code_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let response_path = temp_dir.path().join("response.md");
+        let content = "# Response: 001
+
+## Summary
+Fixed the off-by-one.
+
+## Details
+The loop bound was wrong:
+
+```diff
+-for i in 0..=n {
++for i in 0..n {
+```
+
+Checked with:
+
+```
+cargo test
+```
+
+## Notes
+Half a snippet:
+
+````rust
+fn main() {
+";
+        fs::write(&response_path, content).unwrap();
+
+        let parsed = parse_response(response_path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            parsed.code_blocks,
+            vec![
+                CodeBlock {
+                    language: Some("diff".to_string()),
+                    content: "-for i in 0..=n {\n+for i in 0..n {".to_string(),
+                },
+                CodeBlock {
+                    language: None,
+                    content: "cargo test".to_string(),
+                },
+                // Unterminated: runs to the end of the file
+                CodeBlock {
+                    language: Some("rust".to_string()),
+                    content: "fn main() {".to_string(),
+                },
+            ]
+        );
+        assert_eq!(parsed.diffs, vec!["-for i in 0..=n {\n+for i in 0..n {"]);
+        assert_eq!(
+            parsed.notes.as_deref(),
+            Some("Half a snippet:\n\n````rust\nfn main() {")
+        );
+    }
+
+    #[test]
+    fn test_unterminated_fence_ends_at_section() {
+        let blocks = extract_code_blocks("## Details\n```sh\nmake\n\n## Notes\n```\nlate\n```\n");
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].content, "make");
+        assert_eq!(blocks[1].content, "late");
+    }
+
+    #[test]
+    fn test_parse_response_sections() {
+        let temp_dir = TempDir::new().unwrap();
+        let response_path = temp_dir.path().join("response.md");
+
+        let content = r#"# Response: 004
+Completed: 2026-01-22T10:30:00Z
+
+## Summary
+
+Documented the headers.
+
+## Details
+
+The template now reads:
+
+```markdown
+## fake header
+
+Not a section.
+```
+
+## Test Results
+
+12 passed
+
+## Notes
+"#;
+        fs::write(&response_path, content).unwrap();
+
+        let result = parse_response(response_path.to_str().unwrap()).unwrap();
+        assert_eq!(result.schema_version, RESPONSE_SCHEMA_VERSION);
+        assert!(result.details.unwrap().ends_with("Not a section.\n```"));
+        assert_eq!(
+            result.sections.keys().collect::<Vec<_>>(),
+            vec!["Details", "Notes", "Summary", "Test Results"]
+        );
+        assert_eq!(result.sections["Test Results"], "12 passed");
+        assert_eq!(result.sections["Notes"], "");
+        assert_eq!(result.notes, None);
+        assert_eq!(result.code_blocks.len(), 1);
+        assert!(result.code_blocks[0].content.starts_with("## fake header"));
+    }
+
+    #[test]
+    fn test_parse_response_tolerant_headers() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("response.md");
+        let path = path.to_str().unwrap();
+        let parse = |summary: &str, files: &str, strict_headers: bool| {
+            let content = format!(
+                "# Response: 005\nCompleted: 2026-01-22T10:30:00Z\n\n{}\n\nFixed it.\n\n{}\n\n- src/lib.rs\n\n##notes\n\nNone.\n",
+                summary, files
+            );
+            fs::write(path, content).unwrap();
+            let options = ResponseOptions {
+                strict_headers,
+                ..Default::default()
+            };
+            parse_response_with(path, &options).unwrap()
+        };
+
+        for (summary, files) in [
+            ("## Summary", "## Files modified"),
+            ("##Summary", "## files changed"),
+            ("## SUMMARY", "## Changed Files"),
+            ("## TL;DR", "##  Files   Modified"),
+            ("## tldr", "## Files Changed"),
+        ] {
+            let result = parse(summary, files, false);
+            assert_eq!(result.summary.as_deref(), Some("Fixed it."), "{}", summary);
+            assert_eq!(result.files_modified, vec!["src/lib.rs"], "{}", files);
+            assert_eq!(result.notes.as_deref(), Some("None."));
+
+            let strict = parse(summary, files, true);
+            assert!(strict.files_modified.is_empty(), "{}", files);
+            assert_eq!(strict.notes, None);
+        }
+
+        // The sections map keeps headers as written
+        let result = parse("## TL;DR", "## files changed", false);
+        assert_eq!(
+            result.sections.keys().collect::<Vec<_>>(),
+            vec!["TL;DR", "files changed", "notes"]
+        );
+
+        // Exact headers still parse either way
+        let strict = parse("## Summary", "## Files Modified", true);
+        assert_eq!(strict.summary.as_deref(), Some("Fixed it."));
+        assert_eq!(strict.files_modified, vec!["src/lib.rs"]);
+    }
+
+    #[test]
+    fn test_tolerant_headers_keep_levels() {
+        let content = "## Details\n\nDid it.\n\n### Summary\n\nNot a summary.\n\n## Summary of changes\n\nNor this.\n";
+        assert_eq!(
+            extract_section(content, "## Summary", Headers::Tolerant),
+            None
+        );
+        assert!(!has_section(content, "## Summary", Headers::Tolerant));
+        // A subsection is part of the section above it
+        assert!(extract_section(content, "## Details", Headers::Tolerant)
+            .unwrap()
+            .contains("### Summary"));
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let temp_dir = TempDir::new().unwrap();
+        let response_path = temp_dir.path().join("response.md");
+
+        let content = r#"# Response: 001
+Completed: 2026-01-22T10:30:00Z
+
+## Summary
+
+Implemented the login form with validation.
+
+## Details
+
+Created a new React component with email and password fields.
+Added form validation and error handling.
+
+## Files Modified
+
+- src/components/LoginForm.tsx
+- `src/components/LoginForm.test.tsx`,
+- src/styles/login.css
+
+## Notes
+
+Consider adding rate limiting in the future.
+"#;
+        fs::write(&response_path, content).unwrap();
+
+        let result = parse_response(response_path.to_str().unwrap()).unwrap();
+
+        assert_eq!(
+            result.summary,
+            Some("Implemented the login form with validation.".to_string())
+        );
+        assert!(result.details.is_some());
+        assert_eq!(
+            result.files_modified,
+            vec![
+                "src/components/LoginForm.tsx",
+                "src/components/LoginForm.test.tsx",
+                "src/styles/login.css"
+            ]
+        );
+        assert!(result.files_modified_notes.is_empty());
+        assert!(result.notes.is_some());
+        assert!(
+            result.content_hash.starts_with("sha256:")
+                || result.content_hash.starts_with("blake3:")
+        );
+    }
+
+    #[test]
+    fn test_parse_response_prose_files_modified() {
+        let temp_dir = TempDir::new().unwrap();
+        let response_path = temp_dir.path().join("response.md");
+
+        let content = r#"# Response: 002
+
+## Summary
+
+Cleaned up the helpers.
+
+## Files Modified
+
+I also refactored the helpers in utils.rs and added tests.
+src/utils.rs
+`docs/helper notes.md`
+Cargo.toml
+See the summary above:
+README
+Everything compiles.
+
+## Notes
+
+None.
+"#;
+        fs::write(&response_path, content).unwrap();
+
+        let result = parse_response(response_path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            result.files_modified,
+            vec!["src/utils.rs", "docs/helper notes.md", "Cargo.toml"]
+        );
+        assert_eq!(
+            result.files_modified_notes,
+            vec![
+                "I also refactored the helpers in utils.rs and added tests.",
+                "See the summary above:",
+                "README",
+                "Everything compiles."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_response_file_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("repo");
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::write(repo.join("src/main.rs"), "").unwrap();
+        fs::write(repo.join("src/lib.rs"), "").unwrap();
+        fs::write(repo.join("src/new.rs"), "").unwrap();
+        fs::write(temp_dir.path().join("secret.txt"), "").unwrap();
+        let response_path = temp_dir.path().join("response.md");
+
+        let content = r#"# Response: 003
+
+## Files Modified
+
+- src/main.rs (new)
+- deleted: src/old.ts
+- M src/lib.rs
+- `src/legacy.rs` -> `src/new.rs`
+- README.md
+- ../secret.txt
+- /etc/passwd
+"#;
+        fs::write(&response_path, content).unwrap();
+
+        let result = parse_response_in(response_path.to_str().unwrap(), &repo).unwrap();
+        let changes: Vec<_> = result
+            .file_changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.kind, c.exists, c.outside_repo))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("src/main.rs", ChangeKind::Added, true, false),
+                ("src/old.ts", ChangeKind::Deleted, false, false),
+                ("src/lib.rs", ChangeKind::Modified, true, false),
+                ("src/new.rs", ChangeKind::Renamed, true, false),
+                ("README.md", ChangeKind::Unknown, false, false),
+                ("../secret.txt", ChangeKind::Unknown, false, true),
+                ("/etc/passwd", ChangeKind::Unknown, false, true),
+            ]
+        );
+        assert_eq!(
+            result.file_changes[3].old_path.as_deref(),
+            Some("src/legacy.rs")
+        );
+        // The raw entries stay as they were for older readers
+        assert_eq!(result.files_modified[0], "src/main.rs (new)");
+
+        let json = serde_json::to_value(&result.file_changes[0]).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"path": "src/main.rs", "kind": "added", "exists": true})
+        );
+    }
+
+    #[test]
+    fn test_parse_response_hash_ignores_line_endings() {
+        let temp_dir = TempDir::new().unwrap();
+        let lf_path = temp_dir.path().join("lf.md");
+        let crlf_path = temp_dir.path().join("crlf.md");
+
+        let content = "# Response: 001\n\n## Summary\n\nDone.\n";
+        fs::write(&lf_path, content).unwrap();
+        fs::write(&crlf_path, content.replace('\n', "\r\n")).unwrap();
+
+        let lf = parse_response(lf_path.to_str().unwrap()).unwrap();
+        let crlf = parse_response(crlf_path.to_str().unwrap()).unwrap();
+        assert_eq!(lf.content_hash, crlf.content_hash);
+    }
+
+    #[test]
+    fn test_response_unchanged() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("response.md");
+        fs::write(&path, "## Summary\n\nDone.\n").unwrap();
+        let file = path.to_str().unwrap();
+
+        let hash = parse_response(file).unwrap().content_hash;
+        assert_eq!(
+            response_unchanged(file, Some(&hash)).unwrap(),
+            Some(hash.clone())
+        );
+        assert_eq!(response_unchanged(file, None).unwrap(), None);
+
+        fs::write(&path, "## Summary\n\nDone, with edits.\n").unwrap();
+        assert_eq!(response_unchanged(file, Some(&hash)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_create_then_parse_task_with_attachment() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().join(".mission");
+        let design = temp_dir.path().join("design.md");
+        fs::write(&design, "# Design\n\nThe big picture, at length.\n").unwrap();
+
+        let path = create_task(
+            &mission_dir,
+            &NewTask {
+                task_id: "007".to_string(),
+                priority: "high".to_string(),
+                instructions: "Implement the design.".to_string(),
+                context: Some("See attached.".to_string()),
+                attachments: vec![design.clone()],
+                force: false,
+            },
+        )
+        .unwrap();
+        let file = path.to_str().unwrap();
+        assert!(validate_task(file).unwrap().valid);
+
+        let task = parse_task(file, false).unwrap();
+        assert_eq!(task.task_id.as_deref(), Some("007"));
+        assert_eq!(task.priority.as_deref(), Some("high"));
+        assert_eq!(task.attachments.len(), 1);
+        let attachment = &task.attachments[0];
+        assert!(attachment.exists);
+        assert_eq!(attachment.bytes, fs::metadata(&design).unwrap().len());
+        assert!(attachment.tokens > 0);
+        assert!(attachment.content.is_none());
+        assert_eq!(task.attachment_tokens(), attachment.tokens);
+
+        let inlined = parse_task(file, true).unwrap();
+        assert_eq!(
+            inlined.attachments[0].content.as_deref(),
+            Some("# Design\n\nThe big picture, at length.\n")
+        );
+
+        assert!(create_task(
+            &mission_dir,
+            &NewTask {
+                task_id: "007".to_string(),
+                priority: "normal".to_string(),
+                ..Default::default()
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_created_task_round_trips_through_validate() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().join(".mission");
+        let mut task = NewTask {
+            task_id: "009".to_string(),
+            priority: "critical".to_string(),
+            instructions: "Roll back the release.\n\n- Revert the tag\n".to_string(),
+            context: Some("Error rates doubled after deploy.".to_string()),
+            ..Default::default()
+        };
+
+        let path = create_task(&mission_dir, &task).unwrap();
+        let result = validate_task(path.to_str().unwrap()).unwrap();
+        assert!(result.valid);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let spec = parse_task(path.to_str().unwrap(), false).unwrap();
+        let created = spec.created.unwrap();
+        assert!(
+            chrono::DateTime::parse_from_rfc3339(&created).is_ok(),
+            "{}",
+            created
+        );
+        assert_eq!(
+            spec.instructions.as_deref(),
+            Some("Roll back the release.\n\n- Revert the tag")
+        );
+
+        // Overwritten only when forced
+        task.instructions = "Roll forward instead.".to_string();
+        assert!(create_task(&mission_dir, &task).is_err());
+        task.force = true;
+        create_task(&mission_dir, &task).unwrap();
+        let spec = parse_task(path.to_str().unwrap(), false).unwrap();
+        assert_eq!(spec.instructions.as_deref(), Some("Roll forward instead."));
+    }
+
+    #[test]
+    fn test_create_task_rejects_unknown_priority() {
+        let temp_dir = TempDir::new().unwrap();
+        let task = NewTask {
+            task_id: "010".to_string(),
+            priority: "urgent".to_string(),
+            instructions: "Anything.".to_string(),
+            ..Default::default()
+        };
+        let error = create_task(temp_dir.path(), &task).unwrap_err();
+        assert!(matches!(error, McError::Validation(_)));
+        assert!(!temp_dir.path().join("tasks").exists());
+    }
+
+    #[test]
+    fn test_validate_json_task() {
+        let temp_dir = TempDir::new().unwrap();
+        let task_path = temp_dir.path().join("task-001.json");
+        fs::write(
+            &task_path,
+            r#"{"id":"001","priority":"high","instructions":"Ship it.","context":"Friday.","owner":"ops"}"#,
+        )
+        .unwrap();
+
+        let result = validate_task(task_path.to_str().unwrap()).unwrap();
+        assert!(result.valid, "Errors: {:?}", result.errors);
+        assert_eq!(result.task_id.as_deref(), Some("001"));
+        assert_eq!(result.priority, Some(Priority::High));
+        assert_eq!(result.warnings, vec!["Unknown field 'owner'"]);
+
+        // Sniffed from the content whatever the extension
+        let task_path = temp_dir.path().join("task-002.md");
+        fs::write(
+            &task_path,
+            r#"  {"id":"002","priority":"urgent","created":"today","instructions":7}"#,
+        )
+        .unwrap();
+        let result = validate_task(task_path.to_str().unwrap()).unwrap();
+        assert!(!result.valid);
+        assert_eq!(
+            result.errors,
+            vec![
+                "'instructions' must be a string",
+                "Invalid RFC 3339 timestamp in 'created' (premature end of input): 'today'",
+                "Invalid priority (expected one of: normal, high, critical): 'urgent'",
+            ]
+        );
+
+        fs::write(&task_path, "{\"id\": \"002\",").unwrap();
+        let result = validate_task(task_path.to_str().unwrap()).unwrap();
+        assert!(
+            result.errors[0].starts_with("Invalid JSON: "),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_markdown_task_round_trips_through_json() {
+        let markdown = r#"# Task: 011
+Created: 2026-01-22T10:00:00Z
+Priority: critical
+Depends-On: 009, 010
+
+## Instructions
+    indented first line
+
+1. Fix the parser
+   - keep the fence below intact
+
+```rust
+## not a header
+fn main() {}
+```
+
+## Context
+See @attach sha256:abc/notes.txt
+
+## Response Instructions
+Write response to demo/responses/task-011.md
+"#;
+        let task = markdown_to_task(markdown).unwrap();
+        assert_eq!(task.depends_on, vec!["009", "010"]);
+        assert!(task.instructions.starts_with("    indented first line"));
+        assert!(task.instructions.ends_with("fn main() {}\n```"));
+
+        let json = serde_json::to_string(&task).unwrap();
+        let back: JsonTask = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, task);
+        assert_eq!(task_to_markdown(&back).trim_end(), markdown.trim_end());
+
+        // Optional sections stay out rather than turning up empty
+        let task = JsonTask {
+            id: "012".to_string(),
+            priority: "normal".to_string(),
+            instructions: "Just this.".to_string(),
+            ..Default::default()
+        };
+        let markdown = task_to_markdown(&task);
+        assert_eq!(
+            markdown,
+            "# Task: 012\nPriority: normal\n\n## Instructions\nJust this.\n"
+        );
+        assert_eq!(markdown_to_task(&markdown).unwrap(), task);
+
+        let error = markdown_to_task("# Task: 013\nPriority: high\n").unwrap_err();
+        assert!(matches!(error, McError::Validation(_)));
+    }
+
+    /// A flat-layout task `task_id` with `metadata` lines after Priority.
+    fn write_task_with(mission_dir: &Path, task_id: &str, metadata: &str) {
+        fs::create_dir_all(mission_dir.join("tasks")).unwrap();
+        let content = format!(
+            "# Task: {id}\nCreated: 2026-01-22T10:00:00Z\nPriority: normal\n{metadata}\n\n\
+             ## Instructions\nDo it.\n\n## Response Instructions\nReply.\n",
+            id = task_id,
+        );
+        fs::write(
+            mission_dir.join(format!("tasks/task-{}.md", task_id)),
+            content,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_check_ready() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path();
+        write_task_with(mission_dir, "001", "");
+        write_task_with(mission_dir, "002", "Depends-On: 001");
+        fs::write(
+            mission_dir.join("tasks/task-003.md"),
+            "---\ndepends_on: [\"001\", \"002\"]\n---\n# Task: 003\nCreated: 2026-01-22T10:00:00Z\nPriority: high\nDepends-On: 009\n\n## Instructions\nDo it.\n\n## Response Instructions\nReply.\n",
+        )
+        .unwrap();
+
+        let result =
+            validate_task(mission_dir.join("tasks/task-003.md").to_str().unwrap()).unwrap();
+        assert!(result.valid, "{:?}", result.errors);
+        // Front matter wins over the inline line
+        assert_eq!(result.depends_on, vec!["001", "002"]);
+        assert!(result.metadata.is_empty());
+
+        let ready = check_ready(mission_dir, "001").unwrap();
+        assert!(ready.ready);
+        assert!(ready.depends_on.is_empty());
+
+        let ready = check_ready(mission_dir, "003").unwrap();
+        assert!(!ready.ready);
+        assert_eq!(ready.missing, vec!["001", "002"]);
+
+        fs::create_dir_all(mission_dir.join("status")).unwrap();
+        fs::write(mission_dir.join("status/task-001.status"), "COMPLETE").unwrap();
+        assert!(check_ready(mission_dir, "002").unwrap().ready);
+        assert_eq!(
+            check_ready(mission_dir, "003").unwrap().missing,
+            vec!["002"]
+        );
+        assert!(check_ready(mission_dir, "003").unwrap().warnings.is_empty());
+
+        let error = check_ready(mission_dir, "404").unwrap_err();
+        assert!(matches!(error, McError::Io(_)));
+    }
+
+    #[test]
+    fn test_dependency_cycles_are_warnings() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path();
+        write_task_with(mission_dir, "a", "Depends-On: c");
+        write_task_with(mission_dir, "b", "Depends-On: a");
+        write_task_with(mission_dir, "c", "Depends-On: b, d");
+        write_task_with(mission_dir, "d", "");
+        write_task_with(mission_dir, "e", "Depends-On: e");
+
+        let summary = validate_all(mission_dir, &TaskFilter::default(), |_| {}).unwrap();
+        assert_eq!(
+            summary.warnings,
+            vec![
+                "Dependency cycle: a -> c -> b -> a",
+                "Dependency cycle: e -> e",
+            ]
+        );
+        // A cycle doesn't make the tasks in it invalid, depending on itself does
+        assert_eq!(summary.invalid, 1);
+        let result = validate_task(mission_dir.join("tasks/task-e.md").to_str().unwrap()).unwrap();
+        assert_eq!(result.errors, vec!["Task 'e' depends on itself"]);
+
+        let ready = check_ready(mission_dir, "b").unwrap();
+        assert_eq!(ready.warnings, vec!["Dependency cycle: a -> c -> b -> a"]);
+        assert!(check_ready(mission_dir, "d").unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn test_dependency_cycles_across_formats() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path();
+        write_task_with(mission_dir, "a", "Depends-On: c");
+        fs::write(
+            mission_dir.join("tasks/task-b.json"),
+            r#"{"id":"b","priority":"normal","instructions":"Do it.","depends_on":["a"]}"#,
+        )
+        .unwrap();
+        fs::create_dir_all(mission_dir.join("tasks/c")).unwrap();
+        fs::write(
+            mission_dir.join("tasks/c/task.md"),
+            "# Task: c\nCreated: 2026-01-22T10:00:00Z\nPriority: normal\nDepends-On: b\n\n\
+             ## Instructions\nDo it.\n\n## Response Instructions\nReply.\n",
+        )
+        .unwrap();
+
+        let files = task_files(mission_dir, &TaskFilter::default()).unwrap();
+        let ids: Vec<String> = files.iter().map(|path| task_id(path)).collect();
+        assert_eq!(ids, ["c", "a", "b"]);
+
+        let summary = validate_all(mission_dir, &TaskFilter::default(), |_| {}).unwrap();
+        assert_eq!(summary.files, 3);
+        assert_eq!(summary.warnings, vec!["Dependency cycle: a -> c -> b -> a"]);
+        for id in ["a", "b", "c"] {
+            let ready = check_ready(mission_dir, id).unwrap();
+            assert_eq!(ready.warnings, vec!["Dependency cycle: a -> c -> b -> a"]);
+        }
+    }
+
+    #[test]
+    fn test_convert_task_between_formats() {
+        let temp_dir = TempDir::new().unwrap();
+        let markdown_path = temp_dir.path().join("task-014.md");
+        let markdown = "# Task: 014\nCreated: 2026-01-22T10:00:00Z\nPriority: high\n\n## Instructions\nDo it.\n\n## Response Instructions\nReply.\n";
+        fs::write(&markdown_path, markdown).unwrap();
+
+        let converted = convert_task(&markdown_path, None, None, false).unwrap();
+        assert_eq!(converted.format, TaskFormat::Json);
+        assert_eq!(converted.output, temp_dir.path().join("task-014.json"));
+        let json_task = validate_task(converted.output.to_str().unwrap()).unwrap();
+        assert!(json_task.valid, "{:?}", json_task.errors);
+        let spec = parse_task(converted.output.to_str().unwrap(), false).unwrap();
+        assert_eq!(spec.response_instructions.as_deref(), Some("Reply."));
+
+        // Back again, over the original only when forced
+        let error = convert_task(&converted.output, None, None, false).unwrap_err();
+        assert!(matches!(error, McError::Io(_)));
+        let back = convert_task(&converted.output, None, None, true).unwrap();
+        assert_eq!(back.output, markdown_path);
+        assert_eq!(fs::read_to_string(&markdown_path).unwrap(), markdown);
+    }
+
+    #[test]
+    fn test_parse_json_response() {
+        let temp_dir = TempDir::new().unwrap();
+        let response_path = temp_dir.path().join("task-001.json");
+        let content = r#"{
+  "id": "001",
+  "completed": "2026-01-22T10:30:00Z",
+  "summary": "Implemented the login form.",
+  "details": "```diff\n+form\n```",
+  "files_modified": ["src/components/LoginForm.tsx (new)"]
+}"#;
+        fs::write(&response_path, content).unwrap();
+
+        let result = parse_response(response_path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            result.summary.as_deref(),
+            Some("Implemented the login form.")
+        );
+        assert_eq!(result.file_changes[0].path, "src/components/LoginForm.tsx");
+        assert_eq!(result.file_changes[0].kind, ChangeKind::Added);
+        assert_eq!(result.diffs, vec!["+form"]);
+        assert_eq!(result.notes, None);
+        assert_eq!(result.content_hash, hash::content_hash(content));
+
+        let validation = validate_response(response_path.to_str().unwrap(), Some("001")).unwrap();
+        assert!(validation.valid, "{:?}", validation.errors);
+        assert_eq!(validation.warnings, vec!["Missing '## Notes' section"]);
+
+        fs::write(&response_path, r#"{"summary": "No id"}"#).unwrap();
+        let error = parse_response(response_path.to_str().unwrap()).unwrap_err();
+        assert!(matches!(error, McError::Parse(_)));
+        let validation = validate_response(response_path.to_str().unwrap(), None).unwrap();
+        assert!(validation.errors[0].starts_with("Invalid JSON: "));
+    }
+
+    #[test]
+    fn test_validate_task_attachment_hash_mismatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().join(".mission");
+        let log = temp_dir.path().join("build.log");
+        fs::write(&log, "error: linker failed\n").unwrap();
+
+        let path = create_task(
+            &mission_dir,
+            &NewTask {
+                task_id: "008".to_string(),
+                priority: "normal".to_string(),
+                instructions: "Fix the build.".to_string(),
+                context: None,
+                attachments: vec![log],
+                force: false,
+            },
+        )
+        .unwrap();
+        let file = path.to_str().unwrap();
+
+        let reference = parse_task(file, false).unwrap().attachments[0].path.clone();
+        fs::write(temp_dir.path().join(&reference), "edited after the fact").unwrap();
+
+        let result = validate_task(file).unwrap();
+        assert!(!result.valid);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("hash mismatch"));
+    }
+
+    #[test]
+    fn test_validate_task_attachment_outside_mission() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().join(".mission");
+        fs::create_dir_all(mission_dir.join("attachments")).unwrap();
+        fs::write(temp_dir.path().join("secret.md"), "not for agents").unwrap();
+
+        let path = create_task(
+            &mission_dir,
+            &NewTask {
+                task_id: "009".to_string(),
+                priority: "normal".to_string(),
+                instructions: "Read the secret.".to_string(),
+                context: Some("@attach .mission/attachments/../../secret.md".to_string()),
+                ..NewTask::default()
+            },
+        )
+        .unwrap();
+        let file = path.to_str().unwrap();
+
+        let result = validate_task(file).unwrap();
+        assert!(!result.valid);
+        assert!(result.errors[0].contains("outside"));
+
+        let task = parse_task(file, true).unwrap();
+        assert!(!task.attachments[0].exists);
+        assert!(task.attachments[0].content.is_none());
+    }
+
+    const CONTEXT_HEAVY_TASK: &str = "# Task: 042
+Created: 2026-01-22T10:00:00Z
+Priority: normal
+
+## Instructions
+- Read the design notes.
+- Update the parser.
+- Add tests.
+
+## Context
+The parser was written for the flat layout and assumes one file per task.
+
+Since then the nested layout landed and every task has its own directory, which the parser never learned about. Several call sites now build paths by hand and they disagree on where the status file lives.
+
+Historical note: the original layout came from a prototype and was never documented; the orchestrator grew around it and now depends on the exact file names in a few places that are hard to find.
+
+## Response Instructions
+Write response to .mission/responses/task-042.md
+";
+
+    #[test]
+    fn test_analyze_task_budget_context_dominates() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("task-042.md");
+        fs::write(&path, CONTEXT_HEAVY_TASK).unwrap();
+
+        let task = parse_task(path.to_str().unwrap(), false).unwrap();
+        let counter = TokenCounter::new();
+        let analysis = analyze_task_budget(&task, 100, &counter);
+
+        assert!(analysis.over_budget);
+        let context = analysis
+            .sections
+            .iter()
+            .find(|s| s.section == "context")
+            .unwrap();
+        assert!(context.over_share);
+        let instructions = analysis
+            .sections
+            .iter()
+            .find(|s| s.section == "instructions")
+            .unwrap();
+        assert!(!instructions.over_share);
+
+        let body = task.context.as_deref().unwrap();
+        let context_splits: Vec<&SplitPoint> = analysis
+            .split_points
+            .iter()
+            .filter(|p| p.section == "context")
+            .collect();
+        assert_eq!(context_splits.len(), 2);
+        for point in &context_splits {
+            assert!(body[..point.offset].ends_with("\n\n"));
+            assert!(point.tokens_saved > 0);
+        }
+        assert!(body[context_splits[0].offset..].starts_with("Since then"));
+        assert!(context_splits[0].tokens_saved > context_splits[1].tokens_saved);
+
+        let list_splits = analysis
+            .split_points
+            .iter()
+            .filter(|p| p.section == "instructions")
+            .count();
+        assert_eq!(list_splits, 2);
+
+        // Analysis is read-only
+        assert_eq!(fs::read_to_string(&path).unwrap(), CONTEXT_HEAVY_TASK);
+    }
+
+    const VALID_TASK: &str = "# Task: {id}\nCreated: 2026-01-22T10:00:00Z\nPriority: normal\n\n## Instructions\nDo it.\n\n## Response Instructions\nReply.\n";
+
+    /// A mission with `count` tasks; every tenth is malformed and every third complete.
+    fn mission_with_tasks(count: usize) -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let tasks_dir = temp_dir.path().join("tasks");
+        let status_dir = temp_dir.path().join("status");
+        let responses_dir = temp_dir.path().join("responses");
+        fs::create_dir_all(&tasks_dir).unwrap();
+        fs::create_dir_all(&status_dir).unwrap();
+        fs::create_dir_all(&responses_dir).unwrap();
+
+        for i in 0..count {
+            let id = format!("{:04}", i);
+            let content = if i % 10 == 0 {
+                format!("# Task: {}\n", id)
+            } else {
+                VALID_TASK.replace("{id}", &id)
+            };
+            fs::write(tasks_dir.join(format!("task-{}.md", id)), content).unwrap();
+            if i % 3 == 0 {
+                fs::write(status_dir.join(format!("task-{}.status", id)), "DONE").unwrap();
+                fs::write(responses_dir.join(format!("task-{}.md", id)), "# Response").unwrap();
+            }
+        }
+        temp_dir
+    }
+
+    #[test]
+    fn test_validate_all_streams_in_path_order() {
+        let mission = mission_with_tasks(300);
+
+        let mut files = Vec::new();
+        let summary = validate_all(mission.path(), &TaskFilter::default(), |record| {
+            files.push(record.file.clone());
+        })
+        .unwrap();
+
+        assert_eq!(files.len(), 300);
+        assert!(files.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(summary.files, 300);
+        assert_eq!(summary.invalid, 30);
+        assert_eq!(summary.valid, 270);
+    }
+
+    #[test]
+    fn test_list_tasks_summary_and_limit() {
+        let mission = mission_with_tasks(300);
+
+        let mut entries = Vec::new();
+        let summary = list_tasks(mission.path(), &TaskFilter::default(), |entry| {
+            entries.push(entry.clone());
+        })
+        .unwrap();
+        let ids = |entries: &[TaskEntry]| -> Vec<String> {
+            entries.iter().map(|entry| entry.task_id.clone()).collect()
+        };
+        let streamed = ids(&entries);
+        assert!(streamed.windows(2).all(|pair| pair[0] < pair[1]));
+        // All valid tasks share a Created; the malformed ones, without one, go last
+        sort_by_created(&mut entries);
+        let sorted = ids(&entries);
+        assert_eq!(sorted.first().map(String::as_str), Some("0001"));
+        assert_eq!(sorted.last().map(String::as_str), Some("0290"));
+        assert_eq!(summary.tasks, 300);
+        assert_eq!(summary.complete, 90);
+        assert_eq!(summary.pending, 180);
+        assert_eq!(summary.invalid, 30);
+
+        let filter = TaskFilter {
+            limit: Some(25),
+            since: None,
+        };
+        let summary = list_tasks(mission.path(), &filter, |_| {}).unwrap();
+        assert_eq!(summary.tasks, 25);
+    }
+
+    #[test]
+    fn test_list_tasks_states() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission = temp_dir.path();
+        for dir in ["tasks", "status", "responses"] {
+            fs::create_dir_all(mission.join(dir)).unwrap();
+        }
+        let task = |id: &str, created: &str| {
+            let content = VALID_TASK
+                .replace("{id}", id)
+                .replace("2026-01-22T10:00:00Z", created);
+            fs::write(mission.join(format!("tasks/task-{}.md", id)), content).unwrap();
+        };
+        let touch = |path: &str| fs::write(mission.join(path), "x").unwrap();
+
+        task("done", "2026-01-22T09:00:00Z");
+        touch("status/task-done.status");
+        touch("responses/task-done.md");
+        task("claimed", "2026-01-22T10:00:00+02:00");
+        touch("status/task-claimed.claimed");
+        task("writing", "2026-01-22T11:00:00Z");
+        touch("responses/task-writing.md");
+        task("new", "2026-01-22T07:00:00Z");
+        fs::write(mission.join("tasks/task-bad.md"), "# Task: bad\n").unwrap();
+        touch("status/task-gone.status");
+
+        let mut entries = Vec::new();
+        let summary = list_tasks(mission, &TaskFilter::default(), |entry| {
+            entries.push(entry.clone());
+        })
+        .unwrap();
+
+        let listed = |entries: &[TaskEntry]| -> Vec<(String, TaskState)> {
+            entries
+                .iter()
+                .map(|entry| (entry.task_id.clone(), entry.state))
+                .collect()
+        };
+        let expected = |order: [(&str, TaskState); 6]| -> Vec<(String, TaskState)> {
+            order
+                .iter()
+                .map(|(id, state)| (id.to_string(), *state))
+                .collect()
+        };
+        // Reported in path order as the files are read, orphans last
+        assert_eq!(
+            listed(&entries),
+            expected([
+                ("bad", TaskState::Invalid),
+                ("claimed", TaskState::InProgress),
+                ("done", TaskState::Complete),
+                ("new", TaskState::Pending),
+                ("writing", TaskState::InProgress),
+                ("gone", TaskState::Orphaned),
+            ])
+        );
+        assert!(
+            !entries[0].errors.is_empty(),
+            "invalid tasks carry their errors"
+        );
+
+        sort_by_created(&mut entries);
+        assert_eq!(
+            listed(&entries),
+            expected([
+                ("new", TaskState::Pending),
+                ("claimed", TaskState::InProgress),
+                ("done", TaskState::Complete),
+                ("writing", TaskState::InProgress),
+                ("bad", TaskState::Invalid),
+                ("gone", TaskState::Orphaned),
+            ])
+        );
+        assert_eq!(summary.tasks, 6);
+        assert_eq!(summary.in_progress, 2);
+        assert_eq!(summary.orphaned, 1);
+
+        // Orphans can't be told apart from filtered-out tasks
+        let filter = TaskFilter {
+            limit: Some(1),
+            since: None,
+        };
+        let summary = list_tasks(mission, &filter, |_| {}).unwrap();
+        assert_eq!(summary.orphaned, 0);
+    }
+
+    #[test]
+    fn test_layout_paths() {
+        let mission = Path::new("/m/.mission");
+        let flat = Layout::Flat.paths(mission, "001");
+        assert_eq!(flat.task, mission.join("tasks/task-001.md"));
+        assert_eq!(flat.status, mission.join("status/task-001.status"));
+        assert_eq!(flat.response, mission.join("responses/task-001.md"));
+
+        let nested = Layout::Nested.paths(mission, "001");
+        assert_eq!(nested.task, mission.join("tasks/001/task.md"));
+        assert_eq!(nested.status, mission.join("tasks/001/status"));
+        assert_eq!(nested.response, mission.join("tasks/001/response.md"));
+
+        assert_eq!(mission_dir_of(&flat.task), mission);
+        assert_eq!(mission_dir_of(&nested.task), mission);
+    }
+
+    #[test]
+    fn test_validate_nested_task_with_attachment() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission = temp_dir.path().join(".mission");
+        let source = temp_dir.path().join("notes.txt");
+        fs::write(&source, "context").unwrap();
+        let flat = create_task(
+            &mission,
+            &NewTask {
+                task_id: "001".to_string(),
+                priority: "normal".to_string(),
+                instructions: "Do it.".to_string(),
+                attachments: vec![source],
+                ..NewTask::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(Layout::detect(&mission, "001"), Layout::Flat);
+        let nested = Layout::Nested.paths(&mission, "001").task;
+        fs::create_dir_all(nested.parent().unwrap()).unwrap();
+        fs::rename(&flat, &nested).unwrap();
+        assert_eq!(Layout::detect(&mission, "001"), Layout::Nested);
+
+        // Attachments still resolve from the mission dir two levels up
+        let result = validate_task(&nested.to_string_lossy()).unwrap();
+        assert!(result.valid, "{:?}", result.errors);
+        assert_eq!(result.task_id.as_deref(), Some("001"));
+    }
+
+    #[test]
+    fn test_task_filter_since() {
+        let mission = mission_with_tasks(200);
+        let now = SystemTime::now();
+        let old = now - std::time::Duration::from_secs(7 * 24 * 3600);
+        for path in task_files(mission.path(), &TaskFilter::default()).unwrap() {
+            if task_id(&path).as_str() < "0150" {
+                fs::File::options()
+                    .write(true)
+                    .open(&path)
+                    .unwrap()
+                    .set_modified(old)
+                    .unwrap();
+            }
+        }
+
+        let filter = TaskFilter {
+            limit: None,
+            since: Some(now - std::time::Duration::from_secs(3600)),
+        };
+        let files = task_files(mission.path(), &filter).unwrap();
+        assert_eq!(files.len(), 50);
+        assert_eq!(task_id(&files[0]), "0150");
+    }
+
+    #[test]
+    fn test_extract_section() {
+        let content = r#"## Summary
+
+This is the summary.
+
+## Details
+
+These are the details.
+"#;
+        let summary = extract_section(content, "## Summary", Headers::Exact);
+        assert_eq!(summary, Some("This is the summary.".to_string()));
+
+        let details = extract_section(content, "## Details", Headers::Exact);
+        assert_eq!(details, Some("These are the details.".to_string()));
+    }
+
+    #[test]
+    fn test_extract_section_skips_headers_in_code_blocks() {
+        let content = r#"## Details
+
+Here is the changelog entry I added:
+
+```markdown
+## Summary of changes
+
+- Fixed the parser
+```
+
+And the template, in a longer fence:
+
+````md
+## Summary
+```
+## Notes
+```
+````
+
+## Notes
+
+Nothing else.
+"#;
+        let details = extract_section(content, "## Details", Headers::Exact).unwrap();
+        assert!(details.starts_with("Here is the changelog"));
+        assert!(details.ends_with("````"), "{}", details);
+        assert!(details.contains(
+            "## Summary of changes
+
+- Fixed the parser"
+        ));
+        assert_eq!(extract_section(content, "## Summary", Headers::Exact), None);
+        assert_eq!(
+            extract_section(content, "## Notes", Headers::Exact),
+            Some("Nothing else.".to_string())
+        );
+
+        assert!(has_section(content, "## Notes", Headers::Exact));
+        assert!(!has_section(content, "## Summary", Headers::Exact));
+        assert!(!has_section(
+            content,
+            "## Summary of changes",
+            Headers::Exact
+        ));
+    }
+
+    #[test]
+    fn test_validate_response_ignores_headers_in_code_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let response_path = temp_dir.path().join("response.md");
+        fs::write(
+            &response_path,
+            "# Response: 001\nCompleted: 2026-01-22T10:30:00Z\n\n## Details\n```md\n## Summary\nFake.\n\n## Notes\n```\n",
+        )
+        .unwrap();
+
+        let result = validate_response(response_path.to_str().unwrap(), None).unwrap();
+        assert_eq!(result.errors, vec!["Missing '## Summary' section"]);
+        assert!(result
+            .warnings
+            .contains(&"Missing '## Notes' section".to_string()));
+
+        // Same for a task's required sections
+        let task_path = temp_dir.path().join("task.md");
+        fs::write(
+            &task_path,
+            "# Task: 001\nCreated: 2026-01-22T10:00:00Z\nPriority: normal\n\n## Instructions\nUse this layout:\n```\n## Response Instructions\n```\n",
+        )
+        .unwrap();
+        let result = validate_task(task_path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            result.errors,
+            vec!["Missing '## Response Instructions' section"]
+        );
     }
 }
@@ -0,0 +1,324 @@
+use crate::clock::{self, Clock};
+use crate::error::McError;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A kind of protocol housekeeping file, identified by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HousekeepingKind {
+    /// `.alive` files touched by running agents
+    Heartbeat,
+    /// `.cancel` requests
+    Cancel,
+    /// `.lock` files, optionally holding the owner's pid
+    Lock,
+}
+
+impl HousekeepingKind {
+    pub const ALL: [HousekeepingKind; 3] = [
+        HousekeepingKind::Heartbeat,
+        HousekeepingKind::Cancel,
+        HousekeepingKind::Lock,
+    ];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            HousekeepingKind::Heartbeat => "alive",
+            HousekeepingKind::Cancel => "cancel",
+            HousekeepingKind::Lock => "lock",
+        }
+    }
+
+    fn of(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.extension() == extension)
+    }
+}
+
+impl FromStr for HousekeepingKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "heartbeat" => Ok(HousekeepingKind::Heartbeat),
+            "cancel" => Ok(HousekeepingKind::Cancel),
+            "lock" => Ok(HousekeepingKind::Lock),
+            other => Err(format!(
+                "unknown kind '{}' (expected heartbeat, cancel, or lock)",
+                other
+            )),
+        }
+    }
+}
+
+/// What [`prune`] may remove.
+#[derive(Debug, Clone)]
+pub struct PruneOptions {
+    /// Only files last modified longer ago than this
+    pub older_than: Duration,
+    pub kinds: Vec<HousekeepingKind>,
+    /// Report what would be removed without removing it
+    pub dry_run: bool,
+    /// Source of "now" for file ages
+    pub clock: Arc<dyn Clock>,
+}
+
+impl Default for PruneOptions {
+    fn default() -> Self {
+        PruneOptions {
+            older_than: Duration::from_secs(60 * 60),
+            kinds: HousekeepingKind::ALL.to_vec(),
+            dry_run: false,
+            clock: clock::system(),
+        }
+    }
+}
+
+/// A stale file that was (or in a dry run, would be) removed.
+#[derive(Debug, Serialize)]
+pub struct PrunedFile {
+    pub path: String,
+    pub kind: HousekeepingKind,
+    pub age_secs: u64,
+}
+
+/// A stale-looking file left in place, and why.
+#[derive(Debug, Serialize)]
+pub struct KeptFile {
+    pub path: String,
+    pub kind: HousekeepingKind,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    pub removed: Vec<PrunedFile>,
+    pub kept: Vec<KeptFile>,
+}
+
+/// Remove stale housekeeping files anywhere under `mission_dir`.
+///
+/// Only `.alive`, `.cancel`, and `.lock` files of the selected kinds are
+/// considered, so task, response, and conversation content is never touched.
+/// A lock is only removed once the pid it records has died.
+pub fn prune(mission_dir: &Path, options: &PruneOptions) -> Result<PruneReport, McError> {
+    if !mission_dir.is_dir() {
+        return Err(McError::not_found(format!(
+            "Mission directory not found: {}",
+            mission_dir.display()
+        )));
+    }
+
+    let mut report = PruneReport {
+        dry_run: options.dry_run,
+        removed: Vec::new(),
+        kept: Vec::new(),
+    };
+    let now = options.clock.system_now();
+
+    let mut dirs = vec![mission_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries: Vec<_> = fs::read_dir(&dir)?.filter_map(Result::ok).collect();
+        entries.sort_by_key(|entry| entry.path());
+
+        for entry in entries {
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let kind = match HousekeepingKind::of(&path) {
+                Some(kind) if file_type.is_file() && options.kinds.contains(&kind) => kind,
+                _ => continue,
+            };
+
+            let modified = entry.metadata()?.modified()?;
+            let age = now.duration_since(modified).unwrap_or_default();
+            if age <= options.older_than {
+                continue;
+            }
+
+            let display = path.to_string_lossy().to_string();
+            if kind == HousekeepingKind::Lock {
+                if let Some(reason) = lock_in_use(&path) {
+                    report.kept.push(KeptFile {
+                        path: display,
+                        kind,
+                        reason,
+                    });
+                    continue;
+                }
+            }
+
+            if !options.dry_run {
+                fs::remove_file(&path)?;
+            }
+            report.removed.push(PrunedFile {
+                path: display,
+                kind,
+                age_secs: age.as_secs(),
+            });
+        }
+    }
+
+    report.removed.sort_by(|a, b| a.path.cmp(&b.path));
+    report.kept.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(report)
+}
+
+/// Why a lock file must stay, if it is still in use.
+///
+/// Only a lock recording a dead pid is stale. Advisory locks (like the event
+/// ring's `{agent}.ndjson.lock`) record no pid and are never removed: a
+/// process may open one at any moment, and deleting it would let the next
+/// opener lock a fresh file instead of the one already held.
+fn lock_in_use(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    match content.trim().parse::<u32>() {
+        Ok(pid) if pid_alive(pid) => Some(format!("held by live pid {}", pid)),
+        Ok(_) => None,
+        Err(_) => Some("no pid recorded".to_string()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn pid_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn pid_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(true)
+}
+
+/// Without a cheap liveness check, assume the owner is alive.
+#[cfg(not(unix))]
+fn pid_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Parse an age like `90s`, `30m`, `1h`, or `2d`.
+pub fn parse_age(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid age '{}'", text))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "Invalid age unit in '{}' (use s, m, h, or d)",
+                text
+            ))
+        }
+    };
+    Ok(Duration::from_secs(number * seconds))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::SystemTime;
+    use tempfile::TempDir;
+
+    /// Write `path`, creating its directory, and backdate it by `age`.
+    pub(crate) fn write_aged(path: &Path, content: &str, age: Duration) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        let file = File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn test_prunes_only_stale_housekeeping() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission = temp_dir.path();
+        let stale = Duration::from_secs(3 * 60 * 60);
+        let fresh = Duration::from_secs(60);
+
+        write_aged(&mission.join("status/task-001.alive"), "", stale);
+        write_aged(&mission.join("status/task-002.alive"), "", fresh);
+        write_aged(&mission.join("tasks/task-001.cancel"), "", stale);
+        write_aged(&mission.join("run.lock"), "4294967294", stale);
+        let live_pid = std::process::id().to_string();
+        write_aged(&mission.join("live.lock"), &live_pid, stale);
+        // The event ring's rotation lock records no pid
+        write_aged(&mission.join("events/claude.ndjson.lock"), "", stale);
+        // Old content is never housekeeping
+        write_aged(&mission.join("tasks/task-001.md"), "# Task: 001\n", stale);
+        write_aged(&mission.join("conversation.md"), "## Human\n", stale);
+
+        let dry = prune(
+            mission,
+            &PruneOptions {
+                dry_run: true,
+                ..PruneOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(dry.removed.len(), 3);
+        assert!(mission.join("run.lock").exists());
+
+        let report = prune(mission, &PruneOptions::default()).unwrap();
+        let removed: Vec<&str> = report.removed.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(removed.len(), 3);
+        assert!(!mission.join("status/task-001.alive").exists());
+        assert!(!mission.join("tasks/task-001.cancel").exists());
+        assert!(!mission.join("run.lock").exists());
+
+        assert!(mission.join("status/task-002.alive").exists());
+        assert!(mission.join("live.lock").exists());
+        assert!(mission.join("events/claude.ndjson.lock").exists());
+        assert!(mission.join("tasks/task-001.md").exists());
+        assert!(mission.join("conversation.md").exists());
+
+        assert_eq!(report.kept.len(), 2);
+        assert_eq!(report.kept[0].reason, "no pid recorded");
+        assert!(report.kept[1].reason.contains(&live_pid));
+    }
+
+    #[test]
+    fn test_kinds_filter_and_parse_age() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission = temp_dir.path();
+        let stale = Duration::from_secs(2 * 24 * 60 * 60);
+        write_aged(&mission.join("a.alive"), "", stale);
+        write_aged(&mission.join("b.cancel"), "", stale);
+
+        let options = PruneOptions {
+            kinds: vec!["cancel".parse().unwrap()],
+            older_than: parse_age("1d").unwrap(),
+            ..PruneOptions::default()
+        };
+        let report = prune(mission, &options).unwrap();
+        assert_eq!(report.removed.len(), 1);
+        assert_eq!(report.removed[0].kind, HousekeepingKind::Cancel);
+        assert!(mission.join("a.alive").exists());
+
+        assert_eq!(parse_age("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_age("30m").unwrap(), Duration::from_secs(1800));
+        assert!(parse_age("1w").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+//! One-command smoke test proving file watching and the protocol work on
+//! this host.
+//!
+//! A scripted writer thread plays the agent side against a scratch mission
+//! while the real watch functions wait on it, the same way an orchestrator
+//! would.
+
+use crate::fswatch::WatchOptions;
+use crate::protocol::{self, NewTask};
+use crate::{conversation, tokens, watcher};
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long the scripted writer waits before answering, so each watch
+/// has to see a real filesystem event rather than a file already in place.
+const WRITER_DELAY: Duration = Duration::from_millis(200);
+
+const TASK_ID: &str = "selftest";
+const RESPONSE_SUMMARY: &str = "Selftest task completed.";
+const CONVERSATION_REPLY: &str = "Selftest reply.";
+
+/// A named check, run in order until one fails.
+type Stage<'a> = (&'static str, &'a dyn Fn() -> Result<(), String>);
+
+/// Outcome of one stage.
+#[derive(Debug, Serialize)]
+pub struct CheckResult {
+    pub stage: &'static str,
+    pub passed: bool,
+    pub elapsed_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of a whole selftest run.
+#[derive(Debug, Serialize)]
+pub struct SelftestReport {
+    pub passed: bool,
+    pub mission_dir: String,
+    pub checks: Vec<CheckResult>,
+    /// First stage that failed; later stages are not run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_stage: Option<&'static str>,
+}
+
+/// Run every stage against a scratch mission created under `parent`.
+///
+/// Each watch waits at most `timeout`. The scratch directory is removed
+/// afterwards whether or not the run passed.
+pub fn run(parent: &Path, timeout: Duration, options: &WatchOptions) -> SelftestReport {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let scratch = parent.join(format!("mc-selftest-{}-{}", std::process::id(), nanos));
+    let mission_dir = scratch.join(".mission");
+
+    let mut report = SelftestReport {
+        passed: true,
+        mission_dir: mission_dir.to_string_lossy().to_string(),
+        checks: Vec::new(),
+        failed_stage: None,
+    };
+
+    let stages: [Stage; 5] = [
+        ("create_task", &|| create_task(&mission_dir)),
+        ("watch_task", &|| watch_task(&mission_dir, timeout, options)),
+        ("parse_response", &|| parse_response(&mission_dir)),
+        ("watch_conversation", &|| {
+            watch_conversation(&mission_dir, timeout, options)
+        }),
+        ("count_tokens", &|| count_tokens(&mission_dir)),
+    ];
+
+    for (stage, check) in stages {
+        let start = Instant::now();
+        let outcome = check();
+        let passed = outcome.is_ok();
+        report.checks.push(CheckResult {
+            stage,
+            passed,
+            elapsed_ms: start.elapsed().as_millis() as u64,
+            error: outcome.err(),
+        });
+        if !passed {
+            report.passed = false;
+            report.failed_stage = Some(stage);
+            break;
+        }
+    }
+
+    let _ = fs::remove_dir_all(&scratch);
+    report
+}
+
+fn create_task(mission_dir: &Path) -> Result<(), String> {
+    for dir in ["tasks", "status", "responses"] {
+        fs::create_dir_all(mission_dir.join(dir)).map_err(|e| e.to_string())?;
+    }
+
+    let task = NewTask {
+        task_id: TASK_ID.to_string(),
+        priority: "normal".to_string(),
+        instructions: "Reply to confirm the protocol works on this host.".to_string(),
+        ..NewTask::default()
+    };
+    let path = protocol::create_task(mission_dir, &task).map_err(|e| e.to_string())?;
+
+    let validation = protocol::validate_task(&path.to_string_lossy()).map_err(|e| e.to_string())?;
+    if !validation.valid {
+        return Err(format!(
+            "created task is invalid: {}",
+            validation.errors.join("; ")
+        ));
+    }
+    Ok(())
+}
+
+fn watch_task(mission_dir: &Path, timeout: Duration, options: &WatchOptions) -> Result<(), String> {
+    let response = response_path(mission_dir);
+    let status = mission_dir
+        .join("status")
+        .join(format!("task-{}.status", TASK_ID));
+    let content = format!(
+        "# Response: {}\n\n## Summary\n{}\n\n## Files Modified\n- None\n",
+        TASK_ID, RESPONSE_SUMMARY
+    );
+    let writer = thread::spawn(move || -> std::io::Result<()> {
+        thread::sleep(WRITER_DELAY);
+        fs::write(&response, content)?;
+        fs::write(&status, "DONE")
+    });
+
+    let result = watcher::watch_task(TASK_ID, &mission_dir.to_string_lossy(), timeout, options)
+        .map_err(|e| e.to_string());
+    join_writer(writer)?;
+
+    match result? {
+        watcher::WatchResult::Complete {
+            response_path,
+            response_exists,
+            ..
+        } => {
+            if response_exists {
+                Ok(())
+            } else {
+                Err(format!("response file missing: {}", response_path))
+            }
+        }
+        watcher::WatchResult::Timeout => Err("timed out waiting for status file".to_string()),
+        watcher::WatchResult::Cancelled { reason } => Err(format!("cancelled: {:?}", reason)),
+        watcher::WatchResult::StatusOnly { response_path, .. } => {
+            Err(format!("response never ready: {}", response_path))
+        }
+    }
+}
+
+fn parse_response(mission_dir: &Path) -> Result<(), String> {
+    let parsed = protocol::parse_response(&response_path(mission_dir).to_string_lossy())
+        .map_err(|e| e.to_string())?;
+    match parsed.summary.as_deref() {
+        Some(RESPONSE_SUMMARY) => Ok(()),
+        other => Err(format!("unexpected summary: {:?}", other)),
+    }
+}
+
+fn watch_conversation(
+    mission_dir: &Path,
+    timeout: Duration,
+    options: &WatchOptions,
+) -> Result<(), String> {
+    let path = mission_dir.join("conversation.md");
+    fs::write(&path, "## Human\n\nAre you there?\n\n---\n\n").map_err(|e| e.to_string())?;
+
+    let writer = thread::spawn(move || -> std::io::Result<()> {
+        thread::sleep(WRITER_DELAY);
+        let mut file = fs::OpenOptions::new().append(true).open(&path)?;
+        write!(
+            file,
+            "## Assistant\n\n{}\n\n---END---\n",
+            CONVERSATION_REPLY
+        )
+    });
+
+    let result = conversation::watch(
+        &mission_dir.to_string_lossy(),
+        timeout,
+        &conversation::Completion::default(),
+        options,
+    )
+    .map_err(|e| e.to_string());
+    join_writer(writer)?;
+
+    match result? {
+        conversation::ConversationResult::Complete { response, .. }
+            if response == CONVERSATION_REPLY =>
+        {
+            Ok(())
+        }
+        conversation::ConversationResult::Complete { response, .. } => {
+            Err(format!("unexpected response: {:?}", response))
+        }
+        conversation::ConversationResult::Timeout => {
+            Err("timed out waiting for ---END---".to_string())
+        }
+        conversation::ConversationResult::Cancelled { reason } => {
+            Err(format!("cancelled: {:?}", reason))
+        }
+    }
+}
+
+fn count_tokens(mission_dir: &Path) -> Result<(), String> {
+    let usage = tokens::count_tokens(&mission_dir.join("conversation.md"))?;
+    if usage.total_tokens == 0 {
+        return Err("conversation counted as zero tokens".to_string());
+    }
+    Ok(())
+}
+
+fn response_path(mission_dir: &Path) -> PathBuf {
+    mission_dir
+        .join("responses")
+        .join(format!("task-{}.md", TASK_ID))
+}
+
+fn join_writer(writer: thread::JoinHandle<std::io::Result<()>>) -> Result<(), String> {
+    match writer.join() {
+        Ok(result) => result.map_err(|e| format!("scripted writer failed: {}", e)),
+        Err(_) => Err("scripted writer panicked".to_string()),
+    }
+}
@@ -0,0 +1,464 @@
+//! A long-lived process answering protocol requests over a Unix socket.
+//!
+//! Each connection sends newline-delimited JSON [`Request`]s and reads one
+//! line back per request: the same JSON the matching CLI subcommand prints,
+//! or an [`McError`] as `{"kind", "error"}`. Connections are served on their
+//! own threads, and every watch they run shares the one [`SharedWatcher`] on
+//! the mission dir instead of setting up a watcher per request.
+
+use crate::conversation::{self, Completion};
+use crate::error::McError;
+use crate::fswatch::{SharedWatcher, WatchOptions};
+use crate::protocol::{self, Layout, Priority, TaskFilter};
+use crate::watcher::{self, InboxOptions};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// One request, tagged by `cmd`, e.g. `{"cmd":"watch_task","task_id":"001"}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    /// As `watch-task`
+    WatchTask {
+        task_id: String,
+        #[serde(default = "default_timeout")]
+        timeout: u64,
+        #[serde(default)]
+        require_response: bool,
+    },
+    /// As `watch-inbox`
+    WatchInbox {
+        #[serde(default = "default_timeout")]
+        timeout: u64,
+        #[serde(default)]
+        priority: Option<Priority>,
+        #[serde(default)]
+        claim: bool,
+    },
+    /// As `watch-conversation`
+    WatchConversation {
+        #[serde(default = "default_timeout")]
+        timeout: u64,
+        #[serde(default)]
+        end_marker: Vec<String>,
+        #[serde(default)]
+        done_file: Option<PathBuf>,
+    },
+    /// As `validate-task --task-id`
+    ValidateTask { task_id: String },
+    /// As `parse-response --task-id`
+    ParseResponse { task_id: String },
+    /// As `list-tasks`
+    ListTasks,
+    /// As `check-ready`
+    CheckReady { task_id: String },
+    /// Answered with `{"status": "ok"}`, to check the server is up
+    Ping,
+}
+
+fn default_timeout() -> u64 {
+    300
+}
+
+/// Serve requests on `socket` against `mission_dir` until `options.cancel`
+/// fires, then remove the socket.
+///
+/// A socket file left by a server that's gone is replaced; one a server is
+/// still listening on is an error.
+pub fn serve(socket: &Path, mission_dir: &str, options: &WatchOptions) -> Result<(), McError> {
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(McError::Validation(format!(
+                "A server is already listening on {}",
+                socket.display()
+            )));
+        }
+        std::fs::remove_file(socket).map_err(|e| McError::io(socket.display(), e))?;
+    }
+
+    let shared = SharedWatcher::new(&options.watch_path(Path::new(mission_dir)), options)?;
+    let options = WatchOptions {
+        shared: Some(shared),
+        ..options.clone()
+    };
+
+    let listener = UnixListener::bind(socket).map_err(|e| McError::io(socket.display(), e))?;
+    let _socket = RemoveOnDrop(socket.to_path_buf());
+    // Accepting without blocking lets the loop notice cancellation
+    listener.set_nonblocking(true)?;
+
+    while options.cancel.requested().is_none() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let mission_dir = mission_dir.to_string();
+                let options = options.clone();
+                std::thread::spawn(move || {
+                    let _ = serve_connection(stream, &mission_dir, &options);
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                std::thread::sleep(crate::cancel::CHECK_INTERVAL);
+            }
+            Err(e) => return Err(McError::io(socket.display(), e)),
+        }
+    }
+    Ok(())
+}
+
+struct RemoveOnDrop(PathBuf);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Answer each request line on `stream` until the client hangs up.
+fn serve_connection(
+    stream: UnixStream,
+    mission_dir: &str,
+    options: &WatchOptions,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = serde_json::from_str::<Request>(&line)
+            .map_err(McError::from)
+            .and_then(|request| handle(&request, mission_dir, options))
+            .unwrap_or_else(|e| serde_json::to_value(e).unwrap());
+        writeln!(writer, "{}", response)?;
+    }
+    Ok(())
+}
+
+/// The output the CLI prints for `request`.
+pub fn handle(
+    request: &Request,
+    mission_dir: &str,
+    options: &WatchOptions,
+) -> Result<Value, McError> {
+    let output = match request {
+        Request::WatchTask {
+            task_id,
+            timeout,
+            require_response,
+        } => with_mission_dir(
+            watcher::watch_task_with_progress(
+                task_id,
+                mission_dir,
+                Duration::from_secs(*timeout),
+                options,
+                *require_response,
+                Duration::from_secs(*timeout),
+                |_| {},
+            )?,
+            mission_dir,
+        ),
+        Request::WatchInbox {
+            timeout,
+            priority,
+            claim,
+        } => {
+            let inbox = InboxOptions {
+                priority: *priority,
+                claim: *claim,
+            };
+            with_mission_dir(
+                watcher::watch_inbox(mission_dir, Duration::from_secs(*timeout), &inbox, options)?,
+                mission_dir,
+            )
+        }
+        Request::WatchConversation {
+            timeout,
+            end_marker,
+            done_file,
+        } => {
+            let mut completion = Completion {
+                done_file: done_file.clone(),
+                ..Completion::default()
+            };
+            if !end_marker.is_empty() {
+                completion.markers = end_marker.clone();
+            }
+            with_mission_dir(
+                conversation::watch(
+                    mission_dir,
+                    Duration::from_secs(*timeout),
+                    &completion,
+                    options,
+                )?,
+                mission_dir,
+            )
+        }
+        Request::ValidateTask { task_id } => {
+            let paths = task_paths(mission_dir, task_id, options);
+            serde_json::to_value(protocol::validate_task(&paths.task.to_string_lossy())?)?
+        }
+        Request::ParseResponse { task_id } => {
+            let paths = task_paths(mission_dir, task_id, options);
+            serde_json::to_value(protocol::parse_response(&paths.response.to_string_lossy())?)?
+        }
+        Request::ListTasks => {
+            let mut tasks = Vec::new();
+            let summary =
+                protocol::list_tasks(Path::new(mission_dir), &TaskFilter::default(), |entry| {
+                    tasks.push(entry.clone())
+                })?;
+            protocol::sort_by_created(&mut tasks);
+            serde_json::json!({ "tasks": tasks, "summary": summary })
+        }
+        Request::CheckReady { task_id } => with_mission_dir(
+            protocol::check_ready(Path::new(mission_dir), task_id)?,
+            mission_dir,
+        ),
+        Request::Ping => serde_json::json!({ "status": "ok" }),
+    };
+    Ok(output)
+}
+
+fn task_paths(mission_dir: &str, task_id: &str, options: &WatchOptions) -> protocol::TaskPaths {
+    let mission_dir = Path::new(mission_dir);
+    options
+        .layout
+        .unwrap_or_else(|| Layout::detect(mission_dir, task_id))
+        .paths(mission_dir, task_id)
+}
+
+/// `result` with the mission dir it ran against, as the CLI records it.
+fn with_mission_dir<T: Serialize>(result: T, mission_dir: &str) -> Value {
+    let mut output = serde_json::to_value(result).unwrap();
+    if let Some(obj) = output.as_object_mut() {
+        obj.insert("mission_dir".to_string(), Value::from(mission_dir));
+    }
+    output
+}
+
+/// A connection to a [`serve`] process.
+#[derive(Debug)]
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    pub fn connect(socket: &Path) -> Result<Self, McError> {
+        let writer = UnixStream::connect(socket).map_err(|e| McError::io(socket.display(), e))?;
+        Ok(Client {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+        })
+    }
+
+    /// Send `request` and wait for its answer. An error the server answers
+    /// with is returned as the output it is, not as an `Err`.
+    pub fn request(&mut self, request: &Request) -> Result<Value, McError> {
+        writeln!(self.writer, "{}", serde_json::to_string(request)?)?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(McError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Server closed the connection",
+            )));
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::Ordering;
+    use std::time::Instant;
+    use tempfile::TempDir;
+
+    /// A server on a socket in its own temp dir, stopped when dropped.
+    struct Server {
+        socket: PathBuf,
+        options: WatchOptions,
+        thread: Option<std::thread::JoinHandle<Result<(), McError>>>,
+        _dir: TempDir,
+    }
+
+    impl Server {
+        fn start(mission_dir: &Path) -> Self {
+            let dir = TempDir::new().unwrap();
+            let socket = dir.path().join("mc.sock");
+            let options = WatchOptions::default();
+            let thread = {
+                let (socket, options) = (socket.clone(), options.clone());
+                let mission_dir = mission_dir.to_string_lossy().to_string();
+                std::thread::spawn(move || serve(&socket, &mission_dir, &options))
+            };
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while UnixStream::connect(&socket).is_err() {
+                assert!(Instant::now() < deadline, "server never came up");
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Server {
+                socket,
+                options,
+                thread: Some(thread),
+                _dir: dir,
+            }
+        }
+
+        fn stop(&mut self) -> Result<(), McError> {
+            self.options.cancel.flag.store(true, Ordering::Relaxed);
+            self.thread.take().map_or(Ok(()), |t| t.join().unwrap())
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            let _ = self.stop();
+        }
+    }
+
+    fn mission() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        for dir in ["tasks", "status", "responses"] {
+            fs::create_dir(temp_dir.path().join(dir)).unwrap();
+        }
+        temp_dir
+    }
+
+    #[test]
+    fn test_request_round_trips_as_tagged_json() {
+        let request: Request =
+            serde_json::from_str(r#"{"cmd":"watch_task","task_id":"001","timeout":5}"#).unwrap();
+        assert_eq!(
+            request,
+            Request::WatchTask {
+                task_id: "001".to_string(),
+                timeout: 5,
+                require_response: false,
+            }
+        );
+        let json = serde_json::to_value(Request::ListTasks).unwrap();
+        assert_eq!(json, serde_json::json!({ "cmd": "list_tasks" }));
+    }
+
+    #[test]
+    fn test_serves_concurrent_watches() {
+        let mission = mission();
+        let server = Server::start(mission.path());
+
+        let waiters: Vec<_> = ["001", "002"]
+            .into_iter()
+            .map(|task_id| {
+                let socket = server.socket.clone();
+                std::thread::spawn(move || {
+                    let mut client = Client::connect(&socket).unwrap();
+                    client
+                        .request(&Request::WatchTask {
+                            task_id: task_id.to_string(),
+                            timeout: 10,
+                            require_response: false,
+                        })
+                        .unwrap()
+                })
+            })
+            .collect();
+
+        // Both watches are up and waiting before either task completes
+        std::thread::sleep(Duration::from_millis(300));
+        for task_id in ["002", "001"] {
+            let status = mission
+                .path()
+                .join(format!("status/task-{}.status", task_id));
+            fs::write(status, "COMPLETE").unwrap();
+        }
+
+        for waiter in waiters {
+            let result = waiter.join().unwrap();
+            assert_eq!(result["status"], "complete");
+            assert_eq!(
+                result["mission_dir"],
+                mission.path().to_string_lossy().as_ref()
+            );
+        }
+    }
+
+    #[test]
+    fn test_answers_each_request_on_a_connection() {
+        let mission = mission();
+        fs::write(
+            mission.path().join("tasks/task-001.md"),
+            "# Task\n\nPriority: high\n",
+        )
+        .unwrap();
+        let server = Server::start(mission.path());
+        let mut client = Client::connect(&server.socket).unwrap();
+
+        let pong = client.request(&Request::Ping).unwrap();
+        assert_eq!(pong, serde_json::json!({ "status": "ok" }));
+
+        let listed = client.request(&Request::ListTasks).unwrap();
+        assert_eq!(listed["summary"]["tasks"], 1);
+        assert_eq!(listed["tasks"][0]["task_id"], "001");
+
+        let missing = client
+            .request(&Request::ParseResponse {
+                task_id: "001".to_string(),
+            })
+            .unwrap();
+        assert_eq!(missing["kind"], "io");
+    }
+
+    #[test]
+    fn test_bad_request_is_answered_with_an_error() {
+        let mission = mission();
+        let server = Server::start(mission.path());
+        let mut stream = UnixStream::connect(&server.socket).unwrap();
+        writeln!(stream, r#"{{"cmd":"launch_rockets"}}"#).unwrap();
+        writeln!(stream, r#"{{"cmd":"ping"}}"#).unwrap();
+
+        let mut lines = BufReader::new(stream).lines();
+        let error: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(error["kind"], "parse");
+        let pong: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(pong["status"], "ok");
+    }
+
+    #[test]
+    fn test_removes_socket_on_exit() {
+        let mission = mission();
+        let mut server = Server::start(mission.path());
+        assert!(server.socket.exists());
+
+        server.stop().unwrap();
+        assert!(!server.socket.exists());
+    }
+
+    #[test]
+    fn test_replaces_stale_socket_but_not_live_one() {
+        let mission = mission();
+        let server = Server::start(mission.path());
+        let mission_dir = mission.path().to_string_lossy();
+        let err = serve(&server.socket, &mission_dir, &WatchOptions::default()).unwrap_err();
+        assert!(matches!(err, McError::Validation(_)));
+
+        // A socket file nobody listens on any more
+        let dir = TempDir::new().unwrap();
+        let stale = dir.path().join("stale.sock");
+        drop(UnixListener::bind(&stale).unwrap());
+        let stale_server = {
+            let stale = stale.clone();
+            let mission_dir = mission_dir.to_string();
+            let options = WatchOptions::default();
+            options.cancel.flag.store(true, Ordering::Relaxed);
+            std::thread::spawn(move || serve(&stale, &mission_dir, &options))
+        };
+        stale_server.join().unwrap().unwrap();
+        assert!(!stale.exists());
+    }
+}
@@ -0,0 +1,25 @@
+//! Layout constants shared by everything that writes protocol documents.
+//!
+//! Bump [`PROMPT_SPEC_VERSION`] whenever the prompt layout changes, so
+//! agents and dispatchers can tell which layout a rendered prompt uses.
+
+/// Version of the rendered prompt layout.
+pub const PROMPT_SPEC_VERSION: u32 = 1;
+
+/// Rendered prompt sections, in the order they appear.
+pub const PROMPT_SECTIONS: [&str; 3] = ["INSTRUCTIONS", "CONTEXT", "CONVERSATION"];
+
+/// First line of a rendered prompt.
+pub fn prompt_header() -> String {
+    format!("<!-- mc-prompt v{} -->", PROMPT_SPEC_VERSION)
+}
+
+/// The line that opens a prompt section.
+pub fn section_marker(section: &str) -> String {
+    format!("===== MC:{} =====", section)
+}
+
+/// The line that closes the last prompt section.
+pub fn end_marker() -> String {
+    section_marker("END")
+}
@@ -1,27 +1,399 @@
 use std::fs;
-use std::path::Path;
-use std::sync::mpsc;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
-use serde::Serialize;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode};
+use serde::{Deserialize, Serialize};
 
+use crate::cancel::CancelReason;
+use crate::conversation::{self, Role};
+use crate::fswatch::{self, FsWatch, WatchOptions};
 use knowledge::TokenCounter;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TokenUsage {
     pub total_tokens: usize,
     pub estimated_cost_usd: f64,
     pub conversation_length: usize,
+    /// Model whose prices were used, when one was named
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Cost of the half of the tokens taken as input
+    pub input_cost_usd: f64,
+    /// Cost of the half of the tokens taken as output
+    pub output_cost_usd: f64,
+    /// Input tokens billed at the full input price
+    pub uncached_input_tokens: usize,
+    /// Input tokens taken as read from the prompt cache
+    pub cache_read_tokens: usize,
+    /// Input tokens taken as written to the prompt cache
+    pub cache_write_tokens: usize,
+    /// The model wasn't in the pricing table and no prices were given, so
+    /// the default prices were used
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pricing_assumed: bool,
+    /// A [`TokenBudget`] limit was crossed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub budget_exceeded: bool,
+    /// Which limit was crossed; the token limit when both were
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget_limit: Option<BudgetLimit>,
+    /// Times a streaming watch saw conversation.md removed or renamed away
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub rotations: u32,
+    /// A watch stopped early through [`WatchOptions::cancel`]; the counts
+    /// are those at that point
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancelled: Option<CancelReason>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// Limits on a conversation's size; a watch stops as soon as one is crossed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenBudget {
+    pub max_tokens: Option<usize>,
+    pub max_cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetLimit {
+    MaxTokens,
+    MaxCostUsd,
+}
+
+impl TokenBudget {
+    /// The limit `usage` is over, if any.
+    pub fn exceeded(&self, usage: &TokenUsage) -> Option<BudgetLimit> {
+        if self.max_tokens.is_some_and(|max| usage.total_tokens > max) {
+            Some(BudgetLimit::MaxTokens)
+        } else if self
+            .max_cost_usd
+            .is_some_and(|max| usage.estimated_cost_usd > max)
+        {
+            Some(BudgetLimit::MaxCostUsd)
+        } else {
+            None
+        }
+    }
+
+    /// Mark `usage` when it is over a limit, returning whether it is.
+    fn check(&self, usage: &mut TokenUsage) -> bool {
+        usage.budget_limit = self.exceeded(usage);
+        usage.budget_exceeded = usage.budget_limit.is_some();
+        usage.budget_exceeded
+    }
+}
+
+/// Prices of a model in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    /// Reading input from the prompt cache
+    pub cache_read: f64,
+    /// Writing input to the prompt cache
+    pub cache_write: f64,
+}
+
+impl ModelPrice {
+    const fn new(input: f64, output: f64, cache_read: f64, cache_write: f64) -> Self {
+        ModelPrice {
+            input,
+            output,
+            cache_read,
+            cache_write,
+        }
+    }
+}
+
+/// Prices used when no model is named: Sonnet's.
+pub const DEFAULT_PRICE: ModelPrice = ModelPrice::new(3.0, 15.0, 0.30, 3.75);
+
+/// Known model prices, by model name prefix.
+///
+/// A model id such as `claude-sonnet-4-20250514` matches its longest prefix
+/// in the table, so dated releases need no entries of their own. Prices
+/// change; `--price-input` and `--price-output` override the table.
+#[derive(Debug, Clone)]
+pub struct PricingTable {
+    models: Vec<(String, ModelPrice)>,
+}
+
+impl Default for PricingTable {
+    fn default() -> Self {
+        let models = [
+            ("claude-opus-4-5", ModelPrice::new(5.0, 25.0, 0.50, 6.25)),
+            ("claude-opus-4", ModelPrice::new(15.0, 75.0, 1.50, 18.75)),
+            ("claude-3-opus", ModelPrice::new(15.0, 75.0, 1.50, 18.75)),
+            ("claude-sonnet-4", DEFAULT_PRICE),
+            ("claude-3-7-sonnet", DEFAULT_PRICE),
+            ("claude-3-5-sonnet", DEFAULT_PRICE),
+            ("claude-haiku-4-5", ModelPrice::new(1.0, 5.0, 0.10, 1.25)),
+            ("claude-3-5-haiku", ModelPrice::new(0.80, 4.0, 0.08, 1.0)),
+            ("claude-3-haiku", ModelPrice::new(0.25, 1.25, 0.03, 0.30)),
+            // Aliases for the current model of each family
+            ("opus", ModelPrice::new(5.0, 25.0, 0.50, 6.25)),
+            ("sonnet", DEFAULT_PRICE),
+            ("haiku", ModelPrice::new(1.0, 5.0, 0.10, 1.25)),
+        ];
+        PricingTable {
+            models: models
+                .into_iter()
+                .map(|(name, price)| (name.to_string(), price))
+                .collect(),
+        }
+    }
+}
+
+impl PricingTable {
+    /// Prices of `model`, matched by its longest known prefix.
+    pub fn get(&self, model: &str) -> Option<ModelPrice> {
+        self.models
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| *price)
+    }
+
+    /// The pricing for `model`, with per-million-token overrides taking
+    /// precedence over the table.
+    pub fn pricing(&self, model: Option<&str>, input: Option<f64>, output: Option<f64>) -> Pricing {
+        let known = model.and_then(|model| self.get(model));
+        let base = known.unwrap_or(DEFAULT_PRICE);
+        Pricing {
+            model: model.map(str::to_string),
+            price: ModelPrice {
+                input: input.unwrap_or(base.input),
+                output: output.unwrap_or(base.output),
+                ..base
+            },
+            assumed: model.is_some() && known.is_none() && (input.is_none() || output.is_none()),
+            cache_discount: None,
+        }
+    }
+}
+
+/// Prices to estimate a conversation's cost with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pricing {
+    pub model: Option<String>,
+    pub price: ModelPrice,
+    /// The model is unknown and at least one price fell back to the default
+    pub assumed: bool,
+    /// Fraction of the input price paid for cached input. When set, all of
+    /// a conversation before its most recent Human section is taken as
+    /// cached.
+    pub cache_discount: Option<f64>,
+}
+
+impl Default for Pricing {
+    fn default() -> Self {
+        Pricing {
+            model: None,
+            price: DEFAULT_PRICE,
+            assumed: false,
+            cache_discount: None,
+        }
+    }
+}
+
+/// How a conversation's tokens are taken to have been billed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenSplit {
+    pub uncached_input: usize,
+    pub cache_read: usize,
+    pub cache_write: usize,
+    pub output: usize,
+}
+
+impl TokenSplit {
+    /// Half of `tokens` as input and half as output, the conversation's text
+    /// not saying how it was billed. Of the input, the same share as
+    /// `cached` is of `tokens` is taken as read from the cache.
+    pub fn new(tokens: usize, cached: usize) -> Self {
+        let input = tokens / 2;
+        let cache_read = (input * cached.min(tokens))
+            .checked_div(tokens)
+            .unwrap_or(0);
+        TokenSplit {
+            uncached_input: input - cache_read,
+            cache_read,
+            cache_write: 0,
+            output: tokens - input,
+        }
+    }
+
+    /// Take the input as split by a `<!-- cache: -->` marker, within the
+    /// input there is.
+    fn with_marker(self, marker: CacheMarker) -> Self {
+        let input = self.uncached_input + self.cache_read + self.cache_write;
+        let cache_read = marker.read.min(input);
+        let cache_write = marker.write.min(input - cache_read);
+        TokenSplit {
+            uncached_input: input - cache_read - cache_write,
+            cache_read,
+            cache_write,
+            output: self.output,
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.uncached_input + self.cache_read + self.cache_write + self.output
+    }
+}
+
+/// Input and output cost in USD of `split` at `price`. Cached input is read
+/// at `cache_discount` times the input price when given, else at the
+/// model's cache read price.
+pub fn estimate_cost(
+    split: &TokenSplit,
+    price: &ModelPrice,
+    cache_discount: Option<f64>,
+) -> (f64, f64) {
+    let per_token = |tokens: usize, price: f64| tokens as f64 * price / 1_000_000.0;
+    let cache_read = cache_discount.map_or(price.cache_read, |d| d * price.input);
+    let input = per_token(split.uncached_input, price.input)
+        + per_token(split.cache_read, cache_read)
+        + per_token(split.cache_write, price.cache_write);
+    (input, per_token(split.output, price.output))
+}
+
+impl Pricing {
+    /// Fraction of the input price paid for cached input, from
+    /// `--cache-discount`.
+    pub fn with_cache_discount(mut self, discount: Option<f64>) -> Self {
+        self.cache_discount = discount;
+        self
+    }
+
+    /// Input and output cost of `tokens` none of which were cached.
+    pub fn cost(&self, tokens: usize) -> (f64, f64) {
+        estimate_cost(&TokenSplit::new(tokens, 0), &self.price, None)
+    }
+
+    /// [`TokenUsage`] of `text`: a conversation, or any other file.
+    fn count(&self, text: &str) -> TokenUsage {
+        let counter = TokenCounter::new();
+        let tokens = counter.count(text);
+        let cached = match self.cache_discount {
+            Some(_) => counter.count(cached_prefix(text)),
+            None => 0,
+        };
+        let mut split = TokenSplit::new(tokens, cached);
+        if let Some(marker) = cache_marker(text) {
+            split = split.with_marker(marker);
+        }
+        self.usage(split, text.len())
+    }
+
+    /// [`TokenUsage`] of a text of `length` bytes whose tokens were billed as
+    /// `split`.
+    fn usage(&self, split: TokenSplit, length: usize) -> TokenUsage {
+        let (input_cost_usd, output_cost_usd) =
+            estimate_cost(&split, &self.price, self.cache_discount);
+        TokenUsage {
+            total_tokens: split.total(),
+            estimated_cost_usd: input_cost_usd + output_cost_usd,
+            conversation_length: length,
+            model: self.model.clone(),
+            input_cost_usd,
+            uncached_input_tokens: split.uncached_input,
+            cache_read_tokens: split.cache_read,
+            cache_write_tokens: split.cache_write,
+            output_cost_usd,
+            pricing_assumed: self.assumed,
+            budget_exceeded: false,
+            budget_limit: None,
+            rotations: 0,
+            cancelled: None,
+        }
+    }
+}
+
+/// The part of a conversation before its most recent Human section: what
+/// the cache holds by the time that message is sent.
+fn cached_prefix(content: &str) -> &str {
+    let mut offset = 0;
+    let mut last_human = 0;
+    for line in content.split_inclusive('\n') {
+        if line.starts_with("## Human") {
+            last_human = offset;
+        }
+        offset += line.len();
+    }
+    &content[..last_human]
+}
+
+/// Cache use recorded in conversation.md by whoever sent it, as
+/// `<!-- cache: read=N write=M -->`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CacheMarker {
+    read: usize,
+    write: usize,
+}
+
+/// The last cache marker in `content`, which describes the conversation as
+/// it stands.
+fn cache_marker(content: &str) -> Option<CacheMarker> {
+    content.lines().rev().find_map(|line| {
+        let body = line
+            .trim()
+            .strip_prefix("<!--")?
+            .strip_suffix("-->")?
+            .trim()
+            .strip_prefix("cache:")?;
+        let mut marker = CacheMarker { read: 0, write: 0 };
+        for field in body.split_whitespace() {
+            match field.split_once('=')? {
+                ("read", n) => marker.read = n.parse().ok()?,
+                ("write", n) => marker.write = n.parse().ok()?,
+                _ => return None,
+            }
+        }
+        Some(marker)
+    })
+}
+
+/// Tokens in one conversation turn: an assistant response plus the human
+/// message(s) before it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TurnTokens {
+    /// 1-based, matching the assistant response's position
+    pub turn: usize,
+    /// The assistant response's timestamp
+    pub timestamp: Option<String>,
+    pub tokens: usize,
 }
 
 /// Watch conversation.md and emit token counts when it changes
+///
+/// A conversation already over `budget` is returned at once, marked
+/// `budget_exceeded`, without watching. A cancelled watch returns the count
+/// at that point, marked `cancelled`.
 pub fn watch_conversation_tokens(
     mission_dir: &Path,
     timeout_secs: u64,
+    options: &WatchOptions,
+    pricing: &Pricing,
+    budget: &TokenBudget,
 ) -> Result<TokenUsage, String> {
     let conversation_path = mission_dir.join("conversation.md");
 
+    let mut initial = current_tokens(&conversation_path, pricing)?;
+    if budget.check(&mut initial) {
+        return Ok(initial);
+    }
+    if let Some(reason) = options.cancel.requested() {
+        initial.cancelled = Some(reason);
+        return Ok(initial);
+    }
+
     // If file doesn't exist, wait for it
     if !conversation_path.exists() {
         // Create parent dir if needed
@@ -30,66 +402,378 @@ pub fn watch_conversation_tokens(
         }
     }
 
-    let (tx, rx) = mpsc::channel();
+    // Watch the mission directory
+    let deadline = options.clock.now() + Duration::from_secs(timeout_secs);
+    let watch_dir = options.watch_path(mission_dir);
+    let fs_watch = FsWatch::new(&watch_dir, RecursiveMode::NonRecursive, options, deadline)
+        .map_err(|e| format!("Failed to watch directory: {}", e))?;
 
-    let mut watcher = RecommendedWatcher::new(
-        move |res: Result<notify::Event, notify::Error>| {
-            if let Ok(event) = res {
-                if event.kind.is_modify() || event.kind.is_create() {
-                    let _ = tx.send(());
-                }
-            }
-        },
-        Config::default(),
-    )
-    .map_err(|e| format!("Failed to create watcher: {}", e))?;
+    // Wait for file change or timeout; rotated away means wait for the new file
+    let changed = fswatch::wait_for(&fs_watch, deadline, |event| {
+        Ok(
+            (!rotated_away(event) && (event.kind.is_modify() || event.kind.is_create()))
+                .then_some(()),
+        )
+    })
+    .map_err(|e| format!("Watch error: {}", e))?;
 
-    // Watch the mission directory
-    watcher
-        .watch(mission_dir, RecursiveMode::NonRecursive)
+    // Changed, timed out or cancelled: count current tokens if file exists
+    let mut usage = current_tokens(&conversation_path, pricing)?;
+    if changed.is_none() {
+        usage.cancelled = fs_watch.cancelled();
+    }
+    budget.check(&mut usage);
+    Ok(usage)
+}
+
+/// Keep watching conversation.md until the timeout, calling `on_usage` with
+/// the token counts each time its content changes.
+///
+/// When conversation.md is removed or renamed away, as when the orchestrator
+/// rotates it to conversation.1.md, the count starts over from the new file
+/// and `rotations` goes up by one.
+///
+/// Returns the count at the deadline, so the last line a follower prints is
+/// always the latest count, even when nothing changed. A count over `budget`
+/// ends the watch early: it is returned, marked `budget_exceeded`, instead of
+/// passed to `on_usage`. That includes the count before watching starts.
+/// Cancelling the watch ends it early too, returning the latest count marked
+/// `cancelled`.
+pub fn watch_conversation_tokens_stream<F>(
+    mission_dir: &Path,
+    timeout_secs: u64,
+    options: &WatchOptions,
+    pricing: &Pricing,
+    budget: &TokenBudget,
+    mut on_usage: F,
+) -> Result<TokenUsage, String>
+where
+    F: FnMut(&TokenUsage),
+{
+    let conversation_path = mission_dir.join("conversation.md");
+    fs::create_dir_all(mission_dir).map_err(|e| e.to_string())?;
+
+    let deadline = options.clock.now() + Duration::from_secs(timeout_secs);
+    let watch_dir = options.watch_path(mission_dir);
+    let fs_watch = FsWatch::new(&watch_dir, RecursiveMode::NonRecursive, options, deadline)
         .map_err(|e| format!("Failed to watch directory: {}", e))?;
 
-    let timeout = Duration::from_secs(timeout_secs);
-
-    // Wait for file change or timeout
-    match rx.recv_timeout(timeout) {
-        Ok(()) => {
-            // File changed, count tokens
-            count_tokens(&conversation_path)
-        }
-        Err(mpsc::RecvTimeoutError::Timeout) => {
-            // Timeout - count current tokens if file exists
-            if conversation_path.exists() {
-                count_tokens(&conversation_path)
-            } else {
-                Ok(TokenUsage {
-                    total_tokens: 0,
-                    estimated_cost_usd: 0.0,
-                    conversation_length: 0,
-                })
+    // A write can raise several events; only a new count is a change
+    let mut rotations = 0;
+    // Cleared by a rotation until the new file shows up, as one rename can
+    // raise several events
+    let mut present = true;
+    let mut last = current_tokens(&conversation_path, pricing)?;
+    if budget.check(&mut last) {
+        return Ok(last);
+    }
+    if let Some(reason) = options.cancel.requested() {
+        last.cancelled = Some(reason);
+        return Ok(last);
+    }
+    while let Some(event) = fs_watch
+        .next_debounced(deadline)
+        .map_err(|e| format!("Watch error: {}", e))?
+    {
+        if !event.paths.iter().any(|p| p.ends_with("conversation.md")) {
+            continue;
+        }
+        if rotated_away(&event) {
+            if present {
+                rotations += 1;
+            }
+            present = false;
+        } else if conversation_path.exists() {
+            present = true;
+        }
+        let mut usage = current_tokens(&conversation_path, pricing)?;
+        usage.rotations = rotations;
+        if usage != last {
+            if budget.check(&mut usage) {
+                return Ok(usage);
             }
+            on_usage(&usage);
+            last = usage;
         }
-        Err(e) => Err(format!("Watch error: {}", e)),
     }
+
+    let mut usage = current_tokens(&conversation_path, pricing)?;
+    usage.rotations = rotations;
+    usage.cancelled = fs_watch.cancelled();
+    budget.check(&mut usage);
+    Ok(usage)
 }
 
-/// Count tokens in conversation.md
+/// Token counts of conversation.md, all zero while it doesn't exist.
+fn current_tokens(path: &Path, pricing: &Pricing) -> Result<TokenUsage, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(pricing.count(&content)),
+        // Rotated away between the event and the read
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Ok(pricing.usage(TokenSplit::default(), 0))
+        }
+        Err(e) => Err(format!("Failed to read file: {}", e)),
+    }
+}
+
+/// Whether `event` is conversation.md being removed or renamed to another
+/// name. A rename's first path is the old name.
+fn rotated_away(event: &Event) -> bool {
+    let from_conversation = event
+        .paths
+        .first()
+        .is_some_and(|p| p.ends_with("conversation.md"));
+    match event.kind {
+        EventKind::Remove(_) => from_conversation,
+        EventKind::Modify(ModifyKind::Name(RenameMode::From | RenameMode::Both)) => {
+            from_conversation
+        }
+        // Backends that can't tell the two ends apart
+        EventKind::Modify(ModifyKind::Name(RenameMode::Any)) => {
+            from_conversation && !event.paths[0].exists()
+        }
+        _ => false,
+    }
+}
+
+/// Count tokens in conversation.md at the default prices
 pub fn count_tokens(path: &Path) -> Result<TokenUsage, String> {
+    count_tokens_priced(path, &Pricing::default())
+}
+
+/// Count tokens in conversation.md, costed with `pricing`
+pub fn count_tokens_priced(path: &Path, pricing: &Pricing) -> Result<TokenUsage, String> {
     let content = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    Ok(pricing.count(&content))
+}
+
+/// Estimate cost at the default prices (rough estimate)
+pub fn estimate_cost_usd(tokens: usize) -> f64 {
+    let (input, output) = Pricing::default().cost(tokens);
+    input + output
+}
+
+/// Token counts of several files and their total.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MultiFileTokenUsage {
+    pub files: Vec<FileTokens>,
+    /// Sum over the files counted; `conversation_length` is their total size
+    #[serde(flatten)]
+    pub total: TokenUsage,
+}
+
+/// One file's entry in a [`MultiFileTokenUsage`]: its counts, or why it was
+/// skipped.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileTokens {
+    pub path: String,
+    #[serde(flatten)]
+    pub usage: Option<TokenUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+impl MultiFileTokenUsage {
+    fn new(files: Vec<FileTokens>, pricing: &Pricing) -> Self {
+        let mut split = TokenSplit::default();
+        let mut length = 0;
+        for usage in files.iter().filter_map(|f| f.usage.as_ref()) {
+            let input =
+                usage.uncached_input_tokens + usage.cache_read_tokens + usage.cache_write_tokens;
+            split.uncached_input += usage.uncached_input_tokens;
+            split.cache_read += usage.cache_read_tokens;
+            split.cache_write += usage.cache_write_tokens;
+            split.output += usage.total_tokens - input;
+            length += usage.conversation_length;
+        }
+        MultiFileTokenUsage {
+            files,
+            total: pricing.usage(split, length),
+        }
+    }
+}
+
+/// Count tokens in each of `paths`. A file that can't be read is an error.
+pub fn count_files(paths: &[PathBuf], pricing: &Pricing) -> Result<MultiFileTokenUsage, String> {
+    let files = paths
+        .iter()
+        .map(|path| {
+            let usage = count_tokens_priced(path, pricing)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            Ok(FileTokens {
+                path: path.display().to_string(),
+                usage: Some(usage),
+                warning: None,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(MultiFileTokenUsage::new(files, pricing))
+}
 
+/// Count tokens in text read from `reader`, reported as the file `-`.
+pub fn count_reader(
+    mut reader: impl Read,
+    pricing: &Pricing,
+) -> Result<MultiFileTokenUsage, String> {
+    let mut text = String::new();
+    reader
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Failed to read input: {}", e))?;
+    let file = FileTokens {
+        path: "-".to_string(),
+        usage: Some(pricing.count(&text)),
+        warning: None,
+    };
+    Ok(MultiFileTokenUsage::new(vec![file], pricing))
+}
+
+/// Count tokens in every file matching `patterns`, relative to `base`.
+///
+/// Patterns use `*` and `?` within a path component and `**` for any number
+/// of directories; hidden entries only match a component that starts with
+/// `.`. A path that doesn't exist, a pattern matching nothing and a file that
+/// can't be read each get an entry with a `warning` instead of counts, so one
+/// bad path doesn't hide the rest.
+pub fn count_glob(
+    base: &Path,
+    patterns: &[String],
+    pricing: &Pricing,
+) -> Result<MultiFileTokenUsage, String> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let mut paths = Vec::new();
+        let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+        let root = if pattern.starts_with('/') {
+            PathBuf::from("/")
+        } else {
+            base.to_path_buf()
+        };
+        glob_walk(&root, &components, &mut paths);
+        paths.sort();
+        paths.dedup();
+        if paths.is_empty() {
+            files.push(skipped(pattern.clone(), "no files match"));
+        }
+        for path in paths {
+            let shown = path
+                .strip_prefix(base)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            if !path.exists() {
+                files.push(skipped(shown, "no such file"));
+                continue;
+            }
+            files.push(match count_tokens_priced(&path, pricing) {
+                Ok(usage) => FileTokens {
+                    path: shown,
+                    usage: Some(usage),
+                    warning: None,
+                },
+                Err(e) => skipped(shown, &e),
+            });
+        }
+    }
+    Ok(MultiFileTokenUsage::new(files, pricing))
+}
+
+fn skipped(path: String, warning: &str) -> FileTokens {
+    FileTokens {
+        path,
+        usage: None,
+        warning: Some(warning.to_string()),
+    }
+}
+
+/// Collect the paths under `dir` matching `components`. Literal components
+/// are joined without checking they exist, so a missing file named outright
+/// is still collected; directories matched by a wildcard are left out.
+fn glob_walk(dir: &Path, components: &[&str], out: &mut Vec<PathBuf>) {
+    let Some((first, rest)) = components.split_first() else {
+        out.push(dir.to_path_buf());
+        return;
+    };
+    if *first == "**" {
+        glob_walk(dir, rest, out);
+        for entry in glob_entries(dir, "*") {
+            if entry.is_dir() {
+                glob_walk(&entry, components, out);
+            }
+        }
+    } else if !first.contains(['*', '?']) {
+        glob_walk(&dir.join(first), rest, out);
+    } else {
+        for entry in glob_entries(dir, first) {
+            if !rest.is_empty() || entry.is_file() {
+                glob_walk(&entry, rest, out);
+            }
+        }
+    }
+}
+
+/// Entries of `dir` whose names match `pattern`; none if it can't be read.
+fn glob_entries(dir: &Path, pattern: &str) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            (pattern.starts_with('.') || !name.starts_with('.'))
+                && wildcard_match(pattern.as_bytes(), name.as_bytes())
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Whether `name` matches `pattern`, where `*` is any run of bytes and `?`
+/// any one byte.
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            wildcard_match(rest, name)
+                || name
+                    .split_first()
+                    .is_some_and(|(_, tail)| wildcard_match(pattern, tail))
+        }
+        (Some((b'?', rest)), Some((_, tail))) => wildcard_match(rest, tail),
+        (Some((p, rest)), Some((n, tail))) => p == n && wildcard_match(rest, tail),
+        _ => false,
+    }
+}
+
+/// Per-turn token counts for conversation.md content.
+///
+/// Human messages after the last assistant response form a final turn that
+/// is still waiting for its response.
+pub fn turn_breakdown(content: &str) -> Vec<TurnTokens> {
     let counter = TokenCounter::new();
-    let total_tokens = counter.count(&content);
+    let mut turns = Vec::new();
+    let mut pending = 0;
 
-    // Estimate cost using Claude pricing (rough estimate)
-    // Input: $3/MTok, Output: $15/MTok - assume 50/50 split
-    let avg_cost_per_token = (0.003 + 0.015) / 2.0 / 1000.0;
-    let estimated_cost_usd = total_tokens as f64 * avg_cost_per_token;
+    for turn in conversation::parse_conversation(content) {
+        pending += counter.count(&turn.content);
+        if turn.role == Role::Assistant {
+            turns.push(TurnTokens {
+                turn: turns.len() + 1,
+                timestamp: turn.timestamp,
+                tokens: pending,
+            });
+            pending = 0;
+        }
+    }
 
-    Ok(TokenUsage {
-        total_tokens,
-        estimated_cost_usd,
-        conversation_length: content.len(),
-    })
+    if pending > 0 {
+        turns.push(TurnTokens {
+            turn: turns.len() + 1,
+            timestamp: None,
+            tokens: pending,
+        });
+    }
+    turns
 }
 
 /// Count tokens in a string (for one-off counting)
@@ -101,7 +785,10 @@ pub fn count_string_tokens(text: &str) -> usize {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
     use std::io::Write;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
     use tempfile::TempDir;
 
     #[test]
@@ -110,13 +797,419 @@ mod tests {
         let path = dir.path().join("conversation.md");
 
         let mut file = fs::File::create(&path).unwrap();
-        writeln!(file, "## User\nHello, how are you?\n\n## Assistant\nI'm doing well, thank you for asking!").unwrap();
+        writeln!(
+            file,
+            "## User\nHello, how are you?\n\n## Assistant\nI'm doing well, thank you for asking!"
+        )
+        .unwrap();
 
         let usage = count_tokens(&path).unwrap();
         assert!(usage.total_tokens > 0);
         assert!(usage.estimated_cost_usd > 0.0);
     }
 
+    #[test]
+    fn test_pricing_by_model() {
+        let table = PricingTable::default();
+        // Dated releases match their family's prefix, the longest one winning
+        assert_eq!(table.get("claude-opus-4-20250514").unwrap().input, 15.0);
+        assert_eq!(table.get("claude-opus-4-5-20251101").unwrap().input, 5.0);
+        assert_eq!(table.get("claude-3-5-haiku-latest").unwrap().output, 4.0);
+        assert!(table.get("gpt-4o").is_none());
+
+        let pricing = table.pricing(Some("claude-3-haiku-20240307"), None, None);
+        assert!(!pricing.assumed);
+        let usage = pricing.usage(TokenSplit::new(2_000_000, 0), 100);
+        assert_eq!(usage.model.as_deref(), Some("claude-3-haiku-20240307"));
+        assert_eq!(usage.input_cost_usd, 0.25);
+        assert_eq!(usage.output_cost_usd, 1.25);
+        assert_eq!(usage.estimated_cost_usd, 1.5);
+    }
+
+    #[test]
+    fn test_usage_round_trips_through_json() {
+        let mut usage = PricingTable::default()
+            .pricing(Some("mystery-model"), None, None)
+            .count("## Human\nHello\n");
+        usage.rotations = 2;
+        let json = serde_json::to_string(&usage).unwrap();
+        assert_eq!(serde_json::from_str::<TokenUsage>(&json).unwrap(), usage);
+
+        // Flags left out when unset read back as unset
+        let usage = Pricing::default().count("Hello");
+        let json = serde_json::to_string(&usage).unwrap();
+        assert!(!json.contains("rotations"));
+        assert_eq!(serde_json::from_str::<TokenUsage>(&json).unwrap(), usage);
+    }
+
+    #[test]
+    fn test_unknown_model_assumes_default_pricing() {
+        let table = PricingTable::default();
+        let pricing = table.pricing(Some("gpt-4o"), None, None);
+        assert!(pricing.assumed);
+        assert_eq!(pricing.price, DEFAULT_PRICE);
+        let json = serde_json::to_value(pricing.count("ten tokens")).unwrap();
+        assert_eq!(json["pricing_assumed"], true);
+
+        // Both prices given: nothing is assumed
+        let pricing = table.pricing(Some("gpt-4o"), Some(2.5), Some(10.0));
+        assert!(!pricing.assumed);
+        assert_eq!(pricing.cost(2_000_000), (2.5, 10.0));
+
+        // An override beats the table
+        let pricing = table.pricing(Some("claude-sonnet-4"), Some(1.0), None);
+        assert_eq!((pricing.price.input, pricing.price.output), (1.0, 15.0));
+
+        // No model, no flag
+        let json = serde_json::to_value(Pricing::default().count("ten tokens")).unwrap();
+        assert!(json.get("pricing_assumed").is_none());
+        assert!(json.get("model").is_none());
+        assert_eq!(estimate_cost_usd(1_000_000), 9.0);
+    }
+
+    #[test]
+    fn test_estimate_cost_prices_cache_separately() {
+        let split = TokenSplit {
+            uncached_input: 1_000_000,
+            cache_read: 2_000_000,
+            cache_write: 1_000_000,
+            output: 1_000_000,
+        };
+        // 3 + 2 * 0.30 + 3.75 input, 15 output
+        let (input, output) = estimate_cost(&split, &DEFAULT_PRICE, None);
+        assert!((input - 7.35).abs() < 1e-9, "{}", input);
+        assert_eq!(output, 15.0);
+        // A discount prices reads against the input price instead
+        let (input, _) = estimate_cost(&split, &DEFAULT_PRICE, Some(0.5));
+        assert!((input - 9.75).abs() < 1e-9, "{}", input);
+    }
+
+    #[test]
+    fn test_token_split_takes_prefix_share_as_cached() {
+        assert_eq!(
+            TokenSplit::new(1000, 750),
+            TokenSplit {
+                uncached_input: 125,
+                cache_read: 375,
+                cache_write: 0,
+                output: 500,
+            }
+        );
+        assert_eq!(TokenSplit::new(7, 0).uncached_input, 3);
+        assert_eq!(TokenSplit::new(7, 0).output, 4);
+        assert_eq!(TokenSplit::new(0, 0), TokenSplit::default());
+
+        let marked = TokenSplit::new(1000, 0).with_marker(CacheMarker {
+            read: 400,
+            write: 300,
+        });
+        assert_eq!(
+            (marked.uncached_input, marked.cache_read, marked.cache_write),
+            (0, 400, 100)
+        );
+    }
+
+    #[test]
+    fn test_cache_discount_applies_before_last_human_section() {
+        let content = "## Human\nExplain the parser in detail\n\n---\n\n## Assistant\nIt reads lines and splits them into turns\n\n---END---\n\n## Human\nThanks\n";
+        assert!(cached_prefix(content).ends_with("---END---\n\n"));
+
+        let full = Pricing::default().count(content);
+        assert_eq!(full.cache_read_tokens, 0);
+        let cached = Pricing::default()
+            .with_cache_discount(Some(0.1))
+            .count(content);
+        assert!(cached.cache_read_tokens > 0);
+        assert!(cached.uncached_input_tokens < full.uncached_input_tokens);
+        assert_eq!(cached.total_tokens, full.total_tokens);
+        assert!(cached.estimated_cost_usd < full.estimated_cost_usd);
+    }
+
+    #[test]
+    fn test_cache_marker() {
+        let content =
+            "## Human\nHi\n<!-- cache: read=10 write=2 -->\n<!-- cache: read=40 write=5 -->\n";
+        assert_eq!(
+            cache_marker(content),
+            Some(CacheMarker { read: 40, write: 5 })
+        );
+        assert_eq!(cache_marker("<!-- cache: read=many -->"), None);
+        assert_eq!(cache_marker("<!-- not a marker -->"), None);
+    }
+
+    #[test]
+    fn test_watch_tokens_timeout_counts_current_file() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("conversation.md"),
+            "## Human\nHello there\n",
+        )
+        .unwrap();
+
+        let options = WatchOptions {
+            clock: Arc::new(MockClock::with_auto_advance(Duration::from_secs(60))),
+            ..WatchOptions::default()
+        };
+        let usage = watch_conversation_tokens(
+            dir.path(),
+            300,
+            &options,
+            &Pricing::default(),
+            &TokenBudget::default(),
+        )
+        .unwrap();
+        assert!(usage.total_tokens > 0);
+    }
+
+    #[test]
+    fn test_stream_emits_each_change() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("conversation.md");
+        fs::write(&path, "## Human\nHello there\n").unwrap();
+
+        let writer = std::thread::spawn(move || {
+            for reply in ["\n## Assistant\nHi!\n", "\n## Human\nHow are the tests?\n"] {
+                std::thread::sleep(Duration::from_millis(600));
+                let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+                file.write_all(reply.as_bytes()).unwrap();
+            }
+        });
+
+        let mut seen = Vec::new();
+        let last = watch_conversation_tokens_stream(
+            dir.path(),
+            2,
+            &WatchOptions::default(),
+            &Pricing::default(),
+            &TokenBudget::default(),
+            |usage| seen.push(usage.clone()),
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        assert_eq!(seen.len(), 2, "{:?}", seen);
+        assert!(seen[0].total_tokens < seen[1].total_tokens);
+        assert_eq!(last, seen[1]);
+    }
+
+    #[test]
+    fn test_stream_survives_rotation() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("conversation.md");
+        fs::write(
+            &path,
+            "## Human\nA conversation that has grown far too long\n",
+        )
+        .unwrap();
+
+        let mission = dir.path().to_path_buf();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            fs::rename(&path, mission.join("conversation.1.md")).unwrap();
+            // Long enough for the removal to clear the debounce window
+            std::thread::sleep(Duration::from_millis(600));
+            fs::write(&path, "## Human\nFresh\n").unwrap();
+        });
+
+        let mut seen = Vec::new();
+        let last = watch_conversation_tokens_stream(
+            dir.path(),
+            2,
+            &WatchOptions::default(),
+            &Pricing::default(),
+            &TokenBudget::default(),
+            |usage| seen.push(usage.clone()),
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        // Down to nothing once it's gone, then the new file's count
+        assert_eq!(seen[0].rotations, 1, "{:?}", seen);
+        assert_eq!(seen[0].total_tokens, 0);
+        assert_eq!(last.rotations, 1);
+        assert_eq!(
+            last.total_tokens,
+            Pricing::default().count("## Human\nFresh\n").total_tokens
+        );
+        assert_eq!(seen.last(), Some(&last));
+    }
+
+    #[test]
+    fn test_stream_without_changes_returns_current_count() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("conversation.md"), "## Human\nHello\n").unwrap();
+
+        let options = WatchOptions {
+            clock: Arc::new(MockClock::with_auto_advance(Duration::from_secs(60))),
+            ..WatchOptions::default()
+        };
+        let mut calls = 0;
+        let usage = watch_conversation_tokens_stream(
+            dir.path(),
+            300,
+            &options,
+            &Pricing::default(),
+            &TokenBudget::default(),
+            |_| calls += 1,
+        )
+        .unwrap();
+        assert_eq!(calls, 0);
+        assert!(usage.total_tokens > 0);
+    }
+
+    #[test]
+    fn test_stream_cancelled_keeps_latest_count() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("conversation.md");
+        fs::write(&path, "## Human\nHello\n").unwrap();
+
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+            file.write_all(b"\n## Assistant\nHi!\n").unwrap();
+        });
+
+        // Cancelled as if by a signal once the first change is seen
+        let options = WatchOptions::default();
+        let usage = watch_conversation_tokens_stream(
+            dir.path(),
+            300,
+            &options,
+            &Pricing::default(),
+            &TokenBudget::default(),
+            |_| options.cancel.flag.store(true, Ordering::Relaxed),
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        assert_eq!(usage.cancelled, Some(CancelReason::Signal));
+        assert_eq!(usage.conversation_length, 33);
+    }
+
+    #[test]
+    fn test_over_budget_returns_before_watching() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("conversation.md"),
+            "## Human\nWrite me a long essay\n",
+        )
+        .unwrap();
+
+        // Real clock and a long timeout: only tripping at once gets past this
+        let budget = TokenBudget {
+            max_tokens: Some(2),
+            max_cost_usd: None,
+        };
+        let options = WatchOptions::default();
+        let usage =
+            watch_conversation_tokens(dir.path(), 300, &options, &Pricing::default(), &budget)
+                .unwrap();
+        assert!(usage.budget_exceeded);
+        assert_eq!(usage.budget_limit, Some(BudgetLimit::MaxTokens));
+
+        let budget = TokenBudget {
+            max_tokens: Some(1_000),
+            max_cost_usd: Some(0.000_001),
+        };
+        let mut calls = 0;
+        let usage = watch_conversation_tokens_stream(
+            dir.path(),
+            300,
+            &options,
+            &Pricing::default(),
+            &budget,
+            |_| calls += 1,
+        )
+        .unwrap();
+        assert_eq!(calls, 0);
+        assert_eq!(usage.budget_limit, Some(BudgetLimit::MaxCostUsd));
+        let json = serde_json::to_value(&usage).unwrap();
+        assert_eq!(json["budget_exceeded"], true);
+        assert_eq!(json["budget_limit"], "max_cost_usd");
+    }
+
+    #[test]
+    fn test_turn_breakdown_groups_human_messages_with_response() {
+        let content = "## Human\nFirst question\n\n---\n\n## Assistant [2026-01-22T10:00:00Z]\nFirst answer\n\n---END---\n\n## Human\nFollow-up\n\n---\n";
+        let turns = turn_breakdown(content);
+
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].turn, 1);
+        assert_eq!(turns[0].timestamp.as_deref(), Some("2026-01-22T10:00:00Z"));
+        assert_eq!(
+            turns[0].tokens,
+            count_string_tokens("First question") + count_string_tokens("First answer")
+        );
+        assert_eq!(turns[1].timestamp, None);
+        assert_eq!(turns[1].tokens, count_string_tokens("Follow-up"));
+    }
+
+    #[test]
+    fn test_count_glob_per_file_with_warnings() {
+        let dir = TempDir::new().unwrap();
+        let tasks = dir.path().join("tasks");
+        fs::create_dir_all(tasks.join("done")).unwrap();
+        fs::write(tasks.join("a.md"), "Write the parser").unwrap();
+        fs::write(tasks.join("b.md"), "Test the parser thoroughly").unwrap();
+        fs::write(tasks.join("notes.txt"), "not a task").unwrap();
+        fs::write(tasks.join("done/c.md"), "Done").unwrap();
+
+        let patterns = [
+            "tasks/*.md".to_string(),
+            "tasks/missing.md".to_string(),
+            "specs/*.md".to_string(),
+        ];
+        let usage = count_glob(dir.path(), &patterns, &Pricing::default()).unwrap();
+        let paths: Vec<&str> = usage.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["tasks/a.md", "tasks/b.md", "tasks/missing.md", "specs/*.md"]
+        );
+        assert_eq!(usage.files[2].warning.as_deref(), Some("no such file"));
+        assert_eq!(usage.files[3].warning.as_deref(), Some("no files match"));
+        assert_eq!(
+            usage.total.total_tokens,
+            count_string_tokens("Write the parser")
+                + count_string_tokens("Test the parser thoroughly")
+        );
+
+        let json = serde_json::to_value(&usage).unwrap();
+        assert_eq!(
+            json["files"][0]["total_tokens"],
+            count_string_tokens("Write the parser")
+        );
+        assert!(json["files"][2].get("total_tokens").is_none());
+
+        let nested = count_glob(dir.path(), &["**/*.md".to_string()], &Pricing::default());
+        assert_eq!(nested.unwrap().files.len(), 3);
+    }
+
+    #[test]
+    fn test_count_files_and_reader() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("context.md");
+        fs::write(&path, "Some context").unwrap();
+
+        let usage = count_files(&[path], &Pricing::default()).unwrap();
+        assert_eq!(
+            usage.total.total_tokens,
+            count_string_tokens("Some context")
+        );
+        assert!(count_files(&[dir.path().join("nope.md")], &Pricing::default()).is_err());
+
+        let usage = count_reader("Some context".as_bytes(), &Pricing::default()).unwrap();
+        assert_eq!(usage.files[0].path, "-");
+        assert_eq!(usage.total.conversation_length, 12);
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match(b"*.md", b"task.md"));
+        assert!(wildcard_match(b"task-??.md", b"task-01.md"));
+        assert!(!wildcard_match(b"*.md", b"task.mdx"));
+        assert!(wildcard_match(b"*", b""));
+    }
+
     #[test]
     fn test_count_string_tokens() {
         let tokens = count_string_tokens("Hello world");
@@ -1,89 +1,715 @@
-use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
-use serde::Serialize;
-use std::path::Path;
-use std::sync::mpsc::channel;
-use std::time::Duration;
+use crate::cancel::CancelReason;
+use crate::error::McError;
+use crate::fswatch::{self, FsWatch, WatchOptions};
+use crate::protocol::{self, Layout, Priority, TaskFilter, TaskPaths};
+use notify::{Event, RecursiveMode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "status")]
 pub enum WatchResult {
     #[serde(rename = "complete")]
-    Complete { response_path: String },
+    Complete {
+        /// What the agent wrote in the status file
+        task_status: TaskStatus,
+        /// Text after `FAILED:` or `BLOCKED:`, or the whole file when its
+        /// status isn't recognized
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+        response_path: String,
+        /// The status file appeared but the response file didn't
+        response_exists: bool,
+        /// Re-reads needed before the status and response files stopped changing
+        settle_retries: u32,
+    },
     #[serde(rename = "timeout")]
     Timeout,
+    /// Stopped by [`WatchOptions::cancel`] before the status file appeared
+    #[serde(rename = "cancelled")]
+    Cancelled { reason: CancelReason },
+    /// The status file appeared but, with the response required, the
+    /// response never became ready before the deadline
+    #[serde(rename = "status_only")]
+    StatusOnly {
+        status_path: String,
+        response_path: String,
+    },
+}
+
+/// How long a response's size must hold still to count as written.
+pub const RESPONSE_STABLE_FOR: Duration = Duration::from_millis(200);
+
+/// How a task ended, from the first line of its status file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    /// `DONE`
+    Done,
+    /// `FAILED: reason`
+    Failed,
+    /// `BLOCKED: reason`
+    Blocked,
+    /// Anything else, including an empty file
+    Unknown,
+}
+
+/// Parse status file content into its status and message.
+pub fn parse_status(content: &str) -> (TaskStatus, Option<String>) {
+    let content = content.trim();
+    let first = content.lines().next().unwrap_or_default();
+    let (keyword, rest) = first.split_once(':').unwrap_or((first, ""));
+    let status = match keyword.trim().to_ascii_uppercase().as_str() {
+        "DONE" => TaskStatus::Done,
+        "FAILED" => TaskStatus::Failed,
+        "BLOCKED" => TaskStatus::Blocked,
+        _ => TaskStatus::Unknown,
+    };
+    let message = match status {
+        TaskStatus::Unknown => content,
+        _ => rest.trim(),
+    };
+    let message = (!message.is_empty()).then(|| message.to_string());
+    (status, message)
 }
 
 /// Watch for task completion by monitoring the status directory for a status file.
 ///
 /// Returns when `.mission/status/task-{id}.status` file appears, or on timeout.
+/// The response path is reported under `mission_dir` as given, even when it
+/// is a symlink and the watch runs on its target.
 pub fn watch_task(
     task_id: &str,
     mission_dir: &str,
     timeout: Duration,
-) -> Result<WatchResult, Box<dyn std::error::Error>> {
-    let status_dir = Path::new(mission_dir).join("status");
-    let expected_file = format!("task-{}.status", task_id);
+    options: &WatchOptions,
+) -> Result<WatchResult, McError> {
+    watch_task_with_progress(
+        task_id,
+        mission_dir,
+        timeout,
+        options,
+        false,
+        timeout,
+        |_| {},
+    )
+}
 
-    // Ensure status directory exists
-    if !status_dir.exists() {
+/// Something seen while [`watch_task_with_progress`] waits.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// Still waiting, sent once per heartbeat interval
+    Waiting { task_id: String, elapsed_secs: u64 },
+    /// One of the task's files in the status or responses dir changed size,
+    /// like a response still being written
+    Activity {
+        task_id: String,
+        path: String,
+        /// Size now; missing when the file was removed
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size: Option<u64>,
+    },
+}
+
+/// Like [`watch_task`], calling `on_progress` every `heartbeat` while nothing
+/// has completed and whenever one of the task's files changes size. A zero
+/// `heartbeat` sends no heartbeats.
+///
+/// The responses dir is watched too when it exists at the start. Activity
+/// paths are reported under `mission_dir` as given. A nested task's dir is
+/// never created: until it appears, the nearest dir above it is watched.
+///
+/// With `require_response`, the status file alone doesn't complete the task:
+/// the response must also exist, be non-empty and either pass
+/// [`validate_response`](crate::protocol::validate_response) or hold its size
+/// for [`RESPONSE_STABLE_FOR`]. The responses dir is created and watched for
+/// this. A status file whose response never gets there ends in
+/// [`WatchResult::StatusOnly`].
+pub fn watch_task_with_progress<F>(
+    task_id: &str,
+    mission_dir: &str,
+    timeout: Duration,
+    options: &WatchOptions,
+    require_response: bool,
+    heartbeat: Duration,
+    mut on_progress: F,
+) -> Result<WatchResult, McError>
+where
+    F: FnMut(ProgressEvent),
+{
+    let paths = task_paths(task_id, mission_dir, options);
+    let status_dir = parent_dir(&paths.status);
+    let responses_dir = parent_dir(&paths.response);
+    let expected_file = file_name(&paths.status);
+    let response_file = file_name(&paths.response);
+    // Every file in a nested task's directory is the task's
+    let task_prefix = match paths.layout {
+        Layout::Flat => format!("task-{}.", task_id),
+        Layout::Nested => String::new(),
+    };
+
+    // Ensure the flat layout's status directory exists
+    if paths.layout == Layout::Flat {
         std::fs::create_dir_all(&status_dir)?;
+        if require_response {
+            std::fs::create_dir_all(&responses_dir)?;
+        }
     }
+    let ready = |deadline: Instant| {
+        if !paths.status.exists() {
+            return None;
+        }
+        if require_response && !response_ready(&paths, task_id, options, deadline) {
+            return None;
+        }
+        Some(complete(&paths, options, deadline))
+    };
 
-    // Check if already complete
-    let status_path = status_dir.join(&expected_file);
-    if status_path.exists() {
-        let response_path = Path::new(mission_dir)
-            .join("responses")
-            .join(format!("task-{}.md", task_id));
-        return Ok(WatchResult::Complete {
-            response_path: response_path.to_string_lossy().to_string(),
-        });
+    if let Some(reason) = options.cancel.requested() {
+        return Ok(WatchResult::Cancelled { reason });
+    }
+
+    // Check if already complete; any setup retries come out of the timeout
+    let start = options.clock.now();
+    let deadline = start + timeout;
+    if let Some(result) = ready(deadline) {
+        return Ok(result);
     }
 
-    // Set up watcher
-    let (tx, rx) = channel();
-    let mut watcher = RecommendedWatcher::new(tx, Config::default())?;
-    watcher.watch(&status_dir, RecursiveMode::NonRecursive)?;
+    // Set up watcher; a nested task's directory is watched whole
+    let mode = match paths.layout {
+        Layout::Flat => RecursiveMode::NonRecursive,
+        Layout::Nested => RecursiveMode::Recursive,
+    };
+    let mut dirs = vec![status_dir.clone()];
+    if responses_dir != status_dir && responses_dir.is_dir() {
+        dirs.push(responses_dir.clone());
+    }
+    let plan = || -> Vec<(PathBuf, RecursiveMode)> {
+        dirs.iter()
+            .map(|dir| watch_root(dir, mode, options))
+            .collect()
+    };
+    let mut roots = plan();
+    let mut fs_watch = FsWatch::with_roots(&roots, options, deadline)?;
 
-    // Wait for file creation
-    let deadline = std::time::Instant::now() + timeout;
+    // Wait for file creation, beating in between
+    let heartbeat = if heartbeat.is_zero() {
+        timeout
+    } else {
+        heartbeat
+    };
+    let mut next_beat = start + heartbeat;
+    let mut sizes: HashMap<PathBuf, Option<u64>> = HashMap::new();
     loop {
-        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
-        if remaining.is_zero() {
-            return Ok(WatchResult::Timeout);
-        }
-
-        match rx.recv_timeout(remaining) {
-            Ok(Ok(event)) => {
-                // Check if the expected file was created
-                if event.paths.iter().any(|p| {
-                    p.file_name()
-                        .map(|n| n.to_string_lossy() == expected_file)
-                        .unwrap_or(false)
-                }) {
-                    let response_path = Path::new(mission_dir)
-                        .join("responses")
-                        .join(format!("task-{}.md", task_id));
-                    return Ok(WatchResult::Complete {
-                        response_path: response_path.to_string_lossy().to_string(),
+        let Some(event) = fs_watch.next_debounced(next_beat.min(deadline))? else {
+            if let Some(reason) = fs_watch.cancelled() {
+                return Ok(WatchResult::Cancelled { reason });
+            }
+            let now = options.clock.now();
+            if now >= deadline {
+                if require_response && paths.status.exists() {
+                    return Ok(WatchResult::StatusOnly {
+                        status_path: paths.status.to_string_lossy().to_string(),
+                        response_path: paths.response.to_string_lossy().to_string(),
                     });
                 }
-            }
-            Ok(Err(e)) => return Err(Box::new(e)),
-            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
                 return Ok(WatchResult::Timeout);
             }
-            Err(e) => return Err(Box::new(e)),
+            on_progress(ProgressEvent::Waiting {
+                task_id: task_id.to_string(),
+                elapsed_secs: (now - start).as_secs(),
+            });
+            next_beat += heartbeat;
+            continue;
+        };
+
+        // Move down as the task's dir comes into being; until it does, the
+        // dir standing in for it only matters for that
+        let wanted = plan();
+        if wanted != roots {
+            roots = wanted;
+            fs_watch = FsWatch::with_roots(&roots, options, deadline)?;
+            if let Some(result) = ready(deadline) {
+                return Ok(result);
+            }
+            continue;
         }
+        if !status_dir.is_dir() {
+            continue;
+        }
+
+        for path in &event.paths {
+            let Some(name) = path.file_name().map(|n| n.to_string_lossy()) else {
+                continue;
+            };
+            // Check if the expected file was created
+            if name == expected_file {
+                match ready(deadline) {
+                    Some(result) => return Ok(result),
+                    None => continue,
+                }
+            }
+            if !name.starts_with(&task_prefix) {
+                continue;
+            }
+            let response_changed = require_response && name == response_file;
+            let size = std::fs::metadata(path).ok().map(|m| m.len());
+            if sizes.insert(path.clone(), size) == Some(size) {
+                continue;
+            }
+            // Reported under the dir as given rather than the watched path
+            let shown = roots
+                .iter()
+                .zip(&dirs)
+                .find_map(|((root, _), dir)| Some(dir.join(path.strip_prefix(root).ok()?)))
+                .unwrap_or_else(|| path.clone());
+            on_progress(ProgressEvent::Activity {
+                task_id: task_id.to_string(),
+                path: shown.to_string_lossy().to_string(),
+                size,
+            });
+            if response_changed {
+                if let Some(result) = ready(deadline) {
+                    return Ok(result);
+                }
+            }
+        }
+    }
+}
+
+/// Whether the task's response is fully written: non-empty, and either valid
+/// or the same size [`RESPONSE_STABLE_FOR`] later. Never waits past `deadline`.
+fn response_ready(
+    paths: &TaskPaths,
+    task_id: &str,
+    options: &WatchOptions,
+    deadline: Instant,
+) -> bool {
+    let path = &paths.response;
+    let size = match std::fs::metadata(path) {
+        Ok(metadata) if metadata.len() > 0 => metadata.len(),
+        _ => return false,
+    };
+    let valid = protocol::validate_response(&path.to_string_lossy(), Some(task_id))
+        .is_ok_and(|result| result.valid);
+    if valid {
+        return true;
+    }
+    if options.clock.now() + RESPONSE_STABLE_FOR > deadline {
+        return false;
+    }
+    options.clock.sleep(RESPONSE_STABLE_FOR);
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.len() == size)
+}
+
+/// One task's completion in a [`watch_tasks`] run.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskRecord {
+    pub task_id: String,
+    #[serde(flatten)]
+    pub result: WatchResult,
+}
+
+/// How a [`watch_tasks`] run ended.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum WatchTasksResult {
+    /// Every task completed
+    Complete,
+    /// The deadline hit with these tasks still pending
+    Timeout { pending: Vec<String> },
+    /// Stopped by [`WatchOptions::cancel`] with these tasks still pending
+    Cancelled {
+        reason: CancelReason,
+        pending: Vec<String>,
+    },
+}
+
+/// Watch several tasks of one mission with a single watcher.
+///
+/// `on_record` is called as each task completes, in the order their status
+/// files appeared; tasks already complete are reported first, before any
+/// waiting. Returns once every task has completed or the deadline hits.
+pub fn watch_tasks<F>(
+    task_ids: &[String],
+    mission_dir: &str,
+    timeout: Duration,
+    options: &WatchOptions,
+    mut on_record: F,
+) -> Result<WatchTasksResult, McError>
+where
+    F: FnMut(&TaskRecord),
+{
+    let mut pending: Vec<(&String, TaskPaths)> = Vec::new();
+    for task_id in task_ids {
+        if !pending.iter().any(|(id, _)| *id == task_id) {
+            pending.push((task_id, task_paths(task_id, mission_dir, options)));
+        }
+    }
+
+    // The flat layout's status dir, and each nested task's own dir, or the
+    // nearest dir above it until it exists
+    for (_, paths) in &pending {
+        if paths.layout == Layout::Flat {
+            std::fs::create_dir_all(parent_dir(&paths.status))?;
+        }
+    }
+    let plan = |pending: &[(&String, TaskPaths)]| {
+        let mut roots: Vec<(PathBuf, RecursiveMode)> = Vec::new();
+        for (_, paths) in pending {
+            let mode = match paths.layout {
+                Layout::Flat => RecursiveMode::NonRecursive,
+                Layout::Nested => RecursiveMode::Recursive,
+            };
+            let root = watch_root(&parent_dir(&paths.status), mode, options);
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+        roots
+    };
+
+    let deadline = options.clock.now() + timeout;
+    let mut roots = plan(&pending);
+    let mut fs_watch = FsWatch::with_roots(&roots, options, deadline)?;
+
+    let mut complete_task = |task_id: &str, paths: &TaskPaths| {
+        on_record(&TaskRecord {
+            task_id: task_id.to_string(),
+            result: complete(paths, options, deadline),
+        });
+    };
+
+    if let Some(reason) = options.cancel.requested() {
+        return Ok(WatchTasksResult::Cancelled {
+            reason,
+            pending: pending.into_iter().map(|(id, _)| id.clone()).collect(),
+        });
+    }
+
+    // Sweep everything once the watcher is live, so nothing slips in
+    // between, then look at each event's paths
+    let mut event: Option<Event> = None;
+    loop {
+        match &event {
+            None => pending.retain(|(task_id, paths)| {
+                if paths.status.exists() {
+                    complete_task(task_id, paths);
+                    false
+                } else {
+                    true
+                }
+            }),
+            Some(event) => {
+                for path in &event.paths {
+                    let found = pending
+                        .iter()
+                        .position(|(_, paths)| is_status_file(path, &paths.status));
+                    if let Some(index) = found {
+                        let (task_id, paths) = pending.remove(index);
+                        complete_task(task_id, &paths);
+                    }
+                }
+            }
+        }
+
+        // A nested task's dir that has appeared needs a watch of its own;
+        // sweep again, as its status file may predate that watch
+        let wanted = plan(&pending);
+        if wanted.iter().any(|root| !roots.contains(root)) {
+            roots = wanted;
+            fs_watch = FsWatch::with_roots(&roots, options, deadline)?;
+            event = None;
+            continue;
+        }
+
+        if pending.is_empty() {
+            break;
+        }
+        event = match fs_watch.next_debounced(deadline)? {
+            Some(next) => Some(next),
+            None => break,
+        };
+    }
+
+    if pending.is_empty() {
+        return Ok(WatchTasksResult::Complete);
+    }
+    let pending = pending.into_iter().map(|(id, _)| id.clone()).collect();
+    match fs_watch.cancelled() {
+        Some(reason) => Ok(WatchTasksResult::Cancelled { reason, pending }),
+        None => Ok(WatchTasksResult::Timeout { pending }),
+    }
+}
+
+/// Return the completed result if the task's status file already exists.
+pub(crate) fn check_task(
+    task_id: &str,
+    mission_dir: &str,
+    options: &WatchOptions,
+    deadline: Instant,
+) -> Option<WatchResult> {
+    let paths = task_paths(task_id, mission_dir, options);
+    if paths.status.exists() {
+        Some(complete(&paths, options, deadline))
+    } else {
+        None
+    }
+}
+
+/// The root to watch for files in `dir`: the dir itself in `mode` once it
+/// exists, otherwise the nearest dir above it, non-recursively, to see it
+/// appear. Nothing is created.
+pub(crate) fn watch_root(
+    dir: &Path,
+    mode: RecursiveMode,
+    options: &WatchOptions,
+) -> (PathBuf, RecursiveMode) {
+    if dir.is_dir() {
+        return (options.watch_path(dir), mode);
+    }
+    let ancestor = dir
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.is_dir())
+        .unwrap_or(Path::new("."));
+    (options.watch_path(ancestor), RecursiveMode::NonRecursive)
+}
+
+/// The task's files in [`WatchOptions::layout`], or the layout it is in.
+pub(crate) fn task_paths(task_id: &str, mission_dir: &str, options: &WatchOptions) -> TaskPaths {
+    let mission_dir = Path::new(mission_dir);
+    let layout = options
+        .layout
+        .unwrap_or_else(|| Layout::detect(mission_dir, task_id));
+    layout.paths(mission_dir, task_id)
+}
+
+/// Whether an event's `path` is the status file at `status`, going by the
+/// file name and its dir's, as nested status files all share a name.
+fn is_status_file(path: &Path, status: &Path) -> bool {
+    path.file_name() == status.file_name()
+        && path.parent().and_then(Path::file_name) == status.parent().and_then(Path::file_name)
+}
+
+fn parent_dir(path: &Path) -> PathBuf {
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Build the completed result once the status and response files settle.
+fn complete(paths: &TaskPaths, options: &WatchOptions, deadline: Instant) -> WatchResult {
+    let response_path = &paths.response;
+
+    let mut settle_retries = 0;
+    let status_content = match fswatch::read_settled(&paths.status, options, deadline) {
+        Ok(settled) => {
+            settle_retries += settled.retries;
+            settled.content
+        }
+        Err(_) => String::new(),
+    };
+    if let Ok(settled) = fswatch::read_settled(response_path, options, deadline) {
+        settle_retries += settled.retries;
+    }
+
+    let (task_status, message) = parse_status(&status_content);
+    WatchResult::Complete {
+        task_status,
+        message,
+        response_exists: response_path.exists(),
+        response_path: response_path.to_string_lossy().to_string(),
+        settle_retries,
+    }
+}
+
+/// How a [`watch_inbox`] run ended.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum InboxResult {
+    /// An unclaimed task to work on
+    Task {
+        task_id: String,
+        task_path: String,
+        priority: Priority,
+        created: String,
+        /// Its claim file was created for this watcher
+        claimed: bool,
+    },
+    Timeout,
+    /// Stopped by [`WatchOptions::cancel`] before a task turned up
+    Cancelled {
+        reason: CancelReason,
+    },
+}
+
+/// Which tasks [`watch_inbox`] takes.
+#[derive(Debug, Clone, Default)]
+pub struct InboxOptions {
+    /// Only tasks of this priority
+    pub priority: Option<Priority>,
+    /// Create `status/task-{id}.claimed` for the task, and pass over tasks
+    /// another watcher claimed first
+    pub claim: bool,
+}
+
+/// Wait for a task in `{mission_dir}/tasks/` that nobody has picked up: no
+/// status file and no claim file.
+///
+/// Tasks already waiting are returned first, oldest by Created. Task files
+/// that don't pass [`validate_task`](protocol::validate_task) are passed
+/// over, which also covers files still being written, as are tasks whose
+/// dependencies aren't all done yet (see [`protocol::check_ready`]). With
+/// [`InboxOptions::claim`], the claim file is created with `create_new`, so
+/// of several watchers racing for one task exactly one gets it and the
+/// others keep waiting.
+pub fn watch_inbox(
+    mission_dir: &str,
+    timeout: Duration,
+    inbox: &InboxOptions,
+    options: &WatchOptions,
+) -> Result<InboxResult, McError> {
+    let mission_dir = Path::new(mission_dir);
+    let tasks_dir = mission_dir.join("tasks");
+    let status_dir = mission_dir.join("status");
+    // Status files are watched too, as they can make a waiting task ready
+    for dir in [&tasks_dir, &status_dir] {
+        if !dir.exists() {
+            std::fs::create_dir_all(dir)?;
+        }
+    }
+
+    let deadline = options.clock.now() + timeout;
+    let roots: Vec<(PathBuf, RecursiveMode)> = [&tasks_dir, &status_dir]
+        .into_iter()
+        .map(|dir| (options.watch_path(dir), RecursiveMode::NonRecursive))
+        .collect();
+    let fs_watch = FsWatch::with_roots(&roots, options, deadline)?;
+
+    if let Some(reason) = options.cancel.requested() {
+        return Ok(InboxResult::Cancelled { reason });
+    }
+
+    // Initial sweep after the watcher is live, so nothing slips in between
+    if let Some(found) = take_inbox_task(mission_dir, inbox)? {
+        return Ok(found);
+    }
+    while let Some(event) = fs_watch.next_debounced(deadline)? {
+        let is_task = event.paths.iter().any(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("task-"))
+        });
+        if !is_task {
+            continue;
+        }
+        if let Some(found) = take_inbox_task(mission_dir, inbox)? {
+            return Ok(found);
+        }
+    }
+
+    match fs_watch.cancelled() {
+        Some(reason) => Ok(InboxResult::Cancelled { reason }),
+        None => Ok(InboxResult::Timeout),
+    }
+}
+
+/// The oldest unclaimed task matching `inbox`, claimed if asked to be.
+fn take_inbox_task(
+    mission_dir: &Path,
+    inbox: &InboxOptions,
+) -> Result<Option<InboxResult>, McError> {
+    let status_dir = mission_dir.join("status");
+    let taken = |task_id: &str| {
+        Layout::detect(mission_dir, task_id)
+            .paths(mission_dir, task_id)
+            .status
+            .exists()
+            || status_dir
+                .join(format!("task-{}.claimed", task_id))
+                .exists()
+    };
+
+    let mut candidates = Vec::new();
+    for path in protocol::task_files(mission_dir, &TaskFilter::default())? {
+        let task_id = protocol::task_id(&path);
+        if taken(&task_id) {
+            continue;
+        }
+        let validation = protocol::validate_task(&path.to_string_lossy())?;
+        let (true, Some(priority), Some(created)) =
+            (validation.valid, validation.priority, validation.created)
+        else {
+            continue;
+        };
+        if inbox.priority.is_some_and(|wanted| wanted != priority) {
+            continue;
+        }
+        if !protocol::unmet_dependencies(mission_dir, &validation.depends_on).is_empty() {
+            continue;
+        }
+        let Ok(at) = chrono::DateTime::parse_from_rfc3339(&created) else {
+            continue;
+        };
+        candidates.push((at, path, task_id, priority, created));
+    }
+    candidates.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+
+    for (_, path, task_id, priority, created) in candidates {
+        if inbox.claim && !claim(&status_dir, &task_id)? {
+            continue;
+        }
+        return Ok(Some(InboxResult::Task {
+            task_id,
+            task_path: path.to_string_lossy().to_string(),
+            priority,
+            created,
+            claimed: inbox.claim,
+        }));
+    }
+    Ok(None)
+}
+
+/// Create the task's claim file, returning false if it already exists.
+fn claim(status_dir: &Path, task_id: &str) -> Result<bool, McError> {
+    std::fs::create_dir_all(status_dir)?;
+    let path = status_dir.join(format!("task-{}.claimed", task_id));
+    let claimed_at = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(mut file) => {
+            writeln!(file, "Claimed: {}", claimed_at)
+                .map_err(|e| McError::io(path.display(), e))?;
+            Ok(true)
+        }
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(McError::io(path.display(), e)),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cancel::Cancel;
+    use crate::clock::{Clock, MockClock};
+    use crate::fswatch::WatchStrategy;
     use std::fs;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
     use tempfile::TempDir;
 
+    const RESPONSE_001: &str = "# Response: 001\nCompleted: 2026-01-22T10:30:00Z\n\n\
+                                ## Summary\n\nDone.\n\n## Files Modified\n\n- src/lib.rs\n";
+
     #[test]
     fn test_watch_task_already_complete() {
         let temp_dir = TempDir::new().unwrap();
@@ -99,14 +725,295 @@ mod tests {
         fs::create_dir_all(&responses_dir).unwrap();
         fs::write(responses_dir.join("task-001.md"), "# Response").unwrap();
 
-        let result =
-            watch_task("001", mission_dir.to_str().unwrap(), Duration::from_secs(1)).unwrap();
+        let result = watch_task(
+            "001",
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(1),
+            &WatchOptions::default(),
+        )
+        .unwrap();
 
         match result {
-            WatchResult::Complete { response_path } => {
+            WatchResult::Complete {
+                response_path,
+                task_status,
+                response_exists,
+                ..
+            } => {
                 assert!(response_path.contains("task-001.md"));
+                assert_eq!(task_status, TaskStatus::Done);
+                assert!(response_exists);
+            }
+            other => panic!("Expected complete, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(parse_status("DONE\n"), (TaskStatus::Done, None));
+        assert_eq!(
+            parse_status("FAILED: tests did not pass\n"),
+            (TaskStatus::Failed, Some("tests did not pass".to_string()))
+        );
+        assert_eq!(
+            parse_status("blocked:waiting on API keys"),
+            (TaskStatus::Blocked, Some("waiting on API keys".to_string()))
+        );
+        assert_eq!(
+            parse_status("finished?"),
+            (TaskStatus::Unknown, Some("finished?".to_string()))
+        );
+        assert_eq!(parse_status(""), (TaskStatus::Unknown, None));
+    }
+
+    #[test]
+    fn test_watch_task_failed_without_response() {
+        let temp_dir = TempDir::new().unwrap();
+        let status_dir = temp_dir.path().join("status");
+        fs::create_dir_all(&status_dir).unwrap();
+        fs::write(status_dir.join("task-001.status"), "FAILED: build broke").unwrap();
+
+        let result = watch_task(
+            "001",
+            temp_dir.path().to_str().unwrap(),
+            Duration::from_secs(1),
+            &WatchOptions::default(),
+        )
+        .unwrap();
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["status"], "complete");
+        assert_eq!(json["task_status"], "failed");
+        assert_eq!(json["message"], "build broke");
+        assert_eq!(json["response_exists"], false);
+    }
+
+    #[test]
+    fn test_watch_task_heartbeats_until_timeout() {
+        let temp_dir = TempDir::new().unwrap();
+        let clock = Arc::new(MockClock::with_auto_advance(Duration::from_secs(10)));
+        let options = WatchOptions {
+            clock: clock.clone(),
+            ..WatchOptions::default()
+        };
+
+        let mut beats = Vec::new();
+        let result = watch_task_with_progress(
+            "001",
+            temp_dir.path().to_str().unwrap(),
+            Duration::from_secs(95),
+            &options,
+            false,
+            Duration::from_secs(30),
+            |event| match event {
+                ProgressEvent::Waiting { elapsed_secs, .. } => beats.push(elapsed_secs),
+                other => panic!("unexpected {:?}", other),
+            },
+        )
+        .unwrap();
+
+        assert!(matches!(result, WatchResult::Timeout));
+        assert_eq!(beats, vec![30, 60, 90]);
+    }
+
+    #[test]
+    fn test_watch_task_reports_growing_response() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().to_path_buf();
+        let status_dir = mission_dir.join("status");
+        let responses_dir = mission_dir.join("responses");
+        fs::create_dir_all(&status_dir).unwrap();
+        fs::create_dir_all(&responses_dir).unwrap();
+
+        let writer = std::thread::spawn(move || {
+            let response = responses_dir.join("task-001.md");
+            for text in ["# Partial", "# Partial\n\nand the rest"] {
+                std::thread::sleep(Duration::from_millis(100));
+                fs::write(&response, text).unwrap();
+            }
+            std::thread::sleep(Duration::from_millis(100));
+            fs::write(status_dir.join("task-001.status"), "DONE").unwrap();
+        });
+
+        let mut sizes = Vec::new();
+        let result = watch_task_with_progress(
+            "001",
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(5),
+            &WatchOptions::default(),
+            false,
+            Duration::from_secs(5),
+            |event| {
+                if let ProgressEvent::Activity { path, size, .. } = event {
+                    assert!(path.ends_with("responses/task-001.md"), "{}", path);
+                    sizes.push(size.unwrap_or_default());
+                }
+            },
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        assert!(matches!(result, WatchResult::Complete { .. }));
+        assert_eq!(sizes.last(), Some(&23));
+        // Repeated events for the same size aren't reported twice
+        assert!(sizes.windows(2).all(|w| w[0] != w[1]), "{:?}", sizes);
+    }
+
+    #[test]
+    fn test_watch_task_require_response() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().to_path_buf();
+        let status_dir = mission_dir.join("status");
+        fs::create_dir_all(&status_dir).unwrap();
+
+        // The status lands before the response is written
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            fs::write(status_dir.join("task-001.status"), "DONE").unwrap();
+            let response = mission_dir.join("responses").join("task-001.md");
+            std::thread::sleep(Duration::from_millis(100));
+            fs::write(&response, "# Response: 001\n\n## Sum").unwrap();
+            std::thread::sleep(Duration::from_millis(100));
+            fs::write(&response, RESPONSE_001).unwrap();
+        });
+
+        let result = watch_task_with_progress(
+            "001",
+            temp_dir.path().to_str().unwrap(),
+            Duration::from_secs(5),
+            &WatchOptions::default(),
+            true,
+            Duration::ZERO,
+            |_| {},
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        assert!(
+            matches!(result, WatchResult::Complete { .. }),
+            "{:?}",
+            result
+        );
+        let response = temp_dir.path().join("responses").join("task-001.md");
+        assert_eq!(fs::read_to_string(response).unwrap(), RESPONSE_001);
+    }
+
+    #[test]
+    fn test_watch_task_status_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let status_dir = temp_dir.path().join("status");
+        fs::create_dir_all(&status_dir).unwrap();
+        fs::write(status_dir.join("task-001.status"), "DONE").unwrap();
+
+        let options = WatchOptions {
+            clock: Arc::new(MockClock::with_auto_advance(Duration::from_secs(1))),
+            ..WatchOptions::default()
+        };
+        let result = watch_task_with_progress(
+            "001",
+            temp_dir.path().to_str().unwrap(),
+            Duration::from_secs(5),
+            &options,
+            true,
+            Duration::ZERO,
+            |_| {},
+        )
+        .unwrap();
+
+        match result {
+            WatchResult::StatusOnly {
+                status_path,
+                response_path,
+            } => {
+                assert!(status_path.ends_with("task-001.status"));
+                assert!(response_path.ends_with("task-001.md"));
             }
-            WatchResult::Timeout => panic!("Expected complete, got timeout"),
+            other => panic!("Expected status_only, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_watch_task_cancel_file_after_start() {
+        let temp_dir = TempDir::new().unwrap();
+        let cancel_file = temp_dir.path().join("cancel");
+        let options = WatchOptions {
+            cancel: Cancel {
+                file: Some(cancel_file.clone()),
+                ..Cancel::default()
+            },
+            ..WatchOptions::default()
+        };
+
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            fs::write(cancel_file, "").unwrap();
+        });
+        // Real clock and a long timeout: only the cancel file ends this
+        let result = watch_task(
+            "001",
+            temp_dir.path().to_str().unwrap(),
+            Duration::from_secs(300),
+            &options,
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["status"], "cancelled");
+        assert_eq!(json["reason"], "cancel_file");
+    }
+
+    #[test]
+    fn test_watch_tasks_cancelled_up_front() {
+        let temp_dir = TempDir::new().unwrap();
+        let options = WatchOptions::default();
+        options.cancel.flag.store(true, Ordering::Relaxed);
+
+        let result = watch_tasks(
+            &["001".to_string()],
+            temp_dir.path().to_str().unwrap(),
+            Duration::from_secs(300),
+            &options,
+            |record| panic!("unexpected {:?}", record),
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            WatchTasksResult::Cancelled {
+                reason: CancelReason::Signal,
+                pending: vec!["001".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_watch_task_by_polling() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().to_path_buf();
+        let status_dir = mission_dir.join("status");
+        fs::create_dir_all(&status_dir).unwrap();
+
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            fs::write(status_dir.join("task-001.status"), "DONE").unwrap();
+        });
+
+        let options = WatchOptions {
+            strategy: WatchStrategy::Poll(Duration::from_millis(5)),
+            ..WatchOptions::default()
+        };
+        let result = watch_task(
+            "001",
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(5),
+            &options,
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        match result {
+            WatchResult::Complete { task_status, .. } => assert_eq!(task_status, TaskStatus::Done),
+            other => panic!("Expected complete, got {:?}", other),
         }
     }
 
@@ -119,16 +1026,532 @@ mod tests {
         let status_dir = mission_dir.join("status");
         fs::create_dir_all(&status_dir).unwrap();
 
+        // Five minutes of mock time pass in a handful of idle waits
+        let clock = Arc::new(MockClock::with_auto_advance(Duration::from_secs(60)));
+        let options = WatchOptions {
+            clock: clock.clone(),
+            ..WatchOptions::default()
+        };
+        let start = clock.now();
         let result = watch_task(
             "nonexistent",
             mission_dir.to_str().unwrap(),
-            Duration::from_millis(100),
+            Duration::from_secs(300),
+            &options,
         )
         .unwrap();
 
         match result {
             WatchResult::Timeout => {}
-            WatchResult::Complete { .. } => panic!("Expected timeout, got complete"),
+            other => panic!("Expected timeout, got {:?}", other),
         }
+        assert!(clock.now() - start >= Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_watch_tasks_reports_in_completion_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().to_path_buf();
+        let status_dir = mission_dir.join("status");
+        fs::create_dir_all(&status_dir).unwrap();
+        fs::write(status_dir.join("task-002.status"), "DONE").unwrap();
+
+        let writer = std::thread::spawn(move || {
+            for task_id in ["003", "001"] {
+                std::thread::sleep(Duration::from_millis(150));
+                let name = format!("task-{}.status", task_id);
+                fs::write(status_dir.join(name), "DONE").unwrap();
+            }
+        });
+
+        let task_ids: Vec<String> = ["001", "002", "003", "004"].map(String::from).to_vec();
+        let mut seen = Vec::new();
+        let result = watch_tasks(
+            &task_ids,
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(2),
+            &WatchOptions::default(),
+            |record| seen.push(record.task_id.clone()),
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        // The one already done first, then in the order they appeared
+        assert_eq!(seen, ["002", "003", "001"]);
+        assert_eq!(
+            result,
+            WatchTasksResult::Timeout {
+                pending: vec!["004".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn test_watch_tasks_all_complete_returns_early() {
+        let temp_dir = TempDir::new().unwrap();
+        let status_dir = temp_dir.path().join("status");
+        fs::create_dir_all(&status_dir).unwrap();
+        fs::write(status_dir.join("task-001.status"), "DONE").unwrap();
+
+        // Mock time would run out at once; nothing is left to wait for anyway
+        let options = WatchOptions {
+            clock: Arc::new(MockClock::with_auto_advance(Duration::from_secs(60))),
+            ..WatchOptions::default()
+        };
+        let mut records = Vec::new();
+        let task_ids = ["001".to_string(), "001".to_string()];
+        let result = watch_tasks(
+            &task_ids,
+            temp_dir.path().to_str().unwrap(),
+            Duration::from_secs(300),
+            &options,
+            |record| records.push(serde_json::to_value(record).unwrap()),
+        )
+        .unwrap();
+
+        assert_eq!(result, WatchTasksResult::Complete);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["task_id"], "001");
+        assert_eq!(records[0]["status"], "complete");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_watch_task_symlinked_mission_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("current");
+        fs::create_dir_all(target.join("status")).unwrap();
+        let link = temp_dir.path().join(".mission");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let status_file = link.join("status").join("task-001.status");
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            fs::write(status_file, "DONE").unwrap();
+        });
+
+        let result = watch_task(
+            "001",
+            link.to_str().unwrap(),
+            Duration::from_secs(5),
+            &WatchOptions::default(),
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        // Reported under the link, not the resolved target
+        match result {
+            WatchResult::Complete { response_path, .. } => {
+                assert_eq!(
+                    Path::new(&response_path),
+                    link.join("responses").join("task-001.md")
+                );
+            }
+            other => panic!("Expected complete, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_watch_task_nested_dir_created_later() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().to_path_buf();
+        let task_dir = mission_dir.join("tasks/001");
+
+        let writer = {
+            let task_dir = task_dir.clone();
+            std::thread::spawn(move || {
+                // One level at a time, so the watch has to follow it down
+                std::thread::sleep(Duration::from_millis(200));
+                fs::create_dir(task_dir.parent().unwrap()).unwrap();
+                std::thread::sleep(Duration::from_millis(100));
+                fs::create_dir(&task_dir).unwrap();
+                fs::write(task_dir.join("response.md"), RESPONSE_001).unwrap();
+                fs::write(task_dir.join("status"), "DONE").unwrap();
+            })
+        };
+        let options = WatchOptions {
+            layout: Some(Layout::Nested),
+            ..WatchOptions::default()
+        };
+        let result = watch_task(
+            "001",
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(5),
+            &options,
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        match result {
+            WatchResult::Complete {
+                task_status,
+                response_path,
+                response_exists,
+                ..
+            } => {
+                assert_eq!(task_status, TaskStatus::Done);
+                assert_eq!(PathBuf::from(response_path), task_dir.join("response.md"));
+                assert!(response_exists);
+            }
+            other => panic!("Expected complete, got {:?}", other),
+        }
+        // Nothing of the flat layout was made
+        assert!(!mission_dir.join("status").exists());
+    }
+
+    #[test]
+    fn test_watch_task_nested_never_creates_task_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path();
+        fs::create_dir_all(mission_dir.join("tasks")).unwrap();
+
+        let options = WatchOptions {
+            layout: Some(Layout::Nested),
+            clock: Arc::new(MockClock::with_auto_advance(Duration::from_secs(60))),
+            ..WatchOptions::default()
+        };
+        let task_ids = vec!["0001".to_string()];
+        let result = watch_tasks(
+            &task_ids,
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(300),
+            &options,
+            |_| {},
+        )
+        .unwrap();
+        assert!(matches!(result, WatchTasksResult::Timeout { .. }));
+        let result = watch_task(
+            "0001",
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(300),
+            &options,
+        )
+        .unwrap();
+        assert!(matches!(result, WatchResult::Timeout));
+
+        // A mistyped id leaves nothing behind to be taken for a nested task
+        assert!(!mission_dir.join("tasks/0001").exists());
+        assert_eq!(Layout::detect(mission_dir, "0001"), Layout::Flat);
+    }
+
+    #[test]
+    fn test_watch_tasks_nested_dir_created_later() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().to_path_buf();
+
+        let writer = {
+            let mission_dir = mission_dir.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(150));
+                fs::create_dir(mission_dir.join("tasks")).unwrap();
+                std::thread::sleep(Duration::from_millis(100));
+                fs::create_dir(mission_dir.join("tasks/001")).unwrap();
+                fs::write(mission_dir.join("tasks/001/status"), "DONE").unwrap();
+            })
+        };
+        let options = WatchOptions {
+            layout: Some(Layout::Nested),
+            ..WatchOptions::default()
+        };
+        let mut seen = Vec::new();
+        let result = watch_tasks(
+            &["001".to_string()],
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(5),
+            &options,
+            |record| seen.push(record.task_id.clone()),
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        assert_eq!(seen, ["001"]);
+        assert_eq!(result, WatchTasksResult::Complete);
+    }
+
+    #[test]
+    fn test_watch_task_detects_layout() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path();
+        fs::create_dir_all(mission_dir.join("tasks/002")).unwrap();
+        fs::write(mission_dir.join("tasks/002/status"), "FAILED: no tests").unwrap();
+        // The flat files of another task don't get in the way
+        fs::create_dir_all(mission_dir.join("status")).unwrap();
+        fs::write(mission_dir.join("status/task-001.status"), "DONE").unwrap();
+
+        let watch = |task_id| {
+            watch_task(
+                task_id,
+                mission_dir.to_str().unwrap(),
+                Duration::from_millis(500),
+                &WatchOptions::default(),
+            )
+            .unwrap()
+        };
+        match watch("002") {
+            WatchResult::Complete {
+                task_status,
+                response_path,
+                ..
+            } => {
+                assert_eq!(task_status, TaskStatus::Failed);
+                assert!(response_path.ends_with("tasks/002/response.md"));
+            }
+            other => panic!("Expected complete, got {:?}", other),
+        }
+        match watch("001") {
+            WatchResult::Complete { response_path, .. } => {
+                assert!(response_path.ends_with("responses/task-001.md"))
+            }
+            other => panic!("Expected complete, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_watch_tasks_across_layouts() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().to_path_buf();
+        fs::create_dir_all(mission_dir.join("tasks/002")).unwrap();
+        fs::create_dir_all(mission_dir.join("tasks/003")).unwrap();
+
+        let writer = {
+            let mission_dir = mission_dir.clone();
+            std::thread::spawn(move || {
+                for path in ["tasks/003/status", "status/task-001.status"] {
+                    std::thread::sleep(Duration::from_millis(150));
+                    fs::write(mission_dir.join(path), "DONE").unwrap();
+                }
+            })
+        };
+        let task_ids: Vec<String> = ["001", "002", "003"].map(String::from).to_vec();
+        let mut seen = Vec::new();
+        let result = watch_tasks(
+            &task_ids,
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(2),
+            &WatchOptions::default(),
+            |record| seen.push(record.task_id.clone()),
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        // 002's dir exists, but only 003 wrote a status file
+        assert_eq!(seen, ["003", "001"]);
+        assert_eq!(
+            result,
+            WatchTasksResult::Timeout {
+                pending: vec!["002".to_string()]
+            }
+        );
+    }
+
+    fn write_inbox_task(mission_dir: &Path, task_id: &str, created: &str, priority: &str) {
+        let tasks_dir = mission_dir.join("tasks");
+        fs::create_dir_all(&tasks_dir).unwrap();
+        let content = format!(
+            "# Task: {id}\nCreated: {created}\nPriority: {priority}\n\n\
+             ## Instructions\nDo it.\n\n## Response Instructions\nWrite responses/task-{id}.md\n",
+            id = task_id,
+        );
+        fs::write(tasks_dir.join(format!("task-{}.md", task_id)), content).unwrap();
+    }
+
+    fn inbox_task_id(result: &InboxResult) -> &str {
+        match result {
+            InboxResult::Task { task_id, .. } => task_id,
+            other => panic!("Expected a task, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_watch_inbox_takes_oldest_unclaimed() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path();
+        write_inbox_task(mission_dir, "a", "2026-01-22T12:00:00Z", "normal");
+        write_inbox_task(mission_dir, "b", "2026-01-22T11:00:00+02:00", "high");
+        write_inbox_task(mission_dir, "c", "2026-01-22T08:00:00Z", "normal");
+        // Already done, and not a valid task
+        fs::create_dir_all(mission_dir.join("status")).unwrap();
+        fs::write(mission_dir.join("status/task-c.status"), "DONE").unwrap();
+        fs::write(mission_dir.join("tasks/task-d.md"), "# Task: d\n").unwrap();
+
+        let mission = mission_dir.to_str().unwrap();
+        let timeout = Duration::from_millis(200);
+        let result = watch_inbox(
+            mission,
+            timeout,
+            &InboxOptions::default(),
+            &WatchOptions::default(),
+        )
+        .unwrap();
+        // 11:00+02:00 is 09:00Z
+        match result {
+            InboxResult::Task {
+                task_id,
+                priority,
+                claimed,
+                ..
+            } => {
+                assert_eq!(task_id, "b");
+                assert_eq!(priority, Priority::High);
+                assert!(!claimed);
+            }
+            other => panic!("Expected a task, got {:?}", other),
+        }
+
+        let normal = InboxOptions {
+            priority: Some(Priority::Normal),
+            ..InboxOptions::default()
+        };
+        let result = watch_inbox(mission, timeout, &normal, &WatchOptions::default()).unwrap();
+        assert_eq!(inbox_task_id(&result), "a");
+
+        let critical = InboxOptions {
+            priority: Some(Priority::Critical),
+            ..InboxOptions::default()
+        };
+        let result = watch_inbox(mission, timeout, &critical, &WatchOptions::default()).unwrap();
+        assert!(matches!(result, InboxResult::Timeout));
+    }
+
+    #[test]
+    fn test_watch_inbox_waits_for_new_task() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().to_path_buf();
+        fs::create_dir_all(mission_dir.join("tasks")).unwrap();
+
+        let writer = {
+            let mission_dir = mission_dir.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(200));
+                write_inbox_task(&mission_dir, "new", "2026-01-22T10:00:00Z", "normal");
+            })
+        };
+        let result = watch_inbox(
+            mission_dir.to_str().unwrap(),
+            Duration::from_secs(5),
+            &InboxOptions::default(),
+            &WatchOptions::default(),
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        match result {
+            InboxResult::Task { task_path, .. } => {
+                assert_eq!(
+                    PathBuf::from(task_path),
+                    mission_dir.join("tasks/task-new.md")
+                )
+            }
+            other => panic!("Expected a task, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_watch_inbox_claims_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path();
+        write_inbox_task(mission_dir, "a", "2026-01-22T08:00:00Z", "normal");
+        write_inbox_task(mission_dir, "b", "2026-01-22T09:00:00Z", "normal");
+        fs::create_dir_all(mission_dir.join("status")).unwrap();
+        fs::write(mission_dir.join("status/task-a.claimed"), "").unwrap();
+
+        let mission = mission_dir.to_str().unwrap();
+        let claim = InboxOptions {
+            claim: true,
+            ..InboxOptions::default()
+        };
+        let timeout = Duration::from_millis(200);
+        // a is someone else's; b is claimed now and so gone for the next watcher
+        let result = watch_inbox(mission, timeout, &claim, &WatchOptions::default()).unwrap();
+        assert_eq!(inbox_task_id(&result), "b");
+        assert!(mission_dir.join("status/task-b.claimed").exists());
+        let result = watch_inbox(mission, timeout, &claim, &WatchOptions::default()).unwrap();
+        assert!(matches!(result, InboxResult::Timeout));
+
+        // The claim file is only ever created, never overwritten
+        let status_dir = mission_dir.join("status");
+        fs::write(status_dir.join("task-b.claimed"), "mine").unwrap();
+        assert!(!super::claim(&status_dir, "b").unwrap());
+        assert_eq!(
+            fs::read_to_string(status_dir.join("task-b.claimed")).unwrap(),
+            "mine"
+        );
+    }
+
+    #[test]
+    fn test_watch_inbox_racing_watchers_claim_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().to_path_buf();
+        write_inbox_task(&mission_dir, "a", "2026-01-22T08:00:00Z", "normal");
+
+        let barrier = Arc::new(std::sync::Barrier::new(8));
+        let watchers: Vec<_> = (0..8)
+            .map(|_| {
+                let mission_dir = mission_dir.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    let claim = InboxOptions {
+                        claim: true,
+                        ..InboxOptions::default()
+                    };
+                    barrier.wait();
+                    watch_inbox(
+                        mission_dir.to_str().unwrap(),
+                        Duration::from_millis(300),
+                        &claim,
+                        &WatchOptions::default(),
+                    )
+                    .unwrap()
+                })
+            })
+            .collect();
+        let results: Vec<InboxResult> = watchers.into_iter().map(|w| w.join().unwrap()).collect();
+
+        let won = results
+            .iter()
+            .filter(|r| matches!(r, InboxResult::Task { .. }))
+            .count();
+        assert_eq!(won, 1);
+        assert!(results
+            .iter()
+            .all(|r| matches!(r, InboxResult::Task { .. } | InboxResult::Timeout)));
+    }
+
+    #[test]
+    fn test_watch_inbox_waits_for_dependencies() {
+        let temp_dir = TempDir::new().unwrap();
+        let mission_dir = temp_dir.path().to_path_buf();
+        write_inbox_task(&mission_dir, "a", "2026-01-22T08:00:00Z", "normal");
+        write_inbox_task(&mission_dir, "b", "2026-01-22T09:00:00Z", "normal");
+        // The oldest task, but it needs a first
+        fs::write(
+            mission_dir.join("tasks/task-c.md"),
+            "# Task: c\nCreated: 2026-01-22T07:00:00Z\nPriority: normal\nDepends-On: a\n\n\
+             ## Instructions\nDo it.\n\n## Response Instructions\nReply.\n",
+        )
+        .unwrap();
+        let claim = InboxOptions {
+            claim: true,
+            ..InboxOptions::default()
+        };
+        let mission = mission_dir.to_str().unwrap();
+        let timeout = Duration::from_secs(5);
+
+        let result = watch_inbox(mission, timeout, &claim, &WatchOptions::default()).unwrap();
+        assert_eq!(inbox_task_id(&result), "a");
+        let result = watch_inbox(mission, timeout, &claim, &WatchOptions::default()).unwrap();
+        assert_eq!(inbox_task_id(&result), "b");
+
+        // Finishing a makes c ready while the inbox waits
+        let finisher = {
+            let status = mission_dir.join("status/task-a.status");
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(200));
+                fs::write(status, "COMPLETE").unwrap();
+            })
+        };
+        let result = watch_inbox(mission, timeout, &claim, &WatchOptions::default()).unwrap();
+        finisher.join().unwrap();
+        assert_eq!(inbox_task_id(&result), "c");
     }
 }
@@ -0,0 +1,119 @@
+//! Run subcommands from inside a project, as a user would, to check how the
+//! mission dir is found when `--mission-dir` is left out.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+const CONVERSATION: &str = "## Human\n\nHi\n\n## Assistant\n\nHello\n\n---END---\n";
+
+/// Run `mc-protocol` in `cwd`, returning its exit code, its JSON output (an
+/// error's is on stderr) and any log lines it wrote to stderr besides.
+fn mc_protocol(cwd: &Path, args: &[&str]) -> (i32, Value, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_mc-protocol"))
+        .args(args)
+        .current_dir(cwd)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    let json = if output.status.success() {
+        serde_json::from_slice(&output.stdout).unwrap()
+    } else {
+        serde_json::from_str(stderr.lines().last().unwrap_or_default()).unwrap()
+    };
+    (output.status.code().unwrap(), json, stderr)
+}
+
+/// A project with a `.mission` at its root and a source dir two levels down.
+fn project() -> (TempDir, PathBuf, PathBuf) {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let mission = root.join(".mission");
+    fs::create_dir_all(mission.join("tasks")).unwrap();
+    fs::write(mission.join("conversation.md"), CONVERSATION).unwrap();
+    let nested = root.join("src").join("module");
+    fs::create_dir_all(&nested).unwrap();
+    (temp_dir, mission, nested)
+}
+
+fn mission_dir(output: &Value) -> PathBuf {
+    PathBuf::from(output["mission_dir"].as_str().unwrap())
+}
+
+#[test]
+fn test_subcommands_discover_mission_from_nested_dir() {
+    let (_temp_dir, mission, nested) = project();
+
+    let (code, output, stderr) = mc_protocol(&nested, &["parse-conversation"]);
+    assert_eq!(code, 0, "{}", output);
+    assert_eq!(mission_dir(&output), mission);
+    assert_eq!(output["turns"].as_array().unwrap().len(), 2);
+    assert!(stderr.is_empty(), "{}", stderr);
+
+    for command in ["list-tasks", "validate-all"] {
+        let (code, output, _) = mc_protocol(&nested, &[command]);
+        assert_eq!(code, 0, "{}", output);
+        assert_eq!(mission_dir(&output), mission);
+    }
+
+    let (code, output, _) = mc_protocol(
+        &nested,
+        &["append", "--role", "human", "--content", "Next?"],
+    );
+    assert_eq!(code, 0, "{}", output);
+    assert_eq!(mission_dir(&output), mission);
+    assert!(fs::read_to_string(mission.join("conversation.md"))
+        .unwrap()
+        .contains("Next?"));
+}
+
+#[test]
+fn test_no_discover_uses_cwd() {
+    let (_temp_dir, mission, nested) = project();
+
+    let (code, output, _) = mc_protocol(&nested, &["--no-discover", "parse-conversation"]);
+    assert_ne!(code, 0);
+    assert_eq!(output["kind"], "io");
+    let error = output["error"].as_str().unwrap();
+    assert!(
+        error.contains(&nested.join(".mission").to_string_lossy().to_string()),
+        "{}",
+        error
+    );
+
+    // An explicit dir is taken as given, relative to the cwd
+    let (code, output, _) = mc_protocol(
+        &nested,
+        &[
+            "--no-discover",
+            "parse-conversation",
+            "--mission-dir",
+            "../../.mission",
+        ],
+    );
+    assert_eq!(code, 0, "{}", output);
+    assert_eq!(
+        mission_dir(&output).canonicalize().unwrap(),
+        mission.canonicalize().unwrap()
+    );
+}
+
+#[test]
+fn test_nearest_mission_dir_wins_and_is_logged() {
+    let (_temp_dir, mission, nested) = project();
+    let inner = nested.parent().unwrap().join(".mission");
+    fs::create_dir_all(&inner).unwrap();
+    fs::write(inner.join("conversation.md"), "## Human\n\nInner\n").unwrap();
+
+    let (code, output, stderr) = mc_protocol(&nested, &["parse-conversation"]);
+    assert_eq!(code, 0, "{}", output);
+    assert_eq!(mission_dir(&output), inner);
+    assert_eq!(output["turns"][0]["content"], "Inner");
+
+    let log: Value = serde_json::from_str(stderr.trim()).unwrap();
+    assert_eq!(log["log"], "using nearest mission directory");
+    assert_eq!(log["ignored"][0], mission.to_string_lossy().as_ref());
+}
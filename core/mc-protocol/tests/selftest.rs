@@ -0,0 +1,57 @@
+//! Run the `selftest` subcommand end to end, as an operator would on a new host.
+
+use std::process::Command;
+
+use serde_json::Value;
+
+fn selftest(dir: &std::path::Path) -> (i32, Value) {
+    let output = Command::new(env!("CARGO_BIN_EXE_mc-protocol"))
+        .args(["selftest", "--dir"])
+        .arg(dir)
+        .output()
+        .unwrap();
+    let report = serde_json::from_slice(&output.stdout).unwrap();
+    (output.status.code().unwrap(), report)
+}
+
+#[test]
+fn test_selftest_passes() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let (code, report) = selftest(temp_dir.path());
+
+    assert_eq!(code, 0, "{}", report);
+    assert_eq!(report["passed"], true);
+    let stages: Vec<&str> = report["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|check| check["stage"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        stages,
+        [
+            "create_task",
+            "watch_task",
+            "parse_response",
+            "watch_conversation",
+            "count_tokens"
+        ]
+    );
+
+    // The scratch mission is cleaned up
+    assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_selftest_names_failing_stage() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let not_a_dir = temp_dir.path().join("file");
+    std::fs::write(&not_a_dir, "").unwrap();
+
+    let (code, report) = selftest(&not_a_dir);
+
+    assert_ne!(code, 0);
+    assert_eq!(report["passed"], false);
+    assert_eq!(report["failed_stage"], "create_task");
+    assert!(report["checks"][0]["error"].is_string());
+}
@@ -1,20 +1,15 @@
 use serde::{Deserialize, Serialize};
 use crate::phase::Phase;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum GateStatus {
     Open,
+    #[default]
     Closed,
     AwaitingApproval,
 }
 
-impl Default for GateStatus {
-    fn default() -> Self {
-        GateStatus::Closed
-    }
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GateCriterion {
     pub description: String,
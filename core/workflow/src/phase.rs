@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Phase {
+    #[default]
     Idea,
     Design,
     Implement,
@@ -46,12 +47,6 @@ impl Phase {
     }
 }
 
-impl Default for Phase {
-    fn default() -> Self {
-        Phase::Idea
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
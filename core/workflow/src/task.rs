@@ -1,9 +1,10 @@
 use serde::{Deserialize, Serialize};
 use crate::phase::Phase;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TaskStatus {
+    #[default]
     Pending,
     Ready,
     InProgress,
@@ -23,12 +24,6 @@ impl TaskStatus {
     }
 }
 
-impl Default for TaskStatus {
-    fn default() -> Self {
-        TaskStatus::Pending
-    }
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub id: String,
@@ -0,0 +1,25 @@
+//! Rewrite every golden file from the current parser output.
+//!
+//! Equivalent to running the golden test with `BLESS=1`.
+
+use std::env;
+use std::process::{exit, Command};
+
+fn main() {
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(cargo)
+        .args(["test", "--manifest-path"])
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+        .arg("golden::")
+        .env("BLESS", "1")
+        .status();
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("Failed to run cargo test: {}", e);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! Parse one stream carrying the output of several agents.
+//!
+//! A supervisor running several agents can merge their stdout into one pipe,
+//! marking each line with the agent that wrote it as `name| line`. [`Demux`]
+//! strips the prefix and hands the line to a [`Parser`] of that agent's own,
+//! so formats, turn counters and buffered deltas never mix between agents and
+//! each event carries the agent named by its prefix. Lines without a prefix
+//! belong to [`DEFAULT_AGENT`].
+//!
+//! Events from every agent are numbered in one sequence, in the order they
+//! are emitted.
+
+use crate::state::{AgentState, StreamState};
+use crate::{AgentExit, AgentFormat, Parser, UnifiedEvent};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Agent of lines that have no prefix.
+pub const DEFAULT_AGENT: &str = "default";
+
+/// The parsers of every agent on a stream.
+pub struct Demux {
+    /// Sets up the parser of an agent seen for the first time
+    make: Box<dyn FnMut(&str) -> Parser>,
+    parsers: HashMap<String, Parser>,
+    /// Agent ids in the order they were first seen
+    order: Vec<String>,
+    /// Split prefixes off lines, rather than giving every line to `fallback`
+    prefixed: bool,
+    /// Agent of unprefixed lines and of events about the stream itself
+    fallback: String,
+    /// `seq` of the next event emitted
+    next_seq: u64,
+    /// No line has been routed yet; a byte order mark would hide its prefix
+    at_start: bool,
+}
+
+impl Demux {
+    /// Give every line to `agent_id`, prefixed or not.
+    pub fn single(agent_id: &str, make: impl FnMut(&str) -> Parser + 'static) -> Self {
+        let mut demux = Demux::new(agent_id, false, make);
+        // Its stream ends with it even if the agent never writes a line
+        demux.parser(agent_id);
+        demux
+    }
+
+    /// Give each line to the agent its `ID| ` prefix names.
+    pub fn prefixed(make: impl FnMut(&str) -> Parser + 'static) -> Self {
+        Demux::new(DEFAULT_AGENT, true, make)
+    }
+
+    fn new(fallback: &str, prefixed: bool, make: impl FnMut(&str) -> Parser + 'static) -> Self {
+        Demux {
+            make: Box::new(make),
+            parsers: HashMap::new(),
+            order: Vec::new(),
+            prefixed,
+            fallback: fallback.to_string(),
+            next_seq: 1,
+            at_start: true,
+        }
+    }
+
+    /// Number events from `seq` instead of 1, to continue a previous run
+    pub fn set_start_seq(&mut self, seq: u64) {
+        self.next_seq = seq;
+    }
+
+    /// The `seq` the next event will get
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Format of the agent that unprefixed lines go to, if it has a parser.
+    pub fn format(&self) -> AgentFormat {
+        self.parsers
+            .get(&self.fallback)
+            .map_or(AgentFormat::Unknown, Parser::format)
+    }
+
+    /// How many lines looked like JSON but failed to parse, for all agents
+    pub fn parse_failures(&self) -> u64 {
+        self.parsers.values().map(Parser::parse_failures).sum()
+    }
+
+    /// [`Parser::parse_line`] for the agent the line belongs to.
+    pub fn parse_line(&mut self, mut line: &str) -> Vec<UnifiedEvent> {
+        if std::mem::take(&mut self.at_start) {
+            line = line.strip_prefix('\u{feff}').unwrap_or(line);
+        }
+        let (agent_id, line) = self.route(line);
+        let events = self.parser(&agent_id).parse_line(line);
+        self.number(events)
+    }
+
+    /// [`Parser::parse_stderr_line`] for the agent the line belongs to.
+    pub fn parse_stderr_line(&mut self, line: &str) -> Vec<UnifiedEvent> {
+        let (agent_id, line) = self.route(line);
+        let events = self.parser(&agent_id).parse_stderr_line(line);
+        self.number(events)
+    }
+
+    /// [`Parser::heartbeat`], from the agent of unprefixed lines.
+    pub fn heartbeat(&mut self, idle: Duration) -> Vec<UnifiedEvent> {
+        let fallback = self.fallback.clone();
+        let events = self.parser(&fallback).heartbeat(idle);
+        self.number(events)
+    }
+
+    /// [`Parser::error`], from the agent of unprefixed lines.
+    pub fn error(&mut self, status: &str, message: &str) -> Vec<UnifiedEvent> {
+        let fallback = self.fallback.clone();
+        let events = self.parser(&fallback).error(status, message);
+        self.number(events)
+    }
+
+    /// Time until the first agent's buffered deltas reach their `max_age`.
+    pub fn coalesce_remaining(&self, now: Instant) -> Option<Duration> {
+        self.parsers
+            .values()
+            .filter_map(|parser| parser.coalesce_remaining(now))
+            .min()
+    }
+
+    /// [`Parser::flush_stale`] for every agent.
+    pub fn flush_stale(&mut self, now: Instant) -> Vec<UnifiedEvent> {
+        let mut events = Vec::new();
+        for agent_id in &self.order {
+            events.extend(self.parsers.get_mut(agent_id).unwrap().flush_stale(now));
+        }
+        self.number(events)
+    }
+
+    /// [`Parser::finish_with`] for every agent, in the order they were first
+    /// seen.
+    pub fn finish_with(&mut self, exit: AgentExit) -> Vec<UnifiedEvent> {
+        let mut events = Vec::new();
+        for agent_id in &self.order {
+            events.extend(self.parsers.get_mut(agent_id).unwrap().finish_with(exit));
+        }
+        self.number(events)
+    }
+
+    /// [`Parser::suspend`] for every agent, ahead of saving the state.
+    pub fn suspend(&mut self) -> Vec<UnifiedEvent> {
+        let mut events = Vec::new();
+        for agent_id in &self.order {
+            events.extend(self.parsers.get_mut(agent_id).unwrap().suspend());
+        }
+        self.number(events)
+    }
+
+    /// The state of every agent's parser and of the numbering.
+    pub fn state(&self) -> StreamState {
+        let agents = self
+            .order
+            .iter()
+            .map(|agent_id| AgentState {
+                agent_id: agent_id.clone(),
+                parser: self.parsers[agent_id].state(),
+            })
+            .collect();
+        StreamState::new(self.next_seq, agents)
+    }
+
+    /// Continue from a state saved by an earlier run. Without prefixes, only
+    /// the state of this stream's own agent is taken.
+    pub fn restore(&mut self, state: StreamState) {
+        self.next_seq = state.next_seq;
+        for agent in state.agents {
+            if self.prefixed || agent.agent_id == self.fallback {
+                self.parser(&agent.agent_id).restore(agent.parser);
+            }
+        }
+    }
+
+    /// The agent `line` belongs to, and the line without its prefix.
+    fn route<'a>(&self, line: &'a str) -> (String, &'a str) {
+        match split_prefix(line) {
+            Some((agent_id, line)) if self.prefixed => (agent_id.to_string(), line),
+            _ => (self.fallback.clone(), line),
+        }
+    }
+
+    fn parser(&mut self, agent_id: &str) -> &mut Parser {
+        if !self.parsers.contains_key(agent_id) {
+            let parser = (self.make)(agent_id);
+            self.parsers.insert(agent_id.to_string(), parser);
+            self.order.push(agent_id.to_string());
+        }
+        self.parsers.get_mut(agent_id).unwrap()
+    }
+
+    /// Renumber one agent's events into the stream's sequence.
+    fn number(&mut self, mut events: Vec<UnifiedEvent>) -> Vec<UnifiedEvent> {
+        for event in &mut events {
+            event.seq = self.next_seq;
+            self.next_seq += 1;
+        }
+        events
+    }
+}
+
+/// Split `name| rest` into the agent name and the rest of the line. Names
+/// are letters, digits, `-`, `_` and `.`, so JSON and text that happen to
+/// contain `| ` aren't taken for a prefix.
+pub fn split_prefix(line: &str) -> Option<(&str, &str)> {
+    let (agent_id, rest) = line.split_once('|')?;
+    let valid = !agent_id.is_empty()
+        && agent_id
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return None;
+    }
+    // The space may have been trimmed off an empty line
+    let rest = if rest.is_empty() {
+        rest
+    } else {
+        rest.strip_prefix(' ')?
+    };
+    Some((agent_id, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn demux() -> Demux {
+        Demux::prefixed(|agent_id| {
+            let mut parser = Parser::new(agent_id.to_string());
+            parser.set_timestamps(false);
+            parser
+        })
+    }
+
+    #[test]
+    fn test_split_prefix() {
+        assert_eq!(
+            split_prefix("planner| {\"type\":\"turn\"}"),
+            Some(("planner", "{\"type\":\"turn\"}"))
+        );
+        assert_eq!(split_prefix("worker-2.a|"), Some(("worker-2.a", "")));
+        assert_eq!(
+            split_prefix("worker_1|  indented"),
+            Some(("worker_1", " indented"))
+        );
+        for line in [
+            "no prefix here",
+            "| empty name",
+            "a | b | c",
+            "name|no space",
+            r#"{"command":"ls| wc -l"}"#,
+        ] {
+            assert_eq!(split_prefix(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_agents_keep_their_own_turns() {
+        let response = |text: &str| {
+            format!(
+                r#"{{"candidates":[{{"content":{{"role":"model","parts":[{{"text":"{}"}}]}},"finishReason":"STOP"}}]}}"#,
+                text
+            )
+        };
+        let mut demux = demux();
+        let mut events = Vec::new();
+        for line in [
+            format!("alice| {}", response("first")),
+            format!("bob| {}", response("hello")),
+            format!("alice| {}", response("second")),
+            "[Turn 7] unprefixed".to_string(),
+        ] {
+            events.extend(demux.parse_line(&line));
+        }
+        events.extend(demux.finish_with(AgentExit::Eof));
+
+        let turns: Vec<(&str, Option<u32>)> = events
+            .iter()
+            .filter(|e| e.event_type == "turn")
+            .map(|e| (e.agent_id.as_deref().unwrap(), e.turn))
+            .collect();
+        assert_eq!(
+            turns,
+            [
+                ("alice", Some(1)),
+                ("bob", Some(1)),
+                ("alice", Some(2)),
+                (DEFAULT_AGENT, Some(7)),
+            ]
+        );
+        let seqs: Vec<u64> = events.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, (1..=events.len() as u64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_single_ignores_prefixes() {
+        let mut demux = Demux::single("agent-1", |agent_id| {
+            let mut parser = Parser::new(agent_id.to_string());
+            parser.set_lifecycle(true);
+            parser
+        });
+        demux.set_start_seq(10);
+        let events = demux.parse_line("bob| hello");
+        assert_eq!(events[1].agent_id.as_deref(), Some("agent-1"));
+        assert_eq!(events[1].content.as_deref(), Some("bob| hello"));
+        assert_eq!(events[1].seq, 11);
+
+        let exit = demux.finish_with(AgentExit::Eof);
+        assert_eq!(exit.len(), 1);
+        assert_eq!(exit[0].seq, 12);
+    }
+}
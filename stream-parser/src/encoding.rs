@@ -0,0 +1,263 @@
+//! Wire encodings for emitted events.
+//!
+//! JSON events are written one per line. MessagePack and CBOR records can
+//! contain newlines, so each is preceded by its length as a little-endian
+//! `u32` instead. Binary events are maps keyed by field name, with the same
+//! fields present as in the JSON form.
+//!
+//! Agent output is read as UTF-8, or as UTF-16LE with `--encoding-in utf16`
+//! for the files some PowerShell redirections produce.
+
+use crate::UnifiedEvent;
+use std::char::REPLACEMENT_CHARACTER;
+use std::io::{self, Read, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Encoding {
+    /// NDJSON
+    #[default]
+    Json,
+    /// Length-prefixed MessagePack
+    MsgPack,
+    /// Length-prefixed CBOR
+    Cbor,
+}
+
+impl Encoding {
+    /// The encoding named by `--encoding`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Encoding::Json),
+            "msgpack" => Some(Encoding::MsgPack),
+            "cbor" => Some(Encoding::Cbor),
+            _ => None,
+        }
+    }
+}
+
+/// Text encoding of the agent output being read.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum InputEncoding {
+    #[default]
+    Utf8,
+    /// UTF-16, little-endian
+    Utf16,
+}
+
+impl InputEncoding {
+    /// The encoding named by `--encoding-in`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "utf8" | "utf-8" => Some(InputEncoding::Utf8),
+            "utf16" | "utf-16" | "utf16le" | "utf-16le" => Some(InputEncoding::Utf16),
+            _ => None,
+        }
+    }
+}
+
+/// Bytes of UTF-16LE read from `inner`, handed out as UTF-8. Code units
+/// that don't decode become U+FFFD rather than an error.
+pub struct Utf16Reader<R> {
+    inner: R,
+    /// Bytes read but not decoded yet: an odd byte, or a high surrogate
+    /// waiting for its pair
+    raw: Vec<u8>,
+    decoded: Vec<u8>,
+    /// How much of `decoded` has been handed out
+    pos: usize,
+}
+
+impl<R: Read> Utf16Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Utf16Reader {
+            inner,
+            raw: Vec::new(),
+            decoded: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Decode whole characters from `raw` into `decoded`.
+    fn decode(&mut self) {
+        let mut units: Vec<u16> = self
+            .raw
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        let mut kept = self.raw.len() % 2;
+        if units
+            .last()
+            .is_some_and(|unit| (0xD800..0xDC00).contains(unit))
+        {
+            units.pop();
+            kept += 2;
+        }
+        let text: String = char::decode_utf16(units)
+            .map(|c| c.unwrap_or(REPLACEMENT_CHARACTER))
+            .collect();
+        self.decoded = text.into_bytes();
+        self.pos = 0;
+        self.raw.drain(..self.raw.len() - kept);
+    }
+}
+
+impl<R: Read> Read for Utf16Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut chunk = [0; 8192];
+        while self.pos == self.decoded.len() {
+            let n = self.inner.read(&mut chunk)?;
+            if n == 0 {
+                if self.raw.is_empty() {
+                    return Ok(0);
+                }
+                // Cut off mid-character
+                self.raw.clear();
+                self.decoded = REPLACEMENT_CHARACTER.to_string().into_bytes();
+                self.pos = 0;
+                break;
+            }
+            self.raw.extend_from_slice(&chunk[..n]);
+            self.decode();
+        }
+        let n = buf.len().min(self.decoded.len() - self.pos);
+        buf[..n].copy_from_slice(&self.decoded[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Write one event as a record in `encoding`.
+pub fn write_event(
+    out: &mut impl Write,
+    event: &UnifiedEvent,
+    encoding: Encoding,
+) -> io::Result<()> {
+    let record = match encoding {
+        Encoding::Json => {
+            let json = serde_json::to_string(event).map_err(io::Error::other)?;
+            return writeln!(out, "{}", json);
+        }
+        // Named, so skipped fields don't shift the rest
+        Encoding::MsgPack => rmp_serde::to_vec_named(event).map_err(io::Error::other)?,
+        Encoding::Cbor => {
+            let mut record = Vec::new();
+            ciborium::into_writer(event, &mut record).map_err(io::Error::other)?;
+            record
+        }
+    };
+    let len = u32::try_from(record.len()).map_err(io::Error::other)?;
+    out.write_all(&len.to_le_bytes())?;
+    out.write_all(&record)
+}
+
+/// Read the next length-prefixed record, or `None` at a clean end of input.
+pub fn read_record(input: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut record = vec![0; u32::from_le_bytes(len) as usize];
+    input.read_exact(&mut record)?;
+    Ok(Some(record))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn event() -> UnifiedEvent {
+        let mut event = UnifiedEvent::new("tool_call")
+            .with_agent_id("agent-1")
+            .with_tool("bash", json!({"command": "printf 'a\\nb'", "timeout": 30}))
+            .with_tool_use_id(Some("call-1"));
+        event.total_cost_usd = Some(0.25);
+        event.seq = 7;
+        event
+    }
+
+    fn round_trip(encoding: Encoding) -> Value {
+        let mut out = vec![];
+        write_event(&mut out, &event(), encoding).unwrap();
+        write_event(&mut out, &event(), encoding).unwrap();
+
+        let mut input = out.as_slice();
+        let record = read_record(&mut input).unwrap().unwrap();
+        let value = match encoding {
+            Encoding::MsgPack => rmp_serde::from_slice(&record).unwrap(),
+            Encoding::Cbor => ciborium::from_reader(record.as_slice()).unwrap(),
+            Encoding::Json => unreachable!("not length-prefixed"),
+        };
+        assert!(read_record(&mut input).unwrap().is_some());
+        assert_eq!(read_record(&mut input).unwrap(), None);
+        value
+    }
+
+    #[test]
+    fn test_binary_encodings_decode_to_json_value() {
+        let expected = serde_json::to_value(event()).unwrap();
+        assert_eq!(round_trip(Encoding::MsgPack), expected);
+        assert_eq!(round_trip(Encoding::Cbor), expected);
+    }
+
+    /// Hands out at most `step` bytes per read, to split characters.
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.step.min(buf.len()).min(self.bytes.len());
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Ok(n)
+        }
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn test_utf16_decoded_across_split_reads() {
+        let text = "\u{feff}{\"type\":\"turn\"}\r\nsmile \u{1f600} caf\u{e9}\r\n";
+        let bytes = utf16le(text);
+        for step in [1, 3, 4, 8192] {
+            let mut decoded = String::new();
+            Utf16Reader::new(Trickle {
+                bytes: &bytes,
+                step,
+            })
+            .read_to_string(&mut decoded)
+            .unwrap();
+            assert_eq!(decoded, text, "step {}", step);
+        }
+    }
+
+    #[test]
+    fn test_utf16_cut_off_or_unpaired_is_replaced() {
+        // A lone low surrogate, then a high one cut off by the end of input
+        let mut bytes = utf16le("a");
+        bytes.extend_from_slice(&[0x00, 0xDC, b'b', 0, 0x3D, 0xD8]);
+        let mut decoded = String::new();
+        Utf16Reader::new(bytes.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "a\u{fffd}b\u{fffd}");
+    }
+
+    #[test]
+    fn test_json_is_one_line_per_event() {
+        let mut out = vec![];
+        write_event(&mut out, &event(), Encoding::Json).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().count(), 1);
+        assert_eq!(
+            serde_json::from_str::<UnifiedEvent>(&text).unwrap(),
+            event()
+        );
+    }
+}
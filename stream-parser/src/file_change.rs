@@ -0,0 +1,137 @@
+//! Summarize the file edits Claude Code's tools make.
+//!
+//! `Edit`, `MultiEdit` and `Write` calls carry whole strings of old and new
+//! text in their input. A `file_change` event next to the call gives the UI
+//! what it shows on a file timeline: the path, lines added and removed, and
+//! a unified diff cut to [`DIFF_LINES`] lines.
+
+use crate::UnifiedEvent;
+use serde_json::Value;
+use similar::{ChangeTag, TextDiff};
+
+/// Lines of unified diff kept in a `file_change` event's `content`.
+pub const DIFF_LINES: usize = 40;
+
+/// Lines of context around each change in the diff.
+const CONTEXT_LINES: usize = 3;
+
+/// The `file_change` for a call to `tool` with `input`, if it edits a file.
+/// It names the tool but carries none of its input.
+pub fn file_change(tool: &str, input: &Value) -> Option<UnifiedEvent> {
+    let path = input.get("file_path")?.as_str()?;
+    let text = |edit: &Value, key: &str| {
+        edit.get(key)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+    // Old and new text of each edit; a write's old contents aren't known
+    let edits = match tool {
+        "Edit" => vec![(text(input, "old_string"), text(input, "new_string"))],
+        "MultiEdit" => input
+            .get("edits")?
+            .as_array()?
+            .iter()
+            .map(|edit| (text(edit, "old_string"), text(edit, "new_string")))
+            .collect(),
+        "Write" => vec![(String::new(), input.get("content")?.as_str()?.to_string())],
+        _ => return None,
+    };
+
+    let (mut added, mut removed) = (0, 0);
+    let name = path.trim_start_matches('/');
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", name, name);
+    for (old, new) in &edits {
+        let text_diff = TextDiff::from_lines(old, new);
+        for change in text_diff.iter_all_changes() {
+            match change.tag() {
+                ChangeTag::Insert => added += 1,
+                ChangeTag::Delete => removed += 1,
+                ChangeTag::Equal => {}
+            }
+        }
+        let mut unified = text_diff.unified_diff();
+        unified
+            .context_radius(CONTEXT_LINES)
+            .missing_newline_hint(false);
+        for hunk in unified.iter_hunks() {
+            diff.push_str(&hunk.to_string());
+        }
+    }
+
+    let mut event = UnifiedEvent::new("file_change");
+    event.tool = Some(tool.to_string());
+    event.path = Some(path.to_string());
+    event.lines_added = Some(added);
+    event.lines_removed = Some(removed);
+    let lines: Vec<&str> = diff.lines().collect();
+    if lines.len() > DIFF_LINES {
+        event.truncated = Some(true);
+        event.content_bytes = Some(diff.len() as u64);
+        diff = lines[..DIFF_LINES].join("\n");
+    }
+    event.content = Some(diff.trim_end().to_string());
+    Some(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_edit_diff() {
+        let input = json!({
+            "file_path": "src/header.rs",
+            "old_string": "fn parse(line: &str) {\n    line.strip_prefix(\"# \")\n}",
+            "new_string": "fn parse(line: &str) {\n    let line = line.trim_start();\n    line.strip_prefix(\"# \")\n}",
+        });
+        let event = file_change("Edit", &input).unwrap();
+        assert_eq!(event.event_type, "file_change");
+        assert_eq!(event.path.as_deref(), Some("src/header.rs"));
+        assert_eq!((event.lines_added, event.lines_removed), (Some(1), Some(0)));
+        assert_eq!(
+            event.content.as_deref(),
+            Some(
+                "--- a/src/header.rs\n\
+                 +++ b/src/header.rs\n\
+                 @@ -1,3 +1,4 @@\n \
+                 fn parse(line: &str) {\n\
+                 +    let line = line.trim_start();\n     \
+                 line.strip_prefix(\"# \")\n \
+                 }"
+            )
+        );
+        assert_eq!(event.truncated, None);
+    }
+
+    #[test]
+    fn test_multi_edit_sums_edits() {
+        let input = json!({
+            "file_path": "a.txt",
+            "edits": [
+                {"old_string": "one\ntwo", "new_string": "uno"},
+                {"old_string": "three", "new_string": "tres\ndrei"},
+            ],
+        });
+        let event = file_change("MultiEdit", &input).unwrap();
+        assert_eq!((event.lines_added, event.lines_removed), (Some(3), Some(3)));
+        assert_eq!(event.content.unwrap().matches("@@ ").count(), 2);
+    }
+
+    #[test]
+    fn test_large_write_cut_to_diff_lines() {
+        let content: String = (0..100).map(|i| format!("line {}\n", i)).collect();
+        let input = json!({"file_path": "big.txt", "content": content});
+        let event = file_change("Write", &input).unwrap();
+        assert_eq!(
+            (event.lines_added, event.lines_removed),
+            (Some(100), Some(0))
+        );
+        assert_eq!(event.content.unwrap().lines().count(), DIFF_LINES);
+        assert_eq!(event.truncated, Some(true));
+
+        assert!(file_change("Read", &json!({"file_path": "a.txt"})).is_none());
+        assert!(file_change("Edit", &json!({"old_string": "a"})).is_none());
+    }
+}
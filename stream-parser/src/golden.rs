@@ -0,0 +1,213 @@
+//! Golden-file harness for the parser.
+//!
+//! Each fixture under `tests/fixtures/` is a captured agent transcript. Running
+//! the parser over it must reproduce the sibling `<name>.events.ndjson` file.
+//! Set `BLESS=1` (or run `cargo run --bin regen-goldens`) to rewrite the
+//! goldens after an intentional output change.
+
+use crate::{AgentFormat, Parser};
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that switches the harness from comparing to rewriting.
+const BLESS_ENV: &str = "BLESS";
+
+/// Agent id used for every fixture run.
+const AGENT_ID: &str = "golden";
+
+/// Suffix of the expected-output file next to each fixture.
+const GOLDEN_SUFFIX: &str = ".events.ndjson";
+
+/// Fields whose values change between runs; they are masked in golden output.
+const NONDETERMINISTIC_FIELDS: &[&str] = &["timestamp", "seq"];
+
+/// Lines of context shown around the first mismatch.
+const DIFF_CONTEXT: usize = 2;
+
+/// Directory holding the fixtures and goldens.
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+}
+
+/// All fixture inputs, in path order.
+fn fixtures() -> io::Result<Vec<PathBuf>> {
+    let mut inputs: Vec<PathBuf> = fs::read_dir(fixtures_dir())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && !path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().ends_with(GOLDEN_SUFFIX))
+                    .unwrap_or(false)
+        })
+        .collect();
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// Path of the golden output for a fixture (`claude_tools.jsonl` → `claude_tools.events.ndjson`).
+fn golden_path(fixture: &Path) -> PathBuf {
+    let stem = fixture
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    fixture.with_file_name(format!("{}{}", stem, GOLDEN_SUFFIX))
+}
+
+/// Format hint implied by the fixture name prefix, mirroring the CLI hint.
+fn format_for(fixture: &Path) -> AgentFormat {
+    let name = fixture
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if name.starts_with("python_") {
+        AgentFormat::Python
+    } else if name.starts_with("claude_") {
+        AgentFormat::ClaudeCode
+    } else if name.starts_with("openai_") {
+        AgentFormat::OpenAi
+    } else if name.starts_with("gemini_") {
+        AgentFormat::Gemini
+    } else if name.starts_with("aider_") {
+        AgentFormat::Aider
+    } else {
+        AgentFormat::Unknown
+    }
+}
+
+/// Run the parser over `input` and render normalized NDJSON.
+fn render(input: &str, format: AgentFormat) -> String {
+    let mut parser = Parser::new(AGENT_ID.to_string());
+    parser.set_format(format);
+    parser.set_lifecycle(true);
+
+    let mut out = String::new();
+    let mut events: Vec<_> = input
+        .lines()
+        .flat_map(|line| parser.parse_line(line))
+        .collect();
+    events.extend(parser.finish());
+    for event in events {
+        let value = serde_json::to_value(&event).expect("UnifiedEvent serializes");
+        out.push_str(&normalize(value).to_string());
+        out.push('\n');
+    }
+    out
+}
+
+/// Mask nondeterministic fields so goldens are stable across runs.
+fn normalize(mut event: Value) -> Value {
+    if let Some(obj) = event.as_object_mut() {
+        for field in NONDETERMINISTIC_FIELDS {
+            if let Some(value) = obj.get_mut(*field) {
+                *value = Value::String(format!("<{}>", field));
+            }
+        }
+    }
+    event
+}
+
+/// Describe the first differing line between two renders, with context.
+fn diff(expected: &str, actual: &str) -> Option<String> {
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+
+    let first = (0..expected_lines.len().max(actual_lines.len()))
+        .find(|&i| expected_lines.get(i) != actual_lines.get(i))?;
+
+    let mut out = format!(
+        "first difference at line {} (expected {} lines, got {})\n",
+        first + 1,
+        expected_lines.len(),
+        actual_lines.len()
+    );
+    let context_start = first.saturating_sub(DIFF_CONTEXT);
+    for (i, line) in expected_lines[context_start..first].iter().enumerate() {
+        out.push_str(&format!("    {:>4} | {}\n", context_start + i + 1, line));
+    }
+    out.push_str(&format!(
+        "  - {:>4} | {}\n",
+        first + 1,
+        expected_lines.get(first).unwrap_or(&"<end of file>")
+    ));
+    out.push_str(&format!(
+        "  + {:>4} | {}\n",
+        first + 1,
+        actual_lines.get(first).unwrap_or(&"<end of file>")
+    ));
+    Some(out)
+}
+
+/// Compare one fixture against its golden, or rewrite the golden when blessing.
+fn check(fixture: &Path, bless: bool) -> Result<(), String> {
+    let input = fs::read_to_string(fixture)
+        .map_err(|e| format!("{}: failed to read fixture: {}", fixture.display(), e))?;
+    let actual = render(&input, format_for(fixture));
+    let golden = golden_path(fixture);
+
+    if bless {
+        return fs::write(&golden, actual)
+            .map_err(|e| format!("{}: failed to write golden: {}", golden.display(), e));
+    }
+
+    let expected = fs::read_to_string(&golden).map_err(|e| {
+        format!(
+            "{}: missing golden ({}); run with {}=1 to create it",
+            golden.display(),
+            e,
+            BLESS_ENV
+        )
+    })?;
+
+    match diff(&expected, &actual) {
+        Some(report) => Err(format!("{}: {}", fixture.display(), report)),
+        None => Ok(()),
+    }
+}
+
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_golden_fixtures() {
+        let bless = std::env::var_os(BLESS_ENV).is_some();
+        let fixtures = fixtures().expect("fixtures directory is readable");
+        assert!(!fixtures.is_empty(), "no fixtures found");
+
+        let failures: Vec<String> = fixtures
+            .iter()
+            .filter_map(|fixture| check(fixture, bless).err())
+            .collect();
+
+        assert!(
+            failures.is_empty(),
+            "{} golden mismatch(es); rerun with {}=1 if the change is intended\n\n{}",
+            failures.len(),
+            BLESS_ENV,
+            failures.join("\n")
+        );
+    }
+
+    #[test]
+    fn test_normalize_masks_nondeterministic_fields() {
+        let event = normalize(json!({"type": "turn", "seq": 4, "timestamp": 1700000000000u64}));
+        assert_eq!(
+            event,
+            json!({"type": "turn", "seq": "<seq>", "timestamp": "<timestamp>"})
+        );
+    }
+
+    #[test]
+    fn test_diff_reports_first_mismatch() {
+        let report = diff("a\nb\nc\nd\n", "a\nb\nX\nd\n").unwrap();
+        assert!(report.contains("line 3"));
+        assert!(report.contains("- ") && report.contains("| c"));
+        assert!(report.contains("+ ") && report.contains("| X"));
+        assert!(diff("a\n", "a\n").is_none());
+    }
+}
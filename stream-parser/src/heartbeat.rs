@@ -0,0 +1,83 @@
+//! Tell the UI the parser is alive while the agent is quiet.
+//!
+//! A build or test suite run by a tool call can print nothing for minutes,
+//! which looks the same as a dead parser. Once the agent has produced a
+//! first event, the heartbeat asks for a `heartbeat` event after every
+//! interval without input, until input resumes.
+
+use std::time::{Duration, Instant};
+
+pub struct Heartbeat {
+    every: Duration,
+    /// When the last event was parsed; no heartbeats before the first
+    last_event: Option<Instant>,
+    /// Last input or heartbeat, whichever is later
+    quiet_since: Instant,
+}
+
+impl Heartbeat {
+    pub fn new(every: Duration, started: Instant) -> Self {
+        Heartbeat {
+            every,
+            last_event: None,
+            quiet_since: started,
+        }
+    }
+
+    /// Record a line of input and whether it produced any events.
+    pub fn observe_input(&mut self, now: Instant, produced_events: bool) {
+        self.quiet_since = now;
+        if produced_events {
+            self.last_event = Some(now);
+        }
+    }
+
+    /// Time left before the next heartbeat, or `None` before the first event.
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.last_event?;
+        Some(
+            self.every
+                .saturating_sub(now.saturating_duration_since(self.quiet_since)),
+        )
+    }
+
+    /// If a heartbeat is due, start the next interval and return how long
+    /// it has been since the last event.
+    pub fn beat(&mut self, now: Instant) -> Option<Duration> {
+        if !self.remaining(now)?.is_zero() {
+            return None;
+        }
+        self.quiet_since = now;
+        self.last_event
+            .map(|last| now.saturating_duration_since(last))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_beats_only_after_first_event_while_quiet() {
+        let start = Instant::now();
+        let secs = |n| start + Duration::from_secs(n);
+        let mut heartbeat = Heartbeat::new(Duration::from_secs(10), start);
+
+        // Noise without events doesn't arm it
+        heartbeat.observe_input(secs(5), false);
+        assert_eq!(heartbeat.remaining(secs(60)), None);
+        assert_eq!(heartbeat.beat(secs(60)), None);
+
+        heartbeat.observe_input(secs(60), true);
+        assert_eq!(heartbeat.remaining(secs(65)), Some(Duration::from_secs(5)));
+        assert_eq!(heartbeat.beat(secs(65)), None);
+        assert_eq!(heartbeat.beat(secs(70)), Some(Duration::from_secs(10)));
+        assert_eq!(heartbeat.beat(secs(75)), None);
+        assert_eq!(heartbeat.beat(secs(80)), Some(Duration::from_secs(20)));
+
+        // Input pushes the next beat back even when it yields no event
+        heartbeat.observe_input(secs(85), false);
+        assert_eq!(heartbeat.beat(secs(90)), None);
+        assert_eq!(heartbeat.beat(secs(95)), Some(Duration::from_secs(35)));
+    }
+}
@@ -0,0 +1,2583 @@
+//! Normalize agent output into [`UnifiedEvent`]s.
+//!
+//! The `agent-stream` binary is a thin wrapper over this library: it feeds
+//! stdin (or an `--exec`'d agent) through a [`Parser`] and writes each event
+//! as a line of JSON. Services that already hold the agent's output can embed
+//! the parser directly:
+//!
+//! ```
+//! use agent_stream::{AgentFormat, Parser, UnifiedEvent};
+//!
+//! let mut parser = Parser::new("agent-1".to_string());
+//! parser.set_format(AgentFormat::Python);
+//!
+//! let events = parser.parse_line(r#"{"type":"tool_call","tool":"bash","args":{"command":"ls"}}"#);
+//! assert_eq!(events[0].event_type, "tool_call");
+//! assert_eq!(events[0].tool.as_deref(), Some("bash"));
+//!
+//! // Events round-trip through the NDJSON wire format
+//! let line = serde_json::to_string(&events[0]).unwrap();
+//! let decoded: UnifiedEvent = serde_json::from_str(&line).unwrap();
+//! assert_eq!(decoded, events[0]);
+//!
+//! // End-of-stream events, such as an interrupted session's `session_end`
+//! let trailing = parser.finish();
+//! assert!(trailing.is_empty());
+//! ```
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+pub use mc_events::UnifiedEvent;
+
+pub mod demux;
+pub mod encoding;
+mod file_change;
+#[cfg(test)]
+mod golden;
+pub mod heartbeat;
+mod media;
+pub mod merge;
+mod multiline;
+#[cfg(unix)]
+pub mod output;
+pub mod pretty;
+pub mod profile;
+pub mod replay;
+pub mod rules;
+mod shell;
+pub mod state;
+pub mod terminal;
+pub mod throttle;
+pub mod watchdog;
+
+use multiline::{Feed, Reassembler};
+use profile::ArgProfile;
+use rules::Rules;
+
+/// Lines on an agent's stderr that mean it crashed: a Python traceback or a
+/// Rust panic.
+const CRASH_MARKERS: &[&str] = &["Traceback (most recent call last):", "panicked at"];
+
+/// Characters of a line that failed to parse kept in its `error` event.
+pub const PARSE_FAILURE_SAMPLE_CHARS: usize = 200;
+
+/// JSON lines in a row that must match a format before it is detected.
+const LATCH_LINES: u32 = 2;
+
+/// JSON lines in a row that must match only another format before a
+/// detected format is dropped for it.
+const SWITCH_LINES: u32 = 3;
+
+/// Byte order mark some Windows tools write at the start of a stream.
+const BOM: char = '\u{feff}';
+
+/// Agent format type
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentFormat {
+    Python,
+    ClaudeCode,
+    /// OpenAI/Codex `chat.completion.chunk` stream
+    OpenAi,
+    /// Gemini CLI / Vertex `GenerateContentResponse` stream
+    Gemini,
+    /// Aider's terminal output; only selected by hint
+    Aider,
+    Unknown,
+}
+
+/// How the agent's output ended, as reported by `agent_exit`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AgentExit {
+    /// Input ended with nothing more known about the agent
+    Eof,
+    /// The agent process exited with this code
+    Exited(i32),
+    /// The agent process was killed by this signal
+    Killed(i32),
+    /// The parser was told to stop before input ended
+    Interrupted,
+}
+
+/// An OpenAI tool call whose argument deltas are still arriving.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct PendingToolCall {
+    id: Option<String>,
+    name: String,
+    arguments: String,
+}
+
+/// When to flush text deltas merged by [`Parser::set_coalesce`].
+///
+/// With neither limit set, deltas are only flushed by another event or the
+/// end of the stream.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Coalesce {
+    /// Flush once this much text is buffered
+    pub max_bytes: Option<usize>,
+    /// Flush once the oldest buffered delta is this old
+    pub max_age: Option<Duration>,
+}
+
+/// Text deltas merged into one event, waiting to be flushed.
+#[derive(Debug)]
+struct PendingDelta {
+    event: UnifiedEvent,
+    started: Instant,
+}
+
+/// A diff Aider is printing, collected until it ends.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingDiff {
+    /// Opened by a diff code fence, so only the closing fence ends it
+    fenced: bool,
+    lines: Vec<String>,
+}
+
+/// Claude turn state of a subagent, and the agent id its events get
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Subagent {
+    agent_id: String,
+    current_turn: u32,
+    claude_continuing: bool,
+    claude_stop_reason: Option<String>,
+    api_messages: u64,
+    claude_usage: Option<Usage>,
+}
+
+impl Subagent {
+    fn new(agent_id: String) -> Self {
+        Subagent {
+            agent_id,
+            current_turn: 0,
+            claude_continuing: false,
+            claude_stop_reason: None,
+            api_messages: 0,
+            claude_usage: None,
+        }
+    }
+}
+
+/// Token counts from a Claude `usage` object
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Usage {
+    input: u64,
+    output: u64,
+    cache_read: u64,
+    cache_write: u64,
+}
+
+impl Usage {
+    /// Take the counts `usage` carries. Claude reports running totals for
+    /// the message, so each replaces the last.
+    fn update(&mut self, usage: &Value) {
+        for (key, count) in [
+            ("input_tokens", &mut self.input),
+            ("output_tokens", &mut self.output),
+            ("cache_read_input_tokens", &mut self.cache_read),
+            ("cache_creation_input_tokens", &mut self.cache_write),
+        ] {
+            if let Some(n) = usage.get(key).and_then(|v| v.as_u64()) {
+                *count = n;
+            }
+        }
+    }
+
+    fn add(&mut self, other: Usage) {
+        self.input += other.input;
+        self.output += other.output;
+        self.cache_read += other.cache_read;
+        self.cache_write += other.cache_write;
+    }
+
+    fn attach(&self, event: &mut UnifiedEvent) {
+        event.input_tokens = Some(self.input);
+        event.output_tokens = Some(self.output);
+        event.cache_read_tokens = Some(self.cache_read);
+        event.cache_write_tokens = Some(self.cache_write);
+    }
+}
+
+/// Parser state
+pub struct Parser {
+    format: AgentFormat,
+    /// The format was given rather than detected, so it never changes
+    format_pinned: bool,
+    /// Consecutive JSON lines that matched a format other than `format`
+    format_run: Option<(AgentFormat, u32)>,
+    agent_id: String,
+    current_turn: u32,
+    /// Latest Claude Code session id seen, for resuming a dead agent
+    session_id: Option<String>,
+    /// Whether `agent_start` was emitted; it must come out once
+    agent_started: bool,
+    /// Emit `agent_start` for any agent and `agent_exit` at the end
+    lifecycle: bool,
+    session_ended: bool,
+    /// Argument profile, when enabled with `enable_arg_profile`
+    arg_profile: Option<ArgProfile>,
+    stats_every: u64,
+    calls_since_stats: u64,
+    /// In-flight OpenAI tool calls by (choice index, tool call index)
+    openai_tool_calls: BTreeMap<(u64, u64), PendingToolCall>,
+    /// The last Claude message stopped to use a tool, so the next one
+    /// continues its turn
+    claude_continuing: bool,
+    /// `stop_reason` of the Claude message being streamed
+    claude_stop_reason: Option<String>,
+    /// Claude `message_start`s seen, one per API round-trip
+    api_messages: u64,
+    /// Token usage of the Claude message being streamed
+    claude_usage: Option<Usage>,
+    /// Token usage of every finished Claude message, for `session_end`
+    usage_total: Option<Usage>,
+    /// Claude Code subagents by the `tool_use_id` that launched them, while
+    /// their state is not swapped in
+    subagents: HashMap<String, Subagent>,
+    /// A Gemini response is streaming and has not finished yet
+    gemini_in_turn: bool,
+    /// Diff in Aider output still being printed
+    aider_diff: Option<PendingDiff>,
+    /// Stamp events with the time they were parsed
+    timestamps: bool,
+    /// Timestamp carried by the line being parsed, if any
+    source_timestamp: Option<String>,
+    /// No line has been parsed yet, so one may start with a byte order mark
+    at_start: bool,
+    /// Python tool calls issued so far, for synthesized ids
+    tool_calls_seen: u64,
+    /// Ids of Python tool calls still waiting for their result, oldest first
+    unanswered_tool_calls: VecDeque<String>,
+    /// Trust that every line after an unclosed `{` belongs to it
+    multiline: bool,
+    multiline_limit: usize,
+    /// A JSON object still being reassembled across lines
+    pending_json: Option<Reassembler>,
+    /// Lines that looked like JSON but did not parse
+    parse_failures: u64,
+    /// Emit Claude text as `thinking` and drop thinking blocks, as before
+    /// `message` and `reasoning` events existed
+    legacy_thinking: bool,
+    /// Merge consecutive Claude text deltas, when enabled
+    coalesce: Option<Coalesce>,
+    pending_delta: Option<PendingDelta>,
+    /// The line being parsed was a delta taken into `pending_delta`
+    delta_absorbed: bool,
+    /// `seq` of the next event emitted
+    next_seq: u64,
+    /// Longest `content` or `result` emitted, in bytes
+    max_content_bytes: Option<usize>,
+    /// Mask secrets in emitted events, along with these literal values
+    redact: Option<Vec<String>>,
+    /// Remove ANSI escape sequences from text lines
+    strip_ansi: bool,
+    /// What plain text lines turn into
+    rules: Rules,
+    /// Where images in Claude content are saved, if anywhere
+    media_dir: Option<PathBuf>,
+}
+
+impl Parser {
+    pub fn new(agent_id: String) -> Self {
+        Parser {
+            format: AgentFormat::Unknown,
+            format_pinned: false,
+            format_run: None,
+            agent_id,
+            current_turn: 0,
+            session_id: None,
+            agent_started: false,
+            lifecycle: false,
+            session_ended: false,
+            arg_profile: None,
+            stats_every: 0,
+            calls_since_stats: 0,
+            openai_tool_calls: BTreeMap::new(),
+            claude_continuing: false,
+            claude_stop_reason: None,
+            api_messages: 0,
+            claude_usage: None,
+            usage_total: None,
+            subagents: HashMap::new(),
+            gemini_in_turn: false,
+            aider_diff: None,
+            timestamps: true,
+            source_timestamp: None,
+            at_start: true,
+            tool_calls_seen: 0,
+            unanswered_tool_calls: VecDeque::new(),
+            multiline: false,
+            multiline_limit: multiline::DEFAULT_LIMIT,
+            pending_json: None,
+            parse_failures: 0,
+            legacy_thinking: false,
+            coalesce: None,
+            pending_delta: None,
+            delta_absorbed: false,
+            next_seq: 1,
+            max_content_bytes: None,
+            redact: None,
+            strip_ansi: true,
+            rules: Rules::defaults(),
+            media_dir: None,
+        }
+    }
+
+    /// Pin the agent format instead of detecting it from the stream.
+    /// `Unknown` goes back to detecting.
+    pub fn set_format(&mut self, format: AgentFormat) {
+        self.format = format;
+        self.format_pinned = format != AgentFormat::Unknown;
+        self.format_run = None;
+    }
+
+    /// The pinned or detected agent format, or the one the stream looks
+    /// like so far if none is detected yet
+    pub fn format(&self) -> AgentFormat {
+        match (self.format, self.format_run) {
+            (AgentFormat::Unknown, Some((format, _))) => format,
+            (format, _) => format,
+        }
+    }
+
+    /// Number events from `seq` instead of 1, to continue a previous run
+    pub fn set_start_seq(&mut self, seq: u64) {
+        self.next_seq = seq;
+    }
+
+    /// The `seq` the next event will get
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Cut `content` and `result` longer than `max_bytes`, marking the
+    /// event `truncated` (unlimited by default)
+    pub fn set_max_content_bytes(&mut self, max_bytes: usize) {
+        self.max_content_bytes = Some(max_bytes);
+    }
+
+    /// Replace secrets in `content`, `result` and `args` with `[REDACTED]`:
+    /// common API key shapes, and each of `secrets` wherever it appears
+    pub fn set_redact(&mut self, secrets: Vec<String>) {
+        self.redact = Some(secrets);
+    }
+
+    /// Bracket the stream with lifecycle events (off by default): an
+    /// `agent_start` ahead of the events of the first line that parses, and
+    /// an `agent_exit` from [`Parser::finish_with`].
+    pub fn set_lifecycle(&mut self, enabled: bool) {
+        self.lifecycle = enabled;
+    }
+
+    /// Recognize plain text lines with `rules` instead of the built-in ones
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+    }
+
+    /// Save images from Claude content blocks under `dir`, named by their
+    /// hash, and give the file's path on their `media` events. Without it,
+    /// `media` events only describe the image.
+    pub fn set_media_dir(&mut self, dir: PathBuf) {
+        self.media_dir = Some(dir);
+    }
+
+    /// Remove ANSI colors and other escape sequences from lines that aren't
+    /// JSON (on by default). JSON lines are never touched.
+    pub fn set_strip_ansi(&mut self, enabled: bool) {
+        self.strip_ansi = enabled;
+    }
+
+    /// Turn `timestamp` on events on or off (on by default)
+    pub fn set_timestamps(&mut self, enabled: bool) {
+        self.timestamps = enabled;
+    }
+
+    /// Expect pretty-printed JSON.
+    ///
+    /// A line that opens a JSON object without closing it is always buffered
+    /// until the object closes. Without this, a complete JSON line arriving
+    /// mid-object is taken to mean the object was cut off, and is parsed on
+    /// its own; with it, the line is kept as part of the object.
+    pub fn set_multiline(&mut self, enabled: bool) {
+        self.multiline = enabled;
+    }
+
+    /// Bytes buffered for one multi-line object before it is given up on
+    pub fn set_multiline_limit(&mut self, limit: usize) {
+        self.multiline_limit = limit;
+    }
+
+    /// Map Claude text to `thinking` events and drop extended thinking, for
+    /// consumers that predate `message` and `reasoning`
+    pub fn set_legacy_thinking(&mut self, enabled: bool) {
+        self.legacy_thinking = enabled;
+    }
+
+    /// Merge consecutive Claude `content_block_delta` text into one event.
+    ///
+    /// Deltas of the same event type are buffered and flushed as one event
+    /// when a limit is reached, when any other event is emitted (ahead of
+    /// it, so ordering holds), or at the end of the stream. A stream that
+    /// goes quiet is flushed by [`Parser::flush_stale`].
+    pub fn set_coalesce(&mut self, coalesce: Coalesce) {
+        self.coalesce = Some(coalesce);
+    }
+
+    /// Time until buffered deltas reach `max_age`, if any are buffered.
+    pub fn coalesce_remaining(&self, now: Instant) -> Option<Duration> {
+        let pending = self.pending_delta.as_ref()?;
+        let max_age = self.coalesce?.max_age?;
+        Some(max_age.saturating_sub(now.saturating_duration_since(pending.started)))
+    }
+
+    /// Flush buffered deltas that have reached `max_age`.
+    pub fn flush_stale(&mut self, now: Instant) -> Vec<UnifiedEvent> {
+        if !self.coalesce_remaining(now).is_some_and(|r| r.is_zero()) {
+            return vec![];
+        }
+        let mut events = self.flush_deltas();
+        self.scrub(&mut events);
+        self.number(&mut events);
+        events
+    }
+
+    /// How many lines looked like JSON but failed to parse
+    pub fn parse_failures(&self) -> u64 {
+        self.parse_failures
+    }
+
+    /// Record the argument keys of every tool call.
+    ///
+    /// The aggregate is attached to `session_end` as `tool_profile`, and also
+    /// emitted as a `stats` event every `stats_every` tool calls (0 disables
+    /// the periodic events).
+    pub fn enable_arg_profile(&mut self, stats_every: u64) {
+        self.arg_profile = Some(ArgProfile::default());
+        self.stats_every = stats_every;
+    }
+
+    /// Parse a line and return unified events.
+    ///
+    /// A text line redrawn with `\r`, like a progress bar, is taken at its
+    /// final state. Windows line endings and a byte order mark on the first
+    /// line are ignored.
+    pub fn parse_line(&mut self, line: &str) -> Vec<UnifiedEvent> {
+        self.source_timestamp = None;
+        let mut line = line.trim_end_matches('\r');
+        if std::mem::take(&mut self.at_start) {
+            line = line.strip_prefix(BOM).unwrap_or(line);
+        }
+        // Diff context lines start with a space, so Aider needs the indent
+        let mut events = if self.format == AgentFormat::Aider {
+            let line = self.displayed(line);
+            self.parse_aider_line(line.trim_end())
+        } else {
+            self.parse_trimmed(line.trim())
+        };
+        // Parse failures don't show the agent is up; a buffered delta does
+        let parsed = self.delta_absorbed || events.iter().any(|e| e.event_type != "error");
+        if self.lifecycle && !self.agent_started && parsed {
+            self.agent_started = true;
+            events.insert(
+                0,
+                UnifiedEvent::new("agent_start").with_agent_id(&self.agent_id),
+            );
+        }
+        self.finish_line(events)
+    }
+
+    /// Turn a line the agent wrote to stderr into a `stderr` event, with
+    /// status `crash` when it starts a traceback or reports a panic.
+    pub fn parse_stderr_line(&mut self, line: &str) -> Vec<UnifiedEvent> {
+        self.source_timestamp = None;
+        let line = self.displayed(line);
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            return vec![];
+        }
+        let mut event = UnifiedEvent::new("stderr")
+            .with_agent_id(&self.agent_id)
+            .with_content(line);
+        if CRASH_MARKERS.iter().any(|marker| line.contains(marker)) {
+            event.status = Some("crash".to_string());
+        }
+        self.finish_line(vec![event])
+    }
+
+    /// A `heartbeat` event for an agent that has been quiet for `idle`.
+    pub fn heartbeat(&mut self, idle: Duration) -> Vec<UnifiedEvent> {
+        self.source_timestamp = None;
+        let mut event = UnifiedEvent::new("heartbeat").with_agent_id(&self.agent_id);
+        event.idle_secs = Some(idle.as_secs());
+        self.finish_line(vec![event])
+    }
+
+    /// An `error` event about the parser's own plumbing rather than
+    /// anything the agent wrote.
+    pub fn error(&mut self, status: &str, message: &str) -> Vec<UnifiedEvent> {
+        self.source_timestamp = None;
+        let mut event = UnifiedEvent::new("error")
+            .with_agent_id(&self.agent_id)
+            .with_status(status);
+        event.error = Some(message.to_string());
+        self.finish_line(vec![event])
+    }
+
+    /// Order, profile, stamp and number the events parsed from one line.
+    fn finish_line(&mut self, mut events: Vec<UnifiedEvent>) -> Vec<UnifiedEvent> {
+        // Anything but another delta ends a run of buffered deltas
+        if self.delta_absorbed {
+            self.delta_absorbed = false;
+        } else if !events.is_empty() && self.pending_delta.is_some() {
+            let mut flushed = self.flush_deltas();
+            flushed.append(&mut events);
+            events = flushed;
+        }
+        self.profile_tool_calls(&mut events);
+        self.stamp(&mut events);
+        self.scrub(&mut events);
+        self.number(&mut events);
+        events
+    }
+
+    /// Redact and truncate events about to be emitted. Redaction goes
+    /// first so a cut can't leave half a secret unrecognized.
+    fn scrub(&self, events: &mut [UnifiedEvent]) {
+        for event in events {
+            if let Some(secrets) = &self.redact {
+                event.redact(secrets);
+            }
+            if let Some(max_bytes) = self.max_content_bytes {
+                event.truncate(max_bytes);
+            }
+        }
+    }
+
+    /// Give events the next sequence numbers, in emission order.
+    fn number(&mut self, events: &mut [UnifiedEvent]) {
+        for event in events {
+            event.seq = self.next_seq;
+            self.next_seq += 1;
+        }
+    }
+
+    /// Set `timestamp` on events that don't have one: the source line's own
+    /// timestamp when it carried one, else the current time.
+    ///
+    /// Timestamps are RFC 3339 in UTC with millisecond precision.
+    fn stamp(&self, events: &mut [UnifiedEvent]) {
+        if !self.timestamps || events.is_empty() {
+            return;
+        }
+        let timestamp = self
+            .source_timestamp
+            .clone()
+            .unwrap_or_else(|| Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true));
+        for event in events.iter_mut().filter(|e| e.timestamp.is_none()) {
+            event.timestamp = Some(timestamp.clone());
+        }
+    }
+
+    fn parse_trimmed(&mut self, trimmed: &str) -> Vec<UnifiedEvent> {
+        if trimmed.is_empty() {
+            return vec![];
+        }
+
+        if self.pending_json.is_some() {
+            return self.continue_multiline(trimmed);
+        }
+
+        // Try to parse as JSON
+        let error = match serde_json::from_str::<Value>(trimmed) {
+            Ok(json) => return self.parse_json_line(json),
+            Err(e) => e,
+        };
+
+        // The first line of a pretty-printed object
+        if trimmed.starts_with('{') && Reassembler::opens(trimmed) {
+            self.pending_json = Some(Reassembler::new(self.multiline_limit));
+            return self.continue_multiline(trimmed);
+        }
+
+        // Most likely cut off mid-write; as text it would corrupt rendering
+        if looks_like_json(trimmed) {
+            return vec![self.parse_failure(trimmed, &error)];
+        }
+
+        // Not JSON - treat as plain text output
+        let text = self.displayed(trimmed);
+        match text.trim() {
+            "" => vec![],
+            text => self.parse_text(text),
+        }
+    }
+
+    /// What a text line shows on a terminal: its final state after any
+    /// redraws, without escape sequences unless they are kept.
+    fn displayed<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let line = terminal::final_state(line);
+        if self.strip_ansi {
+            terminal::strip_ansi(line)
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    fn parse_json_line(&mut self, json: Value) -> Vec<UnifiedEvent> {
+        self.source_timestamp = json.get("timestamp").and_then(source_timestamp);
+        self.parse_json(json)
+    }
+
+    /// Feed a line to the object being reassembled.
+    fn continue_multiline(&mut self, line: &str) -> Vec<UnifiedEvent> {
+        let mut pending = self.pending_json.take().expect("reassembling");
+
+        // A whole JSON line mid-object: the agent died while writing the object
+        if !self.multiline && (line.starts_with('{') || line.starts_with('[')) {
+            if let Ok(json) = serde_json::from_str::<Value>(line) {
+                let mut events = vec![self.cut_off(pending)];
+                events.extend(self.parse_json_line(json));
+                return events;
+            }
+        }
+
+        match pending.push(line) {
+            Feed::Incomplete => {
+                self.pending_json = Some(pending);
+                vec![]
+            }
+            Feed::Complete(text) => match serde_json::from_str::<Value>(&text) {
+                Ok(json) => self.parse_json_line(json),
+                Err(e) => vec![self.unparsed(text, format!("invalid multi-line JSON: {}", e))],
+            },
+            Feed::Overflow(text) => {
+                let error = format!(
+                    "multi-line JSON exceeded {} bytes without closing",
+                    self.multiline_limit
+                );
+                vec![self.unparsed(text, error)]
+            }
+        }
+    }
+
+    /// An `error` event for JSON that stopped partway.
+    fn cut_off(&mut self, pending: Reassembler) -> UnifiedEvent {
+        let text = pending.into_text();
+        let error = serde_json::from_str::<Value>(&text).expect_err("brackets never closed");
+        self.parse_failure(&text, &error)
+    }
+
+    /// An `error` event for a line that looked like JSON but did not parse.
+    fn parse_failure(&mut self, text: &str, error: &serde_json::Error) -> UnifiedEvent {
+        self.parse_failures += 1;
+        let sample: String = text.chars().take(PARSE_FAILURE_SAMPLE_CHARS).collect();
+        let mut event = UnifiedEvent::new("error")
+            .with_agent_id(&self.agent_id)
+            .with_content(&sample)
+            .with_status("parse_error");
+        event.error = Some(format!("invalid JSON: {}", error));
+        event
+    }
+
+    /// A `raw` event for buffered lines that did not make a JSON value.
+    fn unparsed(&self, text: String, error: String) -> UnifiedEvent {
+        let mut event = UnifiedEvent::new("raw")
+            .with_agent_id(&self.agent_id)
+            .with_content(&text);
+        event.error = Some(error);
+        event
+    }
+
+    /// Flush end-of-stream events once input is exhausted.
+    ///
+    /// If a Claude Code session was seen but never produced a result event,
+    /// emits a `session_end` with status `interrupted` carrying the last known
+    /// session id so the orchestrator can still resume it.
+    pub fn finish(&mut self) -> Vec<UnifiedEvent> {
+        self.finish_with(AgentExit::Eof)
+    }
+
+    /// [`Parser::finish`], saying how the agent ended in the `agent_exit`
+    /// emitted last when lifecycle events are on.
+    pub fn finish_with(&mut self, exit: AgentExit) -> Vec<UnifiedEvent> {
+        // A stream cut off mid tool call still reports what it had
+        let mut events = self.flush_deltas();
+        events.extend(self.flush_aider_diff());
+        events.extend(self.flush_openai_tool_calls(None));
+        self.source_timestamp = None;
+        if let Some(pending) = self.pending_json.take() {
+            let event = self.cut_off(pending);
+            events.insert(0, event);
+        }
+        if !self.session_ended && (self.session_id.is_some() || self.arg_profile.is_some()) {
+            self.session_ended = true;
+
+            let mut event = self.session_end();
+            if self.session_id.is_some() {
+                event.status = Some("interrupted".to_string());
+            }
+            events.push(event);
+        }
+        if self.lifecycle {
+            let mut event = UnifiedEvent::new("agent_exit").with_agent_id(&self.agent_id);
+            let status = match exit {
+                AgentExit::Eof => "eof",
+                AgentExit::Exited(code) => {
+                    event.exit_code = Some(code);
+                    "exited"
+                }
+                AgentExit::Killed(signal) => {
+                    event.signal = Some(signal);
+                    "killed"
+                }
+                AgentExit::Interrupted => "interrupted",
+            };
+            events.push(event.with_status(status));
+        }
+        self.stamp(&mut events);
+        self.scrub(&mut events);
+        self.number(&mut events);
+        events
+    }
+
+    /// A `session_end` event carrying what the parser knows about the session.
+    fn session_end(&self) -> UnifiedEvent {
+        let mut event = UnifiedEvent::new("session_end")
+            .with_agent_id(&self.agent_id)
+            .with_session_id(self.session_id.as_deref());
+        event.tool_profile = self.arg_profile.as_ref().map(ArgProfile::to_value);
+        if let Some(total) = self.usage_total {
+            total.attach(&mut event);
+        }
+        event
+    }
+
+    /// Feed emitted tool calls to the argument profile, adding stats events.
+    fn profile_tool_calls(&mut self, events: &mut Vec<UnifiedEvent>) {
+        let Some(profile) = self.arg_profile.as_mut() else {
+            return;
+        };
+
+        let mut stats = None;
+        for event in events.iter().filter(|e| e.event_type == "tool_call") {
+            let Some(tool) = &event.tool else {
+                continue;
+            };
+            profile.record(tool, event.args.as_ref().unwrap_or(&Value::Null));
+            self.calls_since_stats += 1;
+            if self.stats_every > 0 && self.calls_since_stats >= self.stats_every {
+                self.calls_since_stats = 0;
+                let mut event = UnifiedEvent::new("stats").with_agent_id(&self.agent_id);
+                event.tool_profile = Some(profile.to_value());
+                stats = Some(event);
+            }
+        }
+        events.extend(stats);
+    }
+
+    /// Parse JSON input (Python, Claude Code, or OpenAI format)
+    fn parse_json(&mut self, json: Value) -> Vec<UnifiedEvent> {
+        let format = if self.format_pinned {
+            self.format
+        } else {
+            self.follow_format(&json)
+        };
+
+        match format {
+            AgentFormat::Python => self.parse_python_json(json),
+            AgentFormat::ClaudeCode => self.parse_claude_json(json),
+            AgentFormat::OpenAi => self.parse_openai_json(json),
+            AgentFormat::Gemini => self.parse_gemini_json(json),
+            AgentFormat::Aider | AgentFormat::Unknown => {
+                // Couldn't detect, try both
+                let events = self.parse_python_json(json.clone());
+                if !events.is_empty() {
+                    return events;
+                }
+                self.parse_claude_json(json)
+            }
+        }
+    }
+
+    /// Follow the format the stream's JSON lines match, returning the one to
+    /// parse the current line as.
+    ///
+    /// The format latches once [`LATCH_LINES`] lines in a row match it, so a
+    /// stray line from a wrapper script can't decide it; until then each line
+    /// is parsed as what it looks like. A latched format gives way to another
+    /// after [`SWITCH_LINES`] lines in a row that could only be the other.
+    fn follow_format(&mut self, json: &Value) -> AgentFormat {
+        let Some((format, certain)) = Self::detect_format(json) else {
+            return self.format();
+        };
+        if format == self.format {
+            self.format_run = None;
+            return format;
+        }
+        let needed = if self.format == AgentFormat::Unknown {
+            LATCH_LINES
+        } else if certain {
+            SWITCH_LINES
+        } else {
+            return self.format;
+        };
+        let run = match self.format_run {
+            Some((candidate, lines)) if candidate == format => lines + 1,
+            _ => 1,
+        };
+        if run >= needed {
+            self.format = format;
+            self.format_run = None;
+            return format;
+        }
+        self.format_run = Some((format, run));
+        self.format()
+    }
+
+    /// The format a JSON line looks like, and whether no other format could
+    /// have written it.
+    fn detect_format(json: &Value) -> Option<(AgentFormat, bool)> {
+        let obj = json.as_object()?;
+        // Gemini responses have no "type" either, just "candidates"
+        if obj.contains_key("candidates") {
+            return Some((AgentFormat::Gemini, true));
+        }
+
+        // OpenAI chunks have no "type", just "object" and "choices[].delta"
+        let is_chunk = obj.get("object").and_then(|v| v.as_str()) == Some("chat.completion.chunk");
+        let has_delta = obj
+            .get("choices")
+            .and_then(|v| v.as_array())
+            .is_some_and(|choices| choices.iter().any(|c| c.get("delta").is_some()));
+        if is_chunk || has_delta {
+            return Some((AgentFormat::OpenAi, true));
+        }
+
+        // Claude Code format has "type" with values like "assistant", "user", "result"
+        match obj.get("type").and_then(|v| v.as_str()) {
+            Some("assistant" | "user" | "result" | "system") => {
+                return Some((AgentFormat::ClaudeCode, true))
+            }
+            // Python format has "type" with values like "turn", "thinking", "tool_call"
+            Some("turn" | "thinking" | "tool_call" | "tool_result") => {
+                return Some((AgentFormat::Python, true))
+            }
+            _ => {}
+        }
+
+        // Claude Code format often has "message" field
+        obj.contains_key("message")
+            .then_some((AgentFormat::ClaudeCode, false))
+    }
+
+    /// Parse Python agent JSON format
+    fn parse_python_json(&mut self, json: Value) -> Vec<UnifiedEvent> {
+        let mut events = vec![];
+
+        if let Some(obj) = json.as_object() {
+            let event_type = obj.get("type").and_then(|v| v.as_str()).unwrap_or("");
+
+            match event_type {
+                "turn" => {
+                    if let Some(num) = obj.get("number").and_then(|v| v.as_u64()) {
+                        self.current_turn = num as u32;
+                        events.push(
+                            UnifiedEvent::new("turn")
+                                .with_agent_id(&self.agent_id)
+                                .with_turn(self.current_turn),
+                        );
+                    }
+                }
+                "thinking" => {
+                    if let Some(content) = obj.get("content").and_then(|v| v.as_str()) {
+                        let mut event = UnifiedEvent::new("thinking")
+                            .with_agent_id(&self.agent_id)
+                            .with_content(content);
+                        if let Some(tokens) = obj.get("tokens").and_then(|v| v.as_u64()) {
+                            event = event.with_tokens(tokens as u32);
+                        }
+                        events.push(event);
+                    }
+                }
+                "tool_call" => {
+                    if let Some(tool) = obj.get("tool").and_then(|v| v.as_str()) {
+                        let args = obj.get("args").cloned().unwrap_or(Value::Null);
+                        let id = self.python_tool_call_id(obj.get("id").and_then(|v| v.as_str()));
+                        events.push(
+                            UnifiedEvent::new("tool_call")
+                                .with_agent_id(&self.agent_id)
+                                .with_tool(tool, args)
+                                .with_tool_use_id(Some(&id)),
+                        );
+                    }
+                }
+                "tool_result" => {
+                    if let Some(content) = obj.get("content").and_then(|v| v.as_str()) {
+                        let id = self.python_tool_result_id(
+                            obj.get("tool_use_id")
+                                .or_else(|| obj.get("id"))
+                                .and_then(|v| v.as_str()),
+                        );
+                        let mut event = UnifiedEvent::new("tool_result")
+                            .with_agent_id(&self.agent_id)
+                            .with_result(content)
+                            .with_tool_use_id(id.as_deref());
+                        if let Some(tokens) = obj.get("tokens").and_then(|v| v.as_u64()) {
+                            event = event.with_tokens(tokens as u32);
+                        }
+                        events.push(event);
+                    }
+                }
+                _ => {
+                    // Unknown event type, pass through as-is
+                    events.push(
+                        UnifiedEvent::new("raw")
+                            .with_agent_id(&self.agent_id)
+                            .with_content(&json.to_string()),
+                    );
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Id for a Python tool call: the source's own, else `{agent}-call-{n}`.
+    fn python_tool_call_id(&mut self, source: Option<&str>) -> String {
+        self.tool_calls_seen += 1;
+        let id = source
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{}-call-{}", self.agent_id, self.tool_calls_seen));
+        self.unanswered_tool_calls.push_back(id.clone());
+        id
+    }
+
+    /// Id for a Python tool result: the source's own, else the oldest call
+    /// still waiting for a result.
+    fn python_tool_result_id(&mut self, source: Option<&str>) -> Option<String> {
+        match source {
+            Some(id) => {
+                self.unanswered_tool_calls.retain(|pending| pending != id);
+                Some(id.to_string())
+            }
+            None => self.unanswered_tool_calls.pop_front(),
+        }
+    }
+
+    /// Parse Claude Code stream-json format.
+    ///
+    /// Lines from a subagent carry the `tool_use_id` of the Task call that
+    /// launched it. They are parsed with that subagent's own agent id and
+    /// turn state swapped in, and tagged with the id as `parent_id`.
+    fn parse_claude_json(&mut self, json: Value) -> Vec<UnifiedEvent> {
+        let Some(parent_id) = json
+            .get("parent_tool_use_id")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+        else {
+            return self.parse_claude_message(json);
+        };
+
+        let n = self.subagents.len() + 1;
+        let mut subagent = self
+            .subagents
+            .remove(&parent_id)
+            .unwrap_or_else(|| Subagent::new(format!("{}.sub-{}", self.agent_id, n)));
+        self.swap_subagent(&mut subagent);
+        let mut events = self.parse_claude_message(json);
+        self.swap_subagent(&mut subagent);
+        self.subagents.insert(parent_id.clone(), subagent);
+
+        for event in &mut events {
+            event.parent_id = Some(parent_id.clone());
+        }
+        events
+    }
+
+    /// Exchange the parent's Claude turn state with a subagent's.
+    fn swap_subagent(&mut self, subagent: &mut Subagent) {
+        std::mem::swap(&mut self.agent_id, &mut subagent.agent_id);
+        std::mem::swap(&mut self.current_turn, &mut subagent.current_turn);
+        std::mem::swap(&mut self.claude_continuing, &mut subagent.claude_continuing);
+        std::mem::swap(
+            &mut self.claude_stop_reason,
+            &mut subagent.claude_stop_reason,
+        );
+        std::mem::swap(&mut self.api_messages, &mut subagent.api_messages);
+        std::mem::swap(&mut self.claude_usage, &mut subagent.claude_usage);
+    }
+
+    /// Parse one Claude Code line for the agent whose state is swapped in
+    fn parse_claude_message(&mut self, json: Value) -> Vec<UnifiedEvent> {
+        let mut events = vec![];
+
+        if let Some(obj) = json.as_object() {
+            let event_type = obj.get("type").and_then(|v| v.as_str()).unwrap_or("");
+
+            // Init, message, and result events all carry the session id
+            if let Some(session_id) = obj.get("session_id").and_then(|v| v.as_str()) {
+                self.session_id = Some(session_id.to_string());
+            }
+
+            match event_type {
+                "system" if !self.agent_started && obj.get("subtype") == Some(&"init".into()) => {
+                    self.agent_started = true;
+                    let mut event = UnifiedEvent::new("agent_start")
+                        .with_agent_id(&self.agent_id)
+                        .with_session_id(self.session_id.as_deref());
+                    event.model = obj
+                        .get("model")
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string);
+                    events.push(event);
+                }
+                "assistant" => {
+                    // Assistant message with content blocks
+                    if let Some(message) = obj.get("message") {
+                        if let Some(content_arr) = message.get("content").and_then(|v| v.as_array())
+                        {
+                            for block in content_arr {
+                                events.extend(self.parse_claude_content_block(block));
+                            }
+                        }
+                    }
+                }
+                // Tool results come back to the model as user messages
+                "user" if has_tool_results(obj.get("message")) => {
+                    let blocks = obj["message"]["content"].as_array().into_iter().flatten();
+                    for block in blocks.filter(|b| b["type"] == "tool_result") {
+                        events.extend(self.parse_claude_content_block(block));
+                    }
+                }
+                "content_block_start" => {
+                    if let Some(block) = obj.get("content_block") {
+                        events.extend(self.parse_claude_content_block(block));
+                    }
+                }
+                "content_block_delta" => {
+                    if let Some(delta) = obj.get("delta") {
+                        let event = if let Some(text) = delta.get("text").and_then(|v| v.as_str()) {
+                            Some(self.claude_text(text))
+                        } else if let Some(thinking) =
+                            delta.get("thinking").and_then(|v| v.as_str())
+                        {
+                            self.claude_reasoning(thinking)
+                        } else {
+                            None
+                        };
+                        if let Some(event) = event {
+                            events.extend(self.coalesce_delta(event));
+                        }
+                    }
+                }
+                "result" => {
+                    if let Some(result) = obj.get("result").and_then(|v| v.as_str()) {
+                        events.push(
+                            UnifiedEvent::new("tool_result")
+                                .with_agent_id(&self.agent_id)
+                                .with_result(result),
+                        );
+                    } else if let Some(result) = obj.get("result") {
+                        events.push(
+                            UnifiedEvent::new("tool_result")
+                                .with_agent_id(&self.agent_id)
+                                .with_result(&result.to_string()),
+                        );
+                    }
+
+                    let is_error = obj.get("is_error").and_then(|v| v.as_bool());
+                    let mut event = self.session_end().with_status(if is_error == Some(true) {
+                        "error"
+                    } else {
+                        "complete"
+                    });
+                    event.num_turns = obj
+                        .get("num_turns")
+                        .and_then(|v| v.as_u64())
+                        .map(|n| n as u32);
+                    event.total_cost_usd = obj.get("total_cost_usd").and_then(|v| v.as_f64());
+                    event.duration_ms = obj.get("duration_ms").and_then(|v| v.as_u64());
+                    // The agent's own totals over what the parser added up
+                    if let Some(usage) = obj.get("usage") {
+                        for (key, count) in [
+                            ("input_tokens", &mut event.input_tokens),
+                            ("output_tokens", &mut event.output_tokens),
+                            ("cache_read_input_tokens", &mut event.cache_read_tokens),
+                            ("cache_creation_input_tokens", &mut event.cache_write_tokens),
+                        ] {
+                            if let Some(n) = usage.get(key).and_then(|v| v.as_u64()) {
+                                *count = Some(n);
+                            }
+                        }
+                    }
+                    events.push(event);
+                    self.session_ended = true;
+                }
+                // Every API round-trip is a message, but a message that
+                // stopped for tool use is continued by the next one after
+                // the results come back, within the same turn
+                "message_start" => {
+                    self.api_messages += 1;
+                    self.claude_stop_reason = None;
+                    let usage = self.claude_usage.insert(Usage::default());
+                    if let Some(start) = obj.get("message").and_then(|m| m.get("usage")) {
+                        usage.update(start);
+                    }
+                    if !self.claude_continuing {
+                        self.current_turn += 1;
+                        let mut event = UnifiedEvent::new("turn")
+                            .with_agent_id(&self.agent_id)
+                            .with_turn(self.current_turn)
+                            .with_session_id(self.session_id.as_deref());
+                        event.api_messages = Some(self.api_messages);
+                        events.push(event);
+                    }
+                }
+                "message_delta" => {
+                    if let Some(reason) = claude_stop_reason(obj) {
+                        self.claude_stop_reason = Some(reason.to_string());
+                    }
+                    if let Some(delta) = obj.get("usage") {
+                        let usage = self.claude_usage.get_or_insert_default();
+                        usage.update(delta);
+                        let mut event = UnifiedEvent::new("usage")
+                            .with_agent_id(&self.agent_id)
+                            .with_turn(self.current_turn);
+                        usage.attach(&mut event);
+                        events.push(event);
+                    } else {
+                        events.push(
+                            UnifiedEvent::new("raw")
+                                .with_agent_id(&self.agent_id)
+                                .with_content(&json.to_string()),
+                        );
+                    }
+                }
+                "message_stop" => {
+                    if let Some(usage) = self.claude_usage.take() {
+                        self.usage_total.get_or_insert_default().add(usage);
+                    }
+                    let reason = claude_stop_reason(obj)
+                        .map(str::to_string)
+                        .or(self.claude_stop_reason.take());
+                    self.claude_continuing = reason.as_deref() == Some("tool_use");
+                    if !self.claude_continuing {
+                        let mut event = UnifiedEvent::new("turn_end")
+                            .with_agent_id(&self.agent_id)
+                            .with_turn(self.current_turn);
+                        event.api_messages = Some(self.api_messages);
+                        events.push(event);
+                    }
+                }
+                "error" => {
+                    let error_msg = obj
+                        .get("error")
+                        .and_then(|e| e.get("message"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("Unknown error");
+                    let mut event = UnifiedEvent::new("error").with_agent_id(&self.agent_id);
+                    event.error = Some(error_msg.to_string());
+                    events.push(event);
+                }
+                _ => {
+                    // Pass through unknown events
+                    events.push(
+                        UnifiedEvent::new("raw")
+                            .with_agent_id(&self.agent_id)
+                            .with_content(&json.to_string()),
+                    );
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Parse an OpenAI `chat.completion.chunk`
+    ///
+    /// Text deltas become `thinking` events. Tool call deltas carry the name
+    /// once and the arguments in fragments, so calls are buffered until the
+    /// choice's `finish_reason` arrives.
+    fn parse_openai_json(&mut self, json: Value) -> Vec<UnifiedEvent> {
+        let mut events = vec![];
+
+        let Some(choices) = json.get("choices").and_then(|v| v.as_array()) else {
+            return events;
+        };
+
+        for choice in choices {
+            let choice_index = choice.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+
+            if let Some(delta) = choice.get("delta") {
+                if let Some(text) = delta.get("content").and_then(|v| v.as_str()) {
+                    if !text.is_empty() {
+                        events.push(
+                            UnifiedEvent::new("thinking")
+                                .with_agent_id(&self.agent_id)
+                                .with_content(text),
+                        );
+                    }
+                }
+
+                if let Some(tool_calls) = delta.get("tool_calls").and_then(|v| v.as_array()) {
+                    for call in tool_calls {
+                        let index = call.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+                        let pending = self
+                            .openai_tool_calls
+                            .entry((choice_index, index))
+                            .or_default();
+                        if let Some(id) = call.get("id").and_then(|v| v.as_str()) {
+                            pending.id = Some(id.to_string());
+                        }
+                        if let Some(function) = call.get("function") {
+                            if let Some(name) = function.get("name").and_then(|v| v.as_str()) {
+                                pending.name.push_str(name);
+                            }
+                            if let Some(args) = function.get("arguments").and_then(|v| v.as_str()) {
+                                pending.arguments.push_str(args);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if choice
+                .get("finish_reason")
+                .is_some_and(|reason| !reason.is_null())
+            {
+                events.extend(self.flush_openai_tool_calls(Some(choice_index)));
+            }
+        }
+
+        events
+    }
+
+    /// Parse a Gemini `GenerateContentResponse` chunk
+    ///
+    /// Text parts become `thinking` events, `functionCall` parts `tool_call`
+    /// and `functionResponse` parts `tool_result`. The first model chunk of a
+    /// response starts a turn and a candidate with a `finishReason` ends it;
+    /// the `turn_end` carries the response's `usageMetadata.totalTokenCount`,
+    /// which streams as a running total.
+    fn parse_gemini_json(&mut self, json: Value) -> Vec<UnifiedEvent> {
+        let mut events = vec![];
+
+        let Some(candidates) = json.get("candidates").and_then(|v| v.as_array()) else {
+            return events;
+        };
+
+        for candidate in candidates {
+            let role = candidate.get("content").and_then(|c| c.get("role"));
+            if !self.gemini_in_turn && role.and_then(|v| v.as_str()) == Some("model") {
+                self.gemini_in_turn = true;
+                self.current_turn += 1;
+                events.push(
+                    UnifiedEvent::new("turn")
+                        .with_agent_id(&self.agent_id)
+                        .with_turn(self.current_turn),
+                );
+            }
+
+            let parts = candidate
+                .get("content")
+                .and_then(|c| c.get("parts"))
+                .and_then(|v| v.as_array());
+            for part in parts.into_iter().flatten() {
+                if let Some(text) = part.get("text").and_then(|v| v.as_str()) {
+                    if !text.is_empty() {
+                        events.push(
+                            UnifiedEvent::new("thinking")
+                                .with_agent_id(&self.agent_id)
+                                .with_content(text),
+                        );
+                    }
+                } else if let Some(call) = part.get("functionCall") {
+                    if let Some(name) = call.get("name").and_then(|v| v.as_str()) {
+                        let args = call
+                            .get("args")
+                            .cloned()
+                            .unwrap_or_else(|| Value::Object(Default::default()));
+                        events.push(
+                            UnifiedEvent::new("tool_call")
+                                .with_agent_id(&self.agent_id)
+                                .with_tool(name, args)
+                                .with_tool_use_id(call.get("id").and_then(|v| v.as_str())),
+                        );
+                    }
+                } else if let Some(response) = part.get("functionResponse") {
+                    let result = match response.get("response") {
+                        Some(Value::String(text)) => text.clone(),
+                        Some(value) => value.to_string(),
+                        None => String::new(),
+                    };
+                    let mut event = UnifiedEvent::new("tool_result")
+                        .with_agent_id(&self.agent_id)
+                        .with_result(&result)
+                        .with_tool_use_id(response.get("id").and_then(|v| v.as_str()));
+                    event.tool = response
+                        .get("name")
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string);
+                    events.push(event);
+                }
+            }
+
+            if candidate.get("finishReason").is_some_and(|r| !r.is_null()) {
+                self.gemini_in_turn = false;
+                let mut event = UnifiedEvent::new("turn_end")
+                    .with_agent_id(&self.agent_id)
+                    .with_turn(self.current_turn);
+                event.tokens = json
+                    .get("usageMetadata")
+                    .and_then(|u| u.get("totalTokenCount"))
+                    .and_then(|v| v.as_u64())
+                    .map(|n| n as u32);
+                events.push(event);
+            }
+        }
+
+        events
+    }
+
+    /// Emit buffered OpenAI tool calls for one choice, or all when `None`.
+    fn flush_openai_tool_calls(&mut self, choice: Option<u64>) -> Vec<UnifiedEvent> {
+        let keys: Vec<(u64, u64)> = self
+            .openai_tool_calls
+            .keys()
+            .filter(|(c, _)| choice.is_none_or(|choice| *c == choice))
+            .copied()
+            .collect();
+
+        keys.into_iter()
+            .filter_map(|key| self.openai_tool_calls.remove(&key))
+            .filter(|call| !call.name.is_empty())
+            .map(|call| {
+                // Arguments are a JSON string; keep them raw if they never completed
+                let args = if call.arguments.trim().is_empty() {
+                    Value::Object(Default::default())
+                } else {
+                    serde_json::from_str(&call.arguments).unwrap_or(Value::String(call.arguments))
+                };
+                UnifiedEvent::new("tool_call")
+                    .with_agent_id(&self.agent_id)
+                    .with_tool(&call.name, args)
+                    .with_tool_use_id(call.id.as_deref())
+            })
+            .collect()
+    }
+
+    /// Parse a Claude Code content block
+    fn parse_claude_content_block(&self, block: &Value) -> Vec<UnifiedEvent> {
+        let mut events = vec![];
+
+        if let Some(obj) = block.as_object() {
+            let block_type = obj.get("type").and_then(|v| v.as_str()).unwrap_or("");
+
+            match block_type {
+                "text" => {
+                    if let Some(text) = obj.get("text").and_then(|v| v.as_str()) {
+                        events.push(self.claude_text(text));
+                    }
+                }
+                "thinking" => {
+                    if let Some(thinking) = obj.get("thinking").and_then(|v| v.as_str()) {
+                        events.extend(self.claude_reasoning(thinking));
+                    }
+                }
+                "tool_use" => {
+                    if let Some(name) = obj.get("name").and_then(|v| v.as_str()) {
+                        let input = obj.get("input").cloned().unwrap_or(Value::Null);
+                        let id = obj.get("id").and_then(|v| v.as_str());
+                        let change = file_change::file_change(name, &input);
+                        events.push(
+                            UnifiedEvent::new("tool_call")
+                                .with_agent_id(&self.agent_id)
+                                .with_tool(name, input)
+                                .with_tool_use_id(id),
+                        );
+                        // Edits also go out summarized, for the file timeline
+                        if let Some(change) = change {
+                            events.push(change.with_agent_id(&self.agent_id).with_tool_use_id(id));
+                        }
+                    }
+                }
+                "tool_result" => {
+                    let id = obj.get("tool_use_id").and_then(|v| v.as_str());
+                    match obj.get("content") {
+                        Some(Value::String(content)) => events.push(
+                            UnifiedEvent::new("tool_result")
+                                .with_agent_id(&self.agent_id)
+                                .with_result(content)
+                                .with_tool_use_id(id),
+                        ),
+                        // Text and images; images follow the result
+                        Some(Value::Array(parts)) => {
+                            let text: Vec<&str> = parts
+                                .iter()
+                                .filter(|part| part["type"] == "text")
+                                .filter_map(|part| part["text"].as_str())
+                                .collect();
+                            events.push(
+                                UnifiedEvent::new("tool_result")
+                                    .with_agent_id(&self.agent_id)
+                                    .with_result(&text.join("\n"))
+                                    .with_tool_use_id(id),
+                            );
+                            for part in parts.iter().filter(|part| part["type"] == "image") {
+                                events.extend(self.claude_media(part, id));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                "image" => events.extend(self.claude_media(block, None)),
+                _ => {}
+            }
+        }
+
+        events
+    }
+
+    /// `media` events for an `image` block, in place of its base64.
+    fn claude_media(&self, block: &Value, tool_use_id: Option<&str>) -> Vec<UnifiedEvent> {
+        media::media(block, self.media_dir.as_deref())
+            .into_iter()
+            .map(|event| {
+                event
+                    .with_agent_id(&self.agent_id)
+                    .with_tool_use_id(tool_use_id)
+            })
+            .collect()
+    }
+
+    /// Buffer a text delta when coalescing, returning whatever that flushes.
+    fn coalesce_delta(&mut self, mut event: UnifiedEvent) -> Vec<UnifiedEvent> {
+        let Some(coalesce) = self.coalesce else {
+            return vec![event];
+        };
+        self.delta_absorbed = true;
+
+        let mut flushed = vec![];
+        match self.pending_delta.as_mut() {
+            Some(pending)
+                if pending.event.event_type == event.event_type
+                    && pending.event.agent_id == event.agent_id =>
+            {
+                let text = event.content.unwrap_or_default();
+                pending
+                    .event
+                    .content
+                    .get_or_insert_default()
+                    .push_str(&text);
+            }
+            _ => {
+                flushed.extend(self.flush_deltas());
+                // Stamped now: the merged event happened when its first delta did
+                self.stamp(std::slice::from_mut(&mut event));
+                self.pending_delta = Some(PendingDelta {
+                    event,
+                    started: Instant::now(),
+                });
+            }
+        }
+
+        let pending = self.pending_delta.as_ref().expect("just buffered");
+        let len = pending.event.content.as_ref().map_or(0, String::len);
+        if coalesce.max_bytes.is_some_and(|max| len >= max)
+            || coalesce
+                .max_age
+                .is_some_and(|age| pending.started.elapsed() >= age)
+        {
+            flushed.extend(self.flush_deltas());
+        }
+        flushed
+    }
+
+    fn flush_deltas(&mut self) -> Vec<UnifiedEvent> {
+        self.pending_delta
+            .take()
+            .map(|p| p.event)
+            .into_iter()
+            .collect()
+    }
+
+    /// A `message` event for text the model wrote to the user.
+    fn claude_text(&self, text: &str) -> UnifiedEvent {
+        let event_type = if self.legacy_thinking {
+            "thinking"
+        } else {
+            "message"
+        };
+        UnifiedEvent::new(event_type)
+            .with_agent_id(&self.agent_id)
+            .with_content(text)
+    }
+
+    /// A `reasoning` event for extended thinking, unless in legacy mode.
+    fn claude_reasoning(&self, thinking: &str) -> Option<UnifiedEvent> {
+        (!self.legacy_thinking).then(|| {
+            UnifiedEvent::new("reasoning")
+                .with_agent_id(&self.agent_id)
+                .with_content(thinking)
+        })
+    }
+
+    /// Parse a line of Aider output
+    ///
+    /// `> ` echoes the user's prompt and starts a turn, `Applied edit to X`
+    /// is an `edit` tool call, and `Commit <hash> <message>` a `commit`
+    /// event. Diffs, fenced or bare, are collected into one `diff` tool call.
+    /// Anything else is handled as plain text.
+    fn parse_aider_line(&mut self, line: &str) -> Vec<UnifiedEvent> {
+        if let Some(diff) = self.aider_diff.as_mut() {
+            if diff.fenced {
+                if line.trim() == "```" {
+                    return self.flush_aider_diff();
+                }
+                diff.lines.push(line.to_string());
+                return vec![];
+            }
+            // A `--- ` header is only a diff if `+++ ` follows it
+            let header_only = diff.lines.len() == 1 && diff.lines[0].starts_with("--- ");
+            let continues = if header_only {
+                line.starts_with("+++ ")
+            } else {
+                is_diff_line(line)
+            };
+            if continues {
+                diff.lines.push(line.to_string());
+                return vec![];
+            }
+            // The bare diff ended with this line
+            let mut events = self.flush_aider_diff();
+            events.extend(self.parse_aider_line(line));
+            return events;
+        }
+
+        let trimmed = line.trim();
+        if trimmed == "```diff" {
+            self.aider_diff = Some(PendingDiff {
+                fenced: true,
+                lines: vec![],
+            });
+            return vec![];
+        }
+        if trimmed.starts_with("--- ") || trimmed.starts_with("diff --git ") {
+            self.aider_diff = Some(PendingDiff {
+                fenced: false,
+                lines: vec![trimmed.to_string()],
+            });
+            return vec![];
+        }
+
+        if let Some(prompt) = line.strip_prefix("> ") {
+            self.current_turn += 1;
+            return vec![UnifiedEvent::new("turn")
+                .with_agent_id(&self.agent_id)
+                .with_turn(self.current_turn)
+                .with_content(prompt.trim())];
+        }
+        if let Some(file) = trimmed.strip_prefix("Applied edit to ") {
+            return vec![UnifiedEvent::new("tool_call")
+                .with_agent_id(&self.agent_id)
+                .with_tool("edit", serde_json::json!({"file": file}))];
+        }
+        if let Some((hash, message)) = trimmed
+            .strip_prefix("Commit ")
+            .and_then(|rest| rest.split_once(' '))
+        {
+            if hash.len() >= 7 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                let mut event = UnifiedEvent::new("commit")
+                    .with_agent_id(&self.agent_id)
+                    .with_content(message);
+                event.args = Some(serde_json::json!({"hash": hash, "message": message}));
+                return vec![event];
+            }
+        }
+
+        self.parse_trimmed(trimmed)
+    }
+
+    /// Emit the Aider diff being collected, if any.
+    ///
+    /// A bare `--- ` line that never got a `+++ ` line wasn't a diff after
+    /// all; its lines are parsed as text instead.
+    fn flush_aider_diff(&mut self) -> Vec<UnifiedEvent> {
+        let Some(diff) = self.aider_diff.take() else {
+            return vec![];
+        };
+        let file = diff
+            .lines
+            .iter()
+            .find_map(|l| l.strip_prefix("+++ "))
+            .map(|f| f.trim().trim_start_matches("b/").to_string());
+        if file.is_none() && !diff.fenced {
+            return diff
+                .lines
+                .iter()
+                .flat_map(|l| self.parse_trimmed(l.trim()))
+                .collect();
+        }
+
+        let mut args = serde_json::json!({"diff": diff.lines.join("\n")});
+        if let Some(file) = file {
+            args["file"] = Value::String(file);
+        }
+        vec![UnifiedEvent::new("tool_call")
+            .with_agent_id(&self.agent_id)
+            .with_tool("diff", args)]
+    }
+
+    /// Parse plain text output (for Python agents that don't output JSON)
+    fn parse_text(&mut self, text: &str) -> Vec<UnifiedEvent> {
+        let Some(event) = self.rules.apply(text) else {
+            // Regular text output
+            return vec![UnifiedEvent::new("output")
+                .with_agent_id(&self.agent_id)
+                .with_content(text)];
+        };
+        if event.event_type == "turn" {
+            if let Some(turn) = event.turn {
+                self.current_turn = turn;
+            }
+        }
+        vec![event.with_agent_id(&self.agent_id)]
+    }
+}
+
+/// Whether a line that failed to parse was meant to be JSON: an object, or
+/// an array (but not a `[tool]` or `[Turn 1]` marker).
+fn looks_like_json(line: &str) -> bool {
+    if line.starts_with('{') {
+        return true;
+    }
+    line.strip_prefix('[')
+        .and_then(|rest| rest.trim_start().chars().next())
+        .is_some_and(|c| matches!(c, '{' | '[' | '"' | ']' | '-' | '0'..='9'))
+}
+
+/// Whether a line continues a unified diff.
+fn is_diff_line(line: &str) -> bool {
+    ["+", "-", " ", "@@", "\\", "diff --git ", "index "]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+}
+
+/// `stop_reason` of a Claude `message_delta`, or of a `message_stop` that
+/// carries one.
+fn claude_stop_reason(obj: &serde_json::Map<String, Value>) -> Option<&str> {
+    obj.get("delta")
+        .or(obj.get("message"))
+        .and_then(|v| v.get("stop_reason"))
+        .or(obj.get("stop_reason"))
+        .and_then(|v| v.as_str())
+}
+
+/// Whether a Claude Code user message carries `tool_result` blocks.
+fn has_tool_results(message: Option<&Value>) -> bool {
+    message
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_array())
+        .is_some_and(|blocks| blocks.iter().any(|b| b["type"] == "tool_result"))
+}
+
+/// Read a source timestamp: RFC 3339 or epoch milliseconds.
+fn source_time(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(text) => Some(DateTime::parse_from_rfc3339(text).ok()?.with_timezone(&Utc)),
+        Value::Number(millis) => DateTime::from_timestamp_millis(millis.as_i64()?),
+        _ => None,
+    }
+}
+
+/// Normalize a source timestamp to the form [`Parser`] emits.
+fn source_timestamp(value: &Value) -> Option<String> {
+    Some(source_time(value)?.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+/// Parse a duration like `500ms`, `2s`, or `1m`.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", text))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        _ => Err(format!(
+            "invalid duration unit in '{}' (use ms, s, or m)",
+            text
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_python_turn() {
+        let mut parser = Parser::new("test".to_string());
+        let events = parser.parse_line(r#"{"type":"turn","number":1}"#);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "turn");
+        assert_eq!(events[0].turn, Some(1));
+    }
+
+    #[test]
+    fn test_parse_python_tool_call() {
+        let mut parser = Parser::new("test".to_string());
+        let events =
+            parser.parse_line(r#"{"type":"tool_call","tool":"bash","args":{"command":"ls"}}"#);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "tool_call");
+        assert_eq!(events[0].tool, Some("bash".to_string()));
+    }
+
+    #[test]
+    fn test_bom_and_crlf_ignored_before_json() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_format(AgentFormat::Python);
+        let events = parser.parse_line("\u{feff}{\"type\":\"turn\",\"number\":1}\r");
+        assert_eq!(events[0].event_type, "turn");
+        let events =
+            parser.parse_line("{\"type\":\"tool_call\",\"tool\":\"bash\",\"args\":{}}\r\r");
+        assert_eq!(events[0].event_type, "tool_call");
+        assert_eq!(parser.parse_failures(), 0);
+    }
+
+    const SESSION: &str = "3f6c2a10-9b7e-4d2a-8c1e-5a0f7e2b9d41";
+
+    #[test]
+    fn test_session_id_on_turn_and_session_end() {
+        let mut parser = Parser::new("test".to_string());
+        parser.parse_line(&format!(
+            r#"{{"type":"system","subtype":"init","session_id":"{}"}}"#,
+            SESSION
+        ));
+
+        let events = parser.parse_line(r#"{"type":"message_start"}"#);
+        assert_eq!(events[0].session_id.as_deref(), Some(SESSION));
+
+        let events = parser.parse_line(&format!(
+            r#"{{"type":"result","is_error":false,"result":"ok","num_turns":3,"total_cost_usd":0.05,"session_id":"{}"}}"#,
+            SESSION
+        ));
+        let end = events.last().unwrap();
+        assert_eq!(end.event_type, "session_end");
+        assert_eq!(end.status.as_deref(), Some("complete"));
+        assert_eq!(end.session_id.as_deref(), Some(SESSION));
+        assert_eq!(end.num_turns, Some(3));
+        assert_eq!(end.total_cost_usd, Some(0.05));
+        assert!(parser.finish().is_empty());
+    }
+
+    #[test]
+    fn test_claude_tool_loop_stays_in_one_turn() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_format(AgentFormat::ClaudeCode);
+        let mut events = vec![];
+        for stop_reason in ["tool_use", "tool_use", "end_turn", "end_turn"] {
+            events.extend(parser.parse_line(r#"{"type":"message_start"}"#));
+            events.extend(parser.parse_line(&format!(
+                r#"{{"type":"message_delta","delta":{{"stop_reason":"{}"}}}}"#,
+                stop_reason
+            )));
+            events.extend(parser.parse_line(r#"{"type":"message_stop"}"#));
+        }
+
+        let turns: Vec<(&str, Option<u32>, Option<u64>)> = events
+            .iter()
+            .filter(|e| e.event_type.starts_with("turn"))
+            .map(|e| (e.event_type.as_str(), e.turn, e.api_messages))
+            .collect();
+        assert_eq!(
+            turns,
+            [
+                ("turn", Some(1), Some(1)),
+                ("turn_end", Some(1), Some(3)),
+                ("turn", Some(2), Some(4)),
+                ("turn_end", Some(2), Some(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_subagent_events_attributed_with_own_turns() {
+        let mut parser = Parser::new("lead".to_string());
+        parser.set_format(AgentFormat::ClaudeCode);
+        let mut events = vec![];
+        for (parent, line) in [
+            (None, r#"{"type":"message_start"}"#),
+            (Some("toolu_A"), r#"{"type":"message_start"}"#),
+            (Some("toolu_B"), r#"{"type":"message_start"}"#),
+            (Some("toolu_A"), r#"{"type":"message_stop"}"#),
+            (Some("toolu_A"), r#"{"type":"message_start"}"#),
+            (None, r#"{"type":"message_stop"}"#),
+        ] {
+            let mut json: Value = serde_json::from_str(line).unwrap();
+            json["parent_tool_use_id"] = parent.into();
+            events.extend(parser.parse_line(&json.to_string()));
+        }
+
+        let summary: Vec<(&str, &str, Option<&str>, Option<u32>)> = events
+            .iter()
+            .map(|e| {
+                (
+                    e.event_type.as_str(),
+                    e.agent_id.as_deref().unwrap(),
+                    e.parent_id.as_deref(),
+                    e.turn,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("turn", "lead", None, Some(1)),
+                ("turn", "lead.sub-1", Some("toolu_A"), Some(1)),
+                ("turn", "lead.sub-2", Some("toolu_B"), Some(1)),
+                ("turn_end", "lead.sub-1", Some("toolu_A"), Some(1)),
+                ("turn", "lead.sub-1", Some("toolu_A"), Some(2)),
+                ("turn_end", "lead", None, Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_claude_usage_per_message_and_totals() {
+        let mut parser = Parser::new("test".to_string());
+        parser.parse_line(&format!(
+            r#"{{"type":"system","subtype":"init","session_id":"{}"}}"#,
+            SESSION
+        ));
+        let mut usage = vec![];
+        for (start, delta) in [
+            (
+                r#"{"input_tokens":100,"output_tokens":1,"cache_read_input_tokens":50}"#,
+                r#"{"output_tokens":20}"#,
+            ),
+            (
+                r#"{"input_tokens":200,"output_tokens":1,"cache_creation_input_tokens":10}"#,
+                r#"{"output_tokens":30}"#,
+            ),
+        ] {
+            parser.parse_line(&format!(
+                r#"{{"type":"message_start","message":{{"usage":{}}}}}"#,
+                start
+            ));
+            usage.extend(parser.parse_line(&format!(
+                r#"{{"type":"message_delta","delta":{{"stop_reason":"end_turn"}},"usage":{}}}"#,
+                delta
+            )));
+            parser.parse_line(r#"{"type":"message_stop"}"#);
+        }
+
+        let counts = |e: &UnifiedEvent| {
+            (
+                e.input_tokens,
+                e.output_tokens,
+                e.cache_read_tokens,
+                e.cache_write_tokens,
+            )
+        };
+        assert_eq!(usage[0].event_type, "usage");
+        assert_eq!(counts(&usage[0]), (Some(100), Some(20), Some(50), Some(0)));
+        assert_eq!(counts(&usage[1]), (Some(200), Some(30), Some(0), Some(10)));
+
+        let end = parser.finish();
+        assert_eq!(end[0].status.as_deref(), Some("interrupted"));
+        assert_eq!(counts(&end[0]), (Some(300), Some(50), Some(50), Some(10)));
+    }
+
+    #[test]
+    fn test_interrupted_session_surfaces_session_id() {
+        let mut parser = Parser::new("test".to_string());
+        parser.parse_line(&format!(
+            r#"{{"type":"system","subtype":"init","session_id":"{}"}}"#,
+            SESSION
+        ));
+
+        let events = parser.finish();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "session_end");
+        assert_eq!(events[0].status.as_deref(), Some("interrupted"));
+        assert_eq!(events[0].session_id.as_deref(), Some(SESSION));
+        assert!(parser.finish().is_empty());
+    }
+
+    #[test]
+    fn test_arg_profile_from_transcript() {
+        let mut parser = Parser::new("test".to_string());
+        parser.enable_arg_profile(3);
+
+        let mut events = vec![];
+        for line in [
+            r#"{"type":"tool_call","tool":"bash","args":{"command":"ls"}}"#,
+            r#"{"type":"tool_call","tool":"Bash","args":{"command":"make","options":{"timeout":30,"retries":2}}}"#,
+            r#"{"type":"tool_call","tool":"read","args":{"file_path":"a.rs","limit":5}}"#,
+            r#"{"type":"tool_call","tool":"read","args":{"file_path":"b.rs","limit":"all"}}"#,
+        ] {
+            events.extend(parser.parse_line(line));
+        }
+
+        let stats: Vec<_> = events.iter().filter(|e| e.event_type == "stats").collect();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].tool_profile.as_ref().unwrap()["bash"]["calls"], 2);
+
+        let end = parser.finish();
+        let profile = end[0].tool_profile.as_ref().unwrap();
+        assert_eq!(end[0].event_type, "session_end");
+        assert_eq!(
+            profile["bash"],
+            serde_json::json!({
+                "calls": 2,
+                "keys": {
+                    "command": ["string"],
+                    "options": ["object"],
+                    "options.retries": ["number"],
+                    "options.timeout": ["number"]
+                }
+            })
+        );
+        assert_eq!(
+            profile["read"]["keys"]["limit"],
+            serde_json::json!(["number", "string"])
+        );
+    }
+
+    #[test]
+    fn test_parse_text_turn() {
+        let mut parser = Parser::new("test".to_string());
+        let events = parser.parse_line("[Turn 1]");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "turn");
+        assert_eq!(events[0].turn, Some(1));
+    }
+
+    #[test]
+    fn test_parse_text_bash() {
+        let mut parser = Parser::new("test".to_string());
+        let events = parser.parse_line("$ ls -la");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "tool_call");
+        assert_eq!(events[0].tool, Some("bash".to_string()));
+        let args = events[0].args.as_ref().unwrap();
+        assert_eq!(args["program"], "ls");
+        assert_eq!(args["argv"], serde_json::json!(["ls", "-la"]));
+        assert_eq!(args["dangerous"], false);
+    }
+
+    #[test]
+    fn test_text_lines_lose_escapes_and_redraws() {
+        let mut parser = Parser::new("test".to_string());
+        let events = parser.parse_line("\x1b[1m[Turn 2]\x1b[0m");
+        assert_eq!(events[0].turn, Some(2));
+
+        let events = parser.parse_line(
+            "\r  0%|          | 0/40 [00:00<?, ?it/s]\r 50%|#####     | 20/40\r100%|##########| 40/40 [00:02<00:00]",
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].content.as_deref(),
+            Some("100%|##########| 40/40 [00:02<00:00]")
+        );
+
+        let events = parser.parse_stderr_line("\x1b[33mWARNING\x1b[0m: slow tokenizer");
+        assert_eq!(
+            events[0].content.as_deref(),
+            Some("WARNING: slow tokenizer")
+        );
+
+        // Escapes inside JSON strings are the agent's content
+        let events =
+            parser.parse_line(r#"{"type":"thinking","content":"\u001b[31mFAILED\u001b[0m"}"#);
+        assert_eq!(events[0].content.as_deref(), Some("\x1b[31mFAILED\x1b[0m"));
+
+        parser.set_strip_ansi(false);
+        let events = parser.parse_line("\x1b[32mok\x1b[0m");
+        assert_eq!(events[0].content.as_deref(), Some("\x1b[32mok\x1b[0m"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration("1m").unwrap(), Duration::from_secs(60));
+        assert!(parse_duration("1h").is_err());
+    }
+
+    #[test]
+    fn test_detected_format_switches_after_a_run_of_lines() {
+        const PYTHON: &str = r#"{"type":"thinking","content":"Booting"}"#;
+        const CLAUDE: &str =
+            r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Hi"}]}}"#;
+
+        let mut parser = Parser::new("test".to_string());
+        parser.parse_line(PYTHON);
+        parser.parse_line(PYTHON);
+        assert_eq!(parser.format(), AgentFormat::Python);
+
+        // Two Claude lines aren't enough to leave a detected format
+        assert_eq!(parser.parse_line(CLAUDE)[0].event_type, "raw");
+        assert_eq!(parser.parse_line(CLAUDE)[0].event_type, "raw");
+        assert_eq!(parser.format(), AgentFormat::Python);
+        let events = parser.parse_line(CLAUDE);
+        assert_eq!(parser.format(), AgentFormat::ClaudeCode);
+        assert_eq!(events[0].event_type, "message");
+
+        // A pinned format never moves
+        let mut parser = Parser::new("test".to_string());
+        parser.set_format(AgentFormat::Python);
+        for _ in 0..5 {
+            parser.parse_line(CLAUDE);
+        }
+        assert_eq!(parser.format(), AgentFormat::Python);
+    }
+
+    #[test]
+    fn test_detect_openai_and_accumulate_tool_call_arguments() {
+        let mut parser = Parser::new("test".to_string());
+        let chunk = |delta: &str, finish: &str| {
+            format!(
+                r#"{{"object":"chat.completion.chunk","choices":[{{"index":0,"delta":{},"finish_reason":{}}}]}}"#,
+                delta, finish
+            )
+        };
+
+        let events = parser.parse_line(&chunk(r#"{"content":"Checking"}"#, "null"));
+        assert_eq!(parser.format(), AgentFormat::OpenAi);
+        assert_eq!(events[0].event_type, "thinking");
+        assert_eq!(events[0].content.as_deref(), Some("Checking"));
+
+        let start =
+            r#"{"tool_calls":[{"index":0,"function":{"name":"shell","arguments":"{\"comm"}}]}"#;
+        assert!(parser.parse_line(&chunk(start, "null")).is_empty());
+        let rest = r#"{"tool_calls":[{"index":0,"function":{"arguments":"and\": \"ls\"}"}}]}"#;
+        assert!(parser.parse_line(&chunk(rest, "null")).is_empty());
+
+        let events = parser.parse_line(&chunk("{}", r#""tool_calls""#));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "tool_call");
+        assert_eq!(events[0].tool.as_deref(), Some("shell"));
+        assert_eq!(events[0].args, Some(serde_json::json!({"command": "ls"})));
+    }
+
+    #[test]
+    fn test_openai_unfinished_tool_call_flushed_at_end() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_format(AgentFormat::OpenAi);
+        parser.parse_line(
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"name":"shell","arguments":"{\"command\": \"l"}}]}}]}"#,
+        );
+
+        let events = parser.finish();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].tool.as_deref(), Some("shell"));
+        assert_eq!(
+            events[0].args,
+            Some(Value::String(r#"{"command": "l"#.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_events_stamped_at_parse_time() {
+        let mut parser = Parser::new("test".to_string());
+        let before = Utc::now() - chrono::Duration::milliseconds(1);
+        let events = parser.parse_line(r#"{"type":"turn","number":1}"#);
+        let after = Utc::now() + chrono::Duration::milliseconds(1);
+
+        let stamped = DateTime::parse_from_rfc3339(events[0].timestamp.as_deref().unwrap())
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(before <= stamped && stamped <= after);
+
+        parser.set_timestamps(false);
+        let events = parser.parse_line(r#"{"type":"turn","number":2}"#);
+        assert_eq!(events[0].timestamp, None);
+    }
+
+    #[test]
+    fn test_source_timestamp_passes_through() {
+        let mut parser = Parser::new("test".to_string());
+        let events = parser.parse_line(
+            r#"{"type":"assistant","timestamp":"2026-03-01T11:00:00.250+01:00","message":{"content":[{"type":"text","text":"hi"},{"type":"tool_use","name":"Bash","input":{}}]}}"#,
+        );
+        assert_eq!(events.len(), 2);
+        for event in &events {
+            assert_eq!(event.timestamp.as_deref(), Some("2026-03-01T10:00:00.250Z"));
+        }
+
+        let events = parser.parse_line(r#"{"type":"assistant","timestamp":1772359200000,"message":{"content":[{"type":"text","text":"hi"}]}}"#);
+        assert_eq!(
+            events[0].timestamp.as_deref(),
+            Some("2026-03-01T10:00:00.000Z")
+        );
+    }
+
+    #[test]
+    fn test_claude_tool_result_carries_tool_use_id() {
+        let mut parser = Parser::new("test".to_string());
+        let call = parser.parse_line(
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","id":"toolu_01A","name":"Bash","input":{"command":"ls"}}]}}"#,
+        );
+        let result = parser.parse_line(
+            r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"toolu_01A","content":"src"}]}}"#,
+        );
+
+        assert_eq!(call[0].event_type, "tool_call");
+        assert_eq!(result[0].event_type, "tool_result");
+        assert_eq!(call[0].tool_use_id.as_deref(), Some("toolu_01A"));
+        assert_eq!(result[0].tool_use_id, call[0].tool_use_id);
+    }
+
+    #[test]
+    fn test_python_tool_use_ids_synthesized_in_order() {
+        let mut parser = Parser::new("agent-1".to_string());
+        let first = parser.parse_line(r#"{"type":"tool_call","tool":"bash","args":{}}"#);
+        let second = parser.parse_line(r#"{"type":"tool_call","tool":"read","args":{}}"#);
+        let first_result = parser.parse_line(r#"{"type":"tool_result","content":"a"}"#);
+        let second_result = parser.parse_line(r#"{"type":"tool_result","content":"b"}"#);
+
+        assert_eq!(first[0].tool_use_id.as_deref(), Some("agent-1-call-1"));
+        assert_eq!(second[0].tool_use_id.as_deref(), Some("agent-1-call-2"));
+        assert_eq!(first_result[0].tool_use_id, first[0].tool_use_id);
+        assert_eq!(second_result[0].tool_use_id, second[0].tool_use_id);
+    }
+
+    #[test]
+    fn test_multiline_object_reassembled() {
+        let mut parser = Parser::new("test".to_string());
+        assert!(parser.parse_line(r#"{"type": "tool_call","#).is_empty());
+        assert!(parser.parse_line(r#"  "tool": "bash","#).is_empty());
+        let events = parser.parse_line(r#"  "args": {"command": "ls"}}"#);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "tool_call");
+        assert_eq!(events[0].tool.as_deref(), Some("bash"));
+        assert_eq!(events[0].args, Some(serde_json::json!({"command": "ls"})));
+    }
+
+    #[test]
+    fn test_multiline_overflow_flushed_as_raw() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_multiline(true);
+        parser.set_multiline_limit(64);
+        assert!(parser.parse_line("{").is_empty());
+        assert!(parser.parse_line(r#"  "type": "thinking","#).is_empty());
+        let events = parser.parse_line(&format!(r#"  "content": "{}","#, "x".repeat(64)));
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "raw");
+        assert!(events[0].content.as_ref().unwrap().starts_with("{\n"));
+        assert!(events[0].error.as_ref().unwrap().contains("64 bytes"));
+
+        // Parsing picks up cleanly after the overflow
+        let events = parser.parse_line(r#"{"type":"turn","number":2}"#);
+        assert_eq!(events[0].event_type, "turn");
+    }
+
+    #[test]
+    fn test_cut_off_object_does_not_swallow_next_event() {
+        let mut parser = Parser::new("test".to_string());
+        assert!(parser
+            .parse_line(r#"{"type":"tool_call","tool":"ba"#)
+            .is_empty());
+        let events = parser.parse_line(r#"{"type":"turn","number":2}"#);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event_type, "error");
+        assert_eq!(events[0].status.as_deref(), Some("parse_error"));
+        assert_eq!(events[1].event_type, "turn");
+    }
+
+    #[test]
+    fn test_truncated_json_is_a_parse_failure() {
+        let mut parser = Parser::new("test".to_string());
+        let line = format!(r#"{{"type":"thinking","content":"{}"#, "x".repeat(300));
+        assert!(parser.parse_line(&line).is_empty());
+        let events = parser.finish();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "error");
+        assert!(events[0]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("EOF while parsing"));
+        let content = events[0].content.as_ref().unwrap();
+        assert_eq!(content.chars().count(), PARSE_FAILURE_SAMPLE_CHARS);
+        assert!(line.starts_with(content.as_str()));
+        assert_eq!(parser.parse_failures(), 1);
+    }
+
+    #[test]
+    fn test_bad_escape_and_broken_array_are_parse_failures() {
+        let mut parser = Parser::new("test".to_string());
+        let escape = parser.parse_line(r#"{"type":"thinking","content":"\ud800 \x41"}"#);
+        let array = parser.parse_line(r#"[1, 2,"#);
+        let marker = parser.parse_line("[read] src/main.rs");
+
+        assert_eq!(escape[0].event_type, "error");
+        assert!(escape[0].error.as_ref().unwrap().contains("escape"));
+        assert_eq!(array[0].event_type, "error");
+        assert_eq!(marker[0].event_type, "tool_call");
+        assert_eq!(parser.parse_failures(), 2);
+    }
+
+    #[test]
+    fn test_claude_init_becomes_agent_start_once() {
+        let mut parser = Parser::new("test".to_string());
+        let init = format!(
+            r#"{{"type":"system","subtype":"init","cwd":"/work","session_id":"{}","tools":["Bash"],"model":"claude-sonnet-4-20250514"}}"#,
+            SESSION
+        );
+        let events = parser.parse_line(&init);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "agent_start");
+        assert_eq!(events[0].session_id.as_deref(), Some(SESSION));
+        assert_eq!(events[0].model.as_deref(), Some("claude-sonnet-4-20250514"));
+
+        assert_eq!(parser.parse_line(&init)[0].event_type, "raw");
+        let other = parser.parse_line(r#"{"type":"system","subtype":"compact_boundary"}"#);
+        assert_eq!(other[0].event_type, "raw");
+    }
+
+    #[test]
+    fn test_result_reports_cost_duration_and_usage() {
+        let mut parser = Parser::new("test".to_string());
+        let events = parser.parse_line(
+            r#"{"type":"result","result":"done","num_turns":2,"total_cost_usd":0.0125,"duration_ms":9120,"usage":{"input_tokens":1800,"output_tokens":96}}"#,
+        );
+        let end = &events[1];
+        assert_eq!(end.event_type, "session_end");
+        assert_eq!(end.total_cost_usd, Some(0.0125));
+        assert_eq!(end.duration_ms, Some(9120));
+        assert_eq!(end.input_tokens, Some(1800));
+        assert_eq!(end.output_tokens, Some(96));
+
+        let json: Value = serde_json::to_value(end).unwrap();
+        assert!(json["total_cost_usd"].is_f64());
+        assert!(json["duration_ms"].is_u64());
+
+        // Usage may be partial or missing
+        let mut parser = Parser::new("test".to_string());
+        let events = parser.parse_line(
+            r#"{"type":"result","result":"done","duration_ms":40,"usage":{"output_tokens":3}}"#,
+        );
+        assert_eq!(events[1].input_tokens, None);
+        assert_eq!(events[1].output_tokens, Some(3));
+
+        let mut parser = Parser::new("test".to_string());
+        let events = parser.parse_line(r#"{"type":"result","result":"done"}"#);
+        assert_eq!(events[1].duration_ms, None);
+        assert_eq!(events[1].input_tokens, None);
+        assert_eq!(events[1].output_tokens, None);
+    }
+
+    const THINKING_THEN_TEXT: &str = r#"{"type":"assistant","message":{"content":[{"type":"thinking","thinking":"The test expects a trimmed header.","signature":"sig"},{"type":"text","text":"I'll trim the header first."}]}}"#;
+
+    #[test]
+    fn test_claude_thinking_and_text_blocks_in_order() {
+        let mut parser = Parser::new("test".to_string());
+        let events = parser.parse_line(THINKING_THEN_TEXT);
+
+        let kinds: Vec<(&str, &str)> = events
+            .iter()
+            .map(|e| (e.event_type.as_str(), e.content.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("reasoning", "The test expects a trimmed header."),
+                ("message", "I'll trim the header first."),
+            ]
+        );
+
+        let delta = parser.parse_line(
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"Hmm."}}"#,
+        );
+        assert_eq!(delta[0].event_type, "reasoning");
+    }
+
+    #[test]
+    fn test_legacy_thinking_keeps_old_mapping() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_legacy_thinking(true);
+        let events = parser.parse_line(THINKING_THEN_TEXT);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "thinking");
+        assert_eq!(
+            events[0].content.as_deref(),
+            Some("I'll trim the header first.")
+        );
+    }
+
+    #[test]
+    fn test_detect_gemini_parts_and_usage() {
+        let mut parser = Parser::new("test".to_string());
+        let events = parser.parse_line(
+            r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"Checking the tests."},{"functionCall":{"name":"run_shell_command","args":{"command":"cargo test"}}}]}}],"usageMetadata":{"promptTokenCount":120,"totalTokenCount":131}}"#,
+        );
+        assert_eq!(parser.format(), AgentFormat::Gemini);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].event_type, "turn");
+        assert_eq!(events[1].event_type, "thinking");
+        assert_eq!(events[2].event_type, "tool_call");
+        assert_eq!(events[2].tool.as_deref(), Some("run_shell_command"));
+        assert_eq!(
+            events[2].args,
+            Some(serde_json::json!({"command": "cargo test"}))
+        );
+
+        let events = parser.parse_line(
+            r#"{"candidates":[{"content":{"role":"user","parts":[{"functionResponse":{"name":"run_shell_command","response":{"output":"ok"}}}]},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":150,"candidatesTokenCount":20,"totalTokenCount":170}}"#,
+        );
+        assert_eq!(events[0].event_type, "tool_result");
+        assert_eq!(events[0].tool.as_deref(), Some("run_shell_command"));
+        assert_eq!(events[0].result.as_deref(), Some(r#"{"output":"ok"}"#));
+        assert_eq!(events[1].event_type, "turn_end");
+        assert_eq!(events[1].turn, Some(1));
+        assert_eq!(events[1].tokens, Some(170));
+    }
+
+    #[test]
+    fn test_aider_edits_diffs_and_commits() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_format(AgentFormat::Aider);
+        let lines = [
+            "> make the header parser tolerate leading spaces",
+            "```diff",
+            "--- src/header.rs",
+            "+++ src/header.rs",
+            "@@ -1,3 +1,3 @@",
+            " pub fn parse(line: &str) -> Option<&str> {",
+            "-    line.strip_prefix(\"# \")",
+            "+    line.trim_start().strip_prefix(\"# \")",
+            "}",
+            "```",
+            "Applied edit to src/header.rs",
+            "Commit 3f9c2ab fix: accept leading whitespace in headers",
+            "Tokens: 4.2k sent, 312 received.",
+        ];
+        let events: Vec<UnifiedEvent> = lines.iter().flat_map(|l| parser.parse_line(l)).collect();
+
+        let types: Vec<&str> = events.iter().map(|e| e.event_type.as_str()).collect();
+        assert_eq!(
+            types,
+            ["turn", "tool_call", "tool_call", "commit", "output"]
+        );
+
+        assert_eq!(events[1].tool.as_deref(), Some("diff"));
+        let args = events[1].args.as_ref().unwrap();
+        assert_eq!(args["file"], "src/header.rs");
+        let diff = args["diff"].as_str().unwrap();
+        assert_eq!(diff.lines().count(), 7);
+        assert!(diff.contains("\n pub fn parse"));
+
+        assert_eq!(events[2].tool.as_deref(), Some("edit"));
+        assert_eq!(
+            events[2].args,
+            Some(serde_json::json!({"file": "src/header.rs"}))
+        );
+        assert_eq!(events[3].args.as_ref().unwrap()["hash"], "3f9c2ab");
+    }
+
+    #[test]
+    fn test_aider_bare_diff_ends_at_first_other_line() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_format(AgentFormat::Aider);
+        let mut events = vec![];
+        for line in [
+            "--- not a diff, just a divider",
+            "diff --git a/README.md b/README.md",
+            "--- a/README.md",
+            "+++ b/README.md",
+            "@@ -1 +1 @@",
+            "-Old title",
+            "+New title",
+            "Applied edit to README.md",
+        ] {
+            events.extend(parser.parse_line(line));
+        }
+        events.extend(parser.finish());
+
+        let types: Vec<&str> = events.iter().map(|e| e.event_type.as_str()).collect();
+        assert_eq!(types, ["output", "tool_call", "tool_call"]);
+        let diff = events[1].args.as_ref().unwrap();
+        assert_eq!(diff["file"], "README.md");
+        assert!(diff["diff"].as_str().unwrap().starts_with("diff --git"));
+    }
+
+    fn text_delta(text: &str) -> String {
+        serde_json::json!({
+            "type": "content_block_delta",
+            "index": 0,
+            "delta": {"type": "text_delta", "text": text},
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_coalesce_bounds_delta_events() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_format(AgentFormat::ClaudeCode);
+        parser.set_coalesce(Coalesce {
+            max_bytes: Some(16),
+            max_age: None,
+        });
+
+        let text: String = ('a'..='z').cycle().take(50).collect();
+        let mut events = vec![];
+        for c in text.chars() {
+            events.extend(parser.parse_line(&text_delta(&c.to_string())));
+        }
+        events.extend(parser.finish());
+
+        assert_eq!(events.len(), 4);
+        assert!(events.iter().all(|e| e.event_type == "message"));
+        let merged: String = events.iter().filter_map(|e| e.content.clone()).collect();
+        assert_eq!(merged, text);
+    }
+
+    #[test]
+    fn test_coalesce_flushes_before_interleaved_tool_use() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_format(AgentFormat::ClaudeCode);
+        parser.set_coalesce(Coalesce::default());
+
+        let mut events = vec![];
+        for c in "Let me check.".chars() {
+            events.extend(parser.parse_line(&text_delta(&c.to_string())));
+        }
+        assert!(events.is_empty());
+        events.extend(parser.parse_line(
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_1","name":"Bash","input":{}}}"#,
+        ));
+        events.extend(parser.parse_line(&text_delta("Done.")));
+        events.extend(parser.finish());
+
+        let kinds: Vec<(&str, Option<&str>)> = events
+            .iter()
+            .map(|e| (e.event_type.as_str(), e.content.as_deref()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("message", Some("Let me check.")),
+                ("tool_call", None),
+                ("message", Some("Done.")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_coalesce_age_limit() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_format(AgentFormat::ClaudeCode);
+        parser.set_coalesce(Coalesce {
+            max_bytes: None,
+            max_age: Some(Duration::from_secs(60)),
+        });
+        assert!(parser.parse_line(&text_delta("a")).is_empty());
+
+        let now = Instant::now();
+        assert!(parser.coalesce_remaining(now).unwrap() > Duration::from_secs(59));
+        assert!(parser.flush_stale(now).is_empty());
+        let stale = parser.flush_stale(now + Duration::from_secs(61));
+        assert_eq!(stale[0].content.as_deref(), Some("a"));
+        assert_eq!(parser.coalesce_remaining(now), None);
+    }
+
+    #[test]
+    fn test_seq_continuous_across_json_and_text() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_start_seq(41);
+        let mut events = vec![];
+        for line in [
+            r#"{"type":"turn","number":1}"#,
+            "plain output",
+            "",
+            r#"{"type":"tool_call","tool":"bash","args":{}}"#,
+            "$ ls",
+            r#"{"type":"tool_result","content":"src"}"#,
+        ] {
+            events.extend(parser.parse_line(line));
+        }
+
+        let seqs: Vec<u64> = events.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, (41..46).collect::<Vec<u64>>());
+        assert_eq!(parser.next_seq(), 46);
+        let line = serde_json::to_string(&events[0]).unwrap();
+        assert!(line.contains(r#""seq":41"#));
+    }
+
+    #[test]
+    fn test_max_content_bytes_truncates_huge_result() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_max_content_bytes(1000);
+        // A two-byte character straddles the limit
+        let content = format!("x{}", "ü".repeat(5 * 1024 * 1024));
+        let line = serde_json::json!({"type": "tool_result", "content": content}).to_string();
+        let events = parser.parse_line(&line);
+
+        let result = events[0].result.as_deref().unwrap();
+        assert_eq!(result.len(), 999);
+        assert!(content.starts_with(result));
+        assert_eq!(events[0].truncated, Some(true));
+        assert_eq!(events[0].content_bytes, Some(content.len() as u64));
+
+        let events = parser.parse_line(r#"{"type":"tool_result","content":"ok"}"#);
+        assert_eq!(events[0].result.as_deref(), Some("ok"));
+        assert_eq!(events[0].truncated, None);
+    }
+
+    #[test]
+    fn test_redact_bash_command_and_output() {
+        let mut parser = Parser::new("test".to_string());
+        parser.set_redact(vec!["s3cr3t-db-pass".to_string()]);
+        let mut events = parser.parse_line(
+            r#"{"type":"tool_call","tool":"bash","args":{"command":"export OPENAI_API_KEY=sk-proj-4fJ9xQ2mZ8rT1vB6nW3k && env"}}"#,
+        );
+        events.extend(parser.parse_line(
+            r#"{"type":"tool_result","content":"OPENAI_API_KEY=sk-proj-4fJ9xQ2mZ8rT1vB6nW3k\nDB_PASSWORD=s3cr3t-db-pass"}"#,
+        ));
+
+        assert_eq!(
+            events[0].args.as_ref().unwrap()["command"],
+            "export OPENAI_API_KEY=[REDACTED] && env"
+        );
+        assert_eq!(
+            events[1].result.as_deref(),
+            Some("OPENAI_API_KEY=[REDACTED]\nDB_PASSWORD=[REDACTED]")
+        );
+    }
+
+    #[test]
+    fn test_stderr_lines_flag_crashes() {
+        let mut parser = Parser::new("test".to_string());
+        let warning = parser.parse_stderr_line("UserWarning: slow tokenizer");
+        let traceback = parser.parse_stderr_line("Traceback (most recent call last):");
+        let panic = parser
+            .parse_stderr_line("thread 'main' panicked at src/main.rs:4:5: index out of bounds");
+
+        assert_eq!(warning[0].event_type, "stderr");
+        assert_eq!(warning[0].status, None);
+        assert_eq!(traceback[0].status.as_deref(), Some("crash"));
+        assert_eq!(panic[0].status.as_deref(), Some("crash"));
+        assert_eq!(panic[0].seq, 3);
+    }
+}
@@ -1,499 +1,1052 @@
-use serde::Serialize;
-use serde_json::Value;
+use agent_stream::demux::Demux;
+use agent_stream::encoding::{self, Encoding, InputEncoding, Utf16Reader};
+use agent_stream::heartbeat::Heartbeat;
+use agent_stream::merge::{self, MergeInput, MergeOptions};
+#[cfg(unix)]
+use agent_stream::output::{self as socket_output, SocketOutput};
+use agent_stream::pretty::{self, Pretty};
+use agent_stream::replay::{self, Pacer};
+use agent_stream::rules::Rules;
+use agent_stream::state;
+use agent_stream::terminal::{RedrawThrottle, Segments, REDRAW_INTERVAL};
+use agent_stream::throttle::{self, Throttle, ThrottleLimits};
+use agent_stream::watchdog::Watchdog;
+use agent_stream::{AgentExit, AgentFormat, Coalesce, Parser, UnifiedEvent};
+use flate2::bufread::MultiGzDecoder;
+use mc_events::ring::{RingLimits, RingWriter};
 use std::env;
-use std::io::{self, BufRead, Write};
-
-/// Unified event format that the orchestrator and UI expect
-#[derive(Debug, Serialize)]
-struct UnifiedEvent {
-    #[serde(rename = "type")]
-    event_type: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    agent_id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    content: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    tool: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    args: Option<Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    result: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    turn: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    tokens: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    status: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    error: Option<String>,
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write};
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Exit code when the consumer expects a different event schema.
+const EXIT_SCHEMA_MISMATCH: i32 = 3;
+
+/// Tool calls between `stats` events when `--arg-profile` is on.
+const ARG_PROFILE_STATS_EVERY: u64 = 100;
+
+/// Exit code for malformed command-line options.
+const EXIT_USAGE: i32 = 2;
+
+/// Exit code when `--require-first-event` expires without a qualifying event.
+const EXIT_NO_EVENTS: i32 = 4;
+
+/// Exit code once `--strict` parse failures have been seen.
+const EXIT_PARSE_FAILURES: i32 = 5;
+
+/// Exit code when the agent's output could not be read to the end, such as
+/// a truncated gzip transcript.
+const EXIT_INPUT_ERROR: i32 = 6;
+
+/// First bytes of a gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How long to keep trying to deliver buffered events to the event socket
+/// at exit.
+#[cfg(unix)]
+const SOCKET_CLOSE_PATIENCE: Duration = Duration::from_secs(5);
+
+/// Events emitted between saves of `--state-file`.
+const STATE_SAVE_EVERY: u64 = 100;
+
+/// Longest the main loop waits for input before checking for SIGINT and
+/// SIGTERM.
+const SIGNAL_POLL: Duration = Duration::from_millis(100);
+
+/// Options given as `--flag` or `--flag value`, anywhere on the command line.
+#[derive(Default)]
+struct Options {
+    arg_profile: bool,
+    /// Leave `timestamp` off events, for consumers matching the old shape
+    no_timestamps: bool,
+    /// Mission dir whose `events/` directory receives a copy of every event
+    forward: Option<String>,
+    forward_limits: RingLimits,
+    /// Give up if no qualifying event is emitted within this long
+    require_first_event: Option<Duration>,
+    /// Event types that qualify; empty means any but plain output
+    require_event_types: Vec<String>,
+    /// Emit a `heartbeat` after this many seconds without input
+    heartbeat_secs: Option<u64>,
+    /// The agent pretty-prints JSON across lines
+    multiline: bool,
+    /// Bytes buffered for one multi-line object before giving up on it
+    multiline_limit: Option<usize>,
+    /// Give up after this many lines that look like JSON but don't parse
+    strict: Option<u64>,
+    /// Claude text as `thinking` events, as before `message` and `reasoning`
+    legacy_thinking: bool,
+    /// Leave ANSI escape sequences in text lines
+    keep_ansi: bool,
+    /// Lines start with `ID| ` naming the agent that wrote them
+    demux_prefix: bool,
+    /// Agent format, instead of detecting it; overrides the positional hint
+    format: Option<String>,
+    /// Rules file recognizing events in plain text lines
+    rules: Option<PathBuf>,
+    /// Merge text deltas, flushing after this long
+    coalesce_ms: Option<u64>,
+    /// Merge text deltas, flushing at this many bytes
+    coalesce_bytes: Option<usize>,
+    /// `seq` of the first event, to continue a previous run's numbering
+    start_seq: Option<u64>,
+    /// Parser state loaded at startup and saved as the stream goes, so a
+    /// restarted parser carries on where the last one stopped
+    state_file: Option<PathBuf>,
+    /// Cut `content` and `result` longer than this many bytes
+    max_content_bytes: Option<usize>,
+    /// Save images from Claude content here
+    media_dir: Option<PathBuf>,
+    /// Mask API keys and the values of `redact_env` in events
+    redact: bool,
+    /// Environment variables whose values are secrets
+    redact_env: Vec<String>,
+    /// Run this agent command and parse its stdout and stderr, instead of
+    /// reading stdin
+    exec: Vec<String>,
+    /// Write events to this Unix socket instead of stdout
+    #[cfg(unix)]
+    output: Option<PathBuf>,
+    /// Listen on the `output` socket rather than connecting to it
+    #[cfg(unix)]
+    listen: bool,
+    /// Events kept while the socket is down
+    #[cfg(unix)]
+    output_backlog: Option<usize>,
+    /// Most events and bytes written per second
+    throttle: ThrottleLimits,
+    encoding: Encoding,
+    /// Text encoding of the agent's output
+    encoding_in: InputEncoding,
+    /// Write each event as a readable line instead of in `encoding`
+    pretty: bool,
+    /// Leave colors out of `pretty` lines
+    no_color: bool,
+    filter: TypeFilter,
+    /// Parse this captured transcript, instead of stdin
+    input: Option<PathBuf>,
+    /// Parse this captured transcript at its original pace, instead of stdin
+    replay: Option<PathBuf>,
+    /// Replay this many times faster than the original
+    speed: Option<f64>,
+    /// Replay wait before lines without a timestamp
+    delay_ms: Option<u64>,
+}
+
+/// Event types to emit, from `--include` and `--exclude`. The parser still
+/// sees every event; only output is filtered.
+#[derive(Default)]
+struct TypeFilter {
+    /// Only these types; takes precedence over `exclude`
+    include: Vec<String>,
+    exclude: Vec<String>,
 }
 
-impl UnifiedEvent {
-    fn new(event_type: &str) -> Self {
-        UnifiedEvent {
-            event_type: event_type.to_string(),
-            agent_id: None,
-            content: None,
-            tool: None,
-            args: None,
-            result: None,
-            turn: None,
-            tokens: None,
-            status: None,
-            error: None,
+impl TypeFilter {
+    fn allows(&self, event_type: &str) -> bool {
+        if !self.include.is_empty() {
+            self.include.iter().any(|t| t == event_type)
+        } else {
+            !self.exclude.iter().any(|t| t == event_type)
         }
     }
+}
 
-    fn with_agent_id(mut self, id: &str) -> Self {
-        self.agent_id = Some(id.to_string());
-        self
-    }
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(Options, Vec<String>), String> {
+    let mut options = Options::default();
+    let mut positional = Vec::new();
 
-    fn with_content(mut self, content: &str) -> Self {
-        self.content = Some(content.to_string());
-        self
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("{} requires a value", name))
+        };
+        match arg.as_str() {
+            "--arg-profile" => options.arg_profile = true,
+            "--no-timestamps" => options.no_timestamps = true,
+            "--multiline" => options.multiline = true,
+            "--legacy-thinking" => options.legacy_thinking = true,
+            "--strip-ansi" => options.keep_ansi = false,
+            "--no-strip-ansi" => options.keep_ansi = true,
+            "--redact" => options.redact = true,
+            "--pretty" => options.pretty = true,
+            "--no-color" => options.no_color = true,
+            "--demux-prefix" => options.demux_prefix = true,
+            #[cfg(unix)]
+            "--listen" => options.listen = true,
+            #[cfg(unix)]
+            "--output" => {
+                let output = value(&arg)?;
+                options.output = match output.as_str() {
+                    "-" | "stdout" => None,
+                    _ => match output.strip_prefix("unix:") {
+                        Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+                        _ => {
+                            return Err(format!(
+                                "unsupported --output {}: expected unix:PATH",
+                                output
+                            ))
+                        }
+                    },
+                }
+            }
+            #[cfg(not(unix))]
+            "--output" => {
+                let output = value(&arg)?;
+                if !matches!(output.as_str(), "-" | "stdout") {
+                    return Err(format!(
+                        "unsupported --output {}: only stdout is available on this platform",
+                        output
+                    ));
+                }
+            }
+            "--encoding" => {
+                let name = value(&arg)?;
+                options.encoding = Encoding::from_name(&name).ok_or_else(|| {
+                    format!(
+                        "unsupported --encoding {}: expected json, msgpack or cbor",
+                        name
+                    )
+                })?
+            }
+            "--encoding-in" => {
+                let name = value(&arg)?;
+                options.encoding_in = InputEncoding::from_name(&name).ok_or_else(|| {
+                    format!("unsupported --encoding-in {}: expected utf8 or utf16", name)
+                })?
+            }
+            "--format" => options.format = Some(value(&arg)?),
+            "--rules" => options.rules = Some(PathBuf::from(value(&arg)?)),
+            "--state-file" => options.state_file = Some(PathBuf::from(value(&arg)?)),
+            "--media-dir" => options.media_dir = Some(PathBuf::from(value(&arg)?)),
+            "--input" => options.input = Some(PathBuf::from(value(&arg)?)),
+            "--replay" => options.replay = Some(PathBuf::from(value(&arg)?)),
+            "--speed" => {
+                let speed: f64 = parse_number(&arg, &value(&arg)?)?;
+                if !(speed.is_finite() && speed > 0.0) {
+                    return Err(format!("--speed must be positive, got {}", speed));
+                }
+                options.speed = Some(speed);
+            }
+            "--delay-ms" => options.delay_ms = Some(parse_number(&arg, &value(&arg)?)?),
+            "--max-events-per-sec" => {
+                options.throttle.events_per_sec = Some(parse_rate(&arg, &value(&arg)?)?)
+            }
+            "--max-bytes-per-sec" => {
+                options.throttle.bytes_per_sec = Some(parse_rate(&arg, &value(&arg)?)?)
+            }
+            #[cfg(unix)]
+            "--output-backlog" => options.output_backlog = Some(parse_number(&arg, &value(&arg)?)?),
+            "--start-seq" => options.start_seq = Some(parse_number(&arg, &value(&arg)?)?),
+            "--coalesce-ms" => options.coalesce_ms = Some(parse_number(&arg, &value(&arg)?)?),
+            "--coalesce-bytes" => options.coalesce_bytes = Some(parse_number(&arg, &value(&arg)?)?),
+            "--max-content-bytes" => {
+                options.max_content_bytes = Some(parse_number(&arg, &value(&arg)?)?)
+            }
+            "--heartbeat-secs" => options.heartbeat_secs = Some(parse_number(&arg, &value(&arg)?)?),
+            "--strict" => options.strict = Some(parse_number(&arg, &value(&arg)?)?),
+            "--multiline-limit" => {
+                options.multiline_limit = Some(parse_number(&arg, &value(&arg)?)?)
+            }
+            "--forward" => options.forward = Some(value(&arg)?),
+            "--forward-max-events" => {
+                options.forward_limits.max_events = Some(parse_number(&arg, &value(&arg)?)?)
+            }
+            "--forward-max-bytes" => {
+                options.forward_limits.max_bytes = Some(parse_number(&arg, &value(&arg)?)?)
+            }
+            "--forward-generations" => {
+                options.forward_limits.generations = parse_number(&arg, &value(&arg)?)?
+            }
+            "--require-first-event" => {
+                options.require_first_event = Some(agent_stream::parse_duration(&value(&arg)?)?)
+            }
+            "--redact-env" => options.redact_env = comma_list(&value(&arg)?),
+            "--require-event-types" => options.require_event_types = comma_list(&value(&arg)?),
+            "--include" => options.filter.include = comma_list(&value(&arg)?),
+            "--exclude" => options.filter.exclude = comma_list(&value(&arg)?),
+            // Everything after --exec is the agent command
+            "--exec" => {
+                options.exec = args.by_ref().collect();
+                if options.exec.is_empty() {
+                    return Err("--exec requires a command".to_string());
+                }
+            }
+            // Unknown flags are ignored, as before
+            flag if flag.starts_with("--") => {}
+            _ => positional.push(arg),
+        }
     }
 
-    fn with_tool(mut self, tool: &str, args: Value) -> Self {
-        self.tool = Some(tool.to_string());
-        self.args = Some(args);
-        self
+    let sources = [
+        options.input.is_some(),
+        options.replay.is_some(),
+        !options.exec.is_empty(),
+    ];
+    if sources.into_iter().filter(|&given| given).count() > 1 {
+        return Err("only one of --input, --replay and --exec can be used".to_string());
     }
 
-    fn with_result(mut self, result: &str) -> Self {
-        self.result = Some(result.to_string());
-        self
-    }
+    Ok((options, positional))
+}
 
-    fn with_turn(mut self, turn: u32) -> Self {
-        self.turn = Some(turn);
-        self
-    }
+/// The non-empty items of a comma-separated flag value.
+fn comma_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
 
-    fn with_tokens(mut self, tokens: u32) -> Self {
-        self.tokens = Some(tokens);
-        self
-    }
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", flag, value))
 }
 
-/// Agent format type
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum AgentFormat {
-    Python,
-    ClaudeCode,
-    Unknown,
+/// A per-second limit, which must let something through.
+fn parse_rate(flag: &str, value: &str) -> Result<u64, String> {
+    match parse_number(flag, value)? {
+        0 => Err(format!("{} must be positive", flag)),
+        rate => Ok(rate),
+    }
 }
 
-/// Parser state
-struct Parser {
-    format: AgentFormat,
-    agent_id: String,
-    current_turn: u32,
+/// Arguments of `agent-stream merge`.
+#[derive(Default)]
+struct MergeArgs {
+    inputs: Vec<String>,
+    /// Write merged events here instead of stdout
+    out: Option<String>,
+    options: MergeOptions,
 }
 
-impl Parser {
-    fn new(agent_id: String) -> Self {
-        Parser {
-            format: AgentFormat::Unknown,
-            agent_id,
-            current_turn: 0,
+fn parse_merge_args(mut args: impl Iterator<Item = String>) -> Result<MergeArgs, String> {
+    let mut merge_args = MergeArgs::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("{} requires a value", name))
+        };
+        match arg.as_str() {
+            "--input" => merge_args.inputs.push(value(&arg)?),
+            "--out" => merge_args.out = Some(value(&arg)?),
+            "--window" => {
+                merge_args.options.window = Some(agent_stream::parse_duration(&value(&arg)?)?)
+            }
+            other => return Err(format!("unexpected merge argument: {}", other)),
         }
     }
 
-    /// Parse a line and return unified events
-    fn parse_line(&mut self, line: &str) -> Vec<UnifiedEvent> {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            return vec![];
-        }
+    if merge_args.inputs.is_empty() {
+        return Err("merge requires at least one --input".to_string());
+    }
+    Ok(merge_args)
+}
+
+/// Merge event logs by event time; the report goes to stderr as JSON.
+fn run_merge(args: MergeArgs) -> Result<(), String> {
+    let mut inputs = Vec::new();
+    for path in &args.inputs {
+        let file = File::open(path).map_err(|e| format!("Error opening {}: {}", path, e))?;
+        inputs.push(MergeInput {
+            name: path.clone(),
+            reader: Box::new(BufReader::new(file)),
+        });
+    }
 
-        // Try to parse as JSON
-        if let Ok(json) = serde_json::from_str::<Value>(trimmed) {
-            return self.parse_json(json);
+    let report = match &args.out {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| format!("Error creating {}: {}", path, e))?;
+            merge::merge(inputs, &mut BufWriter::new(file), &args.options)
         }
+        None => merge::merge(inputs, &mut io::stdout().lock(), &args.options),
+    }
+    .map_err(|e| format!("Error merging: {}", e))?;
+
+    eprintln!("{}", serde_json::to_string(&report).unwrap());
+    Ok(())
+}
 
-        // Not JSON - treat as plain text output
-        self.parse_text(trimmed)
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("merge") {
+        args.next();
+        let merge_args = match parse_merge_args(args) {
+            Ok(merge_args) => merge_args,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(EXIT_USAGE);
+            }
+        };
+        if let Err(e) = run_merge(merge_args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
     }
 
-    /// Parse JSON input (could be Python or Claude Code format)
-    fn parse_json(&mut self, json: Value) -> Vec<UnifiedEvent> {
-        // Detect format from JSON structure
-        if self.format == AgentFormat::Unknown {
-            self.detect_format(&json);
+    let (options, args) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_USAGE);
         }
+    };
+
+    // Get agent ID from args or use default
+    let agent_id = args
+        .first()
+        .cloned()
+        .unwrap_or_else(|| "unknown".to_string());
+
+    // Get format hint from --format or args (optional); it pins the format
+    let format_hint = options
+        .format
+        .as_deref()
+        .or_else(|| args.get(1).map(|s| s.as_str()));
+
+    // Refuse to produce events the consumer can't read
+    if let Err(e) = mc_events::check_schema_env() {
+        let mut event = UnifiedEvent::new("error").with_agent_id(&agent_id);
+        event.error = Some(e.to_string());
+        let _ = encoding::write_event(&mut io::stdout(), &event, options.encoding);
+        std::process::exit(EXIT_SCHEMA_MISMATCH);
+    }
 
-        match self.format {
-            AgentFormat::Python => self.parse_python_json(json),
-            AgentFormat::ClaudeCode => self.parse_claude_json(json),
-            AgentFormat::Unknown => {
-                // Couldn't detect, try both
-                let events = self.parse_python_json(json.clone());
-                if !events.is_empty() {
-                    return events;
+    // Keep a copy of the stream in {mission}/events/{agent}.ndjson
+    let forwarder = match &options.forward {
+        Some(mission_dir) => {
+            let dir = Path::new(mission_dir).join("events");
+            match RingWriter::open(&dir, &agent_id, options.forward_limits) {
+                Ok(writer) => Some(writer),
+                Err(e) => {
+                    eprintln!("Error opening event log in {}: {}", dir.display(), e);
+                    None
                 }
-                self.parse_claude_json(json)
             }
         }
+        None => None,
+    };
+
+    let setup = match ParserSetup::new(&options, format_hint) {
+        Ok(setup) => setup,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+    // Every agent on a demultiplexed stream gets a parser set up the same way
+    let make_parser = move |agent_id: &str| setup.parser(agent_id);
+    let mut parsers = if options.demux_prefix {
+        Demux::prefixed(make_parser)
+    } else {
+        Demux::single(&agent_id, make_parser)
+    };
+    // Read before the output opens, but reported on it
+    let state_error = match options.state_file.as_deref().map(state::load) {
+        Some(Ok(Some(saved))) => {
+            parsers.restore(saved);
+            None
+        }
+        Some(Err(message)) => Some(message),
+        _ => None,
+    };
+    if let Some(seq) = options.start_seq {
+        parsers.set_start_seq(seq);
     }
 
-    /// Detect format from JSON structure
-    fn detect_format(&mut self, json: &Value) {
-        if let Some(obj) = json.as_object() {
-            // Claude Code format has "type" with values like "assistant", "user", "result"
-            if let Some(type_val) = obj.get("type").and_then(|v| v.as_str()) {
-                match type_val {
-                    "assistant" | "user" | "result" | "system" => {
-                        self.format = AgentFormat::ClaudeCode;
-                        return;
-                    }
-                    // Python format has "type" with values like "turn", "thinking", "tool_call"
-                    "turn" | "thinking" | "tool_call" | "tool_result" => {
-                        self.format = AgentFormat::Python;
-                        return;
-                    }
-                    _ => {}
-                }
+    let mut watchdog = options
+        .require_first_event
+        .map(|window| Watchdog::new(window, options.require_event_types.clone(), Instant::now()));
+    let mut heartbeat = options
+        .heartbeat_secs
+        .filter(|&secs| secs > 0)
+        .map(|secs| Heartbeat::new(Duration::from_secs(secs), Instant::now()));
+
+    // Connect (or wait for a client) before the agent starts producing
+    #[cfg(unix)]
+    let (out, socket_error) = match &options.output {
+        Some(path) => {
+            let backlog = options
+                .output_backlog
+                .unwrap_or(socket_output::DEFAULT_BACKLOG_EVENTS);
+            let socket = if options.listen {
+                SocketOutput::listen(path, backlog)
+            } else {
+                SocketOutput::connect(path, backlog)
+            };
+            match socket {
+                Ok(socket) => (Output::Socket(socket), None),
+                Err(e) => (
+                    Output::Stdout(io::stdout().lock()),
+                    Some(format!(
+                        "cannot open event socket {}, writing to stdout: {}",
+                        path.display(),
+                        e
+                    )),
+                ),
             }
+        }
+        None => (Output::Stdout(io::stdout().lock()), None),
+    };
+    #[cfg(not(unix))]
+    let (out, socket_error) = (Output::Stdout(io::stdout().lock()), None::<String>);
+
+    let limits = options.throttle;
+    let throttled = limits.events_per_sec.is_some() || limits.bytes_per_sec.is_some();
+    // Colors only for a person watching a terminal
+    let pretty = options.pretty.then(|| Pretty {
+        color: !options.no_color
+            && env::var_os("NO_COLOR").is_none()
+            && matches!(out, Output::Stdout(_))
+            && io::stdout().is_terminal(),
+        width: env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .unwrap_or(pretty::DEFAULT_WIDTH),
+    });
+    let mut sink = Sink {
+        out,
+        encoding: options.encoding,
+        pretty,
+        forwarder,
+        filter: options.filter,
+        throttle: throttled
+            .then(|| Throttle::new(limits, throttle::DEFAULT_QUEUE_EVENTS, Instant::now())),
+    };
+    if let Some(message) = socket_error {
+        eprintln!("{}", message);
+        sink.emit(&parsers.error("output_unavailable", &message));
+    }
+    // A state that can't be used means starting over, not stopping
+    if let Some(message) = state_error {
+        eprintln!("{}, starting fresh", message);
+        sink.emit(&parsers.error("state_unreadable", &message));
+    }
+    let mut saved_at = parsers.next_seq();
 
-            // Claude Code format often has "message" field
-            if obj.contains_key("message") {
-                self.format = AgentFormat::ClaudeCode;
-                return;
+    let (lines, mut child) = if let Some(path) = &options.replay {
+        let pacer = Pacer::new(
+            options.speed.unwrap_or(1.0),
+            options
+                .delay_ms
+                .map_or(replay::DEFAULT_DELAY, Duration::from_millis),
+        );
+        match replay_file(path, pacer, options.encoding_in) {
+            Ok(lines) => (lines, None),
+            Err(e) => {
+                eprintln!("Error replaying {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    } else if let Some(path) = &options.input {
+        match read_file(path, options.encoding_in) {
+            Ok(lines) => (lines, None),
+            Err(e) => {
+                eprintln!("Error reading {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    } else if options.exec.is_empty() {
+        (read_stdin(options.encoding_in), None)
+    } else {
+        match spawn_agent(&options.exec, options.encoding_in) {
+            Ok((lines, child)) => (lines, Some(child)),
+            Err(e) => {
+                eprintln!("Error starting {}: {}", options.exec[0], e);
+                std::process::exit(1);
             }
         }
+    };
+    // Stop reading on SIGINT or SIGTERM, but still end the stream properly
+    let interrupted = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        if let Err(e) = signal_hook::flag::register(signal, Arc::clone(&interrupted)) {
+            eprintln!("Error handling signal {}: {}", signal, e);
+        }
     }
 
-    /// Parse Python agent JSON format
-    fn parse_python_json(&mut self, json: Value) -> Vec<UnifiedEvent> {
-        let mut events = vec![];
+    let mut input_failed = false;
+    // Live progress bars, one per stream
+    let mut stdout_redraws = RedrawThrottle::new(REDRAW_INTERVAL);
+    let mut stderr_redraws = RedrawThrottle::new(REDRAW_INTERVAL);
+    loop {
+        if interrupted.load(Ordering::Relaxed) {
+            break;
+        }
+        if let Some(path) = &options.state_file {
+            if parsers.next_seq() >= saved_at + STATE_SAVE_EVERY {
+                save_state(path, &parsers);
+                saved_at = parsers.next_seq();
+            }
+        }
+        let now = Instant::now();
+        let watchdog_remaining = watchdog.as_ref().and_then(|w| w.remaining(now));
+        // Checked before reading so a steady stream of noise can't starve it
+        if watchdog_remaining.is_some_and(|remaining| remaining.is_zero()) {
+            let watchdog = watchdog.as_ref().expect("only a watchdog expires");
+            let mut event = watchdog.error_event(&agent_id, parsers.format());
+            event.seq = parsers.next_seq();
+            sink.emit(&[event]);
+            sink.close();
+            std::process::exit(EXIT_NO_EVENTS);
+        }
 
-        if let Some(obj) = json.as_object() {
-            let event_type = obj.get("type").and_then(|v| v.as_str()).unwrap_or("");
+        let wait = watchdog_remaining
+            .into_iter()
+            .chain(parsers.coalesce_remaining(now))
+            .chain(heartbeat.as_ref().and_then(|h| h.remaining(now)))
+            .chain(sink.remaining(now))
+            .fold(SIGNAL_POLL, Duration::min);
+        let line = match lines.recv_timeout(wait) {
+            Ok(line) => line,
+            Err(RecvTimeoutError::Timeout) => {
+                let now = Instant::now();
+                let mut events = parsers.flush_stale(now);
+                if let Some(watchdog) = watchdog.as_mut() {
+                    watchdog.observe_events(&events);
+                }
+                if let Some(idle) = heartbeat.as_mut().and_then(|h| h.beat(now)) {
+                    events.extend(parsers.heartbeat(idle));
+                }
+                sink.emit(&events);
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
 
-            match event_type {
-                "turn" => {
-                    if let Some(num) = obj.get("number").and_then(|v| v.as_u64()) {
-                        self.current_turn = num as u32;
-                        events.push(
-                            UnifiedEvent::new("turn")
-                                .with_agent_id(&self.agent_id)
-                                .with_turn(self.current_turn),
-                        );
-                    }
+        match line {
+            (Source::Stderr, Ok(line)) => {
+                let events = stderr_redraws
+                    .admit(&line, Instant::now())
+                    .map_or_else(Vec::new, |line| parsers.parse_stderr_line(line));
+                if let Some(heartbeat) = heartbeat.as_mut() {
+                    heartbeat.observe_input(Instant::now(), !events.is_empty());
                 }
-                "thinking" => {
-                    if let Some(content) = obj.get("content").and_then(|v| v.as_str()) {
-                        let mut event = UnifiedEvent::new("thinking")
-                            .with_agent_id(&self.agent_id)
-                            .with_content(content);
-                        if let Some(tokens) = obj.get("tokens").and_then(|v| v.as_u64()) {
-                            event = event.with_tokens(tokens as u32);
-                        }
-                        events.push(event);
-                    }
+                if let Some(watchdog) = watchdog.as_mut() {
+                    watchdog.observe_line(&line);
+                    watchdog.observe_events(&events);
                 }
-                "tool_call" => {
-                    if let Some(tool) = obj.get("tool").and_then(|v| v.as_str()) {
-                        let args = obj.get("args").cloned().unwrap_or(Value::Null);
-                        events.push(
-                            UnifiedEvent::new("tool_call")
-                                .with_agent_id(&self.agent_id)
-                                .with_tool(tool, args),
-                        );
-                    }
+                sink.emit(&events);
+            }
+            // Losing stderr is no reason to stop parsing stdout
+            (Source::Stderr, Err(e)) => eprintln!("Error reading agent stderr: {}", e),
+            (Source::Stdout, Ok(line)) => {
+                let events = stdout_redraws
+                    .admit(&line, Instant::now())
+                    .map_or_else(Vec::new, |line| parsers.parse_line(line));
+                if let Some(heartbeat) = heartbeat.as_mut() {
+                    heartbeat.observe_input(Instant::now(), !events.is_empty());
                 }
-                "tool_result" => {
-                    if let Some(content) = obj.get("content").and_then(|v| v.as_str()) {
-                        let mut event = UnifiedEvent::new("tool_result")
-                            .with_agent_id(&self.agent_id)
-                            .with_result(content);
-                        if let Some(tokens) = obj.get("tokens").and_then(|v| v.as_u64()) {
-                            event = event.with_tokens(tokens as u32);
-                        }
-                        events.push(event);
-                    }
+                if let Some(watchdog) = watchdog.as_mut() {
+                    watchdog.observe_line(&line);
+                    watchdog.observe_events(&events);
                 }
-                _ => {
-                    // Unknown event type, pass through as-is
-                    events.push(
-                        UnifiedEvent::new("raw")
-                            .with_agent_id(&self.agent_id)
-                            .with_content(&json.to_string()),
+                sink.emit(&events);
+                if options
+                    .strict
+                    .is_some_and(|limit| parsers.parse_failures() >= limit)
+                {
+                    eprintln!(
+                        "Giving up after {} parse failures",
+                        parsers.parse_failures()
                     );
+                    sink.close();
+                    std::process::exit(EXIT_PARSE_FAILURES);
                 }
             }
+            (Source::Stdout, Err(e)) => {
+                let message = format!("reading agent output failed: {}", e);
+                eprintln!("{}", message);
+                sink.emit(&parsers.error("input_error", &message));
+                input_failed = true;
+                break;
+            }
         }
+    }
 
-        events
+    // Stopped with a state file: the stream is suspended, for the next run
+    // to carry on, rather than ended
+    if let (Some(path), true) = (&options.state_file, interrupted.load(Ordering::Relaxed)) {
+        sink.emit(&parsers.suspend());
+        save_state(path, &parsers);
+        sink.close();
+        return;
     }
 
-    /// Parse Claude Code stream-json format
-    fn parse_claude_json(&mut self, json: Value) -> Vec<UnifiedEvent> {
-        let mut events = vec![];
+    // An interrupted parser doesn't wait for the agent; it got the signal too
+    // or is being stopped along with it
+    let exit = match child.as_mut() {
+        _ if interrupted.load(Ordering::Relaxed) => AgentExit::Interrupted,
+        Some(child) => match child.wait() {
+            Ok(status) => agent_exit(status),
+            Err(e) => {
+                eprintln!("Error waiting for agent: {}", e);
+                AgentExit::Eof
+            }
+        },
+        None => AgentExit::Eof,
+    };
+    sink.emit(&parsers.finish_with(exit));
+    if let Some(path) = &options.state_file {
+        save_state(path, &parsers);
+    }
+    sink.close();
+    if input_failed {
+        std::process::exit(EXIT_INPUT_ERROR);
+    }
+}
 
-        if let Some(obj) = json.as_object() {
-            let event_type = obj.get("type").and_then(|v| v.as_str()).unwrap_or("");
+/// How the agent process ended, going by its exit status.
+#[cfg(unix)]
+fn agent_exit(status: ExitStatus) -> AgentExit {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => AgentExit::Exited(code),
+        (None, Some(signal)) => AgentExit::Killed(signal),
+        (None, None) => AgentExit::Eof,
+    }
+}
 
-            match event_type {
-                "assistant" => {
-                    // Assistant message with content blocks
-                    if let Some(message) = obj.get("message") {
-                        if let Some(content_arr) = message.get("content").and_then(|v| v.as_array())
-                        {
-                            for block in content_arr {
-                                events.extend(self.parse_claude_content_block(block));
-                            }
-                        }
-                    }
-                }
-                "content_block_start" => {
-                    if let Some(block) = obj.get("content_block") {
-                        events.extend(self.parse_claude_content_block(block));
-                    }
-                }
-                "content_block_delta" => {
-                    if let Some(delta) = obj.get("delta") {
-                        if let Some(text) = delta.get("text").and_then(|v| v.as_str()) {
-                            events.push(
-                                UnifiedEvent::new("thinking")
-                                    .with_agent_id(&self.agent_id)
-                                    .with_content(text),
-                            );
-                        }
-                    }
-                }
-                "result" => {
-                    if let Some(result) = obj.get("result").and_then(|v| v.as_str()) {
-                        events.push(
-                            UnifiedEvent::new("tool_result")
-                                .with_agent_id(&self.agent_id)
-                                .with_result(result),
-                        );
-                    } else if let Some(result) = obj.get("result") {
-                        events.push(
-                            UnifiedEvent::new("tool_result")
-                                .with_agent_id(&self.agent_id)
-                                .with_result(&result.to_string()),
-                        );
-                    }
-                }
-                "message_start" => {
-                    self.current_turn += 1;
-                    events.push(
-                        UnifiedEvent::new("turn")
-                            .with_agent_id(&self.agent_id)
-                            .with_turn(self.current_turn),
-                    );
-                }
-                "message_stop" => {
-                    events.push(
-                        UnifiedEvent::new("turn_end")
-                            .with_agent_id(&self.agent_id)
-                            .with_turn(self.current_turn),
-                    );
-                }
-                "error" => {
-                    let error_msg = obj
-                        .get("error")
-                        .and_then(|e| e.get("message"))
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("Unknown error");
-                    let mut event = UnifiedEvent::new("error").with_agent_id(&self.agent_id);
-                    event.error = Some(error_msg.to_string());
-                    events.push(event);
-                }
-                _ => {
-                    // Pass through unknown events
-                    events.push(
-                        UnifiedEvent::new("raw")
-                            .with_agent_id(&self.agent_id)
-                            .with_content(&json.to_string()),
-                    );
-                }
-            }
+/// How the agent process ended; without signals there is only the code.
+#[cfg(not(unix))]
+fn agent_exit(status: ExitStatus) -> AgentExit {
+    status.code().map_or(AgentExit::Eof, AgentExit::Exited)
+}
+
+/// Save the parsers' state, reporting rather than failing on error.
+fn save_state(path: &Path, parsers: &Demux) {
+    if let Err(e) = state::save(path, &parsers.state()) {
+        eprintln!("Error saving state to {}: {}", path.display(), e);
+    }
+}
+
+/// How to set up the parser of each agent, from the command line.
+struct ParserSetup {
+    arg_profile: bool,
+    no_timestamps: bool,
+    multiline: bool,
+    multiline_limit: Option<usize>,
+    legacy_thinking: bool,
+    keep_ansi: bool,
+    rules: Option<Rules>,
+    coalesce: Option<Coalesce>,
+    max_content_bytes: Option<usize>,
+    media_dir: Option<PathBuf>,
+    /// Values to redact, when redacting
+    secrets: Option<Vec<String>>,
+    format: Option<AgentFormat>,
+}
+
+impl ParserSetup {
+    fn new(options: &Options, format_hint: Option<&str>) -> Result<Self, String> {
+        let rules = options.rules.as_deref().map(Rules::load).transpose()?;
+        // Naming variables to redact implies --redact
+        let secrets = (options.redact || !options.redact_env.is_empty()).then(|| {
+            options
+                .redact_env
+                .iter()
+                .filter_map(|name| env::var(name).ok())
+                .collect()
+        });
+        let coalesce =
+            (options.coalesce_ms.is_some() || options.coalesce_bytes.is_some()).then(|| Coalesce {
+                max_bytes: options.coalesce_bytes,
+                max_age: options.coalesce_ms.map(Duration::from_millis),
+            });
+        let format = format_hint.map(|hint| match hint {
+            "python" => AgentFormat::Python,
+            "claude" => AgentFormat::ClaudeCode,
+            "openai" => AgentFormat::OpenAi,
+            "gemini" => AgentFormat::Gemini,
+            "aider" => AgentFormat::Aider,
+            _ => AgentFormat::Unknown,
+        });
+        Ok(ParserSetup {
+            arg_profile: options.arg_profile,
+            no_timestamps: options.no_timestamps,
+            multiline: options.multiline,
+            multiline_limit: options.multiline_limit,
+            legacy_thinking: options.legacy_thinking,
+            keep_ansi: options.keep_ansi,
+            rules,
+            coalesce,
+            max_content_bytes: options.max_content_bytes,
+            media_dir: options.media_dir.clone(),
+            secrets,
+            format,
+        })
+    }
+
+    fn parser(&self, agent_id: &str) -> Parser {
+        let mut parser = Parser::new(agent_id.to_string());
+        if self.arg_profile {
+            parser.enable_arg_profile(ARG_PROFILE_STATS_EVERY);
+        }
+        if self.no_timestamps {
+            parser.set_timestamps(false);
+        }
+        parser.set_multiline(self.multiline);
+        parser.set_legacy_thinking(self.legacy_thinking);
+        parser.set_lifecycle(true);
+        parser.set_strip_ansi(!self.keep_ansi);
+        if let Some(rules) = &self.rules {
+            parser.set_rules(rules.clone());
+        }
+        if let Some(coalesce) = self.coalesce {
+            parser.set_coalesce(coalesce);
+        }
+        if let Some(limit) = self.multiline_limit {
+            parser.set_multiline_limit(limit);
+        }
+        if let Some(max_bytes) = self.max_content_bytes {
+            parser.set_max_content_bytes(max_bytes);
         }
+        if let Some(dir) = &self.media_dir {
+            parser.set_media_dir(dir.clone());
+        }
+        if let Some(secrets) = &self.secrets {
+            parser.set_redact(secrets.clone());
+        }
+        // Pin the format hint if provided
+        if let Some(format) = self.format {
+            parser.set_format(format);
+        }
+        parser
+    }
+}
+
+/// Where events are written: stdout, or a socket with `--output unix:PATH`.
+enum Output {
+    Stdout(io::StdoutLock<'static>),
+    #[cfg(unix)]
+    Socket(SocketOutput),
+}
 
-        events
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            #[cfg(unix)]
+            Output::Socket(socket) => socket.write(buf),
+        }
     }
 
-    /// Parse a Claude Code content block
-    fn parse_claude_content_block(&self, block: &Value) -> Vec<UnifiedEvent> {
-        let mut events = vec![];
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            #[cfg(unix)]
+            Output::Socket(socket) => socket.flush(),
+        }
+    }
+}
 
-        if let Some(obj) = block.as_object() {
-            let block_type = obj.get("type").and_then(|v| v.as_str()).unwrap_or("");
+/// Where emitted events go, and in what form.
+struct Sink {
+    out: Output,
+    encoding: Encoding,
+    /// Readable lines instead of `encoding`, with `--pretty`
+    pretty: Option<Pretty>,
+    /// Copy of the stream in the mission's event log, with `--forward`
+    forwarder: Option<RingWriter>,
+    filter: TypeFilter,
+    /// Events held back by `--max-events-per-sec` and `--max-bytes-per-sec`
+    throttle: Option<Throttle>,
+}
 
-            match block_type {
-                "text" => {
-                    if let Some(text) = obj.get("text").and_then(|v| v.as_str()) {
-                        events.push(
-                            UnifiedEvent::new("thinking")
-                                .with_agent_id(&self.agent_id)
-                                .with_content(text),
-                        );
-                    }
-                }
-                "tool_use" => {
-                    if let Some(name) = obj.get("name").and_then(|v| v.as_str()) {
-                        let input = obj.get("input").cloned().unwrap_or(Value::Null);
-                        events.push(
-                            UnifiedEvent::new("tool_call")
-                                .with_agent_id(&self.agent_id)
-                                .with_tool(name, input),
-                        );
-                    }
+impl Sink {
+    /// Write events that pass the filter to the output and, when
+    /// forwarding, to the event log. The event log gets every event at once;
+    /// when throttled, the output gets what the limits allow.
+    fn emit(&mut self, events: &[UnifiedEvent]) {
+        for event in events {
+            if !self.filter.allows(&event.event_type) {
+                continue;
+            }
+            match self.throttle.as_mut() {
+                Some(throttle) => throttle.push(event.clone()),
+                None => {
+                    let _ = self.write(event);
+                    let _ = self.out.flush();
                 }
-                "tool_result" => {
-                    if let Some(content) = obj.get("content").and_then(|v| v.as_str()) {
-                        events.push(
-                            UnifiedEvent::new("tool_result")
-                                .with_agent_id(&self.agent_id)
-                                .with_result(content),
-                        );
+            }
+            // The event log is NDJSON whatever the output encoding
+            if let Some(writer) = self.forwarder.as_mut() {
+                if let Ok(json) = serde_json::to_string(event) {
+                    if let Err(e) = writer.append(&json) {
+                        eprintln!("Error forwarding event: {}", e);
                     }
                 }
-                _ => {}
             }
         }
+        self.pump(Instant::now());
+    }
 
-        events
+    /// Write the held-back events the limits allow by `now`, flushing once.
+    fn pump(&mut self, now: Instant) {
+        let Some(throttle) = self.throttle.as_mut() else {
+            return;
+        };
+        let (encoding, pretty) = (self.encoding, self.pretty);
+        let events = throttle.release(now, |event| {
+            let mut record = Vec::new();
+            let _ = write_record(&mut record, event, encoding, pretty);
+            record.len()
+        });
+        if events.is_empty() {
+            return;
+        }
+        for event in &events {
+            let _ = self.write(event);
+        }
+        let _ = self.out.flush();
     }
 
-    /// Parse plain text output (for Python agents that don't output JSON)
-    fn parse_text(&mut self, text: &str) -> Vec<UnifiedEvent> {
-        let mut events = vec![];
+    fn write(&mut self, event: &UnifiedEvent) -> io::Result<()> {
+        write_record(&mut self.out, event, self.encoding, self.pretty)
+    }
 
-        // Detect turn markers like "[Turn 1]"
-        if text.starts_with("[Turn ") {
-            if let Some(end) = text.find(']') {
-                if let Ok(num) = text[6..end].parse::<u32>() {
-                    self.current_turn = num;
-                    events.push(
-                        UnifiedEvent::new("turn")
-                            .with_agent_id(&self.agent_id)
-                            .with_turn(num),
-                    );
-                    return events;
-                }
-            }
-        }
+    /// Time until held-back events may be written, if there are any.
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.throttle.as_ref()?.remaining(now)
+    }
 
-        // Detect bash commands like "$ ls -la"
-        if text.starts_with("$ ") {
-            let command = &text[2..];
-            events.push(
-                UnifiedEvent::new("tool_call")
-                    .with_agent_id(&self.agent_id)
-                    .with_tool("bash", serde_json::json!({"command": command})),
-            );
-            return events;
+    /// Write what is still held back, at the allowed rate, and give a socket
+    /// that is down a last chance to take buffered events.
+    fn close(&mut self) {
+        while let Some(wait) = self.remaining(Instant::now()) {
+            thread::sleep(wait);
+            self.pump(Instant::now());
         }
-
-        // Detect tool markers like "[read] path/to/file"
-        if text.starts_with("[") {
-            if let Some(end) = text.find(']') {
-                let tool = &text[1..end];
-                let rest = text[end + 1..].trim();
-                events.push(
-                    UnifiedEvent::new("tool_call")
-                        .with_agent_id(&self.agent_id)
-                        .with_tool(tool, serde_json::json!({"info": rest})),
-                );
-                return events;
+        #[cfg(unix)]
+        if let Output::Socket(socket) = &mut self.out {
+            let undelivered = socket.close(SOCKET_CLOSE_PATIENCE);
+            if undelivered > 0 {
+                eprintln!("{} events never reached the event socket", undelivered);
             }
         }
+    }
+}
 
-        // Regular text output
-        events.push(
-            UnifiedEvent::new("output")
-                .with_agent_id(&self.agent_id)
-                .with_content(text),
-        );
-
-        events
+/// Write `event` as a readable line when `pretty` is set, else in `encoding`.
+fn write_record(
+    out: &mut impl Write,
+    event: &UnifiedEvent,
+    encoding: Encoding,
+    pretty: Option<Pretty>,
+) -> io::Result<()> {
+    match pretty {
+        Some(pretty) => writeln!(out, "{}", pretty.render(event)),
+        None => encoding::write_event(out, event, encoding),
     }
 }
 
-fn main() {
-    // Get agent ID from args or use default
-    let args: Vec<String> = env::args().collect();
-    let agent_id = args.get(1).cloned().unwrap_or_else(|| "unknown".to_string());
+/// Which of the agent's streams a line was read from.
+#[derive(Clone, Copy)]
+enum Source {
+    Stdout,
+    Stderr,
+}
 
-    // Get format hint from args (optional)
-    let format_hint = args.get(2).map(|s| s.as_str());
+type Lines = Receiver<(Source, io::Result<String>)>;
 
-    let mut parser = Parser::new(agent_id);
+/// Read stdin on a separate thread, so the main loop can wait with a timeout.
+fn read_stdin(encoding: InputEncoding) -> Lines {
+    let (tx, rx) = mpsc::channel();
+    forward_lines(BufReader::new(io::stdin()), Source::Stdout, encoding, tx);
+    rx
+}
 
-    // Set format hint if provided
-    if let Some(hint) = format_hint {
-        parser.format = match hint {
-            "python" => AgentFormat::Python,
-            "claude" => AgentFormat::ClaudeCode,
-            _ => AgentFormat::Unknown,
-        };
-    }
+/// Read a captured transcript on a separate thread, as fast as it parses.
+fn read_file(path: &Path, encoding: InputEncoding) -> io::Result<Lines> {
+    let (tx, rx) = mpsc::channel();
+    forward_lines(open_transcript(path)?, Source::Stdout, encoding, tx);
+    Ok(rx)
+}
 
-    let stdin = io::stdin();
-    let stdout = io::stdout();
-    let mut stdout_lock = stdout.lock();
+/// Open a captured transcript, decompressing it when it's gzipped: named
+/// `.gz`, or starting with the gzip magic bytes whatever its name.
+fn open_transcript(path: &Path) -> io::Result<Box<dyn BufRead + Send>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let gzipped = path.extension().is_some_and(|ext| ext == "gz")
+        || reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+    if gzipped {
+        // Several members when rotated logs were concatenated
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
 
-    for line in stdin.lock().lines() {
-        match line {
-            Ok(line) => {
-                let events = parser.parse_line(&line);
-                for event in events {
-                    if let Ok(json) = serde_json::to_string(&event) {
-                        let _ = writeln!(stdout_lock, "{}", json);
-                        let _ = stdout_lock.flush();
-                    }
-                }
+/// Send the lines of a captured transcript at the pace `pacer` sets.
+fn replay_file(path: &Path, mut pacer: Pacer, encoding: InputEncoding) -> io::Result<Lines> {
+    let reader = decoded(open_transcript(path)?, encoding);
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in reader.lines() {
+            if let Ok(line) = &line {
+                thread::sleep(pacer.delay(line));
             }
-            Err(e) => {
-                eprintln!("Error reading line: {}", e);
+            let failed = line.is_err();
+            if tx.send((Source::Stdout, line)).is_err() || failed {
                 break;
             }
         }
-    }
+    });
+    Ok(rx)
+}
+
+/// Start the agent and read its stdout and stderr in arrival order.
+fn spawn_agent(command: &[String], encoding: InputEncoding) -> io::Result<(Lines, Child)> {
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let (tx, rx) = mpsc::channel();
+    let stdout = child.stdout.take().expect("piped");
+    let stderr = child.stderr.take().expect("piped");
+    forward_lines(BufReader::new(stdout), Source::Stdout, encoding, tx.clone());
+    forward_lines(BufReader::new(stderr), Source::Stderr, encoding, tx);
+    Ok((rx, child))
+}
+
+/// Send each line of `reader` from a new thread until it ends or fails.
+/// Redraws of a line still being drawn are sent as they come, ending in `\r`.
+fn forward_lines(
+    reader: impl BufRead + Send + 'static,
+    source: Source,
+    encoding: InputEncoding,
+    tx: Sender<(Source, io::Result<String>)>,
+) {
+    thread::spawn(move || {
+        for line in Segments::new(decoded(reader, encoding)) {
+            let failed = line.is_err();
+            if tx.send((source, line)).is_err() || failed {
+                break;
+            }
+        }
+    });
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_parse_python_turn() {
-        let mut parser = Parser::new("test".to_string());
-        let events = parser.parse_line(r#"{"type":"turn","number":1}"#);
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].event_type, "turn");
-        assert_eq!(events[0].turn, Some(1));
-    }
-
-    #[test]
-    fn test_parse_python_tool_call() {
-        let mut parser = Parser::new("test".to_string());
-        let events = parser.parse_line(r#"{"type":"tool_call","tool":"bash","args":{"command":"ls"}}"#);
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].event_type, "tool_call");
-        assert_eq!(events[0].tool, Some("bash".to_string()));
-    }
-
-    #[test]
-    fn test_parse_text_turn() {
-        let mut parser = Parser::new("test".to_string());
-        let events = parser.parse_line("[Turn 1]");
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].event_type, "turn");
-        assert_eq!(events[0].turn, Some(1));
-    }
-
-    #[test]
-    fn test_parse_text_bash() {
-        let mut parser = Parser::new("test".to_string());
-        let events = parser.parse_line("$ ls -la");
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].event_type, "tool_call");
-        assert_eq!(events[0].tool, Some("bash".to_string()));
+/// `reader` as UTF-8 text, decoded from `encoding`.
+fn decoded(
+    reader: impl BufRead + Send + 'static,
+    encoding: InputEncoding,
+) -> Box<dyn BufRead + Send> {
+    match encoding {
+        InputEncoding::Utf8 => Box::new(reader),
+        InputEncoding::Utf16 => Box::new(BufReader::new(Utf16Reader::new(reader))),
     }
 }
@@ -0,0 +1,143 @@
+//! Turn Claude `image` content blocks into `media` events.
+//!
+//! A screenshot in a tool result arrives as base64 that can run to
+//! megabytes. The `media` event carries its MIME type and decoded size
+//! instead, and, with a media directory set, the path of a file the bytes
+//! were saved to. Files are named by the SHA-256 of their bytes, so an image
+//! seen twice is saved once.
+
+use crate::UnifiedEvent;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+/// Status of the `error` event for an image that can't be decoded or saved.
+pub const MEDIA_ERROR: &str = "media_error";
+
+/// The `media` event for an `image` block, saving its bytes under `dir` when
+/// given. An image that can't be decoded is an `error` event instead; one
+/// that can't be saved is reported alongside a `media` event without a path.
+pub fn media(block: &Value, dir: Option<&Path>) -> Vec<UnifiedEvent> {
+    let source = &block["source"];
+    let media_type = source["media_type"]
+        .as_str()
+        .unwrap_or("application/octet-stream");
+    let mut event = UnifiedEvent::new("media");
+    event.media_type = Some(media_type.to_string());
+
+    // An image given by URL has no bytes here to count or save
+    let Some(data) = source["data"].as_str() else {
+        return vec![event];
+    };
+    let bytes = match STANDARD.decode(data.trim()) {
+        Ok(bytes) => bytes,
+        Err(e) => return vec![media_error(format!("invalid base64 in image: {}", e))],
+    };
+    event.media_bytes = Some(bytes.len() as u64);
+
+    let Some(dir) = dir else {
+        return vec![event];
+    };
+    let hash: String = Sha256::digest(&bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let path = dir.join(format!("{}.{}", hash, extension(media_type)));
+    // Same name, same bytes: a file already there is this image
+    let saved = if path.exists() {
+        Ok(())
+    } else {
+        fs::create_dir_all(dir).and_then(|_| fs::write(&path, &bytes))
+    };
+    match saved {
+        Ok(()) => {
+            event.path = Some(path.display().to_string());
+            vec![event]
+        }
+        Err(e) => {
+            let error = format!("cannot save image to {}: {}", path.display(), e);
+            vec![event, media_error(error)]
+        }
+    }
+}
+
+fn media_error(message: String) -> UnifiedEvent {
+    let mut event = UnifiedEvent::new("error").with_status(MEDIA_ERROR);
+    event.error = Some(message);
+    event
+}
+
+/// File extension for a MIME type.
+fn extension(media_type: &str) -> &str {
+    match media_type {
+        "image/jpeg" => "jpg",
+        "image/svg+xml" => "svg",
+        other => match other.split_once('/') {
+            Some(("image", subtype)) if subtype.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                subtype
+            }
+            _ => "bin",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn image(data: &str) -> Value {
+        json!({
+            "type": "image",
+            "source": {"type": "base64", "media_type": "image/png", "data": data},
+        })
+    }
+
+    #[test]
+    fn test_saved_by_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let block = image(&STANDARD.encode(b"\x89PNG not really"));
+        let events = media(&block, Some(dir.path()));
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.event_type, "media");
+        assert_eq!(event.media_type.as_deref(), Some("image/png"));
+        assert_eq!(event.media_bytes, Some(15));
+        assert!(event.content.is_none());
+
+        let path = event.path.as_deref().unwrap();
+        assert!(path.ends_with(".png"), "{}", path);
+        assert_eq!(fs::read(path).unwrap(), b"\x89PNG not really");
+        // Seen again, it names the same file
+        assert_eq!(
+            media(&block, Some(dir.path()))[0].path.as_deref(),
+            Some(path)
+        );
+    }
+
+    #[test]
+    fn test_without_dir_only_described() {
+        let events = media(&image("aGVsbG8="), None);
+        assert_eq!(events[0].media_bytes, Some(5));
+        assert_eq!(events[0].path, None);
+    }
+
+    #[test]
+    fn test_corrupt_base64_is_an_error() {
+        let events = media(&image("not*base64!"), None);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "error");
+        assert_eq!(events[0].status.as_deref(), Some(MEDIA_ERROR));
+    }
+
+    #[test]
+    fn test_extensions() {
+        assert_eq!(extension("image/jpeg"), "jpg");
+        assert_eq!(extension("image/webp"), "webp");
+        assert_eq!(extension("image/../x"), "bin");
+        assert_eq!(extension("application/pdf"), "bin");
+    }
+}
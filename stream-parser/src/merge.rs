@@ -0,0 +1,327 @@
+//! Merge per-agent event streams into one stream ordered by event time.
+//!
+//! Events are ordered by `timestamp`, then by `seq` for ties. Each input is
+//! taken to be in the order its agent produced it, so a timestamp that goes
+//! backwards (a skewed clock) is held at the latest time already seen on that
+//! input: two events from the same input are never reordered. Lines that are
+//! not valid events are skipped and listed in the [`MergeReport`].
+
+use crate::UnifiedEvent;
+use chrono::DateTime;
+use serde::Serialize;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// One stream to merge.
+pub struct MergeInput {
+    /// Name used in reports, usually the file path
+    pub name: String,
+    pub reader: Box<dyn BufRead + Send>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    /// Merge live streams through a buffer spanning this much event time,
+    /// instead of waiting for a line from every input before writing
+    pub window: Option<Duration>,
+}
+
+/// A line that was skipped because it is not a valid event.
+#[derive(Debug, Serialize)]
+pub struct MalformedLine {
+    pub input: String,
+    pub line: usize,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MergeReport {
+    /// Events written
+    pub events: u64,
+    /// Events whose timestamp went backwards on their input
+    pub clamped: u64,
+    /// Events written after a later event (windowed merges only)
+    pub late: u64,
+    pub malformed: Vec<MalformedLine>,
+}
+
+/// Where an event sorts in the merged stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey {
+    /// Nanoseconds since the epoch, never decreasing on one input
+    time: i64,
+    seq: u64,
+    input: usize,
+    /// Line number on the input
+    line: usize,
+}
+
+/// A validated line waiting to be written.
+#[derive(Debug)]
+struct Entry {
+    key: SortKey,
+    line: String,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// Validation and clamping state for one input.
+struct Cursor {
+    input: usize,
+    name: String,
+    line: usize,
+    /// Time and seq of the previous event
+    last: Option<(i64, u64)>,
+}
+
+impl Cursor {
+    fn new(input: usize, name: String) -> Self {
+        Cursor {
+            input,
+            name,
+            line: 0,
+            last: None,
+        }
+    }
+
+    /// Turn the next raw line into an entry, or record why it can't be one.
+    fn entry(&mut self, raw: &[u8], report: &mut MergeReport) -> Option<Entry> {
+        self.line += 1;
+        let parsed = std::str::from_utf8(raw)
+            .map_err(|_| "invalid UTF-8".to_string())
+            .and_then(|line| {
+                let line = line.trim();
+                if line.is_empty() {
+                    Ok(None)
+                } else {
+                    event_time(line).map(|(time, seq)| Some((line.to_string(), time, seq)))
+                }
+            });
+
+        let (line, time, seq) = match parsed {
+            Ok(Some(parsed)) => parsed,
+            Ok(None) => return None,
+            Err(error) => {
+                report.malformed.push(MalformedLine {
+                    input: self.name.clone(),
+                    line: self.line,
+                    error,
+                });
+                return None;
+            }
+        };
+
+        // An event without a timestamp happened no earlier than the one before
+        let (last_time, last_seq) = self.last.unwrap_or((i64::MIN, 0));
+        let mut time = time.unwrap_or(last_time);
+        if time < last_time {
+            time = last_time;
+            report.clamped += 1;
+        }
+        let seq = match seq {
+            Some(seq) if time > last_time => seq,
+            seq => seq.unwrap_or(last_seq).max(last_seq),
+        };
+        self.last = Some((time, seq));
+
+        Some(Entry {
+            key: SortKey {
+                time,
+                seq,
+                input: self.input,
+                line: self.line,
+            },
+            line,
+        })
+    }
+}
+
+/// Validate `line` against the event schema and return its time and seq.
+fn event_time(line: &str) -> Result<(Option<i64>, Option<u64>), String> {
+    let event: UnifiedEvent = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    let time = match event.timestamp.as_deref() {
+        Some(timestamp) => Some(
+            DateTime::parse_from_rfc3339(timestamp)
+                .ok()
+                .and_then(|t| t.timestamp_nanos_opt())
+                .ok_or_else(|| format!("invalid timestamp '{}'", timestamp))?,
+        ),
+        None => None,
+    };
+    Ok((time, Some(event.seq).filter(|&seq| seq > 0)))
+}
+
+/// Merge `inputs` into `out`, one event per line.
+///
+/// Without a window this is a streaming k-way merge holding one line per
+/// input. With a window, inputs are read as lines arrive and an event is
+/// written once an event more than `window` newer has been seen, or when no
+/// input has produced anything for `window`; an event older than one already
+/// written is then written late rather than dropped.
+pub fn merge(
+    inputs: Vec<MergeInput>,
+    out: &mut impl Write,
+    options: &MergeOptions,
+) -> io::Result<MergeReport> {
+    let mut report = MergeReport::default();
+    match options.window {
+        Some(window) => merge_windowed(inputs, out, window, &mut report)?,
+        None => merge_sorted(inputs, out, &mut report)?,
+    }
+    out.flush()?;
+    Ok(report)
+}
+
+fn merge_sorted(
+    inputs: Vec<MergeInput>,
+    out: &mut impl Write,
+    report: &mut MergeReport,
+) -> io::Result<()> {
+    let mut sources: Vec<(Cursor, Box<dyn BufRead + Send>)> = inputs
+        .into_iter()
+        .enumerate()
+        .map(|(i, input)| (Cursor::new(i, input.name), input.reader))
+        .collect();
+
+    let mut heap = BinaryHeap::new();
+    for (cursor, reader) in sources.iter_mut() {
+        if let Some(entry) = next_entry(cursor, reader, report)? {
+            heap.push(Reverse(entry));
+        }
+    }
+
+    while let Some(Reverse(entry)) = heap.pop() {
+        writeln!(out, "{}", entry.line)?;
+        report.events += 1;
+
+        let (cursor, reader) = &mut sources[entry.key.input];
+        if let Some(next) = next_entry(cursor, reader, report)? {
+            heap.push(Reverse(next));
+        }
+    }
+    Ok(())
+}
+
+/// The next valid entry on an input, or `None` at end of input.
+fn next_entry(
+    cursor: &mut Cursor,
+    reader: &mut dyn BufRead,
+    report: &mut MergeReport,
+) -> io::Result<Option<Entry>> {
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            return Ok(None);
+        }
+        if let Some(entry) = cursor.entry(&buf, report) {
+            return Ok(Some(entry));
+        }
+    }
+}
+
+fn merge_windowed(
+    inputs: Vec<MergeInput>,
+    out: &mut impl Write,
+    window: Duration,
+    report: &mut MergeReport,
+) -> io::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut cursors = Vec::new();
+    for (i, input) in inputs.into_iter().enumerate() {
+        cursors.push(Cursor::new(i, input.name));
+        let tx = tx.clone();
+        let mut reader = input.reader;
+        thread::spawn(move || loop {
+            let mut buf = Vec::new();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) => break,
+                Ok(_) => {
+                    if tx.send((i, Ok(buf))).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send((i, Err(e)));
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    let span = i64::try_from(window.as_nanos()).unwrap_or(i64::MAX);
+    let mut heap = BinaryHeap::new();
+    let mut newest = i64::MIN;
+    let mut written = i64::MIN;
+
+    loop {
+        match rx.recv_timeout(window) {
+            Ok((i, Ok(raw))) => {
+                if let Some(entry) = cursors[i].entry(&raw, report) {
+                    newest = newest.max(entry.key.time);
+                    heap.push(Reverse(entry));
+                }
+                let horizon = newest.saturating_sub(span);
+                while heap
+                    .peek()
+                    .is_some_and(|Reverse(entry)| entry.key.time <= horizon)
+                {
+                    let Reverse(entry) = heap.pop().expect("peeked");
+                    write_live(out, &entry, &mut written, report)?;
+                }
+            }
+            Ok((_, Err(e))) => return Err(e),
+            // Every input is idle; don't hold back what is buffered
+            Err(RecvTimeoutError::Timeout) => {
+                while let Some(Reverse(entry)) = heap.pop() {
+                    write_live(out, &entry, &mut written, report)?;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    while let Some(Reverse(entry)) = heap.pop() {
+        write_live(out, &entry, &mut written, report)?;
+    }
+    Ok(())
+}
+
+fn write_live(
+    out: &mut impl Write,
+    entry: &Entry,
+    written: &mut i64,
+    report: &mut MergeReport,
+) -> io::Result<()> {
+    if entry.key.time < *written {
+        report.late += 1;
+    }
+    *written = (*written).max(entry.key.time);
+    writeln!(out, "{}", entry.line)?;
+    out.flush()?;
+    report.events += 1;
+    Ok(())
+}
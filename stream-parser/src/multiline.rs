@@ -0,0 +1,108 @@
+//! Reassemble JSON objects that an agent pretty-printed across several lines.
+//!
+//! The parser starts a [`Reassembler`] when a line opens an object it does
+//! not close, and feeds it the following lines until the brackets balance.
+
+/// Bytes buffered before an unfinished object is given up on.
+pub const DEFAULT_LIMIT: usize = 1024 * 1024;
+
+/// Outcome of feeding a line to a [`Reassembler`].
+#[derive(Debug, PartialEq)]
+pub enum Feed {
+    /// Brackets are still open
+    Incomplete,
+    /// Brackets balanced; the buffered text is ready to parse
+    Complete(String),
+    /// The buffer passed its limit; what was buffered, for reporting
+    Overflow(String),
+}
+
+/// Buffered lines of one JSON value and the bracket state at their end.
+#[derive(Debug)]
+pub struct Reassembler {
+    buffer: String,
+    limit: usize,
+    depth: i64,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Reassembler {
+    pub fn new(limit: usize) -> Self {
+        Reassembler {
+            buffer: String::new(),
+            limit,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+        }
+    }
+
+    /// Whether `line` opens more brackets than it closes.
+    pub fn opens(line: &str) -> bool {
+        let mut probe = Reassembler::new(usize::MAX);
+        probe.scan(line);
+        probe.depth > 0
+    }
+
+    /// Append a line and report whether the value is finished.
+    pub fn push(&mut self, line: &str) -> Feed {
+        if !self.buffer.is_empty() {
+            self.buffer.push('\n');
+        }
+        self.buffer.push_str(line);
+        self.scan(line);
+
+        if self.depth <= 0 && !self.in_string {
+            Feed::Complete(std::mem::take(&mut self.buffer))
+        } else if self.buffer.len() > self.limit {
+            Feed::Overflow(std::mem::take(&mut self.buffer))
+        } else {
+            Feed::Incomplete
+        }
+    }
+
+    /// Everything buffered so far.
+    pub fn into_text(self) -> String {
+        self.buffer
+    }
+
+    fn scan(&mut self, line: &str) {
+        for c in line.chars() {
+            if self.in_string {
+                match c {
+                    _ if self.escaped => self.escaped = false,
+                    '\\' => self.escaped = true,
+                    '"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '"' => self.in_string = true,
+                '{' | '[' => self.depth += 1,
+                '}' | ']' => self.depth -= 1,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brackets_in_strings_are_ignored() {
+        assert!(Reassembler::opens("{"));
+        assert!(!Reassembler::opens(r#"{"a": "}"}"#));
+        assert!(Reassembler::opens(r#"{"a": "\"{", "b": ["#));
+
+        let mut reassembler = Reassembler::new(DEFAULT_LIMIT);
+        assert_eq!(reassembler.push(r#"{"text": "} ] \"}"#), Feed::Incomplete);
+        assert_eq!(
+            reassembler.push(r#""}"#),
+            Feed::Complete("{\"text\": \"} ] \\\"}\n\"}".to_string())
+        );
+    }
+}
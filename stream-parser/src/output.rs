@@ -0,0 +1,236 @@
+//! Write events to a Unix socket that may come and go.
+//!
+//! With `--output unix:PATH` the parser connects to `PATH` (or, with
+//! `--listen`, binds it and waits for a client) and writes NDJSON there
+//! instead of stdout. When the socket goes away mid-stream, events are
+//! held in a bounded backlog while the parser reconnects with exponential
+//! backoff, and replayed once it gets through. An event written just as the
+//! peer goes away can still be lost: the failure only shows on a later
+//! write.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Events kept while the socket is down, when not configured.
+pub const DEFAULT_BACKLOG_EVENTS: usize = 10_000;
+
+/// Wait before the first reconnection attempt; doubled after each failure.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+enum Endpoint {
+    Connect(PathBuf),
+    /// Accepts without blocking once the first client has connected
+    Listen(UnixListener),
+}
+
+/// Event output to a Unix socket. What is written between flushes is one
+/// record, kept whole in the backlog and sent on `flush`.
+pub struct SocketOutput {
+    endpoint: Endpoint,
+    stream: Option<UnixStream>,
+    /// Records not yet delivered, oldest first
+    backlog: VecDeque<Vec<u8>>,
+    max_backlog: usize,
+    /// Records dropped from a full backlog during the current outage
+    dropped: u64,
+    /// Written since the last flush
+    partial: Vec<u8>,
+    backoff: Duration,
+    next_attempt: Instant,
+}
+
+impl SocketOutput {
+    /// Connect to a socket someone else is listening on.
+    pub fn connect(path: &Path, max_backlog: usize) -> io::Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        Ok(Self::new(
+            Endpoint::Connect(path.to_path_buf()),
+            stream,
+            max_backlog,
+        ))
+    }
+
+    /// Bind `path` and wait for a client. A client that disconnects is
+    /// replaced by the next one to connect.
+    pub fn listen(path: &Path, max_backlog: usize) -> io::Result<Self> {
+        // A socket left behind by an earlier run would make bind fail
+        if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let (stream, _) = listener.accept()?;
+        listener.set_nonblocking(true)?;
+        Ok(Self::new(Endpoint::Listen(listener), stream, max_backlog))
+    }
+
+    fn new(endpoint: Endpoint, stream: UnixStream, max_backlog: usize) -> Self {
+        SocketOutput {
+            endpoint,
+            stream: Some(stream),
+            backlog: VecDeque::new(),
+            max_backlog,
+            dropped: 0,
+            partial: Vec::new(),
+            backoff: INITIAL_BACKOFF,
+            next_attempt: Instant::now(),
+        }
+    }
+
+    /// Whether the socket is currently up.
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Lines waiting for the socket to come back.
+    pub fn backlog(&self) -> usize {
+        self.backlog.len()
+    }
+
+    /// Keep trying to deliver the backlog for up to `patience`, and return
+    /// how many events are still undelivered.
+    pub fn close(&mut self, patience: Duration) -> usize {
+        let deadline = Instant::now() + patience;
+        self.deliver();
+        while !self.backlog.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            thread::sleep(
+                self.next_attempt
+                    .min(deadline)
+                    .saturating_duration_since(now),
+            );
+            self.deliver();
+        }
+        self.backlog.len()
+    }
+
+    fn queue(&mut self, record: Vec<u8>) {
+        if self.backlog.len() >= self.max_backlog {
+            self.backlog.pop_front();
+            self.dropped += 1;
+        }
+        self.backlog.push_back(record);
+    }
+
+    /// Send the backlog, reconnecting first when the socket is down and the
+    /// backoff has passed.
+    fn deliver(&mut self) {
+        if self.stream.is_none() && !self.reconnect() {
+            return;
+        }
+        while let Some(record) = self.backlog.front() {
+            let stream = self.stream.as_mut().expect("connected above");
+            if let Err(e) = stream.write_all(record) {
+                eprintln!("Event socket went away: {}", e);
+                self.stream = None;
+                self.next_attempt = Instant::now() + self.backoff;
+                return;
+            }
+            self.backlog.pop_front();
+        }
+    }
+
+    fn reconnect(&mut self) -> bool {
+        let now = Instant::now();
+        if now < self.next_attempt {
+            return false;
+        }
+        let stream = match &self.endpoint {
+            Endpoint::Connect(path) => UnixStream::connect(path),
+            Endpoint::Listen(listener) => listener.accept().and_then(|(stream, _)| {
+                stream.set_nonblocking(false)?;
+                Ok(stream)
+            }),
+        };
+        match stream {
+            Ok(stream) => {
+                eprintln!(
+                    "Event socket back, replaying {} events ({} dropped)",
+                    self.backlog.len(),
+                    self.dropped
+                );
+                self.stream = Some(stream);
+                self.dropped = 0;
+                self.backoff = INITIAL_BACKOFF;
+                true
+            }
+            Err(_) => {
+                self.next_attempt = now + self.backoff;
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                false
+            }
+        }
+    }
+}
+
+impl Write for SocketOutput {
+    /// Buffer `buf` as part of the current record.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.partial.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Queue the current record and send what the socket will take. Never
+    /// fails: records that can't be sent wait in the backlog.
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.partial.is_empty() {
+            let record = std::mem::take(&mut self.partial);
+            self.queue(record);
+        }
+        self.deliver();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    fn read_lines(stream: UnixStream, n: usize) -> Vec<String> {
+        BufReader::new(stream)
+            .lines()
+            .take(n)
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn test_outage_is_buffered_and_replayed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let mut output = SocketOutput::connect(&path, 2).unwrap();
+        let (peer, _) = listener.accept().unwrap();
+
+        writeln!(output, "one").unwrap();
+        output.flush().unwrap();
+        assert_eq!(read_lines(peer, 1), ["one"]);
+
+        // The reader is gone and nobody is listening
+        drop(listener);
+        fs::remove_file(&path).unwrap();
+        for line in ["two", "three", "four"] {
+            writeln!(output, "{}", line).unwrap();
+            output.flush().unwrap();
+        }
+        assert!(!output.is_connected());
+        assert_eq!(output.backlog(), 2);
+
+        let listener = UnixListener::bind(&path).unwrap();
+        assert_eq!(output.close(Duration::from_secs(5)), 0);
+        let (peer, _) = listener.accept().unwrap();
+        drop(output);
+        assert_eq!(read_lines(peer, 2), ["three", "four"]);
+    }
+}
@@ -0,0 +1,288 @@
+//! Events as one readable line each, for watching a stream by hand.
+//!
+//! `--pretty` writes `[agent-1] TOOL bash: ls -la` instead of NDJSON. Lines
+//! are cut to the terminal width and colored unless `--no-color` is given,
+//! `NO_COLOR` is set or the output isn't a terminal. The events are the same
+//! ones the JSON output carries; only how they are written differs.
+
+use crate::UnifiedEvent;
+use serde_json::Value;
+
+/// Width used when the terminal's is unknown.
+pub const DEFAULT_WIDTH: usize = 120;
+
+/// Argument keys shown for a tool call.
+const SHOWN_ARGS: usize = 3;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const MAGENTA: &str = "\x1b[35m";
+const CYAN: &str = "\x1b[36m";
+
+#[derive(Debug, Clone, Copy)]
+pub struct Pretty {
+    pub color: bool,
+    /// Columns a line may take
+    pub width: usize,
+}
+
+impl Pretty {
+    /// `event` as one line, without the newline.
+    pub fn render(&self, event: &UnifiedEvent) -> String {
+        let agent = format!("[{}]", event.agent_id.as_deref().unwrap_or("?"));
+        let (label, detail) = describe(event);
+        // Room left for the detail after "[agent] LABEL "
+        let used = agent.chars().count() + 1 + label.chars().count() + 1;
+        let detail = truncate(&detail, self.width.saturating_sub(used));
+
+        if !self.color {
+            return format!("{} {} {}", agent, label, detail)
+                .trim_end()
+                .to_string();
+        }
+        let line = format!(
+            "{}{}{} {}{}{} {}",
+            CYAN,
+            agent,
+            RESET,
+            label_color(event),
+            label,
+            RESET,
+            detail
+        );
+        line.trim_end().to_string()
+    }
+}
+
+/// The label and the rest of the line for an event.
+fn describe(event: &UnifiedEvent) -> (String, String) {
+    let tool = event.tool.as_deref().unwrap_or_default();
+    match event.event_type.as_str() {
+        "tool_call" => {
+            let args = event.args.as_ref().map(compact_args).unwrap_or_default();
+            let detail = if args.is_empty() {
+                tool.to_string()
+            } else {
+                format!("{}: {}", tool, args)
+            };
+            ("TOOL".to_string(), detail)
+        }
+        "tool_result" => {
+            let status = event.status.as_ref().map(|s| format!("[{}]", s));
+            let name = joined(tool, status.as_deref().unwrap_or_default());
+            let text = event
+                .error
+                .as_deref()
+                .or(event.result.as_deref())
+                .or(event.content.as_deref())
+                .unwrap_or_default();
+            ("RESULT".to_string(), joined(&name, &one_line(text)))
+        }
+        "thinking" | "reasoning" => {
+            let text = event.content.as_deref().unwrap_or_default();
+            let size = format!("({})", chars(text));
+            (label(&event.event_type), joined(&size, &one_line(text)))
+        }
+        "turn" => {
+            let turn = event.turn.map(|n| n.to_string()).unwrap_or_default();
+            let content = event.content.as_deref().map(one_line).unwrap_or_default();
+            ("TURN".to_string(), joined(&turn, &content))
+        }
+        "file_change" => {
+            let path = event.path.as_deref().unwrap_or_default();
+            let added = event.lines_added.unwrap_or_default();
+            let removed = event.lines_removed.unwrap_or_default();
+            (
+                "FILE".to_string(),
+                format!("{} +{} -{}", path, added, removed),
+            )
+        }
+        "media" => {
+            let media_type = event.media_type.as_deref().unwrap_or_default();
+            let size = event
+                .media_bytes
+                .map(|n| format!("{} bytes", n))
+                .unwrap_or_default();
+            let path = event.path.as_deref().unwrap_or_default();
+            (
+                "MEDIA".to_string(),
+                joined(&joined(media_type, &size), path),
+            )
+        }
+        "error" => {
+            let status = event.status.as_deref().unwrap_or_default();
+            let error = event.error.as_deref().map(one_line).unwrap_or_default();
+            ("ERROR".to_string(), joined(status, &error))
+        }
+        other => {
+            let status = event.status.as_deref().unwrap_or_default();
+            let content = event.content.as_deref().map(one_line).unwrap_or_default();
+            (label(other), joined(status, &content))
+        }
+    }
+}
+
+fn label(event_type: &str) -> String {
+    event_type.to_uppercase()
+}
+
+fn label_color(event: &UnifiedEvent) -> &'static str {
+    let failed = event.error.is_some() || event.status.as_deref() == Some("fail");
+    match event.event_type.as_str() {
+        "error" => RED,
+        "tool_result" if failed => RED,
+        "tool_result" => GREEN,
+        "tool_call" | "file_change" | "media" => YELLOW,
+        "thinking" | "reasoning" => MAGENTA,
+        "turn" | "agent_start" | "agent_exit" | "session_end" => BOLD,
+        _ => DIM,
+    }
+}
+
+/// The first few arguments as `key=value`, or the value alone when there
+/// is only one.
+fn compact_args(args: &Value) -> String {
+    let Some(map) = args.as_object() else {
+        return compact_value(args);
+    };
+    if let [(_, value)] = map.iter().collect::<Vec<_>>()[..] {
+        return compact_value(value);
+    }
+    let mut parts: Vec<String> = map
+        .iter()
+        .take(SHOWN_ARGS)
+        .map(|(key, value)| {
+            let value = compact_value(value);
+            if value.contains(char::is_whitespace) {
+                format!("{}={:?}", key, value)
+            } else {
+                format!("{}={}", key, value)
+            }
+        })
+        .collect();
+    if map.len() > SHOWN_ARGS {
+        parts.push(format!("+{}", map.len() - SHOWN_ARGS));
+    }
+    parts.join(" ")
+}
+
+fn compact_value(value: &Value) -> String {
+    match value {
+        Value::String(text) => one_line(text),
+        other => other.to_string(),
+    }
+}
+
+/// `text` with every run of whitespace, newlines included, as one space.
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// `a b`, leaving out whichever is empty.
+fn joined(a: &str, b: &str) -> String {
+    match (a.is_empty(), b.is_empty()) {
+        (true, _) => b.to_string(),
+        (_, true) => a.to_string(),
+        _ => format!("{} {}", a, b),
+    }
+}
+
+/// Length of `text` in characters, shortened like `1.2k chars`.
+fn chars(text: &str) -> String {
+    let n = text.chars().count();
+    match n {
+        0..1000 => format!("{} chars", n),
+        1000..1_000_000 => format!("{:.1}k chars", n as f64 / 1000.0),
+        _ => format!("{:.1}M chars", n as f64 / 1_000_000.0),
+    }
+}
+
+/// `text` cut to `width` characters, ending in `…` when cut.
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PLAIN: Pretty = Pretty {
+        color: false,
+        width: 60,
+    };
+
+    #[test]
+    fn test_tool_calls_show_first_args() {
+        let event = UnifiedEvent::new("tool_call")
+            .with_agent_id("agent-1")
+            .with_tool("bash", json!({"command": "ls -la"}));
+        assert_eq!(PLAIN.render(&event), "[agent-1] TOOL bash: ls -la");
+
+        let event = UnifiedEvent::new("tool_call")
+            .with_agent_id("agent-1")
+            .with_tool(
+                "Edit",
+                json!({"a": "x y", "b": 2, "c": [1], "d": true, "e": null}),
+            );
+        assert_eq!(
+            PLAIN.render(&event),
+            r#"[agent-1] TOOL Edit: a="x y" b=2 c=[1] +2"#
+        );
+    }
+
+    #[test]
+    fn test_long_content_cut_to_width() {
+        let text = "word ".repeat(300);
+        let event = UnifiedEvent::new("thinking")
+            .with_agent_id("agent-1")
+            .with_content(&text);
+        let line = PLAIN.render(&event);
+        assert!(line.starts_with("[agent-1] THINKING (1.5k chars) word word"));
+        assert_eq!(line.chars().count(), 60);
+        assert!(line.ends_with('…'));
+    }
+
+    #[test]
+    fn test_results_and_errors() {
+        let mut event = UnifiedEvent::new("tool_result")
+            .with_agent_id("a")
+            .with_status("fail");
+        event.tool = Some("test".to_string());
+        event.error = Some("expected 3\nfound 2".to_string());
+        assert_eq!(
+            PLAIN.render(&event),
+            "[a] RESULT test [fail] expected 3 found 2"
+        );
+
+        let mut event = UnifiedEvent::new("tool_result").with_agent_id("a");
+        event.result = Some("ok".to_string());
+        assert_eq!(PLAIN.render(&event), "[a] RESULT ok");
+
+        let event = UnifiedEvent::new("agent_exit")
+            .with_agent_id("a")
+            .with_status("eof");
+        assert_eq!(PLAIN.render(&event), "[a] AGENT_EXIT eof");
+    }
+
+    #[test]
+    fn test_color_only_decorates() {
+        let event = UnifiedEvent::new("turn").with_agent_id("a").with_turn(2);
+        let pretty = Pretty {
+            color: true,
+            width: 60,
+        };
+        let line = pretty.render(&event);
+        assert!(line.contains("\x1b["));
+        assert_eq!(crate::terminal::strip_ansi(&line), "[a] TURN 2");
+    }
+}
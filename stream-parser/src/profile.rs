@@ -0,0 +1,133 @@
+//! Opt-in profile of the argument keys each tool is called with.
+//!
+//! Used by analytics to find tool parameters that are never exercised.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Most distinct keys recorded per tool; further keys set `overflow`.
+pub const MAX_KEYS_PER_TOOL: usize = 64;
+
+/// Most distinct tools profiled; calls to further tools are counted under
+/// [`OTHER_TOOLS`], which has `overflow` set.
+pub const MAX_TOOLS: usize = 64;
+
+/// Profile entry collecting calls to tools past [`MAX_TOOLS`].
+pub const OTHER_TOOLS: &str = "*";
+
+/// Observed argument shapes for one tool.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ToolProfile {
+    pub calls: u64,
+    /// Argument key (`options.timeout` for nested keys) to the JSON types seen
+    pub keys: BTreeMap<String, BTreeSet<&'static str>>,
+    /// Set once a key had to be dropped because of [`MAX_KEYS_PER_TOOL`]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub overflow: bool,
+}
+
+impl ToolProfile {
+    fn record_key(&mut self, key: String, value: &Value) {
+        if !self.keys.contains_key(&key) && self.keys.len() >= MAX_KEYS_PER_TOOL {
+            self.overflow = true;
+            return;
+        }
+        self.keys.entry(key).or_default().insert(type_name(value));
+    }
+}
+
+/// Per-tool argument profiles, keyed by normalized tool name.
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(transparent)]
+pub struct ArgProfile {
+    tools: BTreeMap<String, ToolProfile>,
+}
+
+impl ArgProfile {
+    /// Record one call's arguments. Objects are summarized one level deep.
+    pub fn record(&mut self, tool: &str, args: &Value) {
+        let tool = normalize_tool(tool);
+        if !self.tools.contains_key(&tool) && self.tools.len() >= MAX_TOOLS {
+            let other = self.tools.entry(OTHER_TOOLS.to_string()).or_default();
+            other.calls += 1;
+            other.overflow = true;
+            return;
+        }
+        let profile = self.tools.entry(tool).or_default();
+        profile.calls += 1;
+
+        let Some(args) = args.as_object() else {
+            return;
+        };
+        for (key, value) in args {
+            profile.record_key(key.clone(), value);
+            if let Some(nested) = value.as_object() {
+                for (sub_key, sub_value) in nested {
+                    profile.record_key(format!("{}.{}", key, sub_key), sub_value);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, tool: &str) -> Option<&ToolProfile> {
+        self.tools.get(&normalize_tool(tool))
+    }
+
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+}
+
+/// Tool names differ in case between agents (`Bash` vs `bash`).
+fn normalize_tool(tool: &str) -> String {
+    tool.trim().to_lowercase()
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_caps_keys_per_tool() {
+        let mut profile = ArgProfile::default();
+        let args: serde_json::Map<String, Value> = (0..MAX_KEYS_PER_TOOL + 10)
+            .map(|i| (format!("k{:03}", i), json!(i)))
+            .collect();
+        profile.record("wide", &Value::Object(args));
+        profile.record("wide", &json!({"another": true}));
+
+        let wide = profile.get("wide").unwrap();
+        assert_eq!(wide.keys.len(), MAX_KEYS_PER_TOOL);
+        assert!(wide.overflow);
+        assert!(!wide.keys.contains_key("another"));
+    }
+
+    #[test]
+    fn test_caps_tools() {
+        let mut profile = ArgProfile::default();
+        for i in 0..MAX_TOOLS + 5 {
+            profile.record(&format!("tool{:03}", i), &json!({"arg": i}));
+        }
+        profile.record("tool000", &json!({"arg": 0}));
+
+        assert_eq!(profile.tools.len(), MAX_TOOLS + 1);
+        assert_eq!(profile.get("tool000").unwrap().calls, 2);
+        let other = profile.get(OTHER_TOOLS).unwrap();
+        assert_eq!(other.calls, 5);
+        assert!(other.overflow);
+        assert!(other.keys.is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+//! Pace a captured transcript the way the agent originally wrote it.
+//!
+//! `--replay FILE` feeds a saved log through the parser instead of stdin.
+//! Lines that carry a `timestamp` (RFC 3339 or epoch milliseconds, as the
+//! parser already reads) are spaced by the gaps between them, divided by
+//! `--speed`; lines without one wait a fixed delay.
+
+use crate::source_time;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::time::Duration;
+
+/// Wait between lines without timestamps, when not configured.
+pub const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+pub struct Pacer {
+    /// Multiplier on the original pace; 2.0 replays twice as fast
+    speed: f64,
+    /// Wait before a line that has no timestamp
+    delay: Duration,
+    /// Timestamp of the latest line that had one
+    last: Option<DateTime<Utc>>,
+    started: bool,
+}
+
+impl Pacer {
+    pub fn new(speed: f64, delay: Duration) -> Self {
+        Pacer {
+            speed,
+            delay,
+            last: None,
+            started: false,
+        }
+    }
+
+    /// How long to wait before sending `line`.
+    pub fn delay(&mut self, line: &str) -> Duration {
+        let first = !self.started;
+        self.started = true;
+        let Some(time) = line_time(line) else {
+            return if first { Duration::ZERO } else { self.delay };
+        };
+        let gap = match self.last.replace(time) {
+            Some(last) => (time - last).to_std().unwrap_or_default(),
+            None if first => Duration::ZERO,
+            None => self.delay,
+        };
+        gap.div_f64(self.speed)
+    }
+}
+
+/// When a captured line was written, if it is JSON with a `timestamp`.
+pub fn line_time(line: &str) -> Option<DateTime<Utc>> {
+    let json: Value = serde_json::from_str(line.trim()).ok()?;
+    source_time(json.get("timestamp")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gaps_scaled_by_speed_with_fixed_fallback() {
+        let mut pacer = Pacer::new(2.0, Duration::from_millis(50));
+        let delays: Vec<Duration> = [
+            r#"{"type":"turn","timestamp":1700000000000}"#,
+            r#"{"type":"thinking","timestamp":1700000001000}"#,
+            "plain output",
+            r#"{"type":"tool_call","timestamp":"2023-11-14T22:13:24.000Z"}"#,
+            // Out of order: no waiting backwards
+            r#"{"type":"tool_result","timestamp":1700000000000}"#,
+        ]
+        .iter()
+        .map(|line| pacer.delay(line))
+        .collect();
+
+        assert_eq!(
+            delays,
+            [
+                Duration::ZERO,
+                Duration::from_millis(500),
+                Duration::from_millis(50),
+                Duration::from_millis(1500),
+                Duration::ZERO,
+            ]
+        );
+    }
+}
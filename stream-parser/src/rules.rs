@@ -0,0 +1,248 @@
+//! Turn lines of plain text into events with regex rules.
+//!
+//! A rules file is TOML with one `[[rule]]` table per rule. Each has a
+//! `pattern` and the `type` of event to emit, and may set `tool`, `content`,
+//! `result`, `status`, `error`, `turn` and `args` from templates that refer
+//! to the pattern's capture groups as `${name}`. A `command` template fills
+//! `args` from a shell command line, as for `bash` tool calls.
+//!
+//! ```toml
+//! [[rule]]
+//! pattern = '^FAIL: (?P<test>\S+)'
+//! type = "tool_result"
+//! tool = "test"
+//! status = "fail"
+//! result = "${test}"
+//! ```
+//!
+//! Rules are tried in order and the first that matches wins. The built-in
+//! rules in `default_rules.toml` come after a file's own, unless it sets
+//! `defaults = false`.
+
+use crate::shell;
+use crate::UnifiedEvent;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const DEFAULT_RULES: &str = include_str!("default_rules.toml");
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    /// Try the built-in rules after these
+    #[serde(default = "default_true")]
+    defaults: bool,
+    #[serde(default)]
+    rule: Vec<RuleSpec>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// A rule as written, with templates for each field.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    pattern: String,
+    #[serde(rename = "type")]
+    event_type: String,
+    tool: Option<String>,
+    content: Option<String>,
+    result: Option<String>,
+    status: Option<String>,
+    error: Option<String>,
+    /// Must expand to a number, or the rule doesn't match
+    turn: Option<String>,
+    /// Shell command line to fill `args` from
+    command: Option<String>,
+    #[serde(default)]
+    args: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    regex: Regex,
+    spec: RuleSpec,
+}
+
+/// Ordered text rules; see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl Rules {
+    /// The built-in rules: `[Turn N]` markers, `$ command` lines and
+    /// `[tool] info` markers.
+    pub fn defaults() -> Self {
+        static DEFAULTS: OnceLock<Vec<Rule>> = OnceLock::new();
+        let rules =
+            DEFAULTS.get_or_init(|| compile(DEFAULT_RULES).expect("built-in rules are valid").1);
+        Rules {
+            rules: rules.clone(),
+        }
+    }
+
+    /// Rules from the text of a rules file, followed by the built-in ones
+    /// unless it opts out.
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let (defaults, mut rules) = compile(text)?;
+        if defaults {
+            rules.extend(Rules::defaults().rules);
+        }
+        Ok(Rules { rules })
+    }
+
+    /// Read a rules file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read rules {}: {}", path.display(), e))?;
+        Rules::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// The event the first matching rule makes of `text`, if any.
+    pub fn apply(&self, text: &str) -> Option<UnifiedEvent> {
+        self.rules.iter().find_map(|rule| {
+            let caps = rule.regex.captures(text)?;
+            rule.event(&caps)
+        })
+    }
+}
+
+/// Parse and compile a rules file, returning whether it wants the defaults.
+fn compile(text: &str) -> Result<(bool, Vec<Rule>), String> {
+    let file: RulesFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    let rules = file
+        .rule
+        .into_iter()
+        .enumerate()
+        .map(|(i, spec)| {
+            let regex = Regex::new(&spec.pattern)
+                .map_err(|e| format!("rule {} has an invalid pattern: {}", i + 1, e))?;
+            Ok(Rule { regex, spec })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok((file.defaults, rules))
+}
+
+impl Rule {
+    fn event(&self, caps: &Captures) -> Option<UnifiedEvent> {
+        let expand = |template: &str| {
+            let mut out = String::new();
+            caps.expand(template, &mut out);
+            out
+        };
+        let spec = &self.spec;
+        let mut event = UnifiedEvent::new(&spec.event_type);
+        if let Some(turn) = &spec.turn {
+            event.turn = Some(expand(turn).parse().ok()?);
+        }
+
+        let mut args = match &spec.command {
+            Some(command) => shell::bash_args(&expand(command)),
+            None => Value::Object(Default::default()),
+        };
+        for (key, template) in &spec.args {
+            args[key] = Value::String(expand(template));
+        }
+        if spec.command.is_some() || !spec.args.is_empty() {
+            event.args = Some(args);
+        }
+
+        event.tool = spec.tool.as_deref().map(expand);
+        event.content = spec.content.as_deref().map(expand);
+        event.result = spec.result.as_deref().map(expand);
+        event.status = spec.status.as_deref().map(expand);
+        event.error = spec.error.as_deref().map(expand);
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const TEST_RUNNER: &str = r#"
+[[rule]]
+pattern = '^PASS: (?P<test>\S+)(?: \((?P<ms>\d+) ms\))?'
+type = "tool_result"
+tool = "test"
+status = "pass"
+result = "${test}"
+args = { test = "${test}", ms = "${ms}" }
+
+[[rule]]
+pattern = '^FAIL: (?P<test>\S+?)(?:: (?P<reason>.*))?$'
+type = "tool_result"
+tool = "test"
+status = "fail"
+result = "${test}"
+error = "${reason}"
+"#;
+
+    #[test]
+    fn test_defaults_match_built_in_markers() {
+        let rules = Rules::defaults();
+
+        let turn = rules.apply("[Turn 3] planning").unwrap();
+        assert_eq!((turn.event_type.as_str(), turn.turn), ("turn", Some(3)));
+
+        let bash = rules.apply("$ git push --force").unwrap();
+        assert_eq!(bash.tool.as_deref(), Some("bash"));
+        assert_eq!(bash.args.as_ref().unwrap()["dangerous"], true);
+
+        // Not a turn number, so a tool marker
+        let tool = rules.apply("[Turn x] src/app.py").unwrap();
+        assert_eq!(tool.tool.as_deref(), Some("Turn x"));
+        assert_eq!(tool.args, Some(json!({"info": "src/app.py"})));
+
+        assert!(rules.apply("Looking at the tests").is_none());
+    }
+
+    #[test]
+    fn test_test_runner_rules_before_defaults() {
+        let rules = Rules::from_toml(TEST_RUNNER).unwrap();
+
+        let pass = rules.apply("PASS: parser::turns (12 ms)").unwrap();
+        assert_eq!(pass.event_type, "tool_result");
+        assert_eq!(pass.status.as_deref(), Some("pass"));
+        assert_eq!(pass.result.as_deref(), Some("parser::turns"));
+        assert_eq!(
+            pass.args,
+            Some(json!({"test": "parser::turns", "ms": "12"}))
+        );
+
+        let fail = rules
+            .apply("FAIL: parser::diffs: expected 3 hunks")
+            .unwrap();
+        assert_eq!(fail.status.as_deref(), Some("fail"));
+        assert_eq!(fail.error.as_deref(), Some("expected 3 hunks"));
+
+        assert_eq!(rules.apply("[Turn 2]").unwrap().turn, Some(2));
+
+        let only = Rules::from_toml(&format!("defaults = false\n{}", TEST_RUNNER)).unwrap();
+        assert!(only.apply("[Turn 2]").is_none());
+    }
+
+    #[test]
+    fn test_bad_rules_rejected() {
+        let error =
+            Rules::from_toml("[[rule]]\npattern = '(unclosed'\ntype = \"output\"\n").unwrap_err();
+        assert!(
+            error.starts_with("rule 1 has an invalid pattern"),
+            "{}",
+            error
+        );
+
+        let error =
+            Rules::from_toml("[[rule]]\npattern = 'x'\ntype = \"output\"\ncolour = \"red\"\n")
+                .unwrap_err();
+        assert!(error.contains("colour"), "{}", error);
+    }
+}
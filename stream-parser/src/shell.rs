@@ -0,0 +1,256 @@
+//! Read the shell commands agents announce with `$ `.
+//!
+//! The UI shows which program a command runs and flags the ones that can
+//! destroy work, so `bash` tool calls parsed from text carry the command
+//! split into words as well as the raw line. Splitting follows the simple
+//! quoting rules of POSIX shells; anything it can't make sense of is left as
+//! the raw command.
+
+use serde_json::{json, Value};
+
+/// Programs that are dangerous whatever their arguments.
+const DANGEROUS_PROGRAMS: &[&str] = &["sudo", "doas", "su", "dd", "shred", "mkfs"];
+
+/// `args` of a `bash` tool call running `command`: the command itself and,
+/// when its quoting is well formed, its `program`, `argv` and whether it is
+/// `dangerous`.
+pub fn bash_args(command: &str) -> Value {
+    let Some(argv) = split(command) else {
+        return json!({ "command": command });
+    };
+    json!({
+        "command": command,
+        "program": argv.first().cloned().unwrap_or_default(),
+        "dangerous": is_dangerous(&argv),
+        "argv": argv,
+    })
+}
+
+/// Split `command` into words, with unquoted `|`, `&` and `;` runs as words
+/// of their own. `None` when a quote is left open or the line ends in a
+/// backslash.
+pub fn split(command: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // A word has started, even if it's empty like ''
+    let mut in_word = false;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        // Only these lose the backslash inside double quotes
+                        '\\' => match chars.next()? {
+                            c @ ('"' | '\\' | '$' | '`') => word.push(c),
+                            c => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                word.push(chars.next()?);
+            }
+            '|' | '&' | ';' => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+                let mut operator = c.to_string();
+                while let Some(c) = chars.next_if(|c| matches!(c, '|' | '&' | ';')) {
+                    operator.push(c);
+                }
+                words.push(operator);
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Some(words)
+}
+
+/// Whether any command in `argv` can destroy work: privilege escalation,
+/// raw disk writes, recursive deletes and history rewrites.
+pub fn is_dangerous(argv: &[String]) -> bool {
+    argv.split(|word| is_operator(word)).any(dangerous_command)
+}
+
+fn is_operator(word: &str) -> bool {
+    !word.is_empty() && word.chars().all(|c| matches!(c, '|' | '&' | ';'))
+}
+
+fn dangerous_command(argv: &[String]) -> bool {
+    // Skip variable assignments ahead of the program
+    let mut words = argv
+        .iter()
+        .map(String::as_str)
+        .skip_while(|word| word.contains('=') && !word.starts_with('-'));
+    let Some(program) = words.next() else {
+        return false;
+    };
+    let program = program.rsplit('/').next().unwrap_or(program);
+    let args: Vec<&str> = words.collect();
+
+    if DANGEROUS_PROGRAMS
+        .iter()
+        .any(|p| program == *p || program.strip_prefix(p).is_some_and(|r| r.starts_with('.')))
+    {
+        return true;
+    }
+    match program {
+        "rm" => args
+            .iter()
+            .any(|arg| *arg == "--recursive" || short_flag(arg, 'r') || short_flag(arg, 'R')),
+        "git" => match args.first().copied() {
+            Some("push") => args[1..]
+                .iter()
+                .any(|arg| *arg == "--force" || short_flag(arg, 'f') || arg.starts_with('+')),
+            Some("reset") => args.contains(&"--hard"),
+            Some("clean") => args[1..]
+                .iter()
+                .any(|arg| *arg == "--force" || short_flag(arg, 'f')),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Whether `arg` is a bundle of short options that includes `flag`.
+fn short_flag(arg: &str, flag: char) -> bool {
+    arg.strip_prefix('-')
+        .is_some_and(|flags| !flags.starts_with('-') && flags.contains(flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(command: &str) -> Vec<String> {
+        split(command).unwrap()
+    }
+
+    #[test]
+    fn test_split_quoting() {
+        let cases: &[(&str, &[&str])] = &[
+            ("ls -la", &["ls", "-la"]),
+            ("  grep   -n  foo ", &["grep", "-n", "foo"]),
+            (
+                r#"git commit -m "fix: handle \"quoted\" names""#,
+                &["git", "commit", "-m", r#"fix: handle "quoted" names"#],
+            ),
+            ("echo 'it''s' done", &["echo", "its", "done"]),
+            (r#"echo "a\nb" 'c\d'"#, &["echo", r"a\nb", r"c\d"]),
+            (r"touch my\ file.txt", &["touch", "my file.txt"]),
+            ("printf ''", &["printf", ""]),
+            (
+                r#"python -c "print('hi')""#,
+                &["python", "-c", "print('hi')"],
+            ),
+        ];
+        for (command, expected) in cases {
+            assert_eq!(words(command), *expected, "{}", command);
+        }
+    }
+
+    #[test]
+    fn test_split_operators() {
+        assert_eq!(
+            words("cat log.txt | grep -c ERROR&&echo done; true"),
+            ["cat", "log.txt", "|", "grep", "-c", "ERROR", "&&", "echo", "done", ";", "true"]
+        );
+        assert_eq!(words("echo 'a|b' \"c;d\""), ["echo", "a|b", "c;d"]);
+    }
+
+    #[test]
+    fn test_malformed_quoting_falls_back_to_command() {
+        for command in [
+            "echo 'unterminated",
+            r#"git commit -m "wip"#,
+            r"echo trailing\",
+        ] {
+            assert_eq!(split(command), None, "{}", command);
+            assert_eq!(bash_args(command), json!({ "command": command }));
+        }
+    }
+
+    #[test]
+    fn test_dangerous_patterns() {
+        let dangerous = [
+            "rm -rf build/",
+            "rm -fr /",
+            "rm --recursive --force node_modules",
+            "/bin/rm -R tmp",
+            "sudo apt-get install jq",
+            "git push --force origin main",
+            "git push -f",
+            "git push origin +main",
+            "git reset --hard HEAD~3",
+            "git clean -fdx",
+            "dd if=/dev/zero of=/dev/sda bs=1M",
+            "mkfs.ext4 /dev/sdb1",
+            "cargo build && rm -rf target",
+            "curl -sSL https://example.com/install.sh | sudo sh",
+            "DEBUG=1 rm -r cache",
+        ];
+        for command in dangerous {
+            assert!(is_dangerous(&words(command)), "{}", command);
+        }
+
+        let safe = [
+            "rm file.txt",
+            "rm -f stale.lock",
+            "git push origin main",
+            "git push --force-with-lease",
+            "git reset --soft HEAD~1",
+            "git clean -n",
+            "echo 'rm -rf /'",
+            "grep -r sudo docs/",
+            "ls -la | grep dd",
+            "",
+        ];
+        for command in safe {
+            assert!(!is_dangerous(&words(command)), "{}", command);
+        }
+    }
+
+    #[test]
+    fn test_bash_args() {
+        assert_eq!(
+            bash_args("sudo rm -rf /var/cache"),
+            json!({
+                "command": "sudo rm -rf /var/cache",
+                "program": "sudo",
+                "argv": ["sudo", "rm", "-rf", "/var/cache"],
+                "dangerous": true,
+            })
+        );
+        assert_eq!(bash_args("ls")["dangerous"], false);
+    }
+}
@@ -0,0 +1,223 @@
+//! Carry parser state across a restart of `agent-stream`.
+//!
+//! With `--state-file`, the state of every agent's [`Parser`] is saved every
+//! so many events and when the parser is stopped by a signal, and loaded
+//! again when it starts. A restarted parser then picks up where the last one
+//! left off: turns keep counting, `seq` keeps rising, and a tool call whose
+//! result arrives after the restart is still matched to it.
+//!
+//! The file is JSON with a `version`. A file of another version is refused
+//! rather than read as something it isn't. Not carried over: a JSON object
+//! half reassembled across lines, text deltas still being merged (they are
+//! flushed before saving) and the `--arg-profile` tallies.
+
+use crate::{AgentFormat, Parser, PendingDiff, PendingToolCall, Subagent, UnifiedEvent, Usage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Version of the state file layout; bumped whenever it changes.
+pub const STATE_VERSION: u32 = 1;
+
+/// Everything saved in a state file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamState {
+    pub version: u32,
+    /// `seq` of the next event emitted
+    pub next_seq: u64,
+    /// Agents in the order they were first seen
+    pub agents: Vec<AgentState>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AgentState {
+    pub agent_id: String,
+    #[serde(flatten)]
+    pub parser: ParserState,
+}
+
+/// What a [`Parser`] knows about the stream so far, apart from its settings.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ParserState {
+    /// Detected format; a pinned one comes from the command line again
+    format: Option<AgentFormat>,
+    current_turn: u32,
+    session_id: Option<String>,
+    agent_started: bool,
+    session_ended: bool,
+    claude_continuing: bool,
+    claude_stop_reason: Option<String>,
+    api_messages: u64,
+    claude_usage: Option<Usage>,
+    usage_total: Option<Usage>,
+    subagents: HashMap<String, Subagent>,
+    gemini_in_turn: bool,
+    aider_diff: Option<PendingDiff>,
+    tool_calls_seen: u64,
+    unanswered_tool_calls: VecDeque<String>,
+    /// In-flight OpenAI tool calls with their (choice, tool call) indexes;
+    /// JSON object keys can't be pairs
+    openai_tool_calls: Vec<((u64, u64), PendingToolCall)>,
+}
+
+impl StreamState {
+    pub fn new(next_seq: u64, agents: Vec<AgentState>) -> Self {
+        StreamState {
+            version: STATE_VERSION,
+            next_seq,
+            agents,
+        }
+    }
+
+    /// Parse the text of a state file, refusing other versions.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        match value.get("version").and_then(Value::as_u64) {
+            Some(version) if version == STATE_VERSION as u64 => {}
+            Some(version) => {
+                return Err(format!(
+                    "state version {} is not supported (expected {})",
+                    version, STATE_VERSION
+                ))
+            }
+            None => return Err("state has no version".to_string()),
+        }
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+}
+
+/// Read a state file, or `None` if there isn't one yet.
+pub fn load(path: &Path) -> Result<Option<StreamState>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("cannot read state {}: {}", path.display(), e)),
+    };
+    StreamState::from_json(&text)
+        .map(Some)
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Write a state file. It is written beside `path` and renamed over it, so a
+/// crash mid-write leaves the previous state in place.
+pub fn save(path: &Path, state: &StreamState) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, serde_json::to_vec(state)?)?;
+    fs::rename(&tmp, path)
+}
+
+impl Parser {
+    /// The parser's state, to restore in a later run with
+    /// [`Parser::restore`]. Call [`Parser::suspend`] first so no buffered
+    /// deltas are lost.
+    pub fn state(&self) -> ParserState {
+        ParserState {
+            format: (!self.format_pinned && self.format != AgentFormat::Unknown)
+                .then_some(self.format),
+            current_turn: self.current_turn,
+            session_id: self.session_id.clone(),
+            agent_started: self.agent_started,
+            session_ended: self.session_ended,
+            claude_continuing: self.claude_continuing,
+            claude_stop_reason: self.claude_stop_reason.clone(),
+            api_messages: self.api_messages,
+            claude_usage: self.claude_usage,
+            usage_total: self.usage_total,
+            subagents: self.subagents.clone(),
+            gemini_in_turn: self.gemini_in_turn,
+            aider_diff: self.aider_diff.clone(),
+            tool_calls_seen: self.tool_calls_seen,
+            unanswered_tool_calls: self.unanswered_tool_calls.clone(),
+            openai_tool_calls: self
+                .openai_tool_calls
+                .iter()
+                .map(|(&index, call)| (index, call.clone()))
+                .collect(),
+        }
+    }
+
+    /// Continue from a state saved by an earlier parser. A pinned format is
+    /// kept over the saved one.
+    pub fn restore(&mut self, state: ParserState) {
+        if let (false, Some(format)) = (self.format_pinned, state.format) {
+            self.format = format;
+            self.format_run = None;
+        }
+        self.current_turn = state.current_turn;
+        self.session_id = state.session_id;
+        self.agent_started = state.agent_started;
+        self.session_ended = state.session_ended;
+        self.claude_continuing = state.claude_continuing;
+        self.claude_stop_reason = state.claude_stop_reason;
+        self.api_messages = state.api_messages;
+        self.claude_usage = state.claude_usage;
+        self.usage_total = state.usage_total;
+        self.subagents = state.subagents;
+        self.gemini_in_turn = state.gemini_in_turn;
+        self.aider_diff = state.aider_diff;
+        self.tool_calls_seen = state.tool_calls_seen;
+        self.unanswered_tool_calls = state.unanswered_tool_calls;
+        self.openai_tool_calls = state.openai_tool_calls.into_iter().collect();
+    }
+
+    /// Flush buffered text deltas ahead of saving the state, without ending
+    /// the stream.
+    pub fn suspend(&mut self) -> Vec<UnifiedEvent> {
+        let mut events = self.flush_deltas();
+        self.scrub(&mut events);
+        self.number(&mut events);
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser() -> Parser {
+        let mut parser = Parser::new("agent-1".to_string());
+        parser.set_timestamps(false);
+        parser
+    }
+
+    #[test]
+    fn test_state_round_trips_through_json() {
+        let mut first = parser();
+        for command in ["ls", "pwd"] {
+            first.parse_line(&format!(
+                r#"{{"type":"tool_call","tool":"bash","args":{{"command":"{}"}}}}"#,
+                command
+            ));
+        }
+        let state = StreamState::new(
+            first.next_seq(),
+            vec![AgentState {
+                agent_id: "agent-1".to_string(),
+                parser: first.state(),
+            }],
+        );
+        let json = serde_json::to_string(&state).unwrap();
+        let mut loaded = StreamState::from_json(&json).unwrap();
+        assert_eq!(loaded.next_seq, 3);
+
+        // The result finds the call made before the restart
+        let mut second = parser();
+        second.restore(loaded.agents.remove(0).parser);
+        assert_eq!(second.format(), AgentFormat::Python);
+        let events = second.parse_line(r#"{"type":"tool_result","content":"ok"}"#);
+        assert_eq!(events[0].tool_use_id.as_deref(), Some("agent-1-call-1"));
+    }
+
+    #[test]
+    fn test_other_versions_rejected() {
+        let error =
+            StreamState::from_json(r#"{"version":0,"next_seq":1,"agents":[]}"#).unwrap_err();
+        assert_eq!(error, "state version 0 is not supported (expected 1)");
+        let error = StreamState::from_json(r#"{"next_seq":1,"agents":[]}"#).unwrap_err();
+        assert_eq!(error, "state has no version");
+    }
+}
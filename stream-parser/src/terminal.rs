@@ -0,0 +1,208 @@
+//! Make sense of output written for a terminal.
+//!
+//! Python agents built on rich or tqdm color their logs with ANSI escape
+//! sequences and redraw progress bars in place with `\r`. Neither means
+//! anything once the output is parsed into events. [`strip_ansi`] removes the
+//! escapes, [`final_state`] keeps what a redrawn line ends up showing, and
+//! [`Segments`] splits a stream at each redraw so a live progress bar can be
+//! followed before its line ends.
+
+use std::borrow::Cow;
+use std::io::{self, BufRead};
+use std::time::{Duration, Instant};
+
+/// Least time between events for one live progress bar.
+pub const REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+
+const ESC: char = '\u{1b}';
+const BEL: char = '\u{7}';
+
+/// `text` without ANSI escape sequences: CSI (colors, cursor movement,
+/// erasing), OSC (window titles, hyperlinks) and the short escapes.
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains(ESC) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != ESC {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // Parameters and intermediates up to a final byte
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // Ended by BEL or by ST, which is ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == BEL {
+                        break;
+                    }
+                    if c == ESC {
+                        chars.next_if_eq(&'\\');
+                        break;
+                    }
+                }
+            }
+            // Character set designation names the set in one more character
+            Some('(' | ')') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// What a line redrawn with `\r` ends up showing: the text after the last
+/// redraw. Redraws are taken to cover what they replace, as progress bars
+/// pad or erase the line first.
+pub fn final_state(line: &str) -> &str {
+    line.trim_end_matches('\r')
+        .rsplit('\r')
+        .next()
+        .unwrap_or_default()
+}
+
+/// Lines of a reader, split at `\r` redraws as well as at newlines.
+///
+/// A line that was redrawn is returned with its trailing `\r`, so it can be
+/// told apart from a finished line; `\r\n` ends a line like `\n` does.
+pub struct Segments<R> {
+    reader: R,
+}
+
+impl<R: BufRead> Segments<R> {
+    pub fn new(reader: R) -> Self {
+        Segments { reader }
+    }
+}
+
+impl<R: BufRead> Iterator for Segments<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = Vec::new();
+        loop {
+            let available = match self.reader.fill_buf() {
+                Ok(available) => available,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e)),
+            };
+            if available.is_empty() {
+                return (!line.is_empty()).then(|| decode(line));
+            }
+            let Some(end) = available.iter().position(|&b| b == b'\n' || b == b'\r') else {
+                let len = available.len();
+                line.extend_from_slice(available);
+                self.reader.consume(len);
+                continue;
+            };
+            let redrawn = available[end] == b'\r';
+            line.extend_from_slice(&available[..end]);
+            self.reader.consume(end + 1);
+            if redrawn {
+                match self.reader.fill_buf() {
+                    Ok(next) if next.first() == Some(&b'\n') => self.reader.consume(1),
+                    Ok(_) => line.push(b'\r'),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            return Some(decode(line));
+        }
+    }
+}
+
+fn decode(line: Vec<u8>) -> io::Result<String> {
+    String::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Picks which redraws of a live line from [`Segments`] to parse: at most
+/// one per interval, and none for a line finished within the interval.
+pub struct RedrawThrottle {
+    every: Duration,
+    /// When the line being redrawn started, or last got through
+    since: Option<Instant>,
+}
+
+impl RedrawThrottle {
+    pub fn new(every: Duration) -> Self {
+        RedrawThrottle { every, since: None }
+    }
+
+    /// The text of `line` to parse, if any. Finished lines always get
+    /// through; a redraw only once `every` has passed since the line
+    /// started or its last redraw got through.
+    pub fn admit<'a>(&mut self, line: &'a str, now: Instant) -> Option<&'a str> {
+        let Some(redraw) = line.strip_suffix('\r') else {
+            self.since = None;
+            return Some(line);
+        };
+        let since = *self.since.get_or_insert(now);
+        if now.saturating_duration_since(since) < self.every {
+            return None;
+        }
+        self.since = Some(now);
+        Some(redraw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi_from_colored_logs() {
+        assert_eq!(
+            strip_ansi("\x1b[2m12:00:01\x1b[0m \x1b[1;32mINFO    \x1b[0m Loaded 3 tools"),
+            "12:00:01 INFO     Loaded 3 tools"
+        );
+        assert_eq!(
+            strip_ansi("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\ \x1b]0;title\x07done"),
+            "link done"
+        );
+        assert_eq!(strip_ansi("\x1b(Bplain\x1b[K"), "plain");
+        assert!(matches!(strip_ansi("no escapes"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_final_state_of_redrawn_line() {
+        assert_eq!(
+            final_state("  0%|          | 0/3\r 67%|######6   | 2/3\r100%|##########| 3/3"),
+            "100%|##########| 3/3"
+        );
+        assert_eq!(final_state("done\r"), "done");
+        assert_eq!(final_state("plain"), "plain");
+    }
+
+    #[test]
+    fn test_segments_split_at_redraws() {
+        let input = "\r  0%\r 50%\r100%\nnext\r\nlast".as_bytes();
+        let segments: Vec<String> = Segments::new(input).map(Result::unwrap).collect();
+        assert_eq!(segments, ["\r", "  0%\r", " 50%\r", "100%", "next", "last"]);
+    }
+
+    #[test]
+    fn test_throttle_admits_one_redraw_per_interval() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut throttle = RedrawThrottle::new(Duration::from_secs(1));
+
+        // A bar that finishes quickly shows only its final state
+        assert_eq!(throttle.admit(" 10%\r", ms(0)), None);
+        assert_eq!(throttle.admit(" 90%\r", ms(500)), None);
+        assert_eq!(throttle.admit("100%", ms(600)), Some("100%"));
+
+        assert_eq!(throttle.admit("  0%\r", ms(1000)), None);
+        assert_eq!(throttle.admit(" 30%\r", ms(2000)), Some(" 30%"));
+        assert_eq!(throttle.admit(" 40%\r", ms(2500)), None);
+        assert_eq!(throttle.admit(" 70%\r", ms(3000)), Some(" 70%"));
+    }
+}
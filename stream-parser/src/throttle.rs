@@ -0,0 +1,298 @@
+//! Hold events back so a slow consumer isn't flooded.
+//!
+//! An agent that reads a huge file can turn into megabytes of events in a
+//! moment. With `--max-events-per-sec` or `--max-bytes-per-sec`, events wait
+//! in a bounded queue and are released at the allowed rate, with bursts of up
+//! to one second's worth. When the queue is full, runs of `thinking` and
+//! `output` events are joined into one; an event is only dropped when
+//! nothing can be joined, and the count of dropped events goes on the
+//! stream's `agent_exit`.
+
+use crate::UnifiedEvent;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Events held back before the queue counts as full.
+pub const DEFAULT_QUEUE_EVENTS: usize = 1024;
+
+/// Event types joined when the queue is full.
+const JOINABLE_TYPES: &[&str] = &["thinking", "output"];
+
+/// Event types never dropped, even when the queue is full.
+const KEPT_TYPES: &[&str] = &["agent_start", "agent_exit", "session_end", "error"];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThrottleLimits {
+    pub events_per_sec: Option<u64>,
+    pub bytes_per_sec: Option<u64>,
+}
+
+/// A token bucket refilled at `per_sec`, holding at most one second's worth.
+struct Bucket {
+    per_sec: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn new(per_sec: u64, now: Instant) -> Self {
+        Bucket {
+            per_sec: per_sec as f64,
+            tokens: per_sec as f64,
+            refilled: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.per_sec);
+        self.refilled = now;
+    }
+
+    /// Tokens `cost` needs before it can go; more than a full bucket only
+    /// needs a full bucket, and leaves it in debt.
+    fn needed(&self, cost: f64) -> f64 {
+        cost.min(self.per_sec)
+    }
+
+    /// Time until `cost` can go, from the last refill.
+    fn wait(&self, cost: f64) -> Duration {
+        let missing = self.needed(cost) - self.tokens;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.per_sec)
+        }
+    }
+}
+
+pub struct Throttle {
+    events: Option<Bucket>,
+    bytes: Option<Bucket>,
+    queue: VecDeque<UnifiedEvent>,
+    capacity: usize,
+    /// Size of the event at the front of the queue, once it has been sized
+    front_bytes: Option<usize>,
+    dropped: u64,
+}
+
+impl Throttle {
+    pub fn new(limits: ThrottleLimits, capacity: usize, now: Instant) -> Self {
+        Throttle {
+            events: limits.events_per_sec.map(|rate| Bucket::new(rate, now)),
+            bytes: limits.bytes_per_sec.map(|rate| Bucket::new(rate, now)),
+            queue: VecDeque::new(),
+            capacity,
+            front_bytes: None,
+            dropped: 0,
+        }
+    }
+
+    /// Events dropped so far because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Queue `event`, making room by joining `thinking` and `output` events
+    /// or, failing that, by dropping one.
+    pub fn push(&mut self, event: UnifiedEvent) {
+        if self.queue.len() < self.capacity {
+            self.queue.push_back(event);
+            return;
+        }
+        // The front may already be sized for release, so it's never joined
+        let joinable_back = self.queue.len() > 1 && joinable(self.queue.back().unwrap(), &event);
+        if joinable_back {
+            join(self.queue.back_mut().unwrap(), event);
+            return;
+        }
+        let pair = (1..self.queue.len().saturating_sub(1))
+            .find(|&i| joinable(&self.queue[i], &self.queue[i + 1]));
+        if let Some(i) = pair {
+            let next = self.queue.remove(i + 1).unwrap();
+            join(&mut self.queue[i], next);
+            self.queue.push_back(event);
+            return;
+        }
+        if !kept(&event) {
+            self.dropped += 1;
+            return;
+        }
+        // Make way for an event that must get through
+        if let Some(i) = (1..self.queue.len()).rev().find(|&i| !kept(&self.queue[i])) {
+            self.queue.remove(i);
+            self.dropped += 1;
+        }
+        self.queue.push_back(event);
+    }
+
+    /// Take the events that may go out now, oldest first. `size` is the
+    /// number of bytes an event takes on the wire.
+    pub fn release(
+        &mut self,
+        now: Instant,
+        mut size: impl FnMut(&UnifiedEvent) -> usize,
+    ) -> Vec<UnifiedEvent> {
+        for bucket in self.events.iter_mut().chain(self.bytes.iter_mut()) {
+            bucket.refill(now);
+        }
+        let mut released = Vec::new();
+        while let Some(front) = self.queue.front() {
+            let bytes = *self.front_bytes.get_or_insert_with(|| size(front)) as f64;
+            let ready = self.events.as_ref().is_none_or(|b| b.wait(1.0).is_zero())
+                && self.bytes.as_ref().is_none_or(|b| b.wait(bytes).is_zero());
+            if !ready {
+                break;
+            }
+            if let Some(bucket) = self.events.as_mut() {
+                bucket.tokens -= 1.0;
+            }
+            if let Some(bucket) = self.bytes.as_mut() {
+                bucket.tokens -= bytes;
+            }
+            self.front_bytes = None;
+            let mut event = self.queue.pop_front().unwrap();
+            if event.event_type == "agent_exit" {
+                event.dropped_events = Some(self.dropped);
+            }
+            released.push(event);
+        }
+        released
+    }
+
+    /// Time until the event at the front of the queue may go, or `None` when
+    /// the queue is empty. Only accurate after [`Throttle::release`] has
+    /// sized the front event.
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        let bytes = self.front_bytes.unwrap_or(0) as f64;
+        let wait = |bucket: &Bucket, cost: f64| {
+            bucket
+                .wait(cost)
+                .saturating_sub(now.saturating_duration_since(bucket.refilled))
+        };
+        let events = self.events.as_ref().map(|b| wait(b, 1.0));
+        let bytes = self.bytes.as_ref().map(|b| wait(b, bytes));
+        Some(events.into_iter().chain(bytes).max().unwrap_or_default())
+    }
+}
+
+/// Whether `next` can be folded into `event`.
+fn joinable(event: &UnifiedEvent, next: &UnifiedEvent) -> bool {
+    JOINABLE_TYPES.contains(&event.event_type.as_str())
+        && event.event_type == next.event_type
+        && event.agent_id == next.agent_id
+        && event.parent_id == next.parent_id
+        && event.content.is_some()
+        && next.content.is_some()
+        && event.truncated.is_none()
+        && next.truncated.is_none()
+}
+
+/// Fold `next` into `event`, one line after the other.
+fn join(event: &mut UnifiedEvent, next: UnifiedEvent) {
+    let content = event.content.get_or_insert_with(String::new);
+    content.push('\n');
+    content.push_str(next.content.as_deref().unwrap_or_default());
+}
+
+fn kept(event: &UnifiedEvent) -> bool {
+    KEPT_TYPES.contains(&event.event_type.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(text: &str) -> UnifiedEvent {
+        UnifiedEvent::new("output").with_content(text)
+    }
+
+    fn types(events: &[UnifiedEvent]) -> Vec<&str> {
+        events.iter().map(|e| e.event_type.as_str()).collect()
+    }
+
+    #[test]
+    fn test_events_per_sec_after_a_burst() {
+        let start = Instant::now();
+        let limits = ThrottleLimits {
+            events_per_sec: Some(10),
+            bytes_per_sec: None,
+        };
+        let mut throttle = Throttle::new(limits, 100, start);
+        for i in 0..25 {
+            throttle.push(output(&i.to_string()));
+        }
+
+        assert_eq!(throttle.release(start, |_| 10).len(), 10);
+        assert_eq!(throttle.remaining(start), Some(Duration::from_millis(100)));
+        let at = |ms| start + Duration::from_millis(ms);
+        assert_eq!(throttle.release(at(100), |_| 10).len(), 1);
+        assert_eq!(throttle.release(at(500), |_| 10).len(), 4);
+        assert_eq!(throttle.release(at(5000), |_| 10).len(), 10);
+        assert_eq!(throttle.remaining(at(5000)), None);
+    }
+
+    #[test]
+    fn test_bytes_per_sec_lets_oversized_event_through() {
+        let start = Instant::now();
+        let limits = ThrottleLimits {
+            events_per_sec: None,
+            bytes_per_sec: Some(1000),
+        };
+        let mut throttle = Throttle::new(limits, 100, start);
+        throttle.push(output("big"));
+        throttle.push(output("small"));
+        let size = |e: &UnifiedEvent| {
+            if e.content.as_deref() == Some("big") {
+                5000
+            } else {
+                100
+            }
+        };
+
+        // A full bucket sends it, leaving 4 seconds of debt
+        assert_eq!(throttle.release(start, size).len(), 1);
+        assert_eq!(throttle.release(start, size).len(), 0);
+        let wait = throttle.remaining(start).unwrap();
+        assert!(wait > Duration::from_millis(4000), "{:?}", wait);
+        let later = start + wait + Duration::from_millis(1);
+        assert_eq!(throttle.release(later, size).len(), 1);
+    }
+
+    #[test]
+    fn test_full_queue_joins_text_before_dropping() {
+        let start = Instant::now();
+        let limits = ThrottleLimits {
+            events_per_sec: Some(1),
+            bytes_per_sec: None,
+        };
+        let mut throttle = Throttle::new(limits, 3, start);
+        throttle.push(UnifiedEvent::new("turn"));
+        throttle.push(output("a"));
+        throttle.push(output("b"));
+        throttle.push(output("c"));
+        throttle.push(UnifiedEvent::new("tool_call"));
+        assert_eq!(throttle.dropped(), 0);
+
+        // Nothing left to join
+        throttle.push(UnifiedEvent::new("tool_result"));
+        assert_eq!(throttle.dropped(), 1);
+        throttle.push(UnifiedEvent::new("agent_exit"));
+        assert_eq!(throttle.dropped(), 2);
+
+        let mut events = Vec::new();
+        for s in 0..5 {
+            events.extend(throttle.release(start + Duration::from_secs(s), |_| 0));
+        }
+        assert_eq!(types(&events), ["turn", "output", "agent_exit"]);
+        assert_eq!(events[1].content.as_deref(), Some("a\nb\nc"));
+        assert_eq!(events[2].dropped_events, Some(2));
+    }
+}
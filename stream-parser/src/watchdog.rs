@@ -0,0 +1,193 @@
+//! Give up on an agent that never produces a real event.
+//!
+//! A misconfigured agent can stream log noise for an hour while the
+//! orchestrator waits for a first turn. The watchdog tracks what the parser
+//! has seen and, once the window passes without a qualifying event, builds a
+//! terminal `error` event describing the stream so the supervisor can kill
+//! and retry the agent.
+
+use crate::{AgentFormat, UnifiedEvent};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Event types that don't count as a first event unless asked for: plain
+/// text, unrecognized JSON and stderr are exactly what log noise turns into,
+/// and lifecycle events come with any stream.
+const NOISE_TYPES: &[&str] = &["output", "raw", "stderr", "agent_start", "agent_exit"];
+
+/// Raw lines kept for the error event.
+const SAMPLE_LINES: usize = 5;
+
+/// Characters kept of each sampled line.
+const SAMPLE_CHARS: usize = 200;
+
+pub struct Watchdog {
+    window: Duration,
+    /// Types that satisfy the watchdog; empty means anything but noise
+    required: Vec<String>,
+    started: Instant,
+    satisfied: bool,
+    lines: u64,
+    bytes: u64,
+    events: BTreeMap<String, u64>,
+    sample: Vec<String>,
+}
+
+impl Watchdog {
+    pub fn new(window: Duration, required: Vec<String>, started: Instant) -> Self {
+        Watchdog {
+            window,
+            required,
+            started,
+            satisfied: false,
+            lines: 0,
+            bytes: 0,
+            events: BTreeMap::new(),
+            sample: Vec::new(),
+        }
+    }
+
+    /// Record a raw input line.
+    pub fn observe_line(&mut self, line: &str) {
+        self.lines += 1;
+        self.bytes += line.len() as u64 + 1;
+        let trimmed = line.trim();
+        if !trimmed.is_empty() && self.sample.len() < SAMPLE_LINES {
+            self.sample
+                .push(trimmed.chars().take(SAMPLE_CHARS).collect());
+        }
+    }
+
+    /// Record emitted events; a qualifying one disarms the watchdog for good.
+    pub fn observe_events(&mut self, events: &[UnifiedEvent]) {
+        for event in events {
+            *self.events.entry(event.event_type.clone()).or_insert(0) += 1;
+            if self.qualifies(&event.event_type) {
+                self.satisfied = true;
+            }
+        }
+    }
+
+    fn qualifies(&self, event_type: &str) -> bool {
+        if self.required.is_empty() {
+            !NOISE_TYPES.contains(&event_type)
+        } else {
+            self.required.iter().any(|t| t == event_type)
+        }
+    }
+
+    pub fn is_satisfied(&self) -> bool {
+        self.satisfied
+    }
+
+    /// Time left before the watchdog fires, or `None` once it is satisfied.
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        if self.satisfied {
+            None
+        } else {
+            Some(
+                self.window
+                    .saturating_sub(now.saturating_duration_since(self.started)),
+            )
+        }
+    }
+
+    /// The terminal event describing what was seen instead.
+    pub fn error_event(&self, agent_id: &str, format: AgentFormat) -> UnifiedEvent {
+        let wanted = if self.required.is_empty() {
+            "structured event".to_string()
+        } else {
+            self.required.join(" or ")
+        };
+        let mut event = UnifiedEvent::new("error")
+            .with_agent_id(agent_id)
+            .with_status("no_events");
+        event.error = Some(format!(
+            "no {} within {}ms ({} lines read, format {})",
+            wanted,
+            self.window.as_millis(),
+            self.lines,
+            format_name(format)
+        ));
+        event.diagnostics = Some(json!({
+            "window_ms": self.window.as_millis() as u64,
+            "required_types": self.required,
+            "lines_read": self.lines,
+            "bytes_read": self.bytes,
+            "events": self.events,
+            "format": format_name(format),
+            "sample": self.sample,
+        }));
+        event
+    }
+}
+
+/// The name `--format`-style hints use for a format.
+pub fn format_name(format: AgentFormat) -> &'static str {
+    match format {
+        AgentFormat::Python => "python",
+        AgentFormat::ClaudeCode => "claude",
+        AgentFormat::OpenAi => "openai",
+        AgentFormat::Gemini => "gemini",
+        AgentFormat::Aider => "aider",
+        AgentFormat::Unknown => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fires_on_noise_and_describes_it() {
+        let start = Instant::now();
+        let mut watchdog = Watchdog::new(Duration::from_secs(120), Vec::new(), start);
+        let mut parser = crate::Parser::new("agent-1".to_string());
+        for i in 0..20 {
+            let line = format!("DEBUG loading module {} {}", i, "x".repeat(300));
+            watchdog.observe_line(&line);
+            watchdog.observe_events(&parser.parse_line(&line));
+        }
+
+        assert_eq!(
+            watchdog.remaining(start + Duration::from_secs(60)),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            watchdog.remaining(start + Duration::from_secs(121)),
+            Some(Duration::ZERO)
+        );
+
+        let event = watchdog.error_event("agent-1", parser.format());
+        assert_eq!(event.event_type, "error");
+        assert_eq!(event.status.as_deref(), Some("no_events"));
+        assert!(event.error.unwrap().contains("20 lines read"));
+
+        let diagnostics = event.diagnostics.unwrap();
+        assert_eq!(diagnostics["lines_read"], 20);
+        assert_eq!(diagnostics["events"]["output"], 20);
+        assert_eq!(diagnostics["format"], "unknown");
+        let sample = diagnostics["sample"].as_array().unwrap();
+        assert_eq!(sample.len(), SAMPLE_LINES);
+        assert!(sample[0]
+            .as_str()
+            .unwrap()
+            .starts_with("DEBUG loading module 0"));
+        assert_eq!(sample[0].as_str().unwrap().chars().count(), SAMPLE_CHARS);
+    }
+
+    #[test]
+    fn test_required_types_disarm_for_good() {
+        let start = Instant::now();
+        let required = vec!["turn".to_string(), "tool_call".to_string()];
+        let mut watchdog = Watchdog::new(Duration::from_secs(1), required, start);
+
+        watchdog.observe_events(&[UnifiedEvent::new("thinking")]);
+        assert!(!watchdog.is_satisfied());
+
+        watchdog.observe_events(&[UnifiedEvent::new("tool_call")]);
+        assert!(watchdog.is_satisfied());
+        assert_eq!(watchdog.remaining(start + Duration::from_secs(3600)), None);
+    }
+}
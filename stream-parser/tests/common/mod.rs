@@ -0,0 +1,51 @@
+//! Helpers shared by the integration tests that drive the `agent-stream`
+//! binary. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use agent_stream::UnifiedEvent;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Run `agent-stream` with `args` and the extra `env` vars, write `input` to
+/// its stdin and check it exits with `status`.
+///
+/// `MC_EVENTS_SCHEMA` is cleared so the caller's environment can't change
+/// the output format.
+pub fn output(args: &[&str], env: &[(&str, &str)], input: &str, status: i32) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_agent-stream"))
+        .args(args)
+        .envs(env.iter().copied())
+        .env_remove("MC_EVENTS_SCHEMA")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Runs rejected at startup exit before reading any input
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(
+        output.status.code(),
+        Some(status),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+/// Run `agent-stream` over `input`, expecting success, and parse its events.
+pub fn run(args: &[&str], input: &str) -> Vec<UnifiedEvent> {
+    events(&output(args, &[], input, 0))
+}
+
+/// The ndjson events a run wrote to stdout.
+pub fn events(output: &Output) -> Vec<UnifiedEvent> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+pub fn types(events: &[UnifiedEvent]) -> Vec<&str> {
+    events.iter().map(|e| e.event_type.as_str()).collect()
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::run;
+
+/// A Claude Code agent and two Gemini agents interleaved line by line, and
+/// a line from the supervisor itself.
+const MERGED: &str = r#"planner| {"type":"system","subtype":"init","session_id":"s-plan"}
+coder| {"candidates":[{"content":{"role":"model","parts":[{"text":"Reading"}]},"finishReason":"STOP"}]}
+tester| {"candidates":[{"content":{"role":"model","parts":[{"text":"Running tests"}]},"finishReason":"STOP"}]}
+planner| {"type":"assistant","message":{"content":[{"type":"text","text":"Plan: two steps"}]}}
+coder| {"candidates":[{"content":{"role":"model","parts":[{"text":"Editing"}]},"finishReason":"STOP"}]}
+supervisor: all agents running
+planner| {"type":"result","subtype":"success","session_id":"s-plan"}
+"#;
+
+#[test]
+fn test_interleaved_agents_parsed_apart() {
+    let events = run(&["mission", "--demux-prefix", "--no-timestamps"], MERGED);
+
+    let turns: Vec<(&str, Option<u32>)> = events
+        .iter()
+        .filter(|e| e.event_type == "turn")
+        .map(|e| (e.agent_id.as_deref().unwrap(), e.turn))
+        .collect();
+    assert_eq!(
+        turns,
+        [("coder", Some(1)), ("tester", Some(1)), ("coder", Some(2))]
+    );
+
+    let of = |agent: &str| -> Vec<&str> {
+        events
+            .iter()
+            .filter(|e| e.agent_id.as_deref() == Some(agent))
+            .map(|e| e.event_type.as_str())
+            .collect()
+    };
+    assert_eq!(
+        of("planner"),
+        ["agent_start", "message", "session_end", "agent_exit"]
+    );
+    assert_eq!(of("default"), ["agent_start", "output", "agent_exit"]);
+    assert!(of("mission").is_empty());
+
+    let seqs: Vec<u64> = events.iter().map(|e| e.seq).collect();
+    assert_eq!(seqs, (1..=events.len() as u64).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_prefixes_kept_without_flag() {
+    let events = run(&["mission"], "coder| hello\n");
+    assert_eq!(events[1].agent_id.as_deref(), Some("mission"));
+    assert_eq!(events[1].content.as_deref(), Some("coder| hello"));
+}
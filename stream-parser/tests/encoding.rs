@@ -0,0 +1,53 @@
+mod common;
+
+use agent_stream::encoding::read_record;
+use serde_json::Value;
+
+const INPUT: &str = r#"{"type":"turn","number":1}
+{"type":"tool_call","tool":"bash","args":{"command":"echo 'a\nb'"}}
+"#;
+
+/// The JSON run's events, with timestamps dropped so runs compare equal.
+fn json_events() -> Vec<Value> {
+    let stdout = common::output(&["agent-1", "python"], &[], INPUT, 0).stdout;
+    String::from_utf8(stdout)
+        .unwrap()
+        .lines()
+        .map(|line| without_timestamp(serde_json::from_str(line).unwrap()))
+        .collect()
+}
+
+fn without_timestamp(mut value: Value) -> Value {
+    value.as_object_mut().unwrap().remove("timestamp");
+    value
+}
+
+fn binary_events(encoding: &str, decode: fn(&[u8]) -> Value) -> Vec<Value> {
+    let stdout = common::output(
+        &["agent-1", "python", "--encoding", encoding],
+        &[],
+        INPUT,
+        0,
+    )
+    .stdout;
+    let mut input = stdout.as_slice();
+    let mut events = vec![];
+    while let Some(record) = read_record(&mut input).unwrap() {
+        events.push(without_timestamp(decode(&record)));
+    }
+    events
+}
+
+#[test]
+fn test_msgpack_output_matches_json() {
+    let events = binary_events("msgpack", |record| rmp_serde::from_slice(record).unwrap());
+    assert_eq!(events.len(), 4);
+    assert_eq!(events, json_events());
+}
+
+#[test]
+fn test_cbor_output_matches_json() {
+    let events = binary_events("cbor", |record| ciborium::from_reader(record).unwrap());
+    assert_eq!(events.len(), 4);
+    assert_eq!(events, json_events());
+}
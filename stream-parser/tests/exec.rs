@@ -0,0 +1,54 @@
+use agent_stream::UnifiedEvent;
+use std::process::Command;
+
+/// An agent that logs to both streams, pausing so arrival order is fixed,
+/// then dies with a Python traceback.
+const AGENT: &str = r#"
+echo '{"type":"turn","number":1}'
+sleep 0.1
+echo 'UserWarning: tokenizer is slow' >&2
+sleep 0.1
+echo '{"type":"tool_call","tool":"bash","args":{"command":"ls"}}'
+sleep 0.1
+printf 'Traceback (most recent call last):\n  File "agent.py", line 3\nValueError: boom\n' >&2
+exit 1
+"#;
+
+#[test]
+fn test_exec_interleaves_stderr_and_flags_crash() {
+    let output = Command::new(env!("CARGO_BIN_EXE_agent-stream"))
+        .args(["agent-1", "python", "--exec", "sh", "-c", AGENT])
+        .env_remove("MC_EVENTS_SCHEMA")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let events: Vec<UnifiedEvent> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let summary: Vec<(&str, Option<&str>)> = events
+        .iter()
+        .map(|e| (e.event_type.as_str(), e.status.as_deref()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("agent_start", None),
+            ("turn", None),
+            ("stderr", None),
+            ("tool_call", None),
+            ("stderr", Some("crash")),
+            ("stderr", None),
+            ("stderr", None),
+            ("agent_exit", Some("exited")),
+        ]
+    );
+    assert_eq!(
+        events[2].content.as_deref(),
+        Some("UserWarning: tokenizer is slow")
+    );
+    assert_eq!(events[7].exit_code, Some(1));
+    let seqs: Vec<u64> = events.iter().map(|e| e.seq).collect();
+    assert_eq!(seqs, (1..=8).collect::<Vec<u64>>());
+}
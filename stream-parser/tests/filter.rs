@@ -0,0 +1,51 @@
+mod common;
+
+use common::types;
+
+const INPUT: &str = r#"{"type":"turn","number":1}
+{"type":"thinking","content":"Looking at the tests"}
+{"type":"tool_call","tool":"bash","args":{"command":"cargo test"}}
+{"type":"tool_result","content":"ok"}
+{"type":"turn","number":2}
+{"type":"thinking","content":"All green"}
+"#;
+
+#[test]
+fn test_exclude_drops_thinking_but_keeps_turns() {
+    let events = common::run(&["agent-1", "python", "--exclude", "thinking"], INPUT);
+
+    assert_eq!(
+        types(&events),
+        [
+            "agent_start",
+            "turn",
+            "tool_call",
+            "tool_result",
+            "turn",
+            "agent_exit"
+        ]
+    );
+    let turns: Vec<Option<u32>> = events
+        .iter()
+        .filter(|e| e.event_type == "turn")
+        .map(|e| e.turn)
+        .collect();
+    assert_eq!(turns, [Some(1), Some(2)]);
+}
+
+#[test]
+fn test_include_takes_precedence() {
+    let events = common::run(
+        &[
+            "agent-1",
+            "python",
+            "--include",
+            "turn,tool_result",
+            "--exclude",
+            "turn",
+        ],
+        INPUT,
+    );
+
+    assert_eq!(types(&events), ["turn", "tool_result", "turn"]);
+}
@@ -0,0 +1,50 @@
+use agent_stream::UnifiedEvent;
+use std::process::Command;
+
+/// An agent that is slow to start, then runs a long quiet tool call.
+const AGENT: &str = r#"
+sleep 1.5
+echo '{"type":"turn","number":1}'
+sleep 2.5
+echo '{"type":"tool_result","content":"build ok"}'
+"#;
+
+#[test]
+fn test_heartbeats_while_quiet_after_first_event() {
+    let output = Command::new(env!("CARGO_BIN_EXE_agent-stream"))
+        .args([
+            "agent-1",
+            "python",
+            "--heartbeat-secs",
+            "1",
+            "--exec",
+            "sh",
+            "-c",
+            AGENT,
+        ])
+        .env_remove("MC_EVENTS_SCHEMA")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let events: Vec<UnifiedEvent> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let summary: Vec<(&str, Option<u64>)> = events
+        .iter()
+        .map(|e| (e.event_type.as_str(), e.idle_secs))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("agent_start", None),
+            ("turn", None),
+            ("heartbeat", Some(1)),
+            ("heartbeat", Some(2)),
+            ("tool_result", None),
+            ("agent_exit", None),
+        ]
+    );
+    assert_eq!(events[2].agent_id.as_deref(), Some("agent-1"));
+}
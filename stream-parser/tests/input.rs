@@ -0,0 +1,114 @@
+mod common;
+
+use common::types;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::Write;
+use std::path::Path;
+use std::process::Output;
+
+const TRANSCRIPT: &str = r#"{"type":"turn","number":1}
+{"type":"tool_call","tool":"bash","args":{"command":"cargo test"}}
+{"type":"tool_result","content":"ok"}
+"#;
+
+/// Exit code when the agent's output could not be read to the end.
+const EXIT_INPUT_ERROR: i32 = 6;
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn run(path: &Path, status: i32) -> Output {
+    let args = ["agent-1", "python", "--input", path.to_str().unwrap()];
+    common::output(&args, &[], "", status)
+}
+
+const PARSED: [&str; 5] = [
+    "agent_start",
+    "turn",
+    "tool_call",
+    "tool_result",
+    "agent_exit",
+];
+
+#[test]
+fn test_plain_and_gzipped_files_parse_alike() {
+    let dir = tempfile::tempdir().unwrap();
+    let plain = dir.path().join("agent.ndjson");
+    std::fs::write(&plain, TRANSCRIPT).unwrap();
+    let named = dir.path().join("agent.ndjson.gz");
+    std::fs::write(&named, gzip(TRANSCRIPT.as_bytes())).unwrap();
+    // Detected by its magic bytes alone
+    let unnamed = dir.path().join("agent.log");
+    std::fs::write(&unnamed, gzip(TRANSCRIPT.as_bytes())).unwrap();
+
+    for path in [&plain, &named, &unnamed] {
+        let events = common::events(&run(path, 0));
+        assert_eq!(types(&events), PARSED, "{}", path.display());
+    }
+}
+
+#[test]
+fn test_concatenated_gzip_members() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rotated.ndjson.gz");
+    let (first, rest) = TRANSCRIPT.split_at(TRANSCRIPT.find('\n').unwrap() + 1);
+    let mut data = gzip(first.as_bytes());
+    data.extend(gzip(rest.as_bytes()));
+    std::fs::write(&path, data).unwrap();
+
+    assert_eq!(types(&common::events(&run(&path, 0))), PARSED);
+}
+
+#[test]
+fn test_truncated_gzip_is_an_error_event() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cut.ndjson.gz");
+    let data = gzip(TRANSCRIPT.repeat(50).as_bytes());
+    std::fs::write(&path, &data[..data.len() / 2]).unwrap();
+
+    let events = common::events(&run(&path, EXIT_INPUT_ERROR));
+    let error = events.iter().find(|e| e.event_type == "error").unwrap();
+    assert_eq!(error.status.as_deref(), Some("input_error"));
+    assert_eq!(events.last().unwrap().event_type, "agent_exit");
+}
+
+#[test]
+fn test_input_excludes_exec() {
+    let args = ["agent-1", "--input", "agent.ndjson", "--exec", "true"];
+    common::output(&args, &[], "", 2);
+}
+
+#[test]
+fn test_utf16_input_with_encoding_in() {
+    let fixture = Path::new("tests/encoding/python_utf16.jsonl");
+    let args = [
+        "agent-1",
+        "python",
+        "--encoding-in",
+        "utf16",
+        "--input",
+        fixture.to_str().unwrap(),
+    ];
+    let events = common::events(&common::output(&args, &[], "", 0));
+    assert_eq!(
+        types(&events),
+        [
+            "agent_start",
+            "turn",
+            "thinking",
+            "tool_result",
+            "agent_exit"
+        ]
+    );
+    assert_eq!(
+        events[2].content.as_deref(),
+        Some("Résumé of the café build ✓")
+    );
+
+    // Read as UTF-8 it's an input error, not events
+    assert!(!types(&common::events(&run(fixture, EXIT_INPUT_ERROR))).contains(&"turn"));
+}
@@ -0,0 +1,103 @@
+use agent_stream::UnifiedEvent;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+fn events(output: &Output) -> Vec<UnifiedEvent> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn types(events: &[UnifiedEvent]) -> Vec<&str> {
+    events.iter().map(|e| e.event_type.as_str()).collect()
+}
+
+#[test]
+fn test_stdin_bracketed_by_start_and_eof() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_agent-stream"))
+        .args(["agent-1", "python"])
+        .env_remove("MC_EVENTS_SCHEMA")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Nothing parses from the blank line or the broken one
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"\n{\"type\":\"turn\",\"number\":}\n{\"type\":\"turn\",\"number\":1}\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let events = events(&output);
+    assert_eq!(
+        types(&events),
+        ["error", "agent_start", "turn", "agent_exit"]
+    );
+    assert_eq!(events[1].agent_id.as_deref(), Some("agent-1"));
+    assert_eq!(events[3].status.as_deref(), Some("eof"));
+    assert_eq!(events[3].exit_code, None);
+}
+
+#[test]
+fn test_exec_reports_exit_code_or_signal() {
+    let run = |script: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_agent-stream"))
+            .args(["agent-1", "--exec", "sh", "-c", script])
+            .env_remove("MC_EVENTS_SCHEMA")
+            .output()
+            .unwrap();
+        events(&output).pop().unwrap()
+    };
+
+    let exit = run("echo working; exit 3");
+    assert_eq!(exit.event_type, "agent_exit");
+    assert_eq!(exit.status.as_deref(), Some("exited"));
+    assert_eq!((exit.exit_code, exit.signal), (Some(3), None));
+
+    let exit = run("echo working; kill -KILL $$");
+    assert_eq!(exit.status.as_deref(), Some("killed"));
+    assert_eq!((exit.exit_code, exit.signal), (None, Some(9)));
+}
+
+#[test]
+fn test_sigterm_flushes_deltas_and_reports_interrupted() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_agent-stream"))
+        .args(["agent-1", "claude", "--coalesce-ms", "60000"])
+        .env_remove("MC_EVENTS_SCHEMA")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for text in ["Reading ", "the tests"] {
+        let delta = serde_json::json!({
+            "type": "content_block_delta",
+            "index": 0,
+            "delta": {"type": "text_delta", "text": text},
+        });
+        writeln!(stdin, "{}", delta).unwrap();
+    }
+    stdin.flush().unwrap();
+
+    thread::sleep(Duration::from_millis(300));
+    let status = Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // stdin is still open: only the signal ends the parser
+    let output = child.wait_with_output().unwrap();
+    drop(stdin);
+    assert!(output.status.success());
+    let events = events(&output);
+    assert_eq!(types(&events), ["agent_start", "message", "agent_exit"]);
+    assert_eq!(events[1].content.as_deref(), Some("Reading the tests"));
+    assert_eq!(events[2].status.as_deref(), Some("interrupted"));
+}
@@ -0,0 +1,41 @@
+use agent_stream::UnifiedEvent;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_media_dir_receives_images() {
+    let dir = tempfile::tempdir().unwrap();
+    let media_dir = dir.path().join("media");
+    let output = Command::new(env!("CARGO_BIN_EXE_agent-stream"))
+        .args([
+            "agent-1",
+            "claude",
+            "--input",
+            "tests/fixtures/claude_screenshot.jsonl",
+        ])
+        .arg("--media-dir")
+        .arg(&media_dir)
+        .env_remove("MC_EVENTS_SCHEMA")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let events: Vec<UnifiedEvent> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let media: Vec<&UnifiedEvent> = events.iter().filter(|e| e.event_type == "media").collect();
+    assert_eq!(media.len(), 1);
+    let path = Path::new(media[0].path.as_deref().unwrap());
+    assert_eq!(path.parent(), Some(media_dir.as_path()));
+    assert_eq!(path.extension().unwrap(), "png");
+    let bytes = std::fs::read(path).unwrap();
+    assert_eq!(bytes.len() as u64, media[0].media_bytes.unwrap());
+    assert!(bytes.starts_with(b"\x89PNG"));
+
+    // The corrupt image is reported, and the stream carries on
+    assert!(events
+        .iter()
+        .any(|e| e.status.as_deref() == Some("media_error")));
+    assert_eq!(events.last().unwrap().event_type, "agent_exit");
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use chrono::DateTime;
+use mc_events::UnifiedEvent;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Agent `a` runs on a good clock, `b` logs two malformed lines and one bad
+/// timestamp, and `c`'s clock jumps back two seconds mid-stream.
+const FIXTURES: [&str; 3] = ["agent-a.ndjson", "agent-b.ndjson", "agent-c.ndjson"];
+
+/// Run `agent-stream merge` over the fixtures; the report is its stderr.
+fn run(args: &[&str]) -> (Vec<UnifiedEvent>, Value) {
+    let fixtures: Vec<String> = FIXTURES
+        .iter()
+        .map(|name| format!("tests/merge/{}", name))
+        .collect();
+    let mut command = vec!["merge"];
+    command.extend(args);
+    for fixture in &fixtures {
+        command.extend(["--input", fixture.as_str()]);
+    }
+    let output = common::output(&command, &[], "", 0);
+
+    let events = common::events(&output);
+    let report = serde_json::from_slice(&output.stderr).unwrap();
+    (events, report)
+}
+
+fn order(events: &[UnifiedEvent]) -> Vec<(String, u64)> {
+    events
+        .iter()
+        .map(|e| (e.agent_id.clone().unwrap(), e.seq))
+        .collect()
+}
+
+/// Per agent, seq strictly increases; globally, event time never goes
+/// backwards once each agent's skewed timestamps are held at its latest.
+fn assert_ordering_invariants(events: &[UnifiedEvent]) {
+    let mut last_seq: HashMap<String, u64> = HashMap::new();
+    let mut agent_time: HashMap<String, i64> = HashMap::new();
+    let mut global = i64::MIN;
+
+    for event in events {
+        let agent = event.agent_id.clone().unwrap();
+        let seq = event.seq;
+        if let Some(previous) = last_seq.insert(agent.clone(), seq) {
+            assert!(seq > previous, "agent {} reordered: {:?}", agent, events);
+        }
+
+        let latest = agent_time.get(&agent).copied().unwrap_or(i64::MIN);
+        let time = event
+            .timestamp
+            .as_deref()
+            .map(|ts| {
+                DateTime::parse_from_rfc3339(ts)
+                    .unwrap()
+                    .timestamp_nanos_opt()
+                    .unwrap()
+            })
+            .unwrap_or(latest)
+            .max(latest);
+        agent_time.insert(agent, time);
+        assert!(time >= global, "event time went backwards: {:?}", event);
+        global = time;
+    }
+}
+
+fn expected_order() -> Vec<(String, u64)> {
+    [
+        ("a", 1),
+        ("b", 1),
+        ("a", 2),
+        // Same instant: seq breaks the tie
+        ("b", 2),
+        ("a", 3),
+        ("c", 1),
+        // Skewed back to :02 but kept after c's first event
+        ("c", 2),
+        ("c", 3),
+        ("a", 4),
+        ("c", 4),
+    ]
+    .iter()
+    .map(|(agent, seq)| (agent.to_string(), *seq))
+    .collect()
+}
+
+#[test]
+fn test_merge_orders_by_time_and_preserves_agent_order() {
+    let (events, report) = run(&[]);
+
+    assert_eq!(order(&events), expected_order());
+    assert_ordering_invariants(&events);
+
+    assert_eq!(report["events"], 10);
+    assert_eq!(report["clamped"], 1);
+    assert_eq!(report["late"], 0);
+    let malformed: Vec<(&str, u64)> = report["malformed"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| (m["input"].as_str().unwrap(), m["line"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(
+        malformed,
+        vec![
+            ("tests/merge/agent-b.ndjson", 2),
+            ("tests/merge/agent-b.ndjson", 3),
+            ("tests/merge/agent-b.ndjson", 6)
+        ]
+    );
+    assert!(report["malformed"][0]["error"]
+        .as_str()
+        .unwrap()
+        .contains("shiny"));
+    assert!(report["malformed"][2]["error"]
+        .as_str()
+        .unwrap()
+        .contains("yesterday"));
+}
+
+#[test]
+fn test_windowed_merge_matches_when_window_covers_inputs() {
+    let (events, report) = run(&["--window", "60s"]);
+
+    assert_eq!(order(&events), expected_order());
+    assert_ordering_invariants(&events);
+    assert_eq!(report["late"], 0);
+    assert_eq!(report["malformed"].as_array().unwrap().len(), 3);
+}
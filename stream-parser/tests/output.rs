@@ -0,0 +1,55 @@
+#![cfg(unix)]
+
+mod common;
+
+use agent_stream::UnifiedEvent;
+use common::types;
+use std::io::{BufRead, BufReader};
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+use std::process::Output;
+
+const INPUT: &str = r#"{"type":"turn","number":1}
+{"type":"tool_call","tool":"bash","args":{"command":"ls"}}
+"#;
+
+fn run(socket: &Path) -> Output {
+    let target = format!("unix:{}", socket.display());
+    common::output(&["agent-1", "python", "--output", &target], &[], INPUT, 0)
+}
+
+fn parse(lines: impl Iterator<Item = String>) -> Vec<UnifiedEvent> {
+    lines
+        .map(|line| serde_json::from_str(&line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_events_written_to_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("events.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let output = run(&path);
+    assert!(output.stdout.is_empty());
+
+    let (stream, _) = listener.accept().unwrap();
+    let events = parse(BufReader::new(stream).lines().map(Result::unwrap));
+    assert_eq!(
+        types(&events),
+        ["agent_start", "turn", "tool_call", "agent_exit"]
+    );
+}
+
+#[test]
+fn test_falls_back_to_stdout_with_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let events = common::events(&run(&dir.path().join("missing.sock")));
+    assert_eq!(
+        types(&events),
+        ["error", "agent_start", "turn", "tool_call", "agent_exit"]
+    );
+    assert_eq!(events[0].status.as_deref(), Some("output_unavailable"));
+    assert!(events[0].error.as_deref().unwrap().contains("missing.sock"));
+    assert_eq!(events[0].seq, 1);
+}
@@ -0,0 +1,31 @@
+mod common;
+
+const INPUT: &str = r#"{"type":"turn","number":1}
+{"type":"tool_call","tool":"bash","args":{"command":"ls -la"}}
+{"type":"thinking","content":"The listing shows a Cargo.toml and a src directory, so this is a Rust project"}
+"#;
+
+fn run(columns: &str) -> String {
+    let args = ["agent-1", "python", "--pretty"];
+    let output = common::output(&args, &[("COLUMNS", columns)], INPUT, 0);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_pretty_lines_without_color_when_piped() {
+    assert_eq!(
+        run("200"),
+        "[agent-1] AGENT_START\n\
+         [agent-1] TURN 1\n\
+         [agent-1] TOOL bash: ls -la\n\
+         [agent-1] THINKING (77 chars) The listing shows a Cargo.toml and a src directory, so this is a Rust project\n\
+         [agent-1] AGENT_EXIT eof\n"
+    );
+}
+
+#[test]
+fn test_pretty_lines_fit_the_terminal() {
+    for line in run("40").lines() {
+        assert!(line.chars().count() <= 40, "{}", line);
+    }
+}
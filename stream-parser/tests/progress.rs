@@ -0,0 +1,39 @@
+use agent_stream::UnifiedEvent;
+use std::process::Command;
+
+/// A progress bar that stays on one state for over a second, then finishes
+/// in a burst, followed by a colored log line.
+const AGENT: &str = r#"
+printf '\r  0%%|          | 0/10'
+sleep 0.1
+printf '\r 40%%|####      | 4/10'
+sleep 1.2
+printf '\r 80%%|########  | 8/10'
+printf '\r100%%|##########| 10/10\n'
+printf '\033[32mINFO\033[0m done\n'
+"#;
+
+#[test]
+fn test_live_progress_bar_throttled_to_final_state() {
+    let output = Command::new(env!("CARGO_BIN_EXE_agent-stream"))
+        .args(["agent-1", "--exec", "sh", "-c", AGENT])
+        .env_remove("MC_EVENTS_SCHEMA")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let contents: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str::<UnifiedEvent>(line).unwrap())
+        .filter_map(|event| event.content)
+        .collect();
+    // The 40% state was on screen for over a second; the rest only briefly
+    assert_eq!(
+        contents,
+        [
+            "40%|####      | 4/10",
+            "100%|##########| 10/10",
+            "INFO done"
+        ]
+    );
+}
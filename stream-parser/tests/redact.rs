@@ -0,0 +1,42 @@
+mod common;
+
+use agent_stream::UnifiedEvent;
+
+const INPUT: &str = r#"{"type":"tool_call","tool":"bash","args":{"command":"export OPENAI_API_KEY=sk-proj-4fJ9xQ2mZ8rT1vB6nW3k"}}
+{"type":"tool_result","content":"connected with token tok_live_8c1f"}
+"#;
+
+fn run(args: &[&str]) -> Vec<UnifiedEvent> {
+    let env = [("DEPLOY_TOKEN", "tok_live_8c1f")];
+    common::events(&common::output(args, &env, INPUT, 0))
+}
+
+#[test]
+fn test_redact_env_masks_named_values() {
+    let events = run(&[
+        "agent-1",
+        "python",
+        "--redact",
+        "--redact-env",
+        "DEPLOY_TOKEN,UNSET_VAR",
+    ]);
+
+    assert_eq!(
+        events[1].args.as_ref().unwrap()["command"],
+        "export OPENAI_API_KEY=[REDACTED]"
+    );
+    assert_eq!(
+        events[2].result.as_deref(),
+        Some("connected with token [REDACTED]")
+    );
+}
+
+#[test]
+fn test_no_redaction_by_default() {
+    let events = run(&["agent-1", "python"]);
+
+    assert_eq!(
+        events[2].result.as_deref(),
+        Some("connected with token tok_live_8c1f")
+    );
+}
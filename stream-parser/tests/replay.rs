@@ -0,0 +1,83 @@
+use agent_stream::UnifiedEvent;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn transcript(lines: &[&str]) -> tempfile::NamedTempFile {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), lines.join("\n") + "\n").unwrap();
+    file
+}
+
+fn replay(file: &tempfile::NamedTempFile, args: &[&str]) -> (Output, Duration) {
+    let started = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_agent-stream"))
+        .args(["agent-1", "python", "--replay"])
+        .arg(file.path())
+        .args(args)
+        .env_remove("MC_EVENTS_SCHEMA")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    (output, started.elapsed())
+}
+
+fn events(output: &Output) -> Vec<UnifiedEvent> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_replays_at_original_pace_scaled_by_speed() {
+    let file = transcript(&[
+        r#"{"type":"turn","number":1,"timestamp":1700000000000}"#,
+        r#"{"type":"thinking","content":"hm","timestamp":1700000000600}"#,
+        r#"{"type":"tool_call","tool":"bash","args":{},"timestamp":1700000001200}"#,
+    ]);
+    let (output, elapsed) = replay(&file, &["--speed", "2"]);
+
+    assert!(output.status.success());
+    assert_eq!(events(&output).len(), 5);
+    assert!(elapsed >= Duration::from_millis(600), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(1200), "{:?}", elapsed);
+}
+
+#[test]
+fn test_fixed_delay_without_timestamps() {
+    let file = transcript(&["one", "two", "three"]);
+    let (output, elapsed) = replay(&file, &["--delay-ms", "250"]);
+
+    assert_eq!(events(&output).len(), 5);
+    assert!(elapsed >= Duration::from_millis(500), "{:?}", elapsed);
+}
+
+#[test]
+fn test_ctrl_c_ends_replay_cleanly() {
+    let file = transcript(&[
+        r#"{"type":"turn","number":1,"timestamp":1700000000000}"#,
+        r#"{"type":"turn","number":2,"timestamp":1700000060000}"#,
+    ]);
+    let child = Command::new(env!("CARGO_BIN_EXE_agent-stream"))
+        .args(["agent-1", "python", "--replay"])
+        .arg(file.path())
+        .env_remove("MC_EVENTS_SCHEMA")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let events = events(&output);
+    let types: Vec<&str> = events.iter().map(|e| e.event_type.as_str()).collect();
+    assert_eq!(types, ["agent_start", "turn", "agent_exit"]);
+    assert_eq!(events[1].turn, Some(1));
+    assert_eq!(events[2].status.as_deref(), Some("interrupted"));
+}
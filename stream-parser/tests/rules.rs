@@ -0,0 +1,80 @@
+mod common;
+
+use std::process::Output;
+
+const RULES: &str = r#"
+[[rule]]
+pattern = '^PASS: (?P<test>\S+)'
+type = "tool_result"
+tool = "test"
+status = "pass"
+result = "${test}"
+
+[[rule]]
+pattern = '^FAIL: (?P<test>\S+?)(?:: (?P<reason>.*))?$'
+type = "tool_result"
+tool = "test"
+status = "fail"
+result = "${test}"
+error = "${reason}"
+"#;
+
+const OUTPUT: &str = "[Turn 1]
+$ ./run_tests.sh
+PASS: auth::login
+FAIL: auth::logout: session still active
+2 tests, 1 failure
+";
+
+fn run(rules: &str, status: i32) -> Output {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rules.toml");
+    std::fs::write(&path, rules).unwrap();
+    let args = ["agent-1", "--rules", path.to_str().unwrap()];
+    common::output(&args, &[], OUTPUT, status)
+}
+
+#[test]
+fn test_test_runner_lines_become_tool_results() {
+    let events = common::events(&run(RULES, 0));
+    let summary: Vec<(&str, Option<&str>, Option<&str>)> = events
+        .iter()
+        .map(|e| {
+            (
+                e.event_type.as_str(),
+                e.tool.as_deref(),
+                e.status.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("agent_start", None, None),
+            ("turn", None, None),
+            ("tool_call", Some("bash"), None),
+            ("tool_result", Some("test"), Some("pass")),
+            ("tool_result", Some("test"), Some("fail")),
+            ("output", None, None),
+            ("agent_exit", None, Some("eof")),
+        ]
+    );
+    assert_eq!(events[4].result.as_deref(), Some("auth::logout"));
+    assert_eq!(events[4].error.as_deref(), Some("session still active"));
+}
+
+#[test]
+fn test_invalid_pattern_rejected_at_startup() {
+    let output = run(
+        "[[rule]]\npattern = 'PASS: (?P<test'\ntype = \"tool_result\"\n",
+        2,
+    );
+
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("rule 1 has an invalid pattern"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use agent_stream::state::{self, STATE_VERSION};
+use agent_stream::{AgentExit, Parser, UnifiedEvent};
+use common::{events, types};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+fn gemini(text: &str) -> String {
+    format!(
+        r#"{{"candidates":[{{"content":{{"role":"model","parts":[{{"text":"{}"}}]}},"finishReason":"STOP"}}]}}"#,
+        text
+    )
+}
+
+fn turns(events: &[UnifiedEvent]) -> Vec<u32> {
+    events
+        .iter()
+        .filter(|e| e.event_type == "turn")
+        .filter_map(|e| e.turn)
+        .collect()
+}
+
+fn run(state_file: &Path, lines: &[String]) -> Vec<UnifiedEvent> {
+    let args = ["agent-1", "--state-file", state_file.to_str().unwrap()];
+    let input: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    common::run(&args, &input)
+}
+
+#[test]
+fn test_restored_parser_continues_turns() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+
+    let mut parser = Parser::new("agent-1".to_string());
+    let mut events = Vec::new();
+    for text in ["one", "two"] {
+        events.extend(parser.parse_line(&gemini(text)));
+    }
+    let state = state::StreamState::new(
+        parser.next_seq(),
+        vec![state::AgentState {
+            agent_id: "agent-1".to_string(),
+            parser: parser.state(),
+        }],
+    );
+    state::save(&path, &state).unwrap();
+    drop(parser);
+
+    let mut saved = state::load(&path).unwrap().unwrap();
+    let mut parser = Parser::new("agent-1".to_string());
+    parser.restore(saved.agents.remove(0).parser);
+    parser.set_start_seq(saved.next_seq);
+    // Detected before the restart, so the first line parses as Gemini
+    let resumed = parser.parse_line(&gemini("three"));
+    assert_eq!(turns(&events), [1, 2]);
+    assert_eq!(turns(&resumed), [3]);
+    assert_eq!(resumed[0].seq, events.last().unwrap().seq + 1);
+    assert!(parser.finish_with(AgentExit::Eof).is_empty());
+}
+
+#[test]
+fn test_missing_and_outdated_state_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    assert!(state::load(&path).unwrap().is_none());
+
+    std::fs::write(
+        &path,
+        format!(
+            r#"{{"version":{},"next_seq":5,"agents":[]}}"#,
+            STATE_VERSION + 1
+        ),
+    )
+    .unwrap();
+    let error = state::load(&path).unwrap_err();
+    assert!(error.contains("is not supported"), "{}", error);
+
+    // The binary reports it and starts over
+    let events = run(&path, &[gemini("one")]);
+    assert_eq!(types(&events)[..2], ["error", "agent_start"]);
+    assert_eq!(events[0].status.as_deref(), Some("state_unreadable"));
+    assert_eq!(events[0].seq, 1);
+    let saved = state::load(&path).unwrap().unwrap();
+    assert_eq!(saved.next_seq, events.len() as u64 + 1);
+}
+
+#[test]
+fn test_sigterm_suspends_and_next_run_carries_on() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_agent-stream"))
+        .arg("agent-1")
+        .arg("--state-file")
+        .arg(&path)
+        .env_remove("MC_EVENTS_SCHEMA")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for text in ["one", "two"] {
+        writeln!(stdin, "{}", gemini(text)).unwrap();
+    }
+    stdin.flush().unwrap();
+    thread::sleep(Duration::from_millis(300));
+    let status = Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().unwrap();
+    drop(stdin);
+    assert!(output.status.success());
+
+    // Suspended, not ended
+    let first = events(&output);
+    assert!(!types(&first).contains(&"agent_exit"));
+    assert_eq!(turns(&first), [1, 2]);
+
+    let second = run(&path, &[gemini("three")]);
+    assert_eq!(
+        types(&second),
+        ["turn", "thinking", "turn_end", "agent_exit"]
+    );
+    assert_eq!(turns(&second), [3]);
+    assert_eq!(second[0].seq, first.last().unwrap().seq + 1);
+}
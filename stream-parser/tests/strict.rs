@@ -0,0 +1,48 @@
+mod common;
+
+use common::types;
+
+/// Exit code once `--strict` parse failures have been seen.
+const EXIT_PARSE_FAILURES: i32 = 5;
+
+const INPUT: &str = r#"{"type":"turn","number":1}
+{"type":"tool_call","tool":"bash","args":{"comm
+{"type":"thinking","content":"still going"}
+[1, 2,
+{"type":"thinking","content":"never parsed"}
+"#;
+
+#[test]
+fn test_strict_exits_after_limit() {
+    let output = common::output(
+        &["agent-1", "--strict", "2"],
+        &[],
+        INPUT,
+        EXIT_PARSE_FAILURES,
+    );
+    let events = common::events(&output);
+
+    assert_eq!(
+        types(&events),
+        ["agent_start", "turn", "error", "thinking", "error"]
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("2 parse failures"));
+}
+
+#[test]
+fn test_parse_failures_reported_without_strict() {
+    let events = common::run(&["agent-1"], INPUT);
+
+    assert_eq!(
+        types(&events),
+        [
+            "agent_start",
+            "turn",
+            "error",
+            "thinking",
+            "error",
+            "thinking",
+            "agent_exit"
+        ]
+    );
+}
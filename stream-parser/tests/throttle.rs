@@ -0,0 +1,43 @@
+mod common;
+
+use agent_stream::UnifiedEvent;
+use std::time::{Duration, Instant};
+
+fn run(args: &[&str], input: &str) -> (Vec<UnifiedEvent>, Duration) {
+    let started = Instant::now();
+    let events = common::run(&[&["agent-1", "python"], args].concat(), input);
+    (events, started.elapsed())
+}
+
+fn thinking(lines: usize) -> String {
+    (0..lines)
+        .map(|i| format!("{{\"type\":\"thinking\",\"content\":\"step {}\"}}\n", i))
+        .collect()
+}
+
+#[test]
+fn test_events_per_sec_paces_output() {
+    // agent_start, 40 thinking and agent_exit: a burst of 20, then 22 more
+    // at 20 a second
+    let (events, elapsed) = run(&["--max-events-per-sec", "20"], &thinking(40));
+    assert_eq!(events.len(), 42);
+    assert!(elapsed >= Duration::from_millis(800), "{:?}", elapsed);
+
+    let exit = events.last().unwrap();
+    assert_eq!(exit.event_type, "agent_exit");
+    assert_eq!(exit.dropped_events, Some(0));
+    let seqs: Vec<u64> = events.iter().map(|e| e.seq).collect();
+    assert_eq!(seqs, (1..=42).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_unthrottled_output_has_no_drop_count() {
+    let (events, _) = run(&[], &thinking(3));
+    assert_eq!(events.last().unwrap().dropped_events, None);
+}
+
+#[test]
+fn test_zero_rate_rejected() {
+    let output = common::output(&["agent-1", "--max-bytes-per-sec", "0"], &[], "", 2);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("must be positive"));
+}
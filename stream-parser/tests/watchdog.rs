@@ -0,0 +1,81 @@
+use agent_stream::UnifiedEvent;
+use std::io::{Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// Exit code for an expired `--require-first-event`.
+const EXIT_NO_EVENTS: i32 = 4;
+
+fn spawn(args: &[&str]) -> (Child, ChildStdin) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_agent-stream"))
+        .args(args)
+        .env_remove("MC_EVENTS_SCHEMA")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdin = child.stdin.take().unwrap();
+    (child, stdin)
+}
+
+fn events(child: &mut Child) -> Vec<UnifiedEvent> {
+    let mut stdout = String::new();
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut stdout)
+        .unwrap();
+    stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_fires_when_only_noise_arrives() {
+    let (mut child, mut stdin) = spawn(&["agent-1", "--require-first-event", "300ms"]);
+    for i in 0..3 {
+        writeln!(stdin, "DEBUG still loading plugins ({})", i).unwrap();
+    }
+    stdin.flush().unwrap();
+
+    // stdin stays open: only the watchdog can end the run
+    let status = child.wait().unwrap();
+    drop(stdin);
+    assert_eq!(status.code(), Some(EXIT_NO_EVENTS));
+
+    let events = events(&mut child);
+    let error = events.last().unwrap();
+    assert_eq!(error.event_type, "error");
+    assert_eq!(error.status.as_deref(), Some("no_events"));
+    let diagnostics = error.diagnostics.as_ref().unwrap();
+    assert_eq!(diagnostics["lines_read"], 3);
+    assert_eq!(diagnostics["events"]["output"], 3);
+    assert_eq!(diagnostics["sample"][0], "DEBUG still loading plugins (0)");
+}
+
+#[test]
+fn test_does_not_fire_once_a_required_event_arrived() {
+    let (mut child, mut stdin) = spawn(&[
+        "agent-1",
+        "--require-first-event",
+        "300ms",
+        "--require-event-types",
+        "turn,tool_call",
+    ]);
+    writeln!(stdin, r#"{{"type":"turn","number":1}}"#).unwrap();
+    stdin.flush().unwrap();
+
+    // A slow agent: nothing more until well past the window
+    thread::sleep(Duration::from_millis(700));
+    writeln!(stdin, r#"{{"type":"thinking","content":"still here"}}"#).unwrap();
+    drop(stdin);
+
+    let status = child.wait().unwrap();
+    assert!(status.success());
+    let events = events(&mut child);
+    assert_eq!(events.len(), 4);
+    assert!(events.iter().all(|e| e.event_type != "error"));
+}